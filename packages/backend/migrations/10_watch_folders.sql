@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS watch_folders (
+    id TEXT PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    space_id TEXT NOT NULL,
+    propagate_deletions INTEGER NOT NULL DEFAULT 0,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS watched_files (
+    folder_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    modified_at INTEGER NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_resource INTEGER NOT NULL,
+    PRIMARY KEY (folder_id, path)
+);
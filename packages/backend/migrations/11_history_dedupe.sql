@@ -0,0 +1,39 @@
+CREATE INDEX IF NOT EXISTS idx_history_entries_imports ON history_entries(entry_type, url, source_profile);
+
+-- imports before the visit counts stored a row per visit, they are merged into
+-- the earliest row of their url, type and profile
+UPDATE history_entries AS h
+SET visit_count = (
+        SELECT SUM(d.visit_count) FROM history_entries d
+        WHERE d.entry_type = h.entry_type AND d.url = h.url AND d.source_profile IS h.source_profile
+    ),
+    typed_count = (
+        SELECT SUM(d.typed_count) FROM history_entries d
+        WHERE d.entry_type = h.entry_type AND d.url = h.url AND d.source_profile IS h.source_profile
+    ),
+    title = COALESCE((
+        SELECT d.title FROM history_entries d
+        WHERE d.entry_type = h.entry_type AND d.url = h.url AND d.source_profile IS h.source_profile
+            AND d.title IS NOT NULL
+        ORDER BY COALESCE(d.last_visited_at, d.created_at) DESC
+        LIMIT 1
+    ), h.title),
+    last_visited_at = (
+        SELECT MAX(COALESCE(d.last_visited_at, d.created_at)) FROM history_entries d
+        WHERE d.entry_type = h.entry_type AND d.url = h.url AND d.source_profile IS h.source_profile
+    )
+WHERE h.entry_type LIKE 'Import%' AND h.url IS NOT NULL AND h.id = (
+    SELECT d.id FROM history_entries d
+    WHERE d.entry_type = h.entry_type AND d.url = h.url AND d.source_profile IS h.source_profile
+    ORDER BY d.created_at ASC, d.id ASC
+    LIMIT 1
+);
+
+DELETE FROM history_entries
+WHERE entry_type LIKE 'Import%' AND url IS NOT NULL AND id != (
+    SELECT d.id FROM history_entries d
+    WHERE d.entry_type = history_entries.entry_type AND d.url = history_entries.url
+        AND d.source_profile IS history_entries.source_profile
+    ORDER BY d.created_at ASC, d.id ASC
+    LIMIT 1
+);
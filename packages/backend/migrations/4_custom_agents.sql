@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS custom_agents (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    system_prompt TEXT NOT NULL,
+    model TEXT,
+    allowed_tools TEXT NOT NULL,
+    max_iterations INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS custom_tools (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL,
+    parameters_schema TEXT NOT NULL,
+    execution_message TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
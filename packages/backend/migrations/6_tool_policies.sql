@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS tool_policies (
+    tool_name TEXT PRIMARY KEY,
+    policy TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE history_entries ADD COLUMN source_profile TEXT;
@@ -0,0 +1,13 @@
+ALTER TABLE history_entries ADD COLUMN visit_count INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE history_entries ADD COLUMN typed_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE history_entries ADD COLUMN last_visited_at TEXT;
+ALTER TABLE history_entries ADD COLUMN last_transition TEXT;
+
+CREATE TABLE IF NOT EXISTS history_import_marks (
+    browser_type TEXT NOT NULL,
+    profile_id TEXT NOT NULL,
+    last_visit_id INTEGER NOT NULL,
+    last_visit_time INTEGER NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (browser_type, profile_id)
+);
@@ -0,0 +1,332 @@
+use crossbeam_channel as crossbeam;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use super::io::{AgentIO, StatusMessage};
+use super::tools::{Tool, ToolCall, ToolRiskLevel};
+use crate::ai::llm::client::cancellation::POLL_INTERVAL;
+use crate::ai::llm::client::CancellationToken;
+use crate::store::models::{random_uuid, ToolPolicy};
+use crate::{BackendError, BackendResult};
+
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+// sent to the user through `AgentIO::write_status`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub tool: String,
+    pub description: String,
+    pub arguments: serde_json::Value,
+    pub risk_level: ToolRiskLevel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalDecision {
+    Approved,
+    // with the reason, which is given to the agent as the tool result
+    Refused(String),
+}
+
+// the per tool policies and the approval requests waiting for an answer,
+// shared by all agents of an orchestrator
+pub struct ToolApprovals {
+    policies: RwLock<HashMap<String, ToolPolicy>>,
+    pending: Mutex<HashMap<String, crossbeam::Sender<bool>>>,
+    timeout: Duration,
+}
+
+impl ToolApprovals {
+    pub fn new(policies: HashMap<String, ToolPolicy>, timeout: Duration) -> Self {
+        Self {
+            policies: RwLock::new(policies),
+            pending: Mutex::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    pub fn default_policy(risk_level: ToolRiskLevel) -> ToolPolicy {
+        match risk_level {
+            ToolRiskLevel::Low | ToolRiskLevel::Medium => ToolPolicy::AlwaysAllow,
+            ToolRiskLevel::High => ToolPolicy::Ask,
+        }
+    }
+
+    // `None` goes back to the default policy for the tool's risk level
+    pub fn set_policy(&self, tool_name: &str, policy: Option<ToolPolicy>) {
+        if let Ok(mut policies) = self.policies.write() {
+            match policy {
+                Some(policy) => policies.insert(tool_name.to_string(), policy),
+                None => policies.remove(tool_name),
+            };
+        }
+    }
+
+    pub fn policy(&self, tool: &dyn Tool) -> ToolPolicy {
+        self.policies
+            .read()
+            .ok()
+            .and_then(|policies| policies.get(tool.name()).cloned())
+            .unwrap_or_else(|| Self::default_policy(tool.risk_level()))
+    }
+
+    // answers a pending approval request, false if it isn't pending (anymore)
+    pub fn respond(&self, request_id: &str, approved: bool) -> bool {
+        let tx = match self.pending.lock() {
+            Ok(mut pending) => pending.remove(request_id),
+            Err(_) => None,
+        };
+        tx.is_some_and(|tx| tx.send(approved).is_ok())
+    }
+
+    // applies the tool's policy to the call, blocks until the user answers when asking
+    pub fn check(
+        &self,
+        tool: &dyn Tool,
+        tool_call: &ToolCall,
+        parameters: &serde_json::Value,
+        io: &dyn AgentIO,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<ApprovalDecision> {
+        match self.policy(tool) {
+            ToolPolicy::AlwaysAllow => return Ok(ApprovalDecision::Approved),
+            ToolPolicy::Deny => {
+                return Ok(ApprovalDecision::Refused(format!(
+                    "the user does not allow the tool `{}`",
+                    tool_call.function.name
+                )))
+            }
+            ToolPolicy::Ask => {}
+        }
+
+        let request = ApprovalRequest {
+            id: random_uuid(),
+            tool: tool_call.function.name.clone(),
+            description: tool.description().to_string(),
+            arguments: parameters.clone(),
+            risk_level: tool.risk_level(),
+        };
+        let (tx, rx) = crossbeam::bounded(1);
+        self.pending
+            .lock()
+            .map_err(|_| BackendError::GenericError("approvals lock poisoned".to_string()))?
+            .insert(request.id.clone(), tx);
+
+        let result = io
+            .write_status(StatusMessage::new_approval_request(&serde_json::to_string(
+                &request,
+            )?))
+            .and_then(|_| self.wait(&rx, cancellation_token));
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&request.id);
+        }
+        Ok(match result? {
+            Some(true) => ApprovalDecision::Approved,
+            Some(false) => ApprovalDecision::Refused(format!(
+                "the user rejected the call of `{}`",
+                request.tool
+            )),
+            None => ApprovalDecision::Refused(format!(
+                "the user did not approve the call of `{}` in time",
+                request.tool
+            )),
+        })
+    }
+
+    // `None` on timeout
+    fn wait(
+        &self,
+        rx: &crossbeam::Receiver<bool>,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Option<bool>> {
+        let started = Instant::now();
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(approved) => return Ok(Some(approved)),
+                Err(crossbeam::RecvTimeoutError::Disconnected) => return Ok(None),
+                Err(crossbeam::RecvTimeoutError::Timeout) => {
+                    cancellation_token.check()?;
+                    if started.elapsed() >= self.timeout {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::brain::agents::context::ContextManager;
+    use crate::ai::brain::agents::io::StatusType;
+    use crate::ai::brain::agents::tools::FunctionCall;
+    use crate::ai::llm::client::Model;
+    use std::sync::Arc;
+
+    struct RiskyTool;
+
+    impl Tool for RiskyTool {
+        fn name(&self) -> &str {
+            "delete_notes"
+        }
+
+        fn description(&self) -> &str {
+            "Deletes notes"
+        }
+
+        fn execution_message(&self) -> Option<&str> {
+            None
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn risk_level(&self) -> ToolRiskLevel {
+            ToolRiskLevel::High
+        }
+
+        fn execute(
+            &self,
+            _parameters: serde_json::Value,
+            _execution_id: String,
+            _model: Model,
+            _custom_key: Option<String>,
+            _io: &dyn AgentIO,
+            _context_manager: &mut dyn ContextManager,
+            _cancellation_token: CancellationToken,
+        ) -> BackendResult<()> {
+            Ok(())
+        }
+    }
+
+    // answers every approval request right away, or never with `None`
+    struct AnsweringIO {
+        approvals: Arc<ToolApprovals>,
+        answer: Option<bool>,
+        requests: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl AgentIO for AnsweringIO {
+        fn get_id(&self) -> String {
+            "test".to_string()
+        }
+
+        fn write(&self, _content: &str) -> BackendResult<()> {
+            Ok(())
+        }
+
+        fn write_status(&self, message: StatusMessage) -> BackendResult<()> {
+            if let StatusType::ApprovalRequest = message.status_type {
+                let request: serde_json::Value = serde_json::from_str(&message.value)?;
+                if let Some(answer) = self.answer {
+                    let id = request["id"].as_str().unwrap_or_default();
+                    assert!(self.approvals.respond(id, answer));
+                }
+                self.requests.lock().unwrap().push(request);
+            }
+            Ok(())
+        }
+
+        fn read(&self) -> BackendResult<String> {
+            Ok(String::new())
+        }
+
+        fn clear(&self) -> BackendResult<()> {
+            Ok(())
+        }
+    }
+
+    fn check(approvals: &Arc<ToolApprovals>, answer: Option<bool>) -> (ApprovalDecision, usize) {
+        let io = AnsweringIO {
+            approvals: Arc::clone(approvals),
+            answer,
+            requests: Mutex::new(vec![]),
+        };
+        let tool_call = ToolCall {
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "delete_notes".to_string(),
+                arguments: "{\"all\": true}".to_string(),
+            },
+        };
+        let decision = approvals
+            .check(
+                &RiskyTool,
+                &tool_call,
+                &serde_json::json!({"all": true}),
+                &io,
+                &CancellationToken::new(),
+            )
+            .unwrap();
+        let requests = io.requests.lock().unwrap();
+        if let Some(request) = requests.first() {
+            assert_eq!(request["tool"], "delete_notes");
+            assert_eq!(request["risk_level"], "high");
+            assert_eq!(request["arguments"]["all"], true);
+        }
+        (decision, requests.len())
+    }
+
+    #[test]
+    fn test_tool_approvals() {
+        let approvals = Arc::new(ToolApprovals::new(
+            HashMap::new(),
+            Duration::from_millis(300),
+        ));
+
+        // high risk tools ask by default
+        assert_eq!(
+            check(&approvals, Some(true)),
+            (ApprovalDecision::Approved, 1)
+        );
+        assert!(matches!(
+            check(&approvals, Some(false)),
+            (ApprovalDecision::Refused(_), 1)
+        ));
+        assert!(matches!(
+            check(&approvals, None),
+            (ApprovalDecision::Refused(_), 1)
+        ));
+        assert!(!approvals.respond("unknown", true));
+
+        approvals.set_policy("delete_notes", Some(ToolPolicy::AlwaysAllow));
+        assert_eq!(check(&approvals, None), (ApprovalDecision::Approved, 0));
+        approvals.set_policy("delete_notes", Some(ToolPolicy::Deny));
+        assert!(matches!(
+            check(&approvals, Some(true)),
+            (ApprovalDecision::Refused(_), 0)
+        ));
+        approvals.set_policy("delete_notes", None);
+        assert_eq!(
+            check(&approvals, Some(true)),
+            (ApprovalDecision::Approved, 1)
+        );
+    }
+
+    #[test]
+    fn test_tool_approval_cancelled() {
+        let approvals = Arc::new(ToolApprovals::new(HashMap::new(), DEFAULT_APPROVAL_TIMEOUT));
+        let io = AnsweringIO {
+            approvals: Arc::clone(&approvals),
+            answer: None,
+            requests: Mutex::new(vec![]),
+        };
+        let tool_call = ToolCall {
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "delete_notes".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(approvals
+            .check(&RiskyTool, &tool_call, &serde_json::json!({}), &io, &token)
+            .is_err());
+        assert!(approvals.pending.lock().unwrap().is_empty());
+    }
+}
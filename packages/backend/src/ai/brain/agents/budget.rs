@@ -0,0 +1,293 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::io::{AgentIO, StatusMessage};
+use crate::ai::llm::client::{CompletionUsage, Model, ModelPrice};
+use crate::BackendResult;
+
+// limits of a single agent run, `None` is unlimited
+//
+// sub-agents called as tools have their own limits, their tokens and cost count here
+// as well but their tool calls don't
+#[derive(Debug, Clone, Default)]
+pub struct AgentBudget {
+    pub max_tokens: Option<u64>,
+    pub max_tool_calls: Option<usize>,
+    pub max_duration: Option<Duration>,
+    // in usd, only enforced when the price of the model is known
+    pub max_cost: Option<f64>,
+    // overrides the list price of the model, e.g. for custom models
+    pub price: Option<ModelPrice>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    Tokens,
+    ToolCalls,
+    Duration,
+    Cost,
+}
+
+impl std::fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BudgetLimit::Tokens => "token",
+            BudgetLimit::ToolCalls => "tool call",
+            BudgetLimit::Duration => "time",
+            BudgetLimit::Cost => "cost",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// what a run used, reported at its end
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BudgetUsage {
+    pub completions: usize,
+    pub tokens: u64,
+    pub tool_calls: usize,
+    pub duration_ms: u64,
+    // `None` when the price of the model isn't known
+    pub cost: Option<f64>,
+    // the limit that ended the run early
+    pub exhausted: Option<BudgetLimit>,
+}
+
+pub struct BudgetTracker {
+    budget: AgentBudget,
+    model: Model,
+    price: Option<ModelPrice>,
+    started_at: Instant,
+    usage: Mutex<BudgetUsage>,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: AgentBudget, model: &Model) -> Self {
+        let price = budget.price.or_else(|| model.price());
+        if budget.max_cost.is_some() && price.is_none() {
+            tracing::warn!("no price for model {:?}, the cost limit is ignored", model);
+        }
+        Self {
+            budget,
+            model: model.clone(),
+            price,
+            started_at: Instant::now(),
+            usage: Mutex::new(BudgetUsage {
+                cost: price.map(|_| 0.0),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn record_completion(&self, completion: &CompletionUsage) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.completions += 1;
+            usage.tokens += self.model.total_tokens(completion);
+            if let (Some(cost), Some(price)) = (usage.cost.as_mut(), self.price.as_ref()) {
+                *cost += self.model.cost(completion, price);
+            }
+        }
+    }
+
+    // the tokens and cost of a sub-agent that ran as a tool of this run
+    pub fn record_sub_agent_usage(&self, sub_agent_usage: &BudgetUsage) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.completions += sub_agent_usage.completions;
+            usage.tokens += sub_agent_usage.tokens;
+            if let (Some(cost), Some(sub_agent_cost)) = (usage.cost.as_mut(), sub_agent_usage.cost)
+            {
+                *cost += sub_agent_cost;
+            }
+        }
+    }
+
+    // counts the tool call, false if no tool calls are left
+    pub fn start_tool_call(&self) -> bool {
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(_) => return false,
+        };
+        if self
+            .budget
+            .max_tool_calls
+            .is_some_and(|max| usage.tool_calls >= max)
+        {
+            return false;
+        }
+        usage.tool_calls += 1;
+        true
+    }
+
+    // time left for tool calls, `None` without a time limit
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.budget
+            .max_duration
+            .map(|max| max.saturating_sub(self.started_at.elapsed()))
+    }
+
+    // the first limit that was reached, remembered for the usage report
+    pub fn exhausted(&self) -> Option<BudgetLimit> {
+        let mut usage = self.usage.lock().ok()?;
+        if usage.exhausted.is_some() {
+            return usage.exhausted;
+        }
+        let exhausted = if self
+            .budget
+            .max_tokens
+            .is_some_and(|max| usage.tokens >= max)
+        {
+            Some(BudgetLimit::Tokens)
+        } else if self
+            .budget
+            .max_tool_calls
+            .is_some_and(|max| usage.tool_calls >= max)
+        {
+            Some(BudgetLimit::ToolCalls)
+        } else if self.remaining_time() == Some(Duration::ZERO) {
+            Some(BudgetLimit::Duration)
+        } else if self
+            .budget
+            .max_cost
+            .zip(usage.cost)
+            .is_some_and(|(max, cost)| cost >= max)
+        {
+            Some(BudgetLimit::Cost)
+        } else {
+            None
+        };
+        usage.exhausted = exhausted;
+        exhausted
+    }
+
+    pub fn usage(&self) -> BudgetUsage {
+        let mut usage = self
+            .usage
+            .lock()
+            .map(|usage| usage.clone())
+            .unwrap_or_default();
+        usage.duration_ms = self.started_at.elapsed().as_millis() as u64;
+        usage
+    }
+}
+
+// the io tools are called with, rolls the usage of sub-agents up into the calling run
+pub struct SubAgentUsageIO<'a> {
+    io: &'a dyn AgentIO,
+    budget: &'a BudgetTracker,
+}
+
+impl<'a> SubAgentUsageIO<'a> {
+    pub fn new(io: &'a dyn AgentIO, budget: &'a BudgetTracker) -> Self {
+        Self { io, budget }
+    }
+}
+
+impl AgentIO for SubAgentUsageIO<'_> {
+    fn get_id(&self) -> String {
+        self.io.get_id()
+    }
+
+    fn write(&self, content: &str) -> BackendResult<()> {
+        self.io.write(content)
+    }
+
+    fn write_status(&self, message: StatusMessage) -> BackendResult<()> {
+        self.io.write_status(message)
+    }
+
+    fn read(&self) -> BackendResult<String> {
+        self.io.read()
+    }
+
+    fn clear(&self) -> BackendResult<()> {
+        self.io.clear()
+    }
+
+    // not passed on, the calling run reports the sub-agent's usage as part of its own
+    fn report_usage(&self, usage: &BudgetUsage) {
+        self.budget.record_sub_agent_usage(usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_tracker() {
+        let usage = CompletionUsage {
+            input_tokens: 800,
+            output_tokens: 200,
+            ..Default::default()
+        };
+
+        let tracker = BudgetTracker::new(
+            AgentBudget {
+                max_tokens: Some(1500),
+                max_tool_calls: Some(2),
+                ..Default::default()
+            },
+            &Model::GPT4o,
+        );
+        tracker.record_completion(&usage);
+        assert_eq!(tracker.exhausted(), None);
+        assert!(tracker.start_tool_call());
+        assert!(tracker.start_tool_call());
+        assert!(!tracker.start_tool_call());
+        assert_eq!(tracker.exhausted(), Some(BudgetLimit::ToolCalls));
+        // the first limit that was reached sticks
+        tracker.record_completion(&usage);
+        assert_eq!(tracker.exhausted(), Some(BudgetLimit::ToolCalls));
+        let report = tracker.usage();
+        assert_eq!(report.completions, 2);
+        assert_eq!(report.tokens, 2000);
+        assert_eq!(report.tool_calls, 2);
+        assert_eq!(report.exhausted, Some(BudgetLimit::ToolCalls));
+        assert!(report.cost.is_some_and(|cost| cost > 0.0));
+
+        let model = Model::Custom {
+            name: "local".to_string(),
+            provider: crate::ai::llm::client::Provider::Custom("http://localhost".to_string()),
+            max_tokens: 8000,
+            vision: false,
+        };
+        let tracker = BudgetTracker::new(
+            AgentBudget {
+                max_cost: Some(0.001),
+                ..Default::default()
+            },
+            &model,
+        );
+        tracker.record_completion(&usage);
+        assert_eq!(tracker.usage().cost, None);
+        assert_eq!(tracker.exhausted(), None);
+
+        let tracker = BudgetTracker::new(
+            AgentBudget {
+                max_cost: Some(0.0005),
+                price: Some(ModelPrice {
+                    input: 1.0,
+                    output: 1.0,
+                    cache_read: 0.0,
+                    cache_write: 0.0,
+                }),
+                ..Default::default()
+            },
+            &model,
+        );
+        tracker.record_completion(&usage);
+        assert_eq!(tracker.exhausted(), Some(BudgetLimit::Cost));
+
+        let tracker = BudgetTracker::new(
+            AgentBudget {
+                max_duration: Some(Duration::ZERO),
+                ..Default::default()
+            },
+            &model,
+        );
+        assert_eq!(tracker.remaining_time(), Some(Duration::ZERO));
+        assert_eq!(tracker.exhausted(), Some(BudgetLimit::Duration));
+    }
+}
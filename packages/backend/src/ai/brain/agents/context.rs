@@ -1,18 +1,121 @@
-use crate::{ai::llm::models::Message, BackendResult};
+use crate::{ai::llm::models::Message, BackendError, BackendResult};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// the content of a url, fetched without access to the context
+#[derive(Debug, Clone)]
+pub struct FetchedUrl {
+    pub url: String,
+    pub content_type: String,
+    pub title: Option<String>,
+    pub content: Option<String>,
+}
+
+pub trait UrlFetcher: Send + Sync {
+    fn fetch_url(&self, url: &str) -> BackendResult<FetchedUrl>;
+}
 
 // TODO: should we return Vec<BackendResult> for non atomic batch apis?
-pub trait ContextManager {
+pub trait ContextManager: Send {
     fn get_context(&self, key: &str) -> BackendResult<Vec<Message>>;
     fn remove_context_messages(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()>;
     fn populate_context_content(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()>;
     fn add_resources(&mut self, key: &str, resource_ids: &[String]) -> BackendResult<()>;
     fn add_url(&mut self, key: &str, url: &str) -> BackendResult<()>;
     fn add_urls(&mut self, key: &str, urls: &[String]) -> BackendResult<Vec<BackendResult<()>>>;
+    // fetches urls for `add_fetched_url`, so that a shared context manager isn't locked
+    // while a page loads, `None` if `add_url` has to be used
+    fn url_fetcher(&self) -> Option<Arc<dyn UrlFetcher>> {
+        None
+    }
+    fn add_fetched_url(&mut self, key: &str, fetched: FetchedUrl) -> BackendResult<()>;
+    // text that doesn't come from a url or resource, e.g. the output of an external tool
+    fn add_text(&mut self, key: &str, title: &str, content: &str) -> BackendResult<()>;
     // TODO: this is mainly for backwards compatibility, we should phase it out
     fn get_sources_xml(&self, key: &str) -> BackendResult<String>;
     fn get_citation(&self, key: &str, message_id: &str, cited_text: &str) -> BackendResult<String>;
 }
 
+// gives tools running in parallel access to the same context manager,
+// every call holds the lock so mutations are serialised, urls are fetched outside of it
+pub struct SharedContextManager<'a, 'b> {
+    inner: &'a Mutex<&'b mut dyn ContextManager>,
+}
+
+impl<'a, 'b> SharedContextManager<'a, 'b> {
+    pub fn new(inner: &'a Mutex<&'b mut dyn ContextManager>) -> Self {
+        Self { inner }
+    }
+
+    fn lock(&self) -> BackendResult<MutexGuard<'a, &'b mut dyn ContextManager>> {
+        self.inner
+            .lock()
+            .map_err(|_| BackendError::GenericError("context manager lock poisoned".to_string()))
+    }
+}
+
+impl ContextManager for SharedContextManager<'_, '_> {
+    fn get_context(&self, key: &str) -> BackendResult<Vec<Message>> {
+        self.lock()?.get_context(key)
+    }
+
+    fn remove_context_messages(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()> {
+        self.lock()?.remove_context_messages(key, message_ids)
+    }
+
+    fn populate_context_content(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()> {
+        self.lock()?.populate_context_content(key, message_ids)
+    }
+
+    fn add_resources(&mut self, key: &str, resource_ids: &[String]) -> BackendResult<()> {
+        self.lock()?.add_resources(key, resource_ids)
+    }
+
+    fn add_url(&mut self, key: &str, url: &str) -> BackendResult<()> {
+        let fetcher = self.lock()?.url_fetcher();
+        match fetcher {
+            Some(fetcher) => {
+                let fetched = fetcher.fetch_url(url)?;
+                self.lock()?.add_fetched_url(key, fetched)
+            }
+            None => self.lock()?.add_url(key, url),
+        }
+    }
+
+    fn add_urls(&mut self, key: &str, urls: &[String]) -> BackendResult<Vec<BackendResult<()>>> {
+        let fetcher = self.lock()?.url_fetcher();
+        match fetcher {
+            Some(fetcher) => Ok(urls
+                .iter()
+                .map(|url| {
+                    let fetched = fetcher.fetch_url(url)?;
+                    self.lock()?.add_fetched_url(key, fetched)
+                })
+                .collect()),
+            None => self.lock()?.add_urls(key, urls),
+        }
+    }
+
+    fn url_fetcher(&self) -> Option<Arc<dyn UrlFetcher>> {
+        self.lock().ok()?.url_fetcher()
+    }
+
+    fn add_fetched_url(&mut self, key: &str, fetched: FetchedUrl) -> BackendResult<()> {
+        self.lock()?.add_fetched_url(key, fetched)
+    }
+
+    fn add_text(&mut self, key: &str, title: &str, content: &str) -> BackendResult<()> {
+        self.lock()?.add_text(key, title, content)
+    }
+
+    fn get_sources_xml(&self, key: &str) -> BackendResult<String> {
+        self.lock()?.get_sources_xml(key)
+    }
+
+    fn get_citation(&self, key: &str, message_id: &str, cited_text: &str) -> BackendResult<String> {
+        self.lock()?.get_citation(key, message_id, cited_text)
+    }
+}
+
 pub struct MockContextManager;
 
 impl MockContextManager {
@@ -54,6 +157,14 @@ impl ContextManager for MockContextManager {
         Ok(vec![])
     }
 
+    fn add_fetched_url(&mut self, _key: &str, _fetched: FetchedUrl) -> BackendResult<()> {
+        Ok(())
+    }
+
+    fn add_text(&mut self, _key: &str, _title: &str, _content: &str) -> BackendResult<()> {
+        Ok(())
+    }
+
     fn get_sources_xml(&self, _key: &str) -> BackendResult<String> {
         Ok("<sources></sources>".to_string())
     }
@@ -67,3 +178,99 @@ impl ContextManager for MockContextManager {
         Ok("<citation></citation>".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel as crossbeam;
+
+    struct BlockingFetcher {
+        started: crossbeam::Sender<()>,
+        proceed: crossbeam::Receiver<()>,
+    }
+
+    impl UrlFetcher for BlockingFetcher {
+        fn fetch_url(&self, url: &str) -> BackendResult<FetchedUrl> {
+            self.started.send(()).unwrap();
+            self.proceed.recv().unwrap();
+            Ok(FetchedUrl {
+                url: url.to_string(),
+                content_type: "Context(Webpage)".to_string(),
+                title: None,
+                content: Some("page".to_string()),
+            })
+        }
+    }
+
+    struct FetchingContextManager {
+        fetcher: Arc<BlockingFetcher>,
+        added: Vec<String>,
+    }
+
+    impl ContextManager for FetchingContextManager {
+        fn get_context(&self, _key: &str) -> BackendResult<Vec<Message>> {
+            Ok(vec![])
+        }
+        fn remove_context_messages(&mut self, _key: &str, _ids: &[String]) -> BackendResult<()> {
+            Ok(())
+        }
+        fn populate_context_content(&mut self, _key: &str, _ids: &[String]) -> BackendResult<()> {
+            Ok(())
+        }
+        fn add_resources(&mut self, _key: &str, _ids: &[String]) -> BackendResult<()> {
+            Ok(())
+        }
+        fn add_url(&mut self, _key: &str, _url: &str) -> BackendResult<()> {
+            panic!("urls are fetched through the url fetcher")
+        }
+        fn add_urls(
+            &mut self,
+            _key: &str,
+            _urls: &[String],
+        ) -> BackendResult<Vec<BackendResult<()>>> {
+            panic!("urls are fetched through the url fetcher")
+        }
+        fn url_fetcher(&self) -> Option<Arc<dyn UrlFetcher>> {
+            Some(self.fetcher.clone())
+        }
+        fn add_fetched_url(&mut self, _key: &str, fetched: FetchedUrl) -> BackendResult<()> {
+            self.added.push(fetched.url);
+            Ok(())
+        }
+        fn add_text(&mut self, _key: &str, _title: &str, _content: &str) -> BackendResult<()> {
+            Ok(())
+        }
+        fn get_sources_xml(&self, _key: &str) -> BackendResult<String> {
+            Ok(String::new())
+        }
+        fn get_citation(&self, _key: &str, _id: &str, _text: &str) -> BackendResult<String> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_shared_context_manager_fetches_urls_without_the_lock() {
+        let (started_tx, started) = crossbeam::bounded(1);
+        let (proceed, proceed_rx) = crossbeam::bounded(1);
+        let mut inner = FetchingContextManager {
+            fetcher: Arc::new(BlockingFetcher {
+                started: started_tx,
+                proceed: proceed_rx,
+            }),
+            added: vec![],
+        };
+        let shared: Mutex<&mut dyn ContextManager> = Mutex::new(&mut inner);
+
+        std::thread::scope(|scope| {
+            let fetch = scope
+                .spawn(|| SharedContextManager::new(&shared).add_url("key", "https://deta.surf"));
+            started.recv().unwrap();
+            // other tools can use the context while the page loads
+            assert!(shared.try_lock().is_ok());
+            proceed.send(()).unwrap();
+            fetch.join().unwrap().unwrap();
+        });
+
+        assert_eq!(inner.added, vec!["https://deta.surf".to_string()]);
+    }
+}
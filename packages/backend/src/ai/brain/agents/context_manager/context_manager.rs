@@ -2,17 +2,19 @@ use std::sync::Arc;
 
 use super::prompt::prompt;
 use crate::ai::brain::agents::context_manager::tools::{
-    AddResourcesTool, AddUrlsTool, PopulateContextContentTool,
+    AddResourcesTool, PopulateContextContentTool,
 };
 use crate::ai::brain::agents::{Agent, AgentConfig};
-use crate::ai::llm::client::LLMClient;
+use crate::ai::llm::client::ChatCompletionProvider;
 
-pub fn create_context_manager_agent(client: Arc<LLMClient>) -> Agent {
+pub fn create_context_manager_agent(client: Arc<dyn ChatCompletionProvider>) -> Agent {
     let system_prompt = prompt();
 
     let config = AgentConfig {
         name: "context_manager_agent".to_string(),
         max_iterations: 3,
+        // its tools only touch the context which is serialised anyway
+        max_parallel_tool_calls: 1,
         system_prompt,
         fallback_to_text: true,
         retry_on_parse_error: true,
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use crate::ai::brain::agents::{tools::Tool, Agent, AgentConfig};
+use crate::ai::llm::client::ChatCompletionProvider;
+use crate::store::models::CustomAgent;
+
+// `tools` are the instances of the definition's allowed tools
+pub fn create_custom_agent(
+    client: Arc<dyn ChatCompletionProvider>,
+    definition: &CustomAgent,
+    tools: Vec<Box<dyn Tool>>,
+) -> Agent {
+    let config = AgentConfig {
+        name: definition.name.clone(),
+        max_iterations: definition.max_iterations.max(1) as usize,
+        max_parallel_tool_calls: 4,
+        system_prompt: definition.system_prompt.clone(),
+        fallback_to_text: true,
+        retry_on_parse_error: true,
+        write_status_to_io: true,
+        // the answer is what the user asked the agent for, e.g. a summary
+        write_final_response_to_io: true,
+    };
+
+    let mut agent = Agent::new(client, config);
+    for tool in tools {
+        agent.add_tool(tool);
+    }
+    agent
+}
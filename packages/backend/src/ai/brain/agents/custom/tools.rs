@@ -0,0 +1,260 @@
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::ai::brain::agents::budget::AgentBudget;
+use crate::ai::brain::agents::io::StatusMessage;
+use crate::ai::brain::agents::tools::ToolRiskLevel;
+use crate::ai::brain::agents::{Agent, AgentIO, AgentResult, ContextManager, ExecuteConfig, Tool};
+use crate::ai::brain::js_tools::{JSToolRegistry, ToolName};
+use crate::ai::brain::prompts::current_time_prompt;
+use crate::ai::llm::client::{CancellationToken, Model};
+use crate::store::models::{CustomAgent, CustomTool};
+use crate::{BackendError, BackendResult};
+
+// calls the js function registered under the tool's name with the arguments as a json string
+pub struct JSCustomTool {
+    definition: CustomTool,
+    parameters_schema: serde_json::Value,
+    js_tool_registry: Arc<JSToolRegistry>,
+}
+
+impl JSCustomTool {
+    pub fn new(
+        definition: CustomTool,
+        js_tool_registry: Arc<JSToolRegistry>,
+    ) -> BackendResult<Self> {
+        let parameters_schema = serde_json::from_str(&definition.parameters_schema)?;
+        Ok(Self {
+            definition,
+            parameters_schema,
+            js_tool_registry,
+        })
+    }
+}
+
+// what the js function resolves to, everything is optional
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct JSCustomToolResult {
+    // written to the note
+    #[serde(default)]
+    pub output: Option<String>,
+    // added to the context
+    #[serde(default)]
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub resource_ids: Vec<String>,
+}
+
+impl Tool for JSCustomTool {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn description(&self) -> &str {
+        &self.definition.description
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        self.definition.execution_message.as_deref()
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        self.parameters_schema.clone()
+    }
+
+    // runs user defined code in the app
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::Medium
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let result: Option<JSCustomToolResult> = self.js_tool_registry.execute_tool(
+            &ToolName::Custom(self.definition.name.clone()),
+            Some(vec![parameters.to_string()]),
+        )?;
+        cancellation_token.check()?;
+        let result = result.unwrap_or_default();
+
+        let key = io.get_id();
+        if !result.urls.is_empty() {
+            for (url, added) in result
+                .urls
+                .iter()
+                .zip(context_manager.add_urls(&key, &result.urls)?)
+            {
+                if let Err(e) = added {
+                    io.write_status(StatusMessage::new_error(&format!(
+                        "Failed to add {}: {}",
+                        url, e
+                    )))?;
+                }
+            }
+        }
+        if !result.resource_ids.is_empty() {
+            context_manager.add_resources(&key, &result.resource_ids)?;
+        }
+        if let Some(output) = result.output {
+            io.write(&output)?;
+        }
+        Ok(())
+    }
+}
+
+// a user defined agent the lead agent delegates tasks to
+pub struct CustomAgentTool {
+    name: String,
+    description: String,
+    model: Option<Model>,
+    agent: Agent,
+}
+
+impl CustomAgentTool {
+    pub fn new(definition: &CustomAgent, agent: Agent) -> BackendResult<Self> {
+        let model = definition
+            .model
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?;
+        Ok(Self {
+            name: definition.name.clone(),
+            description: definition.description.clone(),
+            model,
+            agent,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct CustomAgentArgs {
+    task: String,
+}
+
+impl Tool for CustomAgentTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        None
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "task": {
+                    "type": "string",
+                    "description": "The task for the agent with everything it needs to know"
+                },
+            },
+            "required": ["task"]
+        })
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        execution_id: String,
+        model: Model,
+        custom_key: Option<String>,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let args: CustomAgentArgs = serde_json::from_value(parameters)?;
+
+        let config = ExecuteConfig {
+            user_message: args.task,
+            execution_id,
+            model: self.model.clone().unwrap_or(model),
+            custom_key,
+            system_message_preamble: Some(current_time_prompt()),
+            allowed_tools: None,
+            recorder: None,
+            history: vec![],
+            budget: AgentBudget::default(),
+            fixture_dir: None,
+        };
+        let result = self
+            .agent
+            .execute(config, io, context_manager, cancellation_token)?;
+        match result {
+            AgentResult::Success(_response) => Ok(()),
+            AgentResult::MaxIterationsReached(response) => Err(BackendError::GenericError(
+                format!("{} agent max iterations reached: {}", self.name, response),
+            )),
+            AgentResult::Cancelled => Err(BackendError::LLMClientErrorCancelled),
+            AgentResult::Error(error) => Err(BackendError::GenericError(format!(
+                "{} agent error: {}",
+                self.name, error
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::brain::agents::context::MockContextManager;
+    use crate::ai::brain::agents::io::MemoryIO;
+    use crate::ai::brain::replay::{Fixture, FixtureReplayer, JSToolFixture, RecordedJSTool};
+    use crate::store::models::{current_time, random_uuid};
+
+    #[test]
+    fn test_js_custom_tool() {
+        let registry = Arc::new(JSToolRegistry::new());
+        let replayer = Arc::new(FixtureReplayer::new(Fixture {
+            completions: vec![],
+            js_tools: vec![RecordedJSTool {
+                tool: "fetch_calendar".to_string(),
+                result: Some(json!({"output": "2 meetings today", "resource_ids": ["r1"]})),
+                error: None,
+            }],
+        }));
+        registry
+            .set_fixture(Some(JSToolFixture::Replay(replayer.clone())))
+            .unwrap();
+
+        let tool = JSCustomTool::new(
+            CustomTool {
+                id: random_uuid(),
+                name: "fetch_calendar".to_string(),
+                description: "Fetches the user's meetings".to_string(),
+                parameters_schema: r#"{"type": "object", "properties": {}}"#.to_string(),
+                execution_message: None,
+                created_at: current_time(),
+                updated_at: current_time(),
+            },
+            registry,
+        )
+        .unwrap();
+        assert_eq!(tool.parameters_schema()["type"], "object");
+
+        let io = MemoryIO::new();
+        tool.execute(
+            json!({}),
+            "test".to_string(),
+            Model::GPT4oMini,
+            None,
+            &io,
+            &mut MockContextManager::new(),
+            CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(io.read().unwrap(), "2 meetings today");
+        assert_eq!(replayer.remaining().unwrap(), 0);
+    }
+}
@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use super::budget::BudgetUsage;
 use crate::BackendResult;
 use std::sync::RwLock;
 
@@ -9,6 +10,10 @@ pub enum StatusType {
     Status,
     Error,
     Sources,
+    // the value is a json `ApprovalRequest`, answered through `ToolApprovals::respond`
+    ApprovalRequest,
+    // the value is a json `BudgetUsage`, written at the end of a run
+    Usage,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -39,15 +44,34 @@ impl StatusMessage {
             value: value.to_string(),
         }
     }
+
+    pub fn new_approval_request(value: &str) -> Self {
+        Self {
+            status_type: StatusType::ApprovalRequest,
+            value: value.to_string(),
+        }
+    }
+
+    pub fn new_usage(value: &str) -> Self {
+        Self {
+            status_type: StatusType::Usage,
+            value: value.to_string(),
+        }
+    }
 }
 
 // TODO: readAt and writeAt, append?
-pub trait AgentIO {
+// shared between tools running in parallel
+pub trait AgentIO: Send + Sync {
     fn get_id(&self) -> String;
     fn write(&self, content: &str) -> BackendResult<()>;
     fn write_status(&self, message: StatusMessage) -> BackendResult<()>;
     fn read(&self) -> BackendResult<String>;
     fn clear(&self) -> BackendResult<()>;
+
+    // called with the usage of every finished run, sub-agents called as tools
+    // report theirs to the calling agent through it
+    fn report_usage(&self, _usage: &BudgetUsage) {}
 }
 
 pub struct MemoryIO {
@@ -0,0 +1,334 @@
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::ai::brain::agents::io::StatusMessage;
+use crate::ai::brain::agents::{AgentIO, ContextManager, Tool};
+use crate::ai::llm::client::{CancellationToken, Model};
+use crate::ai::AI;
+use crate::store::db::Database;
+use crate::store::models::{CompositeResource, Resource};
+use crate::{BackendError, BackendResult};
+
+pub const LIBRARY_SEARCH_TOOL_NAME: &str = "library_search";
+
+const DEFAULT_MAX_RESULTS: usize = 5;
+const MAX_RESULTS_LIMIT: usize = 20;
+// candidates fetched from each engine before filtering
+const CANDIDATES_LIMIT: usize = 100;
+const EMBEDDINGS_DISTANCE_THRESHOLD: f32 = 0.4;
+// dampens the weight of the top ranks in reciprocal rank fusion, 60 is the usual choice
+const RRF_K: f64 = 60.0;
+
+// hybrid keyword + embeddings search over the user's saved resources
+pub struct LibrarySearchTool {
+    // rusqlite connections are not `Sync`
+    db: Mutex<Database>,
+    ai: Arc<AI>,
+}
+
+impl LibrarySearchTool {
+    pub fn new(db_path: &str, ai: Arc<AI>) -> BackendResult<Self> {
+        Ok(Self {
+            db: Mutex::new(Database::new(db_path, false)?),
+            ai,
+        })
+    }
+
+    // resource ids in the spaces whose name matches, `None` if there is no space filter
+    fn space_resource_ids(
+        db: &Database,
+        space: Option<&str>,
+    ) -> BackendResult<Option<Vec<String>>> {
+        let space = match space {
+            Some(space) if !space.trim().is_empty() => space.trim(),
+            _ => return Ok(None),
+        };
+        let mut resource_ids = vec![];
+        for item in db.search_spaces(space)? {
+            resource_ids.extend(db.list_resource_ids_by_space_id(&item.space.id)?);
+        }
+        Ok(Some(resource_ids))
+    }
+
+    fn search(&self, args: &LibrarySearchArgs) -> BackendResult<Vec<CompositeResource>> {
+        let filters = LibrarySearchFilters::from_args(args)?;
+        let max_results = args
+            .max_results
+            .unwrap_or(DEFAULT_MAX_RESULTS)
+            .clamp(1, MAX_RESULTS_LIMIT);
+
+        let db = self
+            .db
+            .lock()
+            .map_err(|e| BackendError::GenericError(format!("library db lock poisoned: {}", e)))?;
+        let filtered_resource_ids = Self::space_resource_ids(&db, args.space.as_deref())?;
+
+        let keyword_results: Vec<CompositeResource> = db
+            .search_resources(
+                &args.query,
+                &filtered_resource_ids,
+                false,
+                Some(CANDIDATES_LIMIT as i64),
+            )?
+            .items
+            .into_iter()
+            .map(|item| item.resource)
+            .collect();
+        // an empty space filter has no embeddings to search
+        let embeddings_results = match filtered_resource_ids
+            .as_ref()
+            .is_none_or(|ids| !ids.is_empty())
+        {
+            true => self.ai.vector_search(
+                &db,
+                args.query.clone(),
+                CANDIDATES_LIMIT,
+                filtered_resource_ids,
+                true,
+                Some(EMBEDDINGS_DISTANCE_THRESHOLD),
+            )?,
+            false => vec![],
+        };
+
+        let mut resources = HashMap::new();
+        let rankings: Vec<Vec<String>> = vec![keyword_results, embeddings_results]
+            .into_iter()
+            .map(|results| {
+                let mut seen = HashSet::new();
+                results
+                    .into_iter()
+                    .filter(|r| filters.matches(&r.resource))
+                    .filter(|r| seen.insert(r.resource.id.clone()))
+                    .map(|r| {
+                        let id = r.resource.id.clone();
+                        resources.entry(id.clone()).or_insert(r);
+                        id
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(reciprocal_rank_fusion(&rankings)
+            .into_iter()
+            .take(max_results)
+            .filter_map(|id| resources.remove(&id))
+            .collect())
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct LibrarySearchArgs {
+    query: String,
+    space: Option<String>,
+    resource_types: Option<Vec<String>>,
+    created_after: Option<String>,
+    created_before: Option<String>,
+    max_results: Option<usize>,
+}
+
+struct LibrarySearchFilters {
+    // lowercased human readable types
+    resource_types: Option<HashSet<String>>,
+    created_after: Option<chrono::DateTime<chrono::Utc>>,
+    created_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl LibrarySearchFilters {
+    fn from_args(args: &LibrarySearchArgs) -> BackendResult<Self> {
+        let resource_types = args
+            .resource_types
+            .as_ref()
+            .filter(|types| !types.is_empty())
+            .map(|types| types.iter().map(|t| t.trim().to_lowercase()).collect());
+        Ok(Self {
+            resource_types,
+            created_after: args.created_after.as_deref().map(parse_date).transpose()?,
+            created_before: args.created_before.as_deref().map(parse_date).transpose()?,
+        })
+    }
+
+    fn matches(&self, resource: &Resource) -> bool {
+        if resource.resource_type.ends_with(".ignore") {
+            return false;
+        }
+        if let Some(types) = &self.resource_types {
+            if !types.contains(&resource.get_human_readable_type().to_lowercase()) {
+                return false;
+            }
+        }
+        if self.created_after.is_some_and(|t| resource.created_at < t) {
+            return false;
+        }
+        if self
+            .created_before
+            .is_some_and(|t| resource.created_at >= t)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+// merges the rankings of the keyword and embeddings search, each id scores 1 / (k + rank)
+// in every ranking it appears in, so ids found by both engines rise to the top
+fn reciprocal_rank_fusion(rankings: &[Vec<String>]) -> Vec<String> {
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    // ties keep the order in which the ids were first seen
+    let mut order: Vec<&str> = vec![];
+    for ranking in rankings {
+        for (rank, id) in ranking.iter().enumerate() {
+            let score = scores.entry(id).or_insert_with(|| {
+                order.push(id);
+                0.0
+            });
+            *score += 1.0 / (RRF_K + rank as f64 + 1.0);
+        }
+    }
+    order.sort_by(|a, b| scores[b].total_cmp(&scores[a]));
+    order.into_iter().map(|id| id.to_string()).collect()
+}
+
+// accepts RFC 3339 timestamps or plain `YYYY-MM-DD` dates (midnight UTC)
+fn parse_date(value: &str) -> BackendResult<chrono::DateTime<chrono::Utc>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|e| BackendError::GenericError(format!("invalid date '{}': {}", value, e)))
+}
+
+impl Tool for LibrarySearchTool {
+    fn name(&self) -> &str {
+        LIBRARY_SEARCH_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Searches the user's own saved library (pages, PDFs, notes, posts, images, ...) and adds the best matches to the context. Use it for questions about things the user saved or read before."
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        Some("Searching your library...")
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Keywords or a short description of what to look for"
+                },
+                "space": {
+                    "type": "string",
+                    "description": "Only search resources in the space (folder) with this name"
+                },
+                "resource_types": {
+                    "type": "array",
+                    "items": {
+                        "type": "string"
+                    },
+                    "description": "Only return these types, e.g. \"Link\", \"Article\", \"PDF\", \"Note\", \"Image\", \"Youtube Video\""
+                },
+                "created_after": {
+                    "type": "string",
+                    "description": "Only return resources saved at or after this date (YYYY-MM-DD or RFC 3339)"
+                },
+                "created_before": {
+                    "type": "string",
+                    "description": "Only return resources saved before this date (YYYY-MM-DD or RFC 3339)"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "description": "The maximum number of resources to add to the context",
+                    "default": DEFAULT_MAX_RESULTS
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let args: LibrarySearchArgs = serde_json::from_value(parameters)?;
+        let results = self.search(&args)?;
+        cancellation_token.check()?;
+
+        if results.is_empty() {
+            return Err(BackendError::GenericError(format!(
+                "no resources found in the library for '{}'",
+                args.query
+            )));
+        }
+        io.write_status(StatusMessage::new_status(&format!(
+            "Found {} resources in your library",
+            results.len()
+        )))?;
+
+        let resource_ids: Vec<String> = results.into_iter().map(|r| r.resource.id).collect();
+        context_manager.add_resources(&io.get_id(), &resource_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(resource_type: &str, created_at: &str) -> Resource {
+        Resource {
+            id: "id".to_string(),
+            resource_path: String::new(),
+            resource_type: resource_type.to_string(),
+            created_at: parse_date(created_at).unwrap(),
+            updated_at: parse_date(created_at).unwrap(),
+            deleted: 0,
+        }
+    }
+
+    #[test]
+    fn test_library_search_filters() {
+        let args: LibrarySearchArgs = serde_json::from_value(json!({
+            "query": "rust",
+            "resource_types": ["pdf", " Link"],
+            "created_after": "2024-03-01",
+            "created_before": "2024-04-01T00:00:00Z"
+        }))
+        .unwrap();
+        let filters = LibrarySearchFilters::from_args(&args).unwrap();
+
+        assert!(filters.matches(&resource("application/pdf", "2024-03-01")));
+        assert!(filters.matches(&resource("application/vnd.space.link", "2024-03-31")));
+        assert!(!filters.matches(&resource("application/vnd.space.link", "2024-04-01")));
+        assert!(!filters.matches(&resource("application/pdf", "2024-02-29")));
+        assert!(!filters.matches(&resource("application/vnd.space.article", "2024-03-15")));
+        assert!(!filters.matches(&resource("application/pdf.ignore", "2024-03-15")));
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let keyword = ids(&["a", "b", "c"]);
+        let embeddings = ids(&["d", "c", "b"]);
+
+        // found by both engines beats the top hit of a single one
+        assert_eq!(
+            reciprocal_rank_fusion(&[keyword.clone(), embeddings]),
+            ids(&["b", "c", "a", "d"])
+        );
+        assert_eq!(reciprocal_rank_fusion(&[keyword.clone(), vec![]]), keyword);
+    }
+
+    #[test]
+    fn test_library_search_invalid_date() {
+        assert!(parse_date("last month").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use crossbeam_channel as crossbeam;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ai::llm::client::cancellation::POLL_INTERVAL;
+use crate::ai::llm::client::CancellationToken;
+use crate::store::models::McpServer;
+use crate::{BackendError, BackendResult};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+// upper bound for a single request, the cancellation token can end it earlier
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+type PendingRequests = Arc<Mutex<HashMap<u64, crossbeam::Sender<BackendResult<Value>>>>>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "inputSchema", default)]
+    pub input_schema: Value,
+    #[serde(default)]
+    pub annotations: McpToolAnnotations,
+}
+
+// hints of the server about the tool's behavior, not to be trusted for anything but defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct McpToolAnnotations {
+    #[serde(rename = "readOnlyHint", default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpContent {
+    Text {
+        text: String,
+    },
+    Resource {
+        resource: McpResourceContent,
+    },
+    // images, audio, ... which agents can't use yet
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct McpResourceContent {
+    #[serde(default)]
+    pub uri: Option<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct McpToolCallResult {
+    #[serde(default)]
+    pub content: Vec<McpContent>,
+    #[serde(rename = "isError", default)]
+    pub is_error: bool,
+}
+
+impl McpToolCallResult {
+    // the text parts of the result, other content is skipped
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|content| match content {
+                McpContent::Text { text } => Some(text.as_str()),
+                McpContent::Resource { resource } => resource.text.as_deref(),
+                McpContent::Other => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// JSON-RPC client for an MCP server running as a child process,
+// messages are newline delimited json over the child's stdin and stdout
+pub struct McpClient {
+    name: String,
+    child: Mutex<Child>,
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: PendingRequests,
+    closed: Arc<AtomicBool>,
+    next_id: AtomicU64,
+}
+
+fn write_message(stdin: &Mutex<ChildStdin>, message: &Value) -> BackendResult<()> {
+    let mut stdin = stdin
+        .lock()
+        .map_err(|_| BackendError::GenericError("mcp stdin lock poisoned".to_string()))?;
+    writeln!(stdin, "{}", message)?;
+    stdin.flush()?;
+    Ok(())
+}
+
+impl McpClient {
+    // starts the server and does the initialization handshake
+    pub fn spawn(
+        server: &McpServer,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Self> {
+        let child = Command::new(&server.command)
+            .args(&server.args)
+            .envs(&server.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                BackendError::GenericError(format!(
+                    "failed to start mcp server {}: {}",
+                    server.name, e
+                ))
+            })?;
+        let client = Self::start(server.name.clone(), child)?;
+        client.initialize(cancellation_token)?;
+        Ok(client)
+    }
+
+    fn start(name: String, mut child: Child) -> BackendResult<Self> {
+        let (stdin, stdout, stderr) =
+            match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+                (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+                _ => {
+                    return Err(BackendError::GenericError(
+                        "mcp server stdio not piped".to_string(),
+                    ))
+                }
+            };
+        let stdin = Arc::new(Mutex::new(stdin));
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let reader = {
+            let name = name.clone();
+            let stdin = stdin.clone();
+            let pending = pending.clone();
+            let closed = closed.clone();
+            move || {
+                for line in BufReader::new(stdout).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<Value>(&line) {
+                        Ok(message) => handle_message(&name, message, &stdin, &pending),
+                        Err(e) => tracing::warn!("invalid message from mcp server {}: {}", name, e),
+                    }
+                }
+                // the server exited, nothing pending will be answered anymore
+                closed.store(true, Ordering::SeqCst);
+                if let Ok(mut pending) = pending.lock() {
+                    for (_, tx) in pending.drain() {
+                        let _ = tx.send(Err(BackendError::GenericError(format!(
+                            "mcp server {} exited",
+                            name
+                        ))));
+                    }
+                }
+            }
+        };
+        std::thread::Builder::new()
+            .name(format!("mcp-{}", name))
+            .spawn(reader)?;
+
+        let stderr_name = name.clone();
+        std::thread::Builder::new()
+            .name(format!("mcp-{}-stderr", name))
+            .spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    tracing::debug!("mcp server {}: {}", stderr_name, line);
+                }
+            })?;
+
+        Ok(Self {
+            name,
+            child: Mutex::new(child),
+            stdin,
+            pending,
+            closed,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn initialize(&self, cancellation_token: &CancellationToken) -> BackendResult<()> {
+        self.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {
+                    "name": "surf",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+            cancellation_token,
+        )?;
+        self.notify("notifications/initialized", json!({}))
+    }
+
+    pub fn request(
+        &self,
+        method: &str,
+        params: Value,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Value> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(BackendError::GenericError(format!(
+                "mcp server {} exited",
+                self.name
+            )));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = crossbeam::bounded(1);
+        self.pending
+            .lock()
+            .map_err(|_| BackendError::GenericError("mcp pending lock poisoned".to_string()))?
+            .insert(id, tx);
+
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(e) = write_message(&self.stdin, &message) {
+            self.forget(id);
+            return Err(e);
+        }
+
+        let started = Instant::now();
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(result) => return result,
+                Err(crossbeam::RecvTimeoutError::Disconnected) => {
+                    return Err(BackendError::GenericError(format!(
+                        "mcp server {} exited",
+                        self.name
+                    )))
+                }
+                Err(crossbeam::RecvTimeoutError::Timeout) => {
+                    let error = match cancellation_token.check() {
+                        Err(e) => e,
+                        Ok(()) if started.elapsed() >= REQUEST_TIMEOUT => {
+                            BackendError::GenericError(format!(
+                                "mcp server {} did not answer {} in time",
+                                self.name, method
+                            ))
+                        }
+                        Ok(()) => continue,
+                    };
+                    self.forget(id);
+                    let _ = self.notify(
+                        "notifications/cancelled",
+                        json!({"requestId": id, "reason": error.to_string()}),
+                    );
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+
+    pub fn notify(&self, method: &str, params: Value) -> BackendResult<()> {
+        write_message(
+            &self.stdin,
+            &json!({"jsonrpc": "2.0", "method": method, "params": params}),
+        )
+    }
+
+    pub fn list_tools(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Vec<McpToolInfo>> {
+        let mut tools = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params, cancellation_token)?;
+            let page: Vec<McpToolInfo> =
+                serde_json::from_value(result.get("tools").cloned().unwrap_or(json!([])))?;
+            tools.extend(page);
+            cursor = result
+                .get("nextCursor")
+                .and_then(|c| c.as_str())
+                .map(String::from);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    pub fn call_tool(
+        &self,
+        name: &str,
+        arguments: Value,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<McpToolCallResult> {
+        let result = self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+            cancellation_token,
+        )?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        if let Ok(mut child) = self.child.lock() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn handle_message(
+    name: &str,
+    message: Value,
+    stdin: &Mutex<ChildStdin>,
+    pending: &PendingRequests,
+) {
+    let id = message.get("id").cloned();
+    // requests from the server, only pings are supported
+    if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+        if let Some(id) = id {
+            let response = match method {
+                "ping" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
+                _ => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("method not found: {}", method)},
+                }),
+            };
+            if let Err(e) = write_message(stdin, &response) {
+                tracing::warn!("failed to answer mcp server {}: {}", name, e);
+            }
+        }
+        return;
+    }
+
+    let id = match id.as_ref().and_then(|id| id.as_u64()) {
+        Some(id) => id,
+        None => return,
+    };
+    let tx = match pending.lock() {
+        Ok(mut pending) => pending.remove(&id),
+        Err(_) => None,
+    };
+    if let Some(tx) = tx {
+        let result = match message.get("error") {
+            Some(error) => Err(BackendError::GenericError(format!(
+                "mcp server {} error {}: {}",
+                name,
+                error.get("code").unwrap_or(&Value::Null),
+                error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("unknown error")
+            ))),
+            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let _ = tx.send(result);
+    }
+}
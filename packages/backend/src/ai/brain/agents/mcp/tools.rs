@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use super::client::{McpClient, McpToolInfo};
+use crate::ai::brain::agents::io::StatusMessage;
+use crate::ai::brain::agents::tools::ToolRiskLevel;
+use crate::ai::brain::agents::{AgentIO, ContextManager, Tool};
+use crate::ai::llm::client::{CancellationToken, Model};
+use crate::{BackendError, BackendResult};
+
+// a tool of an MCP server, its output is added to the context
+pub struct McpTool {
+    client: Arc<McpClient>,
+    // `<server>_<tool>` so that tools of different servers don't clash
+    name: String,
+    remote_name: String,
+    description: String,
+    parameters_schema: serde_json::Value,
+    read_only: bool,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, info: McpToolInfo) -> Self {
+        let name = format!("{}_{}", client.name(), info.name)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let parameters_schema = if info.input_schema.is_object() {
+            info.input_schema
+        } else {
+            serde_json::json!({"type": "object", "properties": {}})
+        };
+        let description = match info.description {
+            Some(description) => description,
+            None => format!("Tool {} of {}", info.name, client.name()),
+        };
+        Self {
+            name,
+            description,
+            remote_name: info.name,
+            parameters_schema,
+            read_only: info.annotations.read_only,
+            client,
+        }
+    }
+
+    // all tools the server offers
+    pub fn discover(
+        client: Arc<McpClient>,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Vec<Self>> {
+        Ok(client
+            .list_tools(cancellation_token)?
+            .into_iter()
+            .map(|info| Self::new(client.clone(), info))
+            .collect())
+    }
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        None
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        self.parameters_schema.clone()
+    }
+
+    // anything could happen on the server's side unless it says otherwise
+    fn risk_level(&self) -> ToolRiskLevel {
+        if self.read_only {
+            ToolRiskLevel::Low
+        } else {
+            ToolRiskLevel::High
+        }
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        io.write_status(StatusMessage::new_status(&format!(
+            "Using {} from {}...",
+            self.remote_name,
+            self.client.name()
+        )))?;
+        let result = self
+            .client
+            .call_tool(&self.remote_name, parameters, &cancellation_token)?;
+        let text = result.text();
+        if result.is_error {
+            return Err(BackendError::GenericError(format!(
+                "{} failed: {}",
+                self.remote_name, text
+            )));
+        }
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        context_manager.add_text(
+            &io.get_id(),
+            &format!("{} ({})", self.remote_name, self.client.name()),
+            &text,
+        )
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ai::brain::agents::context::MockContextManager;
+    use crate::ai::brain::agents::io::MemoryIO;
+    use crate::store::models::{current_time, random_uuid, McpServer};
+
+    fn stub_server() -> McpServer {
+        McpServer {
+            id: random_uuid(),
+            name: "tracker".to_string(),
+            command: "sh".to_string(),
+            args: vec![format!(
+                "{}/src/ai/brain/fixtures/mcp_stub_server.sh",
+                env!("CARGO_MANIFEST_DIR")
+            )],
+            env: Default::default(),
+            enabled: true,
+            created_at: current_time(),
+            updated_at: current_time(),
+        }
+    }
+
+    #[test]
+    fn test_mcp_tools() {
+        let token = CancellationToken::new();
+        let client = Arc::new(McpClient::spawn(&stub_server(), &token).unwrap());
+        let tools = McpTool::discover(client.clone(), &token).unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert_eq!(names, vec!["tracker_search_issues", "tracker_fail"]);
+        assert_eq!(tools[0].parameters_schema()["required"][0], "query");
+
+        let result = client
+            .call_tool(
+                "search_issues",
+                serde_json::json!({"query": "crash"}),
+                &token,
+            )
+            .unwrap();
+        assert_eq!(result.text(), "#42 crash on startup");
+
+        let execute = |tool: &McpTool| {
+            tool.execute(
+                serde_json::json!({"query": "crash"}),
+                "test".to_string(),
+                Model::GPT4oMini,
+                None,
+                &MemoryIO::new(),
+                &mut MockContextManager::new(),
+                token.clone(),
+            )
+        };
+        execute(&tools[0]).unwrap();
+        let error = execute(&tools[1]).unwrap_err().to_string();
+        assert!(error.contains("tracker unavailable"));
+        assert!(client
+            .call_tool("missing", serde_json::json!({}), &token)
+            .is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+use crate::ai::brain::agents::tools::ToolRiskLevel;
+use crate::ai::brain::agents::{AgentIO, ContextManager, Tool};
+use crate::ai::llm::client::{CancellationToken, Model};
+use crate::ai::AI;
+use crate::store::db::Database;
+use crate::{BackendError, BackendResult};
+
+// shared by the memory tools
+struct MemoryStore {
+    // rusqlite connections are not `Sync`
+    db: Mutex<Database>,
+    ai: Arc<AI>,
+}
+
+impl MemoryStore {
+    fn db(&self) -> BackendResult<std::sync::MutexGuard<'_, Database>> {
+        self.db
+            .lock()
+            .map_err(|_| BackendError::GenericError("memory db lock poisoned".to_string()))
+    }
+}
+
+// tools to save, update and forget what the assistant remembers about the user,
+// the relevant memories are part of the system prompt with their ids
+pub fn create_memory_tools(db_path: &str, ai: Arc<AI>) -> BackendResult<Vec<Box<dyn Tool>>> {
+    let store = Arc::new(MemoryStore {
+        db: Mutex::new(Database::new(db_path, false)?),
+        ai,
+    });
+    Ok(vec![
+        Box::new(SaveMemoryTool {
+            store: Arc::clone(&store),
+        }),
+        Box::new(UpdateMemoryTool {
+            store: Arc::clone(&store),
+        }),
+        Box::new(ForgetMemoryTool { store }),
+    ])
+}
+
+#[derive(Debug, Deserialize)]
+struct SaveMemoryArgs {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateMemoryArgs {
+    id: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForgetMemoryArgs {
+    id: String,
+}
+
+pub struct SaveMemoryTool {
+    store: Arc<MemoryStore>,
+}
+
+impl Tool for SaveMemoryTool {
+    fn name(&self) -> &str {
+        "save_memory"
+    }
+
+    fn description(&self) -> &str {
+        "Remembers a lasting preference or fact about the user for future conversations, e.g. \"writes in British English\" or \"their thesis is about bee navigation\". Only use it for things the user would want remembered, not for details of the current task."
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        Some("Remembering...")
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "content": {
+                    "type": "string",
+                    "description": "The preference or fact as a short self-contained sentence"
+                }
+            },
+            "required": ["content"]
+        })
+    }
+
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::Medium
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        _io: &dyn AgentIO,
+        _context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let args: SaveMemoryArgs = serde_json::from_value(parameters)?;
+        cancellation_token.check()?;
+        let mut db = self.store.db()?;
+        self.store.ai.save_memory(&mut db, &args.content)?;
+        Ok(())
+    }
+}
+
+pub struct UpdateMemoryTool {
+    store: Arc<MemoryStore>,
+}
+
+impl Tool for UpdateMemoryTool {
+    fn name(&self) -> &str {
+        "update_memory"
+    }
+
+    fn description(&self) -> &str {
+        "Replaces a remembered preference or fact about the user that changed or was wrong, by the id shown in the system prompt."
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        Some("Updating memory...")
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "The id of the memory"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The new preference or fact as a short self-contained sentence"
+                }
+            },
+            "required": ["id", "content"]
+        })
+    }
+
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::High
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        _io: &dyn AgentIO,
+        _context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let args: UpdateMemoryArgs = serde_json::from_value(parameters)?;
+        cancellation_token.check()?;
+        let mut db = self.store.db()?;
+        self.store
+            .ai
+            .update_memory(&mut db, &args.id, &args.content)?;
+        Ok(())
+    }
+}
+
+pub struct ForgetMemoryTool {
+    store: Arc<MemoryStore>,
+}
+
+impl Tool for ForgetMemoryTool {
+    fn name(&self) -> &str {
+        "forget_memory"
+    }
+
+    fn description(&self) -> &str {
+        "Forgets a remembered preference or fact about the user, by the id shown in the system prompt. Use it when the user asks to forget something or it no longer applies."
+    }
+
+    fn execution_message(&self) -> Option<&str> {
+        Some("Forgetting...")
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "The id of the memory"
+                }
+            },
+            "required": ["id"]
+        })
+    }
+
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::High
+    }
+
+    fn execute(
+        &self,
+        parameters: serde_json::Value,
+        _execution_id: String,
+        _model: Model,
+        _custom_key: Option<String>,
+        _io: &dyn AgentIO,
+        _context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<()> {
+        let args: ForgetMemoryArgs = serde_json::from_value(parameters)?;
+        cancellation_token.check()?;
+        if !self.store.db()?.delete_memory(&args.id)? {
+            return Err(BackendError::GenericError(format!(
+                "memory {} not found",
+                args.id
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,29 +1,43 @@
+pub mod approval;
+pub mod budget;
 pub mod context;
 pub mod context_manager;
+pub mod custom;
 pub mod io;
+pub mod library;
+pub mod mcp;
+pub mod memory;
+pub mod run_log;
 pub mod surflet;
 pub mod tools;
 pub mod websearch;
 
+use approval::{ApprovalDecision, ToolApprovals};
+use budget::{AgentBudget, BudgetTracker, SubAgentUsageIO};
 use io::AgentIO;
+use run_log::{RecordedIteration, RunRecorder};
 use tools::{FunctionCall, Tool, ToolCall, ToolResult};
 
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tracing::instrument::WithSubscriber;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use crate::ai::brain::agents::context::ContextManager;
+use crate::ai::brain::agents::context::{ContextManager, SharedContextManager};
 use crate::ai::brain::agents::io::StatusMessage;
+use crate::ai::brain::prompts::budget_exhausted_prompt;
+use crate::ai::llm::client::tokens::{estimate_message_token, estimate_messages_tokens};
 use crate::ai::llm::client::{
-    CancellationToken, ChatCompletionProvider, ChatCompletionStream, Model,
+    CancellationToken, ChatCompletionProvider, ChatCompletionStream, CompletionUsage, Model,
 };
 use crate::ai::llm::models::MessageRole;
+use crate::store::models::{current_time, AgentRunIterationOutcome};
 use crate::BackendResult;
 use crate::{ai::llm::models::Message, BackendError};
 
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ToolCallRequest {
@@ -68,6 +82,8 @@ enum CitationParseResult {
 pub struct AgentConfig {
     pub name: String,
     pub max_iterations: usize,
+    // how many tool calls of one iteration may run at the same time, 1 runs them in order
+    pub max_parallel_tool_calls: usize,
     pub system_prompt: String,
     pub fallback_to_text: bool,
     pub retry_on_parse_error: bool,
@@ -80,6 +96,7 @@ impl Default for AgentConfig {
         Self {
             name: "Default Agent".to_string(),
             max_iterations: 3,
+            max_parallel_tool_calls: 4,
             system_prompt: "You are a helpful AI assistant that can use tools to complete tasks. When you need to use a tool, call it with the appropriate parameters. Continue using tools until the task is complete, then provide a final response.".to_string(),
             fallback_to_text: true,
             retry_on_parse_error: true,
@@ -101,6 +118,8 @@ pub struct Agent {
     client: Arc<dyn ChatCompletionProvider>,
     tools: HashMap<String, Box<dyn Tool>>,
     config: AgentConfig,
+    // when unset every tool call is executed
+    approvals: Option<Arc<ToolApprovals>>,
 }
 
 pub struct ExecuteConfig {
@@ -111,6 +130,15 @@ pub struct ExecuteConfig {
     pub custom_key: Option<String>,
     // allowed tool names
     pub allowed_tools: Option<HashSet<String>>,
+    // persists the run, only set for top level runs
+    pub recorder: Option<Arc<dyn RunRecorder>>,
+    // iterations of an earlier attempt when resuming a run, they count towards `max_iterations`
+    pub history: Vec<RecordedIteration>,
+    // once used up the agent is asked for a final answer without further tool calls
+    pub budget: AgentBudget,
+    // records the llm responses and js tool results of the run to `<dir>/<execution_id>.json`
+    // for replaying it in tests, only used for top level runs
+    pub fixture_dir: Option<PathBuf>,
 }
 
 impl Agent {
@@ -119,32 +147,70 @@ impl Agent {
             client,
             tools: HashMap::new(),
             config,
+            approvals: None,
         }
     }
 
+    pub fn set_tool_approvals(&mut self, approvals: Arc<ToolApprovals>) {
+        self.approvals = Some(approvals);
+    }
+
     pub fn add_tool(&mut self, tool: Box<dyn Tool>) {
         let name = tool.name().to_string();
         self.tools.insert(name, tool);
     }
 
+    pub fn remove_tool(&mut self, name: &str) -> bool {
+        self.tools.remove(name).is_some()
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
     pub fn execute(
         &self,
         config: ExecuteConfig,
         io: &dyn AgentIO,
         context_manager: &mut dyn ContextManager,
         cancellation_token: CancellationToken,
+    ) -> BackendResult<AgentResult> {
+        let budget = BudgetTracker::new(config.budget.clone(), &config.model);
+        let result =
+            self.execute_iterations(config, io, context_manager, cancellation_token, &budget);
+
+        let usage = budget.usage();
+        tracing::info!("Agent: {}, usage: {:?}", self.config.name, usage);
+        io.report_usage(&usage);
+        if result.is_ok() && self.config.write_status_to_io {
+            io.write_status(StatusMessage::new_usage(&serde_json::to_string(&usage)?))?;
+        }
+        result
+    }
+
+    fn execute_iterations(
+        &self,
+        config: ExecuteConfig,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+        budget: &BudgetTracker,
     ) -> BackendResult<AgentResult> {
         let system_messages = vec![Message::new_system(&self.build_system_prompt(
-            config.system_message_preamble,
-            config.allowed_tools,
+            config.system_message_preamble.clone(),
+            config.allowed_tools.clone(),
             self.config.write_final_response_to_io,
         ))];
 
         let user_msg = Message::new_user(&config.user_message);
         let io_id = io.get_id();
-        let mut tool_usage_history = Vec::new();
+        let mut tool_usage_history: Vec<Message> = config
+            .history
+            .iter()
+            .flat_map(|recorded| recorded.to_messages())
+            .collect();
 
-        for iteration in 0..self.config.max_iterations {
+        for iteration in config.history.len()..self.config.max_iterations {
             if cancellation_token.is_cancelled() {
                 tracing::info!("Agent execution cancelled");
                 return Ok(AgentResult::Cancelled);
@@ -156,6 +222,10 @@ impl Agent {
                 iteration + 1,
                 self.config.max_iterations
             );
+            let started_at = current_time();
+            if let Some(recorder) = &config.recorder {
+                recorder.begin_iteration(iteration);
+            }
 
             // Build messages in order: system_message -> context_messages -> current_io_state -> tool_usage_history -> user_message
             let mut messages = vec![];
@@ -176,10 +246,22 @@ impl Agent {
             // Tool usage history (accumulated across iterations)
             messages.extend_from_slice(&tool_usage_history);
 
-            // User message (always last)
-            messages.push(user_msg.clone());
+            // User message (always last), once the budget is used up the agent has to answer
+            let exhausted = budget.exhausted();
+            match exhausted {
+                Some(limit) => {
+                    tracing::info!("Agent: {}, {} budget exhausted", self.config.name, limit);
+                    messages.push(Message::new_user(&format!(
+                        "{}\n\n{}",
+                        config.user_message,
+                        budget_exhausted_prompt(&limit.to_string())
+                    )));
+                }
+                None => messages.push(user_msg.clone()),
+            }
+            let estimated_input_tokens = estimate_messages_tokens(&messages);
 
-            let stream = self.client.create_streaming_chat_completion(
+            let mut stream = self.client.create_streaming_chat_completion(
                 messages,
                 &config.model,
                 config.custom_key.as_deref(),
@@ -187,33 +269,56 @@ impl Agent {
                 cancellation_token.clone(),
             )?;
 
-            let response = self.process_streaming_response_xml(stream, io, context_manager)?;
+            let response = self.process_streaming_response_xml(&mut stream, io, context_manager)?;
+            // not every provider (or replayed fixture) reports the usage
+            let usage = stream.usage().cloned().unwrap_or_else(|| CompletionUsage {
+                input_tokens: estimated_input_tokens as u32,
+                output_tokens: estimate_message_token(&Message::new_assistant(&response)) as u32,
+                ..Default::default()
+            });
+            budget.record_completion(&usage);
 
             tracing::debug!("Agent: {}, LLM Response: {}", self.config.name, response);
 
             match self.parse_xml_response(&response)? {
-                LLMResponse::ToolCalls(tool_calls) => {
-                    let mut tool_results = Vec::new();
-
-                    for tool_call in tool_calls {
-                        let result = self.execute_tool_call(
-                            &tool_call,
-                            config.execution_id.clone(),
-                            config.model.clone(),
-                            config.custom_key.clone(),
-                            io,
-                            context_manager,
-                            cancellation_token.clone(),
-                        )?;
-                        tool_results.push(result);
+                // no tools are run once the budget is used up, whatever the agent wrote
+                // besides the tool calls is its answer
+                LLMResponse::ToolCalls(_) if exhausted.is_some() => {
+                    tracing::warn!(
+                        "Agent: {}, tool calls after the budget was exhausted, ignoring them",
+                        self.config.name
+                    );
+                    let answer = self.strip_tool_calls(&response);
+                    let recorded = RecordedIteration {
+                        outcome: AgentRunIterationOutcome::FinalAnswer,
+                        response,
+                        tool_results: vec![],
+                    };
+                    self.record_iteration(&config, iteration, &recorded, started_at);
+                    if !answer.is_empty() {
+                        io.write(&answer)?;
                     }
+                    return Ok(AgentResult::Success(answer));
+                }
+                LLMResponse::ToolCalls(tool_calls) => {
+                    let tool_results = self.execute_tool_calls(
+                        &tool_calls,
+                        iteration,
+                        &config,
+                        io,
+                        context_manager,
+                        &cancellation_token,
+                        budget,
+                    )?;
 
                     // Add to persistent tool usage history
-                    tool_usage_history.push(Message::new_assistant(&response));
-                    for tool_result in tool_results {
-                        tool_usage_history
-                            .push(Message::new_tool(&tool_result.name, &tool_result.status));
-                    }
+                    let recorded = RecordedIteration {
+                        outcome: AgentRunIterationOutcome::ToolCalls,
+                        response,
+                        tool_results,
+                    };
+                    self.record_iteration(&config, iteration, &recorded, started_at);
+                    tool_usage_history.extend(recorded.to_messages());
                 }
                 LLMResponse::FinalResponse(final_response) => {
                     tracing::info!(
@@ -221,11 +326,24 @@ impl Agent {
                         self.config.name,
                         final_response
                     );
+                    let recorded = RecordedIteration {
+                        outcome: AgentRunIterationOutcome::FinalAnswer,
+                        response,
+                        tool_results: vec![],
+                    };
+                    self.record_iteration(&config, iteration, &recorded, started_at);
                     return Ok(AgentResult::Success(final_response));
                 }
                 LLMResponse::ParseError(raw_response) => {
+                    let recorded = RecordedIteration {
+                        outcome: AgentRunIterationOutcome::ParseError,
+                        response: raw_response.clone(),
+                        tool_results: vec![],
+                    };
+                    self.record_iteration(&config, iteration, &recorded, started_at);
                     if self.config.retry_on_parse_error
                         && iteration < self.config.max_iterations - 1
+                        && exhausted.is_none()
                     {
                         tracing::warn!(
                             "Parse error on iteration {}, retrying with correction prompt",
@@ -233,10 +351,7 @@ impl Agent {
                         );
 
                         // Add to tool usage history for retry
-                        tool_usage_history.push(Message::new_assistant(&raw_response));
-                        tool_usage_history.push(Message::new_user(
-                            "I couldn't parse your previous response. Please provide your response using the specified XML format with proper <final_answer> or <tool_calls> tags."
-                        ));
+                        tool_usage_history.extend(recorded.to_messages());
                         continue;
                     } else if self.config.fallback_to_text {
                         tracing::warn!("Parse error, falling back to text response");
@@ -263,7 +378,7 @@ impl Agent {
 
     fn process_streaming_response_xml(
         &self,
-        mut stream: ChatCompletionStream,
+        stream: &mut ChatCompletionStream,
         io: &dyn AgentIO,
         context_manager: &dyn ContextManager,
     ) -> BackendResult<String> {
@@ -577,7 +692,7 @@ For tool calls:
 IMPORTANT:
 - Always use the XML format shown above
 - Use <final_answer> when the task is complete{formatting_note}
-- Use <tool_calls> when you need to execute tools, YOU CAN'T USE THE SAME TOOL TWICE IN A SINGLE ITERATION
+- Use <tool_calls> when you need to execute tools, the tools within one <tool_calls> run at the same time so only group calls that don't depend on each other
 - Tool parameters must be valid JSON objects
 - You can call multiple tools by including multiple <tool> elements within <tool_calls>
 - Citations should reference context message IDs and include the specific text being cited"#,
@@ -621,7 +736,7 @@ For tool calls:
 IMPORTANT:
 - Always use the XML format shown above
 - Use <final_answer> when the task is complete{formatting_note}
-- Use <tool_calls> when you need to execute tools, YOU CAN'T USE THE SAME TOOL TWICE IN A SINGLE ITERATION
+- Use <tool_calls> when you need to execute tools, the tools within one <tool_calls> run at the same time so only group calls that don't depend on each other
 - Tool parameters must be valid JSON objects within the <tool> tags
 - You can call multiple tools by including multiple <tool> elements within <tool_calls>
 - Citations should reference context message IDs and include the specific text being cited"#,
@@ -675,6 +790,23 @@ IMPORTANT:
         Ok(LLMResponse::ParseError(response.to_string()))
     }
 
+    // the response without its `<tool_calls>` block
+    fn strip_tool_calls(&self, response: &str) -> String {
+        let (start_tag, end_tag) = ("<tool_calls>", "</tool_calls>");
+        let text = match response.find(start_tag) {
+            Some(start_pos) => {
+                let rest = &response[start_pos + start_tag.len()..];
+                let after = rest
+                    .find(end_tag)
+                    .map(|end_pos| &rest[end_pos + end_tag.len()..])
+                    .unwrap_or_default();
+                format!("{}{}", &response[..start_pos], after)
+            }
+            None => response.to_string(),
+        };
+        text.trim().to_string()
+    }
+
     fn extract_xml_content(&self, response: &str, tag_name: &str) -> Option<String> {
         let start_tag = format!("<{}>", tag_name);
         let end_tag = format!("</{}>", tag_name);
@@ -690,7 +822,7 @@ IMPORTANT:
     }
 
     fn parse_tool_calls(&self, tool_calls_content: &str) -> BackendResult<Vec<ToolCall>> {
-        let mut tool_calls_map: HashMap<String, ToolCall> = HashMap::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
         let mut reader = Reader::from_str(tool_calls_content);
 
         let mut buf = Vec::new();
@@ -743,8 +875,7 @@ IMPORTANT:
                                 },
                             };
 
-                            // Insert or replace - last write wins
-                            tool_calls_map.insert(tool_name, tool_call);
+                            tool_calls.push(tool_call);
                         }
                         current_tool_content.clear();
                     }
@@ -759,13 +890,121 @@ IMPORTANT:
             buf.clear();
         }
 
-        // Convert HashMap values to Vec, preserving insertion order is not guaranteed
-        // If you need to preserve order, consider using IndexMap instead
-        let tool_calls: Vec<ToolCall> = tool_calls_map.into_values().collect();
-
         Ok(tool_calls)
     }
 
+    fn record_iteration(
+        &self,
+        config: &ExecuteConfig,
+        iteration: usize,
+        recorded: &RecordedIteration,
+        started_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        if let Some(recorder) = &config.recorder {
+            recorder.record_iteration(
+                iteration,
+                recorded.outcome.clone(),
+                &recorded.response,
+                started_at,
+            );
+        }
+    }
+
+    // runs the calls on up to `max_parallel_tool_calls` threads,
+    // the results are in the same order as the calls
+    #[allow(clippy::too_many_arguments)]
+    fn execute_tool_calls(
+        &self,
+        tool_calls: &[ToolCall],
+        iteration: usize,
+        config: &ExecuteConfig,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: &CancellationToken,
+        budget: &BudgetTracker,
+    ) -> BackendResult<Vec<ToolResult>> {
+        // tools can't run past the time budget
+        let cancellation_token = match budget.remaining_time() {
+            Some(remaining) => cancellation_token.with_timeout(remaining),
+            None => cancellation_token.clone(),
+        };
+        let io = SubAgentUsageIO::new(io, budget);
+        let io: &dyn AgentIO = &io;
+        let execute = |position: usize, context_manager: &mut dyn ContextManager| {
+            let tool_call = &tool_calls[position];
+            let started_at = current_time();
+            let result = if budget.start_tool_call() {
+                self.execute_tool_call(
+                    tool_call,
+                    config.execution_id.clone(),
+                    config.model.clone(),
+                    config.custom_key.clone(),
+                    io,
+                    context_manager,
+                    cancellation_token.clone(),
+                )?
+            } else {
+                ToolResult {
+                    role: MessageRole::Assistant.to_string(),
+                    name: tool_call.function.name.clone(),
+                    status: "Error: Tool call budget exhausted, not executed".to_string(),
+                }
+            };
+            if let Some(recorder) = &config.recorder {
+                recorder.record_tool_call(iteration, position, tool_call, &result, started_at);
+            }
+            Ok(result)
+        };
+
+        let parallelism = self
+            .config
+            .max_parallel_tool_calls
+            .clamp(1, tool_calls.len().max(1));
+        if parallelism == 1 {
+            return (0..tool_calls.len())
+                .map(|position| execute(position, context_manager))
+                .collect();
+        }
+
+        let shared_context_manager = Mutex::new(context_manager);
+        let next_call = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<BackendResult<ToolResult>>>> =
+            Mutex::new(tool_calls.iter().map(|_| None).collect());
+
+        std::thread::scope(|scope| {
+            for _ in 0..parallelism {
+                scope.spawn(|| {
+                    let mut context_manager = SharedContextManager::new(&shared_context_manager);
+                    loop {
+                        let index = next_call.fetch_add(1, Ordering::SeqCst);
+                        if index >= tool_calls.len() {
+                            break;
+                        }
+                        let result = execute(index, &mut context_manager);
+                        if let Ok(mut results) = results.lock() {
+                            results[index] = Some(result);
+                        }
+                    }
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .map_err(|_| BackendError::GenericError("tool results lock poisoned".to_string()))?
+            .into_iter()
+            .zip(tool_calls)
+            .map(|(result, tool_call)| {
+                result.unwrap_or_else(|| {
+                    Err(BackendError::GenericError(format!(
+                        "tool '{}' did not finish",
+                        tool_call.function.name
+                    )))
+                })
+            })
+            .collect()
+    }
+
     fn execute_tool_call(
         &self,
         tool_call: &ToolCall,
@@ -801,6 +1040,27 @@ IMPORTANT:
                 }
             };
 
+        if let Some(approvals) = &self.approvals {
+            if let ApprovalDecision::Refused(reason) = approvals.check(
+                tool.as_ref(),
+                tool_call,
+                &parameters,
+                io,
+                &cancellation_token,
+            )? {
+                tracing::info!(
+                    "Tool '{}' not executed: {}",
+                    tool_call.function.name,
+                    reason
+                );
+                return Ok(ToolResult {
+                    role: MessageRole::Assistant.to_string(),
+                    name: tool_call.function.name.clone(),
+                    status: format!("Error: {}", reason),
+                });
+            }
+        }
+
         tracing::debug!("Executing tool: {}", tool_call.function.name);
         tracing::debug!("Parameters: {}", parameters);
 
@@ -841,6 +1101,7 @@ IMPORTANT:
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai::brain::replay::{Fixture, FixtureReplayer};
     use crate::ai::llm::{client::CancellationToken, models::Message};
     use serde_json::json;
     use std::sync::Arc;
@@ -852,6 +1113,10 @@ mod tests {
         name: String,
         description: String,
         should_fail: bool,
+        // calls wait until this many calls are running at the same time
+        rendezvous: Option<(Arc<AtomicUsize>, usize)>,
+        // reported like the usage of a sub-agent run
+        usage: Option<budget::BudgetUsage>,
     }
 
     impl MockTool {
@@ -860,6 +1125,8 @@ mod tests {
                 name: name.to_string(),
                 description: description.to_string(),
                 should_fail: false,
+                rendezvous: None,
+                usage: None,
             }
         }
     }
@@ -900,6 +1167,21 @@ mod tests {
             _context_manager: &mut dyn ContextManager,
             _cancellation_token: CancellationToken,
         ) -> BackendResult<()> {
+            if let Some((running, expected)) = &self.rendezvous {
+                running.fetch_add(1, Ordering::SeqCst);
+                let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+                while running.load(Ordering::SeqCst) < *expected {
+                    if std::time::Instant::now() > deadline {
+                        return Err(BackendError::GenericError(
+                            "the other tool calls are not running".to_string(),
+                        ));
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+            }
+            if let Some(usage) = &self.usage {
+                io.report_usage(usage);
+            }
             if self.should_fail {
                 return Err(BackendError::GenericError(
                     "Tool execution failed".to_string(),
@@ -918,7 +1200,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_xml_tool_call_parsing() {
-        let agent = create_test_agent(vec![], None);
+        let mut agent = create_test_agent(vec![], None);
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
 
         let response = r#"<tool_calls>
 <tool name="search">
@@ -960,7 +1243,9 @@ This is the final answer to the user's question.
 
     #[tokio::test]
     async fn test_xml_multiple_tool_calls() {
-        let agent = create_test_agent(vec![], None);
+        let mut agent = create_test_agent(vec![], None);
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
+        agent.add_tool(Box::new(MockTool::new("process", "Processes")));
 
         let response = r#"<tool_calls>
 <tool name="search">
@@ -1002,16 +1287,272 @@ This is some text with a citation <citation>
         }
     }
 
+    #[test]
+    fn test_xml_tool_calls_keep_order_and_repeats() {
+        let mut agent = create_test_agent(vec![], None);
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
+        agent.add_tool(Box::new(MockTool::new("process", "Processes")));
+
+        let response = r#"<tool_calls>
+<tool name="search">{"query": "first"}</tool>
+<tool name="process">{"query": "data"}</tool>
+<tool name="search">{"query": "second"}</tool>
+</tool_calls>"#;
+
+        match agent.parse_xml_response(response).unwrap() {
+            LLMResponse::ToolCalls(calls) => {
+                let names: Vec<&str> = calls.iter().map(|c| c.function.name.as_str()).collect();
+                assert_eq!(names, vec!["search", "process", "search"]);
+                assert!(calls[2].function.arguments.contains("second"));
+            }
+            _ => panic!("Expected tool calls"),
+        }
+    }
+
+    #[test]
+    fn test_tool_calls_run_in_parallel() {
+        // every call only returns once all four are running at the same time
+        let running = Arc::new(AtomicUsize::new(0));
+        let mut agent = create_test_agent(vec![], None);
+        for (name, should_fail) in [("search", false), ("broken", true)] {
+            agent.add_tool(Box::new(MockTool {
+                should_fail,
+                rendezvous: Some((running.clone(), 4)),
+                ..MockTool::new(name, "Mock tool")
+            }));
+        }
+        let tool_calls: Vec<ToolCall> = ["search", "broken", "search", "search"]
+            .iter()
+            .map(|name| ToolCall {
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: name.to_string(),
+                    arguments: r#"{"query": "q"}"#.to_string(),
+                },
+            })
+            .collect();
+        let config = ExecuteConfig {
+            execution_id: "test".to_string(),
+            user_message: String::new(),
+            system_message_preamble: None,
+            model: Model::GPT4o,
+            custom_key: None,
+            allowed_tools: None,
+            recorder: None,
+            history: vec![],
+            budget: AgentBudget::default(),
+            fixture_dir: None,
+        };
+
+        let results = agent
+            .execute_tool_calls(
+                &tool_calls,
+                0,
+                &config,
+                &io::MemoryIO::new(),
+                &mut context::MockContextManager::new(),
+                &CancellationToken::new(),
+                &BudgetTracker::new(AgentBudget::default(), &Model::GPT4o),
+            )
+            .unwrap();
+
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["search", "broken", "search", "search"]);
+        assert_eq!(results[0].status, "Success");
+        assert!(results[1].status.starts_with("Error"));
+        assert_eq!(results[2].status, "Success");
+        assert_eq!(results[3].status, "Success");
+    }
+
+    #[test]
+    fn test_replay_tool_call_then_citation() {
+        let fixture: Fixture =
+            serde_json::from_str(include_str!("../fixtures/tool_call_then_citation.json")).unwrap();
+        let replayer = Arc::new(FixtureReplayer::new(fixture));
+        let mut agent = Agent::new(replayer.clone(), AgentConfig::default());
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
+
+        let io = io::MemoryIO::new();
+        let result = agent
+            .execute(
+                ExecuteConfig {
+                    execution_id: "test".to_string(),
+                    user_message: "what is rust?".to_string(),
+                    system_message_preamble: None,
+                    model: Model::GPT4oMini,
+                    custom_key: None,
+                    allowed_tools: None,
+                    recorder: None,
+                    history: vec![],
+                    budget: AgentBudget::default(),
+                    fixture_dir: None,
+                },
+                &io,
+                &mut context::MockContextManager::new(),
+                CancellationToken::new(),
+            )
+            .unwrap();
+
+        match result {
+            AgentResult::Success(answer) => assert!(answer.contains("blazingly fast")),
+            _ => panic!("Expected success"),
+        }
+        let output = io.read().unwrap();
+        assert!(output.contains("Executing search with query: rust language"));
+        assert!(output.ends_with("Rust is fast <citation></citation> and memory safe."));
+        assert_eq!(replayer.remaining().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_budget_exhausted_forces_final_answer() {
+        let tool_call = r#"<tool_calls><tool name="search">{"query": "rust"}</tool></tool_calls>"#;
+        let client = Arc::new(MockLLMClient::new(vec![
+            tool_call.to_string(),
+            r#"<final_answer>Partial answer</final_answer>"#.to_string(),
+        ]));
+        let mut agent = Agent::new(client.clone(), AgentConfig::default());
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
+
+        let io = io::MemoryIO::new();
+        let result = agent
+            .execute(
+                ExecuteConfig {
+                    execution_id: "test".to_string(),
+                    user_message: "what is rust?".to_string(),
+                    system_message_preamble: None,
+                    model: Model::GPT4oMini,
+                    custom_key: None,
+                    allowed_tools: None,
+                    recorder: None,
+                    history: vec![],
+                    budget: AgentBudget {
+                        max_tool_calls: Some(1),
+                        ..Default::default()
+                    },
+                    fixture_dir: None,
+                },
+                &io,
+                &mut context::MockContextManager::new(),
+                CancellationToken::new(),
+            )
+            .unwrap();
+
+        match result {
+            AgentResult::Success(answer) => assert_eq!(answer, "Partial answer"),
+            other => panic!("Expected success, got {:?}", other),
+        }
+        // after the only allowed tool call the agent is asked to answer right away
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].content[0].get_content().contains("budget"));
+        assert!(requests[1].content[0]
+            .get_content()
+            .contains("tool call budget"));
+    }
+
+    fn execute_with_budget(
+        agent: &Agent,
+        io: &dyn AgentIO,
+        budget: AgentBudget,
+    ) -> BackendResult<AgentResult> {
+        agent.execute(
+            ExecuteConfig {
+                execution_id: "test".to_string(),
+                user_message: "what is rust?".to_string(),
+                system_message_preamble: None,
+                model: Model::GPT4oMini,
+                custom_key: None,
+                allowed_tools: None,
+                recorder: None,
+                history: vec![],
+                budget,
+                fixture_dir: None,
+            },
+            io,
+            &mut context::MockContextManager::new(),
+            CancellationToken::new(),
+        )
+    }
+
+    #[test]
+    fn test_tool_calls_after_exhausted_budget_are_ignored() {
+        let tool_call = r#"<tool_calls><tool name="search">{"query": "rust"}</tool></tool_calls>"#;
+        let client = Arc::new(MockLLMClient::new(vec![
+            tool_call.to_string(),
+            format!("Rust is a systems programming language.\n{}", tool_call),
+        ]));
+        let mut agent = Agent::new(client, AgentConfig::default());
+        agent.add_tool(Box::new(MockTool::new("search", "Searches")));
+
+        let io = io::MemoryIO::new();
+        let result = execute_with_budget(
+            &agent,
+            &io,
+            AgentBudget {
+                max_tool_calls: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        match result {
+            AgentResult::Success(answer) => {
+                assert_eq!(answer, "Rust is a systems programming language.")
+            }
+            other => panic!("Expected success, got {:?}", other),
+        }
+        assert_eq!(io.read().unwrap().matches("Executing search").count(), 1);
+    }
+
+    #[test]
+    fn test_sub_agent_usage_counts_towards_the_budget() {
+        let tool_call =
+            r#"<tool_calls><tool name="research">{"query": "rust"}</tool></tool_calls>"#;
+        let client = Arc::new(MockLLMClient::new(vec![
+            tool_call.to_string(),
+            r#"<final_answer>Partial answer</final_answer>"#.to_string(),
+        ]));
+        let mut agent = Agent::new(client.clone(), AgentConfig::default());
+        agent.add_tool(Box::new(MockTool {
+            usage: Some(budget::BudgetUsage {
+                completions: 3,
+                tokens: 10_000,
+                ..Default::default()
+            }),
+            ..MockTool::new("research", "Runs a research agent")
+        }));
+
+        let io = io::MemoryIO::new();
+        let result = execute_with_budget(
+            &agent,
+            &io,
+            AgentBudget {
+                max_tokens: Some(10_000),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(result, AgentResult::Success(_)));
+        // the sub-agent used up the tokens of the run
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].content[0]
+            .get_content()
+            .contains("token budget"));
+    }
+
     // Helper function for tests
     fn create_test_agent(responses: Vec<String>, config: Option<AgentConfig>) -> Agent {
         let config = config.unwrap_or_default();
         Agent::new(Arc::new(MockLLMClient::new(responses)), config)
     }
 
-    // Note: MockLLMClient would need to be updated to implement streaming
     struct MockLLMClient {
         responses: Vec<String>,
         current_response: std::sync::Mutex<usize>,
+        // the last message of every streamed request
+        requests: std::sync::Mutex<Vec<Message>>,
     }
 
     impl MockLLMClient {
@@ -1019,6 +1560,7 @@ This is some text with a citation <citation>
             Self {
                 responses,
                 current_response: std::sync::Mutex::new(0),
+                requests: std::sync::Mutex::new(vec![]),
             }
         }
     }
@@ -1030,6 +1572,7 @@ This is some text with a citation <citation>
             _model: &Model,
             _custom_key: Option<&str>,
             _response_format: Option<serde_json::Value>,
+            _cancellation_token: CancellationToken,
         ) -> BackendResult<String> {
             let mut current = self.current_response.lock().unwrap();
             if *current < self.responses.len() {
@@ -1043,14 +1586,26 @@ This is some text with a citation <citation>
 
         fn create_streaming_chat_completion(
             &self,
-            _messages: Vec<Message>,
-            _model: &Model,
-            _custom_key: Option<&str>,
-            _response_format: Option<serde_json::Value>,
-            _cancellation_token: CancellationToken,
+            messages: Vec<Message>,
+            model: &Model,
+            custom_key: Option<&str>,
+            response_format: Option<serde_json::Value>,
+            cancellation_token: CancellationToken,
         ) -> BackendResult<ChatCompletionStream> {
-            // Mock streaming implementation would be needed here
-            unimplemented!("Mock streaming implementation needed for tests")
+            if let Some(message) = messages.last() {
+                self.requests.lock().unwrap().push(message.clone());
+            }
+            let response = self.create_chat_completion(
+                messages,
+                model,
+                custom_key,
+                response_format,
+                cancellation_token.clone(),
+            )?;
+            Ok(ChatCompletionStream::from_chunks(
+                vec![Ok(response)],
+                cancellation_token,
+            ))
         }
     }
 }
@@ -0,0 +1,54 @@
+use super::tools::{ToolCall, ToolResult};
+use crate::ai::llm::models::Message;
+use crate::store::models::AgentRunIterationOutcome;
+
+const PARSE_ERROR_CORRECTION_PROMPT: &str = "I couldn't parse your previous response. Please provide your response using the specified XML format with proper <final_answer> or <tool_calls> tags.";
+
+// receives everything an agent does during a run so that it can be persisted,
+// errors are the recorder's business and must not fail the run
+pub trait RunRecorder: Send + Sync {
+    fn begin_iteration(&self, iteration: usize);
+    fn record_tool_call(
+        &self,
+        iteration: usize,
+        position: usize,
+        tool_call: &ToolCall,
+        result: &ToolResult,
+        started_at: chrono::DateTime<chrono::Utc>,
+    );
+    // called once the iteration's tool calls have finished
+    fn record_iteration(
+        &self,
+        iteration: usize,
+        outcome: AgentRunIterationOutcome,
+        response: &str,
+        started_at: chrono::DateTime<chrono::Utc>,
+    );
+}
+
+// a finished iteration of an earlier attempt of the run, used to resume it
+#[derive(Debug, Clone)]
+pub struct RecordedIteration {
+    pub outcome: AgentRunIterationOutcome,
+    pub response: String,
+    pub tool_results: Vec<ToolResult>,
+}
+
+impl RecordedIteration {
+    // the messages the iteration added to the agent's tool usage history
+    pub fn to_messages(&self) -> Vec<Message> {
+        let mut messages = vec![Message::new_assistant(&self.response)];
+        match self.outcome {
+            AgentRunIterationOutcome::ToolCalls => {
+                for result in &self.tool_results {
+                    messages.push(Message::new_tool(&result.name, &result.status));
+                }
+            }
+            AgentRunIterationOutcome::ParseError => {
+                messages.push(Message::new_user(PARSE_ERROR_CORRECTION_PROMPT));
+            }
+            AgentRunIterationOutcome::FinalAnswer => {}
+        }
+        messages
+    }
+}
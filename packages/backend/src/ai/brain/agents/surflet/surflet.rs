@@ -4,12 +4,12 @@ use crate::ai::{
         agents::{surflet::tools::SurfletCreator, Agent, AgentConfig},
         js_tools::JSToolRegistry,
     },
-    llm::client::LLMClient,
+    llm::client::ChatCompletionProvider,
 };
 use std::sync::Arc;
 
 pub fn create_surflet_agent(
-    client: Arc<LLMClient>,
+    client: Arc<dyn ChatCompletionProvider>,
     js_tool_registry: Arc<JSToolRegistry>,
 ) -> Agent {
     let system_prompt = prompt();
@@ -17,6 +17,7 @@ pub fn create_surflet_agent(
     let config = AgentConfig {
         name: "surflet_agent".to_string(),
         max_iterations: 2,
+        max_parallel_tool_calls: 4,
         system_prompt,
         fallback_to_text: false,
         retry_on_parse_error: true,
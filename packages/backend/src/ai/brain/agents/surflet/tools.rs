@@ -1,6 +1,7 @@
 use serde_json::json;
 use std::sync::Arc;
 
+use crate::ai::brain::agents::tools::ToolRiskLevel;
 use crate::ai::brain::agents::{AgentIO, ContextManager, Tool};
 use crate::ai::brain::js_tools::{JSToolRegistry, ToolName};
 use crate::ai::llm::client::{CancellationToken, Model};
@@ -52,6 +53,10 @@ impl Tool for SurfletCreator {
         Some("Creating a Surflet...")
     }
 
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::Medium
+    }
+
     fn parameters_schema(&self) -> serde_json::Value {
         json!({
             "type": "object",
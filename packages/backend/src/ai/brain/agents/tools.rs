@@ -12,12 +12,28 @@ use serde::{Deserialize, Serialize};
 #[error("Tool error")]
 pub struct ToolError;
 
+// how much harm a tool call can do, decides the default approval policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolRiskLevel {
+    // reads data or only changes the agent's context
+    Low,
+    // creates things the user can easily remove
+    Medium,
+    // changes the user's data or has effects outside of surf
+    High,
+}
+
 pub trait Tool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn execution_message(&self) -> Option<&str>;
     fn parameters_schema(&self) -> serde_json::Value;
 
+    fn risk_level(&self) -> ToolRiskLevel {
+        ToolRiskLevel::Low
+    }
+
     fn execute(
         &self,
         parameters: serde_json::Value,
@@ -42,7 +58,7 @@ pub struct FunctionCall {
     pub arguments: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ToolResult {
     pub role: String,
     pub name: String,
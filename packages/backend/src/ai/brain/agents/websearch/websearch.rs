@@ -4,10 +4,10 @@ use super::prompt::prompt;
 use super::tools::SearchEngineCaller;
 use crate::ai::brain::agents::{context_manager::tools::AddUrlsTool, Agent, AgentConfig};
 use crate::ai::brain::js_tools::JSToolRegistry;
-use crate::ai::llm::client::LLMClient;
+use crate::ai::llm::client::ChatCompletionProvider;
 
 pub fn create_web_search_agent(
-    client: Arc<LLMClient>,
+    client: Arc<dyn ChatCompletionProvider>,
     js_tool_registry: Arc<JSToolRegistry>,
 ) -> Agent {
     let system_prompt = prompt();
@@ -15,6 +15,7 @@ pub fn create_web_search_agent(
     let config = AgentConfig {
         name: "websearch_agent".to_string(),
         max_iterations: 3,
+        max_parallel_tool_calls: 4,
         system_prompt,
         fallback_to_text: true,
         retry_on_parse_error: true,
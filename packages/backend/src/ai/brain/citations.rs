@@ -0,0 +1,264 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::store::models::ResourceTextContentMetadata;
+
+// share of the cited words that have to show up close together in the source
+const APPROXIMATE_THRESHOLD: f32 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationStatus {
+    // the cited text appears in the source, ignoring case and punctuation
+    Verified,
+    // most of the cited words appear close together, e.g. a paraphrase
+    Approximate,
+    Unverified,
+}
+
+impl CitationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CitationStatus::Verified => "verified",
+            CitationStatus::Approximate => "approximate",
+            CitationStatus::Unverified => "unverified",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CitationCheck {
+    pub status: CitationStatus,
+    // share of the cited words found in the best matching part of the source
+    pub score: f32,
+    // where the match is, only set if the citation is supported
+    pub page: Option<u32>,
+    pub timestamp: Option<f32>,
+}
+
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// best share of the cited words found in a window of the same length,
+// and the end of that window in the source
+fn best_window(cited: &[String], source: &[String]) -> (f32, usize) {
+    let mut wanted: HashMap<&str, usize> = HashMap::new();
+    for word in cited {
+        *wanted.entry(word.as_str()).or_default() += 1;
+    }
+    let mut window: HashMap<&str, usize> = HashMap::new();
+    let mut matched = 0;
+    let mut best = 0;
+    let mut best_end = 0;
+    for (i, word) in source.iter().enumerate() {
+        let count = window.entry(word.as_str()).or_default();
+        if *count < wanted.get(word.as_str()).copied().unwrap_or(0) {
+            matched += 1;
+        }
+        *count += 1;
+        if i >= cited.len() {
+            let removed = source[i - cited.len()].as_str();
+            let count = window.entry(removed).or_default();
+            if *count <= wanted.get(removed).copied().unwrap_or(0) {
+                matched -= 1;
+            }
+            *count -= 1;
+        }
+        if matched > best {
+            best = matched;
+            best_end = i + 1;
+        }
+    }
+    (best as f32 / cited.len() as f32, best_end)
+}
+
+// end of the first exact occurrence of the words in the source
+fn find_words(cited: &[String], source: &[String]) -> Option<usize> {
+    source
+        .windows(cited.len())
+        .position(|window| window == cited)
+        .map(|start| start + cited.len())
+}
+
+// only exact matches are verified
+fn fuzzy_match(score: f32) -> (CitationStatus, f32) {
+    if score >= APPROXIMATE_THRESHOLD {
+        (CitationStatus::Approximate, score)
+    } else {
+        (CitationStatus::Unverified, score)
+    }
+}
+
+// what the agent cites, see the citation format in the lead agent prompt
+enum CitedWords {
+    Text(Vec<String>),
+    // the first and last words of the cited text, separated by `;;;`
+    Anchors(Vec<String>, Vec<String>),
+}
+
+impl CitedWords {
+    fn parse(cited_text: &str) -> Self {
+        if let Some((first, last)) = cited_text.split_once(";;;") {
+            let (first, last) = (normalized_words(first), normalized_words(last));
+            if first.is_empty() || last.is_empty() {
+                return CitedWords::Text([first, last].concat());
+            }
+            return CitedWords::Anchors(first, last);
+        }
+        CitedWords::Text(normalized_words(cited_text))
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            CitedWords::Text(words) => words.is_empty(),
+            CitedWords::Anchors(..) => false,
+        }
+    }
+
+    // the anchors have to be in order, the last words after the first ones
+    fn check(&self, source: &[String]) -> (CitationStatus, f32) {
+        match self {
+            CitedWords::Text(words) => {
+                if find_words(words, source).is_some() {
+                    return (CitationStatus::Verified, 1.0);
+                }
+                fuzzy_match(best_window(words, source).0)
+            }
+            CitedWords::Anchors(first, last) => {
+                if let Some(first_end) = find_words(first, source) {
+                    if find_words(last, &source[first_end..]).is_some() {
+                        return (CitationStatus::Verified, 1.0);
+                    }
+                }
+                let (first_score, first_end) = best_window(first, source);
+                let (last_score, _) = best_window(last, &source[first_end..]);
+                fuzzy_match(first_score.min(last_score))
+            }
+        }
+    }
+}
+
+// checks the cited text against the text contents of the cited source,
+// on equal scores the earlier source wins so the cited part should come first
+pub fn verify_citation<'a>(
+    cited_text: &str,
+    sources: impl IntoIterator<Item = (&'a str, &'a ResourceTextContentMetadata)>,
+) -> CitationCheck {
+    let mut check = CitationCheck {
+        status: CitationStatus::Unverified,
+        score: 0.0,
+        page: None,
+        timestamp: None,
+    };
+    let cited = CitedWords::parse(cited_text);
+    if cited.is_empty() {
+        return check;
+    }
+
+    for (content, metadata) in sources {
+        let (status, score) = cited.check(&normalized_words(content));
+        if score > check.score {
+            check = CitationCheck {
+                status,
+                score,
+                page: None,
+                timestamp: None,
+            };
+            if status != CitationStatus::Unverified {
+                check.page = metadata.page;
+                check.timestamp = metadata.timestamp;
+            }
+        }
+        if status == CitationStatus::Verified {
+            break;
+        }
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_citation() {
+        let page = |page| ResourceTextContentMetadata {
+            page: Some(page),
+            ..Default::default()
+        };
+        let (first, second) = (page(1), page(2));
+        let sources = [
+            (
+                "Revenue grew by 12% in 2023, driven mostly by the new subscription tier.",
+                &first,
+            ),
+            (
+                "The board expects growth to slow down next year as the market matures.",
+                &second,
+            ),
+        ];
+
+        let check = verify_citation("growth to SLOW down next year,", sources);
+        assert_eq!(check.status, CitationStatus::Verified);
+        assert_eq!(check.page, Some(2));
+
+        let check = verify_citation(
+            "revenue grew by 12% in 2023 driven by the new subscription tier",
+            sources,
+        );
+        assert_eq!(check.status, CitationStatus::Approximate);
+        assert_eq!(check.page, Some(1));
+
+        let check = verify_citation("profits tripled after the merger", sources);
+        assert_eq!(check.status, CitationStatus::Unverified);
+        assert_eq!(check.page, None);
+
+        let check = verify_citation("...", sources);
+        assert_eq!(check.status, CitationStatus::Unverified);
+    }
+
+    #[test]
+    fn test_verify_citation_anchors() {
+        let page = |page| ResourceTextContentMetadata {
+            page: Some(page),
+            ..Default::default()
+        };
+        let (first, second) = (page(1), page(2));
+        let sources = [
+            (
+                "Revenue grew by 12% in 2023, driven mostly by the new subscription tier.",
+                &first,
+            ),
+            (
+                "The board expects growth to slow down next year as the market matures.",
+                &second,
+            ),
+        ];
+
+        let check = verify_citation("The board expects;;;market matures", sources);
+        assert_eq!(check.status, CitationStatus::Verified);
+        assert_eq!(check.page, Some(2));
+
+        // the last words have to come after the first ones
+        let check = verify_citation("market matures;;;The board expects", sources);
+        assert_ne!(check.status, CitationStatus::Verified);
+
+        // and both have to be in the same source
+        let check = verify_citation("Revenue grew by;;;the market matures", sources);
+        assert_eq!(check.status, CitationStatus::Unverified);
+
+        let check = verify_citation(
+            "The board clearly expects growth;;;as the market matures",
+            sources,
+        );
+        assert_eq!(check.status, CitationStatus::Approximate);
+        assert_eq!(check.page, Some(2));
+
+        let check = verify_citation("growth to slow down;;;", sources);
+        assert_eq!(check.status, CitationStatus::Verified);
+    }
+}
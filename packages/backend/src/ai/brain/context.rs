@@ -1,16 +1,20 @@
 use crate::{
     ai::{
         brain::{
-            agents::context::ContextManager,
+            agents::context::{ContextManager, FetchedUrl, UrlFetcher},
+            citations::{verify_citation, CitationCheck, CitationStatus},
             js_tools::{JSToolRegistry, ToolName},
         },
         llm::models::{ContextMessage, Message},
         youtube::{fetch_transcript, is_youtube_video_url},
     },
-    store::{db::Database, models::CompositeResource},
+    store::{
+        db::Database,
+        models::{CompositeResource, ResourceTextContentMetadata},
+    },
     BackendError, BackendResult,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 #[derive(Clone, Debug)]
@@ -21,19 +25,22 @@ pub struct ContextItem {
 }
 
 impl ContextItem {
-    pub fn to_citation(&self, cited_text: &str) -> String {
+    // the page or timestamp of the match takes precedence over the item's own
+    pub fn to_citation(&self, cited_text: &str, check: &CitationCheck) -> String {
         let mut timestamp = String::new();
         let mut url = String::new();
         let mut page = String::new();
         let mut uid = String::new();
         let mut resource_id = String::new();
-        if let Some(ts) = &self.message.timestamp {
+        if let Some(ts) = check.timestamp {
+            timestamp = ts.to_string();
+        } else if let Some(ts) = &self.message.timestamp {
             timestamp = ts.to_string();
         }
         if let Some(u) = &self.message.source_url {
             url = u.to_string();
         }
-        if let Some(p) = self.message.page {
+        if let Some(p) = check.page.or(self.message.page) {
             page = p.to_string();
         }
         if let Some(id) = &self.resource_text_content_id {
@@ -43,13 +50,14 @@ impl ContextItem {
             resource_id = rid.clone();
         }
 
-        format!("<citation data-text=\"{}\" data-uid=\"{}\" data-resource-id=\"{}\" data-timestamp=\"{}\" data-url=\"{}\" data-page=\"{}\">{}</citation>", 
+        format!("<citation data-text=\"{}\" data-uid=\"{}\" data-resource-id=\"{}\" data-timestamp=\"{}\" data-url=\"{}\" data-page=\"{}\" data-verification=\"{}\">{}</citation>", 
             html_escape::encode_safe(cited_text),
             uid,
             resource_id,
             timestamp,
             html_escape::encode_safe(&url),
             page,
+            check.status.as_str(),
             self.message.id
         )
     }
@@ -97,13 +105,43 @@ impl ContextItem {
 pub struct LLMContext {
     note_id: String,
     context_items: HashMap<String, ContextItem>,
+    // id of the next context item, items can be removed so ids are never derived from the count
+    next_id: usize,
     inline_images: Vec<String>,
-    user_lang_preference: Option<String>,
-    js_tool_registry: Arc<JSToolRegistry>,
+    url_fetcher: Arc<PageFetcher>,
     // TODO: not have a new database connection for each context
     db: Database,
 }
 
+// loads web pages through the js tools and youtube videos as transcripts
+struct PageFetcher {
+    js_tool_registry: Arc<JSToolRegistry>,
+    user_lang_preference: Option<String>,
+}
+
+impl UrlFetcher for PageFetcher {
+    fn fetch_url(&self, url: &str) -> BackendResult<FetchedUrl> {
+        if is_youtube_video_url(url) {
+            let yt_transcript = fetch_transcript(url, self.user_lang_preference.as_deref())?;
+            return Ok(FetchedUrl {
+                url: url.to_string(),
+                content_type: "Context(YouTube Transcript)".to_string(),
+                title: None,
+                content: Some(yt_transcript.transcript),
+            });
+        }
+        let result: ScrapeWebpageResult = self
+            .js_tool_registry
+            .execute_tool(&ToolName::ScrapeURL, Some(vec![url.to_string()]))?;
+        Ok(FetchedUrl {
+            url: url.to_string(),
+            content_type: "Context(Webpage)".to_string(),
+            title: Some(result.title),
+            content: result.content,
+        })
+    }
+}
+
 // TODO: should we move this to js_tools.rs?
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct ScrapeWebpageResult {
@@ -124,15 +162,20 @@ impl LLMContext {
     ) -> BackendResult<Self> {
         let db = Database::new(db_path, false)?;
         let resources = db.list_resources_metadata_by_ids(&resource_ids)?;
-        let context_items = Self::context_metadata_messages_from_resources(&resources);
+        let mut next_id = 0;
+        let context_items =
+            Self::context_metadata_messages_from_resources(&resources, &mut next_id);
 
         let mut llm_context = Self {
             note_id: note_id.clone(),
-            js_tool_registry,
+            url_fetcher: Arc::new(PageFetcher {
+                js_tool_registry,
+                user_lang_preference,
+            }),
             context_items: context_items.clone(),
+            next_id,
             inline_images: inline_images.to_vec(),
             db,
-            user_lang_preference,
         };
         if context_items.len() <= 5 {
             for ci in context_items.values() {
@@ -142,6 +185,12 @@ impl LLMContext {
         Ok(llm_context)
     }
 
+    fn next_context_id(&mut self) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        id.to_string()
+    }
+
     fn check_note_id(&self, key: &str) -> BackendResult<()> {
         if self.note_id == key {
             Ok(())
@@ -150,12 +199,15 @@ impl LLMContext {
         }
     }
 
+    // takes the ids from `next_id` so that new items don't replace existing ones
     fn context_metadata_messages_from_resources(
         resources: &[CompositeResource],
+        next_id: &mut usize,
     ) -> HashMap<String, ContextItem> {
         let mut messages = HashMap::new();
-        for (i, resource) in resources.iter().enumerate() {
-            let id = i.to_string();
+        for resource in resources {
+            let id = next_id.to_string();
+            *next_id += 1;
             let mut msg = ContextMessage {
                 // we don't use the actual resource id as it's a uuid
                 // which is too long, use simple index instead
@@ -198,7 +250,6 @@ impl LLMContext {
 
     // this fetches resource text content from the db & adds each piece as separate context items
     fn add_resource_text_content(&mut self, context_id: &str) -> BackendResult<()> {
-        let mut insert_at = self.context_items.len();
         if let Some(context_item) = self.context_items.get_mut(context_id) {
             if context_item.resource_id.is_none() {
                 // TODO: is this a non-error?
@@ -229,8 +280,8 @@ impl LLMContext {
 
             let mut insertions = HashMap::<String, ContextItem>::new();
             for (i, text_content) in text_contents.iter().skip(1).enumerate() {
-                let new_context_id = format!("{}", insert_at);
-                insert_at += 1;
+                let new_context_id = self.next_id.to_string();
+                self.next_id += 1;
 
                 let mut new_message = context_item.message.clone();
                 new_message.id = new_context_id.clone();
@@ -257,6 +308,38 @@ impl LLMContext {
         }
         Ok(())
     }
+
+    // matches the cited text against all text contents of the cited resource as the
+    // model might cite a part under the wrong id, items without a resource only have their content
+    fn verify_citation(&self, context_item: &ContextItem, cited_text: &str) -> CitationCheck {
+        let mut text_contents = match &context_item.resource_id {
+            Some(resource_id) => self
+                .db
+                .list_resource_text_content_by_resource_id(resource_id)
+                .unwrap_or_else(|e| {
+                    tracing::warn!("failed to load text content of {}: {}", resource_id, e);
+                    vec![]
+                }),
+            None => vec![],
+        };
+        if text_contents.is_empty() {
+            let content = context_item.message.content.as_deref().unwrap_or_default();
+            let metadata = ResourceTextContentMetadata {
+                page: context_item.message.page,
+                ..Default::default()
+            };
+            return verify_citation(cited_text, [(content, &metadata)]);
+        }
+        // the cited part goes first so it wins ties
+        text_contents
+            .sort_by_key(|tc| Some(&tc.id) != context_item.resource_text_content_id.as_ref());
+        verify_citation(
+            cited_text,
+            text_contents
+                .iter()
+                .map(|tc| (tc.content.as_str(), &tc.metadata)),
+        )
+    }
 }
 
 impl ContextManager for LLMContext {
@@ -296,47 +379,57 @@ impl ContextManager for LLMContext {
 
     fn add_resources(&mut self, key: &str, resource_ids: &[String]) -> BackendResult<()> {
         self.check_note_id(key)?;
-        let resources = self.db.list_resources_metadata_by_ids(
-            &resource_ids
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>(),
-        )?;
-        let new_items = Self::context_metadata_messages_from_resources(&resources);
-        for (id, context_item) in new_items {
-            self.context_items.insert(id, context_item);
+        let existing: HashSet<&String> = self
+            .context_items
+            .values()
+            .filter_map(|ci| ci.resource_id.as_ref())
+            .collect();
+        let resource_ids: Vec<String> = resource_ids
+            .iter()
+            .filter(|id| !existing.contains(id))
+            .cloned()
+            .collect();
+        let resources = self.db.list_resources_metadata_by_ids(&resource_ids)?;
+        let new_items =
+            Self::context_metadata_messages_from_resources(&resources, &mut self.next_id);
+        let new_ids: Vec<String> = new_items.keys().cloned().collect();
+        self.context_items.extend(new_items);
+        // same as on creation, a few resources are cheap enough to load right away
+        if new_ids.len() <= 5 {
+            for id in new_ids {
+                self.add_resource_text_content(&id)?;
+            }
         }
         Ok(())
     }
 
     // TODO: can't really ignore the key in check in the trait impl but it's fine for now
-    fn add_url(&mut self, _key: &str, url: &str) -> BackendResult<()> {
-        let (content_type, title, content, _screenshot) = if is_youtube_video_url(url) {
-            let yt_transcript = fetch_transcript(url, self.user_lang_preference.as_deref())?;
-            (
-                "Context(YouTube Transcript)",
-                None,
-                Some(yt_transcript.transcript),
-                None,
-            )
-        } else {
-            let result: ScrapeWebpageResult = self
-                .js_tool_registry
-                .execute_tool(&ToolName::ScrapeURL, Some(vec![url.to_string()]))?;
-            (
-                "Context(Webpage)",
-                Some(result.title),
-                result.content,
-                result.screenshot,
-            )
-        };
+    fn add_url(&mut self, key: &str, url: &str) -> BackendResult<()> {
+        let fetched = self.url_fetcher.fetch_url(url)?;
+        self.add_fetched_url(key, fetched)
+    }
+
+    fn add_urls(&mut self, key: &str, urls: &[String]) -> BackendResult<Vec<BackendResult<()>>> {
+        self.check_note_id(key)?;
+        let mut results = Vec::new();
+        for url in urls {
+            let result = self.add_url(key, url);
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    fn url_fetcher(&self) -> Option<Arc<dyn UrlFetcher>> {
+        Some(self.url_fetcher.clone())
+    }
 
+    fn add_fetched_url(&mut self, _key: &str, fetched: FetchedUrl) -> BackendResult<()> {
         let msg = ContextMessage {
-            id: format!("{}", self.context_items.len()),
-            content_type: content_type.to_string(),
-            title,
-            content,
-            source_url: Some(url.to_string()),
+            id: self.next_context_id(),
+            content_type: fetched.content_type,
+            title: fetched.title,
+            content: fetched.content,
+            source_url: Some(fetched.url),
             page: None,
             author: None,
             description: None,
@@ -355,14 +448,30 @@ impl ContextManager for LLMContext {
         Ok(())
     }
 
-    fn add_urls(&mut self, key: &str, urls: &[String]) -> BackendResult<Vec<BackendResult<()>>> {
+    fn add_text(&mut self, key: &str, title: &str, content: &str) -> BackendResult<()> {
         self.check_note_id(key)?;
-        let mut results = Vec::new();
-        for url in urls {
-            let result = self.add_url(key, url);
-            results.push(result);
-        }
-        Ok(results)
+        let msg = ContextMessage {
+            id: self.next_context_id(),
+            content_type: "Context(Tool Result)".to_string(),
+            title: Some(title.to_string()),
+            content: Some(content.to_string()),
+            source_url: None,
+            page: None,
+            author: None,
+            description: None,
+            created_at: None,
+            timestamp: None,
+        };
+
+        self.context_items.insert(
+            msg.id.clone(),
+            ContextItem {
+                message: msg,
+                resource_id: None,
+                resource_text_content_id: None,
+            },
+        );
+        Ok(())
     }
 
     fn get_sources_xml(&self, key: &str) -> BackendResult<String> {
@@ -378,7 +487,15 @@ impl ContextManager for LLMContext {
     fn get_citation(&self, key: &str, message_id: &str, cited_text: &str) -> BackendResult<String> {
         self.check_note_id(key)?;
         if let Some(ci) = self.context_items.get(message_id) {
-            Ok(ci.to_citation(cited_text))
+            let check = self.verify_citation(ci, cited_text);
+            if check.status == CitationStatus::Unverified {
+                tracing::warn!(
+                    "citation of context item {} not found in its source: {:?}",
+                    message_id,
+                    cited_text
+                );
+            }
+            Ok(ci.to_citation(cited_text, &check))
         } else {
             Err(BackendError::GenericError(
                 "Message ID not found".to_string(),
@@ -386,3 +503,71 @@ impl ContextManager for LLMContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_ids_are_not_reused_after_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db").to_string_lossy().to_string();
+        Database::new(&db_path, true).unwrap();
+        let mut context = LLMContext::new(
+            &db_path,
+            Arc::new(JSToolRegistry::new()),
+            "note".to_string(),
+            &[],
+            &[],
+            None,
+        )
+        .unwrap();
+
+        context.add_text("note", "first", "one").unwrap();
+        context.add_text("note", "second", "two").unwrap();
+        context
+            .remove_context_messages("note", &["0".to_string()])
+            .unwrap();
+        context.add_text("note", "third", "three").unwrap();
+
+        let mut ids: Vec<&str> = context.context_items.keys().map(|id| id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert_eq!(
+            context.context_items["1"].message.title.as_deref(),
+            Some("second")
+        );
+    }
+
+    #[test]
+    fn test_get_citation_with_anchors() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db").to_string_lossy().to_string();
+        Database::new(&db_path, true).unwrap();
+        let mut context = LLMContext::new(
+            &db_path,
+            Arc::new(JSToolRegistry::new()),
+            "note".to_string(),
+            &[],
+            &[],
+            None,
+        )
+        .unwrap();
+        context
+            .add_text(
+                "note",
+                "report",
+                "The board expects growth to slow down next year as the market matures.",
+            )
+            .unwrap();
+
+        let citation = context
+            .get_citation("note", "0", "the board expects;;;market matures")
+            .unwrap();
+        assert!(citation.contains("data-verification=\"verified\""));
+        let citation = context
+            .get_citation("note", "0", "market matures;;;the board expects")
+            .unwrap();
+        assert!(citation.contains("data-verification=\"unverified\""));
+    }
+}
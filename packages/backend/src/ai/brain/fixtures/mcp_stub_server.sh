@@ -0,0 +1,30 @@
+#!/bin/sh
+# minimal MCP server for tests: answers initialize, tools/list and tools/call
+# by matching on the method, requests are expected as compact json
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+    case "$line" in
+    *'"method":"initialize"'*)
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"stub","version":"0.1.0"}}}\n' "$id"
+        ;;
+    *'"method":"tools/list"'*)
+        case "$line" in
+        *'"cursor"'*)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"fail","description":"Always fails","inputSchema":{"type":"object","properties":{}}}]}}\n' "$id"
+            ;;
+        *)
+            printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"search_issues","description":"Searches the issue tracker","inputSchema":{"type":"object","properties":{"query":{"type":"string"}},"required":["query"]}}],"nextCursor":"2"}}\n' "$id"
+            ;;
+        esac
+        ;;
+    *'"name":"search_issues"'*)
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"#42 crash on startup"},{"type":"image","data":"","mimeType":"image/png"}]}}\n' "$id"
+        ;;
+    *'"name":"fail"'*)
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"tracker unavailable"}],"isError":true}}\n' "$id"
+        ;;
+    *'"method":"tools/call"'*)
+        printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32602,"message":"unknown tool"}}\n' "$id"
+        ;;
+    esac
+done
@@ -1,10 +1,9 @@
 use super::agents::io::AgentIO;
 use neon::prelude::*;
 use neon::{event::Channel, handle::Root, types::JsFunction};
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
-use std::{fs, thread};
 
 use crate::ai::brain::agents::io::StatusMessage;
 use crate::{BackendError, BackendResult};
@@ -12,6 +12,7 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex, RwLock};
 
+use crate::ai::brain::replay::JSToolFixture;
 use crate::{BackendError, BackendResult};
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
@@ -20,8 +21,34 @@ pub enum ToolName {
     SearchDoneCallback,
     SurfletDoneCallback,
     ScrapeURL,
+    // user defined tools, see `agents::custom`
+    Custom(String),
 }
 
+impl ToolName {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ToolName::SearchAPI => "web_search_api",
+            ToolName::SearchDoneCallback => "web_search_done_callback",
+            ToolName::ScrapeURL => "scrape_url",
+            ToolName::SurfletDoneCallback => "surflet_done_callback",
+            ToolName::Custom(name) => name,
+        }
+    }
+
+    // a built-in tool or one of the custom tools stored in the database
+    pub fn parse(s: &str, custom_tool_names: &[String]) -> BackendResult<Self> {
+        match ToolName::from_str(s) {
+            Ok(tool_name) => Ok(tool_name),
+            Err(_) if custom_tool_names.iter().any(|name| name == s) => {
+                Ok(ToolName::Custom(s.to_string()))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// only the built-in tools, see `ToolName::parse` for custom tools
 impl FromStr for ToolName {
     type Err = BackendError;
     fn from_str(s: &str) -> BackendResult<Self> {
@@ -30,10 +57,8 @@ impl FromStr for ToolName {
             "web_search_done_callback" => Ok(ToolName::SearchDoneCallback),
             "scrape_url" => Ok(ToolName::ScrapeURL),
             "surflet_done_callback" => Ok(ToolName::SurfletDoneCallback),
-            _ => Err(BackendError::GenericError(format!(
-                "Unexpected tool name: {}",
-                s
-            ))),
+            "" => Err(BackendError::GenericError("Empty tool name".to_string())),
+            _ => Err(BackendError::GenericError(format!("Unknown tool: {}", s))),
         }
     }
 }
@@ -46,6 +71,7 @@ pub struct ToolEntry {
 #[derive(Clone)]
 pub struct JSToolRegistry {
     tools: Arc<RwLock<HashMap<ToolName, ToolEntry>>>,
+    fixture: Arc<RwLock<Option<JSToolFixture>>>,
 }
 
 impl Default for JSToolRegistry {
@@ -58,9 +84,19 @@ impl JSToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: Arc::new(RwLock::new(HashMap::new())),
+            fixture: Arc::new(RwLock::new(None)),
         }
     }
 
+    // records the tool results or answers the tool calls from a fixture, see `brain::replay`
+    pub fn set_fixture(&self, fixture: Option<JSToolFixture>) -> BackendResult<()> {
+        let mut current = self.fixture.write().map_err(|_| {
+            BackendError::GenericError("Failed to acquire write lock on tool fixture".to_string())
+        })?;
+        *current = fixture;
+        Ok(())
+    }
+
     pub fn add_tool(
         &self,
         tool_name: ToolName,
@@ -93,6 +129,36 @@ impl JSToolRegistry {
         T: Send + 'static,
         for<'cx> T: TryIntoJs<'cx>,
         R: for<'de> Deserialize<'de> + Send + 'static,
+    {
+        let fixture = self
+            .fixture
+            .read()
+            .map_err(|_| {
+                BackendError::GenericError(
+                    "Failed to acquire read lock on tool fixture".to_string(),
+                )
+            })?
+            .clone();
+        let result = match &fixture {
+            Some(JSToolFixture::Replay(replayer)) => replayer.next_js_tool_result(tool_name),
+            _ => self.call_js_tool(tool_name, args),
+        };
+        if let Some(JSToolFixture::Record(recorder)) = &fixture {
+            recorder.record_js_tool(tool_name, &result);
+        }
+        serde_json::from_value(result?).map_err(|e| {
+            BackendError::GenericError(format!("Failed to deserialize tool result: {}", e))
+        })
+    }
+
+    fn call_js_tool<T>(
+        &self,
+        tool_name: &ToolName,
+        args: Option<Vec<T>>,
+    ) -> BackendResult<serde_json::Value>
+    where
+        T: Send + 'static,
+        for<'cx> T: TryIntoJs<'cx>,
     {
         let (callback_arc, channel) = {
             let tools = self.tools.read().map_err(|_| {
@@ -112,7 +178,7 @@ impl JSToolRegistry {
             }
         };
 
-        let (tx, rx) = std::sync::mpsc::channel::<BackendResult<R>>();
+        let (tx, rx) = std::sync::mpsc::channel::<BackendResult<serde_json::Value>>();
 
         channel.send(move |mut cx| {
             let callback_root = {
@@ -166,7 +232,7 @@ impl JSToolRegistry {
                                 .downcast_or_throw::<JsString, _>(&mut cx)
                                 .map(|js_string| js_string.value(&mut cx))?;
 
-                            match serde_json::from_str::<R>(&json_string) {
+                            match serde_json::from_str::<serde_json::Value>(&json_string) {
                                 Ok(extracted) => {
                                     let _ = tx_clone.send(Ok(extracted));
                                 }
@@ -196,7 +262,7 @@ impl JSToolRegistry {
                     }
                 };
             } else {
-                match Json::<R>::try_from_js(&mut cx, result) {
+                match Json::<serde_json::Value>::try_from_js(&mut cx, result) {
                     Ok(Ok(Json(extracted))) => {
                         let _ = tx.send(Ok(extracted));
                     }
@@ -233,3 +299,24 @@ impl JSToolRegistry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_name() {
+        let custom_tool_names = vec!["lookup_ticket".to_string()];
+        assert_eq!(
+            ToolName::parse("scrape_url", &custom_tool_names).unwrap(),
+            ToolName::ScrapeURL
+        );
+        assert_eq!(
+            ToolName::parse("lookup_ticket", &custom_tool_names).unwrap(),
+            ToolName::Custom("lookup_ticket".to_string())
+        );
+        assert!(ToolName::parse("lookup_tickets", &custom_tool_names).is_err());
+        assert!(ToolName::parse("", &custom_tool_names).is_err());
+        assert!("lookup_ticket".parse::<ToolName>().is_err());
+    }
+}
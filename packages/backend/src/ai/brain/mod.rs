@@ -1,8 +1,11 @@
 pub mod agents;
+pub mod citations;
 pub mod context;
 pub mod io;
 pub mod js_tools;
 pub mod orchestrator;
+pub mod replay;
+pub mod run_log;
 pub mod tools;
 
 mod prompts;
@@ -1,28 +1,64 @@
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::ai::brain::agents::approval::{ToolApprovals, DEFAULT_APPROVAL_TIMEOUT};
+use crate::ai::brain::agents::budget::AgentBudget;
 use crate::ai::brain::agents::context::ContextManager;
 use crate::ai::brain::agents::context_manager::context_manager::create_context_manager_agent;
+use crate::ai::brain::agents::custom::agent::create_custom_agent;
+use crate::ai::brain::agents::custom::tools::{CustomAgentTool, JSCustomTool};
 use crate::ai::brain::agents::io::AgentIO;
+use crate::ai::brain::agents::library::tools::{LibrarySearchTool, LIBRARY_SEARCH_TOOL_NAME};
+use crate::ai::brain::agents::mcp::client::McpClient;
+use crate::ai::brain::agents::mcp::tools::McpTool;
+use crate::ai::brain::agents::memory::tools::create_memory_tools;
 use crate::ai::brain::agents::surflet::surflet::create_surflet_agent;
-use crate::ai::brain::agents::websearch::tools::SearchEngineCaller;
+use crate::ai::brain::agents::tools::Tool;
+use crate::ai::brain::agents::websearch::tools::{
+    SearchEngineCaller, SEARCH_ENGINE_CALLER_TOOL_NAME,
+};
 use crate::ai::brain::agents::AgentResult;
 use crate::ai::brain::agents::{Agent, AgentConfig, ExecuteConfig};
 use crate::ai::brain::js_tools::JSToolRegistry;
 use crate::ai::brain::prompts::{current_time_prompt, lead_agent_prompt};
+use crate::ai::brain::replay::{FixtureRecorder, JSToolFixture};
+use crate::ai::brain::run_log::{
+    resume_state, AgentRunRecorder, RecordingContextManager, RecordingIO,
+};
 use crate::ai::brain::tools::ContextManagementTool;
-use crate::ai::llm::client::{CancellationToken, LLMClient, Model};
+use crate::ai::llm::client::{CancellationToken, ChatCompletionProvider, LLMClient, Model};
+use crate::ai::prompts::memories_prompt;
+use crate::ai::AI;
+use crate::store::db::Database;
+use crate::store::models::{
+    current_time, AgentRun, AgentRunStatus, AgentRunTranscript, CustomTool, ToolPolicy,
+};
 use crate::{BackendError, BackendResult};
 
 use super::tools::SurfletAgentTool;
 
+const MCP_STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
 #[allow(dead_code)]
 pub struct Orchestrator {
     api_base: String,
     api_key: String,
-    llm_client: Arc<LLMClient>,
+    llm_client: Arc<dyn ChatCompletionProvider>,
     model: Model,
     lead_agent: Option<Agent>,
     js_tool_registry: Arc<JSToolRegistry>,
+    // agent runs are recorded here
+    db_path: String,
+    // records the runs that ask for a fixture, see `ExecuteConfig::fixture_dir`
+    fixture_recorder: Arc<FixtureRecorder>,
+    ai: Arc<AI>,
+    // names of the user defined tools and agents added to the lead agent
+    custom_tool_names: Vec<String>,
+    // names of the tools of the mcp servers added to the lead agent
+    mcp_tool_names: Vec<String>,
+    // shared by the lead agent and the custom agents
+    tool_approvals: Arc<ToolApprovals>,
 }
 
 impl Orchestrator {
@@ -31,25 +67,37 @@ impl Orchestrator {
         api_key: String,
         default_model: Model,
         js_tool_registry: Arc<JSToolRegistry>,
+        db_path: &str,
+        ai: Arc<AI>,
     ) -> BackendResult<Self> {
-        let llm_client = LLMClient::new(api_base.clone(), api_key.clone()).map_err(|e| {
+        let llm_client = LLMClient::new().map_err(|e| {
             BackendError::GenericError(
                 format!("failed to create new llm client: {:?}", e).to_string(),
             )
         })?;
 
-        let llm_client = Arc::new(llm_client);
+        // only records while a run with a fixture dir is running
+        let fixture_recorder = Arc::new(FixtureRecorder::new(Arc::new(llm_client)));
+        fixture_recorder.set_recording(false);
+        js_tool_registry.set_fixture(Some(JSToolFixture::Record(fixture_recorder.clone())))?;
+        let llm_client: Arc<dyn ChatCompletionProvider> = fixture_recorder.clone();
 
         let lead_config = AgentConfig {
             name: "Lead Agent".to_string(),
             max_iterations: 10,
+            max_parallel_tool_calls: 4,
             system_prompt: lead_agent_prompt(),
             fallback_to_text: true,
             retry_on_parse_error: true,
             write_status_to_io: true,
             write_final_response_to_io: true,
         };
-        let lead_agent = Agent::new(llm_client.clone(), lead_config);
+        let tool_approvals = Arc::new(ToolApprovals::new(
+            Database::new(db_path, false)?.list_tool_policies()?,
+            DEFAULT_APPROVAL_TIMEOUT,
+        ));
+        let mut lead_agent = Agent::new(llm_client.clone(), lead_config);
+        lead_agent.set_tool_approvals(Arc::clone(&tool_approvals));
         let mut orc = Self {
             api_base,
             api_key,
@@ -57,10 +105,20 @@ impl Orchestrator {
             llm_client,
             lead_agent: Some(lead_agent),
             js_tool_registry,
+            db_path: db_path.to_string(),
+            fixture_recorder,
+            ai: ai.clone(),
+            custom_tool_names: vec![],
+            mcp_tool_names: vec![],
+            tool_approvals,
         };
         orc.init_web_search_agent()?;
         orc.init_surflet_agent()?;
         orc.init_context_manager_agent()?;
+        orc.init_library_search_tool(db_path, ai.clone())?;
+        orc.init_memory_tools(db_path, ai)?;
+        orc.load_custom_agents()?;
+        orc.load_mcp_tools()?;
         Ok(orc)
     }
 
@@ -88,6 +146,24 @@ impl Orchestrator {
         Ok(())
     }
 
+    pub fn init_library_search_tool(&mut self, db_path: &str, ai: Arc<AI>) -> BackendResult<()> {
+        let library_search_tool = Box::new(LibrarySearchTool::new(db_path, ai)?);
+        if let Some(ref mut lead_agent) = self.lead_agent {
+            lead_agent.add_tool(library_search_tool);
+        }
+        Ok(())
+    }
+
+    pub fn init_memory_tools(&mut self, db_path: &str, ai: Arc<AI>) -> BackendResult<()> {
+        let memory_tools = create_memory_tools(db_path, ai)?;
+        if let Some(ref mut lead_agent) = self.lead_agent {
+            for tool in memory_tools {
+                lead_agent.add_tool(tool);
+            }
+        }
+        Ok(())
+    }
+
     pub fn init_surflet_agent(&mut self) -> BackendResult<()> {
         let surflet_agent = create_surflet_agent(
             Arc::clone(&self.llm_client),
@@ -100,10 +176,132 @@ impl Orchestrator {
         Ok(())
     }
 
+    // (re)loads the user defined tools and agents from the db and adds them to the lead agent,
+    // definitions that are invalid or would shadow a built-in tool are skipped
+    pub fn load_custom_agents(&mut self) -> BackendResult<()> {
+        let db = Database::new(&self.db_path, false)?;
+        let custom_tools = db.list_custom_tools()?;
+        let custom_agents = db.list_custom_agents()?;
+
+        let mut tools: Vec<Box<dyn Tool>> = vec![];
+        for definition in &custom_tools {
+            match JSCustomTool::new(definition.clone(), Arc::clone(&self.js_tool_registry)) {
+                Ok(tool) => tools.push(Box::new(tool)),
+                Err(e) => tracing::warn!("skipping custom tool {}: {}", definition.name, e),
+            }
+        }
+        for definition in &custom_agents {
+            let mut agent_tools = vec![];
+            for name in &definition.allowed_tools {
+                match self.create_custom_agent_tool(name, &custom_tools) {
+                    Ok(Some(tool)) => agent_tools.push(tool),
+                    Ok(None) => tracing::warn!(
+                        "custom agent {} uses unknown tool {}",
+                        definition.name,
+                        name
+                    ),
+                    Err(e) => tracing::warn!(
+                        "custom agent {} failed to create tool {}: {}",
+                        definition.name,
+                        name,
+                        e
+                    ),
+                }
+            }
+            let mut agent =
+                create_custom_agent(Arc::clone(&self.llm_client), definition, agent_tools);
+            agent.set_tool_approvals(Arc::clone(&self.tool_approvals));
+            match CustomAgentTool::new(definition, agent) {
+                Ok(tool) => tools.push(Box::new(tool)),
+                Err(e) => tracing::warn!("skipping custom agent {}: {}", definition.name, e),
+            }
+        }
+
+        let lead_agent = match self.lead_agent.as_mut() {
+            Some(lead_agent) => lead_agent,
+            None => return Ok(()),
+        };
+        for name in self.custom_tool_names.drain(..) {
+            lead_agent.remove_tool(&name);
+        }
+        for tool in tools {
+            let name = tool.name().to_string();
+            if lead_agent.has_tool(&name) {
+                tracing::warn!("custom tool {} would shadow another tool, skipping", name);
+                continue;
+            }
+            lead_agent.add_tool(tool);
+            self.custom_tool_names.push(name);
+        }
+        Ok(())
+    }
+
+    // (re)starts the enabled mcp servers and adds their tools to the lead agent,
+    // servers that fail to start are skipped
+    pub fn load_mcp_tools(&mut self) -> BackendResult<()> {
+        let servers = Database::new(&self.db_path, false)?.list_mcp_servers()?;
+
+        let mut tools = vec![];
+        for server in servers.iter().filter(|server| server.enabled) {
+            // don't let a hanging server block the orchestrator
+            let token = CancellationToken::new().with_timeout(MCP_STARTUP_TIMEOUT);
+            let result = McpClient::spawn(server, &token)
+                .and_then(|client| McpTool::discover(Arc::new(client), &token));
+            match result {
+                Ok(server_tools) => tools.extend(server_tools),
+                Err(e) => tracing::warn!("skipping mcp server {}: {}", server.name, e),
+            }
+        }
+
+        let lead_agent = match self.lead_agent.as_mut() {
+            Some(lead_agent) => lead_agent,
+            None => return Ok(()),
+        };
+        // the old clients shut their servers down once their tools are dropped
+        for name in self.mcp_tool_names.drain(..) {
+            lead_agent.remove_tool(&name);
+        }
+        for tool in tools {
+            let name = tool.name().to_string();
+            if lead_agent.has_tool(&name) {
+                tracing::warn!("mcp tool {} would shadow another tool, skipping", name);
+                continue;
+            }
+            lead_agent.add_tool(Box::new(tool));
+            self.mcp_tool_names.push(name);
+        }
+        Ok(())
+    }
+
+    // the built-in tools custom agents may use and the custom tools
+    fn create_custom_agent_tool(
+        &self,
+        name: &str,
+        custom_tools: &[CustomTool],
+    ) -> BackendResult<Option<Box<dyn Tool>>> {
+        let tool: Box<dyn Tool> = match name {
+            LIBRARY_SEARCH_TOOL_NAME => {
+                Box::new(LibrarySearchTool::new(&self.db_path, Arc::clone(&self.ai))?)
+            }
+            SEARCH_ENGINE_CALLER_TOOL_NAME => {
+                Box::new(SearchEngineCaller::new(Arc::clone(&self.js_tool_registry)))
+            }
+            _ => match custom_tools.iter().find(|tool| tool.name == name) {
+                Some(definition) => Box::new(JSCustomTool::new(
+                    definition.clone(),
+                    Arc::clone(&self.js_tool_registry),
+                )?),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(tool))
+    }
+
     pub fn create_agent(&self, name: String, system_prompt: String) -> Agent {
         let config = AgentConfig {
             name,
             max_iterations: 3,
+            max_parallel_tool_calls: 4,
             system_prompt,
             fallback_to_text: true,
             retry_on_parse_error: true,
@@ -113,13 +311,96 @@ impl Orchestrator {
         Agent::new(self.llm_client.clone(), config)
     }
 
-    pub fn get_llm_client(&self) -> Arc<LLMClient> {
+    pub fn get_llm_client(&self) -> Arc<dyn ChatCompletionProvider> {
         Arc::clone(&self.llm_client)
     }
 
-    // TODO: should we take an execution id?
+    // runs the lead agent and records the run under `config.execution_id`
     pub fn execute_lead_agent(
         &self,
+        config: ExecuteConfig,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        let now = current_time();
+        let run = AgentRun {
+            id: config.execution_id.clone(),
+            note_id: io.get_id(),
+            model: serde_json::to_string(&config.model)?,
+            user_message: config.user_message.clone(),
+            status: AgentRunStatus::Running,
+            final_answer: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let mut db = Database::new(&self.db_path, false)?;
+        db.create_agent_run(&run)?;
+        self.run_lead_agent(db, config, io, context_manager, cancellation_token)
+    }
+
+    // continues a run that was cancelled, failed or crashed from its last finished iteration
+    //
+    // the context is rebuilt by replaying the recorded context changes into `context_manager`,
+    // which should be freshly created for the note of the run
+    pub fn resume_lead_agent(
+        &self,
+        run_id: &str,
+        custom_key: Option<String>,
+        budget: AgentBudget,
+        io: &dyn AgentIO,
+        context_manager: &mut dyn ContextManager,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        let mut db = Database::new(&self.db_path, false)?;
+        let transcript = db.get_agent_run_transcript(run_id)?.ok_or_else(|| {
+            BackendError::GenericError(format!("agent run not found: {}", run_id))
+        })?;
+        if transcript.run.status == AgentRunStatus::Completed {
+            return Err(BackendError::GenericError(format!(
+                "agent run already completed: {}",
+                run_id
+            )));
+        }
+        if transcript.run.note_id != io.get_id() {
+            return Err(BackendError::GenericError(
+                "agent run belongs to a different note".to_string(),
+            ));
+        }
+
+        let (history, context_events) = resume_state(&transcript)?;
+        // whatever the unfinished iteration recorded is redone
+        db.truncate_agent_run(run_id, history.len() as i64)?;
+        for event in context_events {
+            if let Err(e) = event.replay(&transcript.run.note_id, context_manager) {
+                tracing::warn!(
+                    "failed to replay context event of agent run {}: {}",
+                    run_id,
+                    e
+                );
+            }
+        }
+        db.update_agent_run_status(run_id, &AgentRunStatus::Running, None, None)?;
+
+        let config = ExecuteConfig {
+            execution_id: transcript.run.id,
+            user_message: transcript.run.user_message,
+            system_message_preamble: None,
+            model: serde_json::from_str(&transcript.run.model)?,
+            custom_key,
+            allowed_tools: None,
+            recorder: None,
+            history,
+            budget,
+            fixture_dir: None,
+        };
+        self.run_lead_agent(db, config, io, context_manager, cancellation_token)
+    }
+
+    fn run_lead_agent(
+        &self,
+        mut db: Database,
         mut config: ExecuteConfig,
         io: &dyn AgentIO,
         context_manager: &mut dyn ContextManager,
@@ -130,18 +411,103 @@ impl Orchestrator {
             .as_ref()
             .ok_or_else(|| BackendError::GenericError("Lead agent not configured".to_string()))?;
 
-        config.system_message_preamble = Some(current_time_prompt());
-        let result = agent.execute(config, io, context_manager, cancellation_token)?;
-        match result {
+        let run_id = config.execution_id.clone();
+        let recorder = Arc::new(AgentRunRecorder::new(&self.db_path, run_id.clone())?);
+        let io = RecordingIO::new(io, &recorder);
+        let mut context_manager = RecordingContextManager::new(context_manager, &recorder);
+
+        let mut preamble = current_time_prompt();
+        // the run still works without memories, e.g. when the embeddings model is down
+        match self.ai.relevant_memories(&db, &config.user_message) {
+            Ok(memories) => {
+                if let Some(prompt) = memories_prompt(&memories, true) {
+                    preamble.push_str("\n\n");
+                    preamble.push_str(&prompt);
+                }
+            }
+            Err(e) => tracing::warn!("failed to get relevant memories: {}", e),
+        }
+        config.system_message_preamble = Some(preamble);
+        config.recorder = Some(recorder.clone());
+        let fixture_dir = config.fixture_dir.take();
+        if fixture_dir.is_some() {
+            self.fixture_recorder.set_recording(true);
+        }
+        let result = agent.execute(config, &io, &mut context_manager, cancellation_token);
+        if let Some(dir) = fixture_dir {
+            self.save_fixture(&run_id, &dir);
+        }
+
+        let (status, final_answer, error) = match &result {
+            Ok(AgentResult::Success(response))
+            | Ok(AgentResult::MaxIterationsReached(response)) => {
+                (AgentRunStatus::Completed, Some(response.as_str()), None)
+            }
+            Ok(AgentResult::Cancelled) => (AgentRunStatus::Cancelled, None, None),
+            Ok(AgentResult::Error(error)) => (AgentRunStatus::Failed, None, Some(error.clone())),
+            Err(BackendError::LLMClientErrorCancelled) => (AgentRunStatus::Cancelled, None, None),
+            Err(e) => (AgentRunStatus::Failed, None, Some(e.to_string())),
+        };
+        if let Err(e) = db.update_agent_run_status(&run_id, &status, final_answer, error.as_deref())
+        {
+            tracing::warn!("failed to update status of agent run {}: {}", run_id, e);
+        }
+
+        match result? {
             AgentResult::Success(response) => Ok(response),
             AgentResult::MaxIterationsReached(response) => {
                 Ok(format!("Max iterations reached: {}", response))
             }
-            AgentResult::Cancelled => Err(BackendError::CancelledError),
+            AgentResult::Cancelled => Err(BackendError::LLMClientErrorCancelled),
             AgentResult::Error(error) => Err(BackendError::GenericError(format!(
                 "Agent error: {}",
                 error
             ))),
         }
     }
+
+    // runs that overlap a recorded run end up in its fixture as well
+    fn save_fixture(&self, run_id: &str, dir: &Path) {
+        self.fixture_recorder.set_recording(false);
+        let result = self
+            .fixture_recorder
+            .take()
+            .and_then(|fixture| fixture.save(&dir.join(format!("{}.json", run_id))));
+        if let Err(e) = result {
+            tracing::warn!("failed to save fixture of agent run {}: {}", run_id, e);
+        }
+    }
+
+    // answers an approval request a running agent emitted,
+    // false if the request is unknown or was already answered
+    pub fn respond_to_tool_approval(&self, request_id: &str, approved: bool) -> bool {
+        self.tool_approvals.respond(request_id, approved)
+    }
+
+    // `None` resets the tool to the default policy of its risk level
+    pub fn set_tool_policy(
+        &self,
+        tool_name: &str,
+        policy: Option<ToolPolicy>,
+    ) -> BackendResult<()> {
+        let mut db = Database::new(&self.db_path, false)?;
+        db.set_tool_policy(tool_name, policy.as_ref())?;
+        self.tool_approvals.set_policy(tool_name, policy);
+        Ok(())
+    }
+
+    pub fn list_agent_runs(
+        &self,
+        note_id: Option<&str>,
+        limit: Option<i64>,
+    ) -> BackendResult<Vec<AgentRun>> {
+        Database::new(&self.db_path, false)?.list_agent_runs(note_id, limit)
+    }
+
+    pub fn get_agent_run_transcript(
+        &self,
+        run_id: &str,
+    ) -> BackendResult<Option<AgentRunTranscript>> {
+        Database::new(&self.db_path, false)?.get_agent_run_transcript(run_id)
+    }
 }
@@ -15,6 +15,7 @@ pub fn lead_agent_prompt() -> String {
 
 **Tool Selection Strategy:**
 - For current/recent information → Use search/retrieval tools
+- For things the user saved or read before → Use library search tools
 - For interactive content/apps → Use creation/visualization tools  
 - For context optimization → Use management/organization tools
 - For complex requests → Chain multiple tools as needed
@@ -104,3 +105,10 @@ Use only the first 3-4 words and last 3-4 words of the cited text.
 
 Focus on delivering well-researched, beautifully formatted content that directly addresses user requests while leveraging the most suitable combination of available tools. Every response should look professional and document-ready.".to_string()
 }
+
+pub fn budget_exhausted_prompt(limit: &str) -> String {
+    format!(
+        "The {} budget for this request is used up and no more tools can be called. Answer now in `<final_answer>` with the information you already have and briefly mention what is missing, if anything.",
+        limit
+    )
+}
@@ -0,0 +1,478 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::ai::brain::js_tools::ToolName;
+use crate::ai::llm::client::{
+    CancellationToken, ChatCompletionProvider, ChatCompletionStream, Model,
+};
+use crate::ai::llm::models::Message;
+use crate::{BackendError, BackendResult};
+
+// everything an agent run got from outside: the llm responses and the js tool results,
+// recorded during a real run and replayed to rerun it offline and deterministically
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub completions: Vec<RecordedCompletion>,
+    pub js_tools: Vec<RecordedJSTool>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RecordedCompletion {
+    // last message of the request, requests are matched on it when replaying
+    // so that calls made from tools running in parallel get the right response
+    pub request: Option<Message>,
+    pub streaming: bool,
+    // the whole response for non streaming completions
+    pub chunks: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedJSTool {
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Fixture {
+    pub fn load(path: &Path) -> BackendResult<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> BackendResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> BackendResult<std::sync::MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| BackendError::GenericError("fixture lock poisoned".to_string()))
+}
+
+// replayed errors are generic errors, so they are stored without the error kind
+// to come back with the same message
+fn error_message(e: &BackendError) -> String {
+    match e {
+        BackendError::GenericError(message) => message.clone(),
+        e => e.to_string(),
+    }
+}
+
+// passes requests through to `inner` and records the responses while recording
+pub struct FixtureRecorder {
+    inner: Arc<dyn ChatCompletionProvider>,
+    recording: AtomicBool,
+    // shared with the streams that are still being consumed
+    completions: Mutex<Vec<Arc<Mutex<RecordedCompletion>>>>,
+    js_tools: Mutex<Vec<RecordedJSTool>>,
+}
+
+impl FixtureRecorder {
+    pub fn new(inner: Arc<dyn ChatCompletionProvider>) -> Self {
+        Self {
+            inner,
+            recording: AtomicBool::new(true),
+            completions: Mutex::new(vec![]),
+            js_tools: Mutex::new(vec![]),
+        }
+    }
+
+    // requests made while not recording are only passed through
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, Ordering::SeqCst);
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    fn start_completion(
+        &self,
+        messages: &[Message],
+        streaming: bool,
+    ) -> BackendResult<Option<Arc<Mutex<RecordedCompletion>>>> {
+        if !self.is_recording() {
+            return Ok(None);
+        }
+        let completion = Arc::new(Mutex::new(RecordedCompletion {
+            request: messages.last().cloned(),
+            streaming,
+            ..Default::default()
+        }));
+        lock(&self.completions)?.push(completion.clone());
+        Ok(Some(completion))
+    }
+
+    pub fn record_js_tool(&self, tool: &ToolName, result: &BackendResult<serde_json::Value>) {
+        if !self.is_recording() {
+            return;
+        }
+        let recorded = RecordedJSTool {
+            tool: tool.as_str().to_string(),
+            result: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(error_message),
+        };
+        if let Ok(mut js_tools) = self.js_tools.lock() {
+            js_tools.push(recorded);
+        }
+    }
+
+    // everything recorded so far, the recorder starts over afterwards
+    pub fn take(&self) -> BackendResult<Fixture> {
+        let completions = std::mem::take(&mut *lock(&self.completions)?)
+            .iter()
+            .map(|completion| lock(completion).map(|c| c.clone()))
+            .collect::<BackendResult<Vec<_>>>()?;
+        Ok(Fixture {
+            completions,
+            js_tools: std::mem::take(&mut *lock(&self.js_tools)?),
+        })
+    }
+}
+
+impl ChatCompletionProvider for FixtureRecorder {
+    fn create_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        let completion = self.start_completion(&messages, false)?;
+        let result = self.inner.create_chat_completion(
+            messages,
+            model,
+            custom_key,
+            response_format,
+            cancellation_token,
+        );
+        if let Some(completion) = completion {
+            let mut completion = lock(&completion)?;
+            match &result {
+                Ok(response) => completion.chunks.push(response.clone()),
+                Err(e) => completion.error = Some(error_message(e)),
+            }
+        }
+        result
+    }
+
+    fn create_streaming_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<ChatCompletionStream> {
+        let completion = match self.start_completion(&messages, true)? {
+            Some(completion) => completion,
+            None => {
+                return self.inner.create_streaming_chat_completion(
+                    messages,
+                    model,
+                    custom_key,
+                    response_format,
+                    cancellation_token,
+                )
+            }
+        };
+        let stream = match self.inner.create_streaming_chat_completion(
+            messages,
+            model,
+            custom_key,
+            response_format,
+            cancellation_token,
+        ) {
+            Ok(stream) => stream,
+            Err(e) => {
+                lock(&completion)?.error = Some(error_message(&e));
+                return Err(e);
+            }
+        };
+        Ok(stream.with_tap(move |item| {
+            if let Ok(mut completion) = completion.lock() {
+                match item {
+                    Ok(chunk) => completion.chunks.push(chunk.clone()),
+                    Err(e) => completion.error = Some(error_message(e)),
+                }
+            }
+        }))
+    }
+}
+
+// answers requests from a fixture instead of calling a provider, recorded errors
+// come back as `BackendError::GenericError`
+pub struct FixtureReplayer {
+    // `None` once replayed
+    completions: Mutex<Vec<Option<RecordedCompletion>>>,
+    js_tools: Mutex<Vec<Option<RecordedJSTool>>>,
+}
+
+impl FixtureReplayer {
+    pub fn new(fixture: Fixture) -> Self {
+        Self {
+            completions: Mutex::new(fixture.completions.into_iter().map(Some).collect()),
+            js_tools: Mutex::new(fixture.js_tools.into_iter().map(Some).collect()),
+        }
+    }
+
+    pub fn load(path: &Path) -> BackendResult<Self> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+
+    // the first recorded completion not replayed yet with the same last message
+    fn next_completion(
+        &self,
+        messages: &[Message],
+        streaming: bool,
+    ) -> BackendResult<RecordedCompletion> {
+        let request = messages.last();
+        lock(&self.completions)?
+            .iter_mut()
+            .find(|c| {
+                c.as_ref()
+                    .is_some_and(|c| c.streaming == streaming && c.request.as_ref() == request)
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                BackendError::GenericError(format!(
+                    "no recorded completion left for request: {:?}",
+                    request
+                ))
+            })
+    }
+
+    pub fn next_js_tool_result(&self, tool: &ToolName) -> BackendResult<serde_json::Value> {
+        let recorded = lock(&self.js_tools)?
+            .iter_mut()
+            .find(|t| t.as_ref().is_some_and(|t| t.tool == tool.as_str()))
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                BackendError::GenericError(format!(
+                    "no recorded result left for js tool {}",
+                    tool.as_str()
+                ))
+            })?;
+        match (recorded.result, recorded.error) {
+            (_, Some(error)) => Err(BackendError::GenericError(error)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(serde_json::Value::Null),
+        }
+    }
+
+    // completions and js tool results that were never asked for,
+    // anything left means the run took a different path than the recorded one
+    pub fn remaining(&self) -> BackendResult<usize> {
+        let completions = lock(&self.completions)?.iter().flatten().count();
+        let js_tools = lock(&self.js_tools)?.iter().flatten().count();
+        Ok(completions + js_tools)
+    }
+}
+
+impl ChatCompletionProvider for FixtureReplayer {
+    fn create_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        _model: &Model,
+        _custom_key: Option<&str>,
+        _response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        cancellation_token.check()?;
+        let completion = self.next_completion(&messages, false)?;
+        match completion.error {
+            Some(error) => Err(BackendError::GenericError(error)),
+            None => Ok(completion.chunks.concat()),
+        }
+    }
+
+    fn create_streaming_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        _model: &Model,
+        _custom_key: Option<&str>,
+        _response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<ChatCompletionStream> {
+        let completion = self.next_completion(&messages, true)?;
+        let mut chunks: Vec<BackendResult<String>> =
+            completion.chunks.into_iter().map(Ok).collect();
+        if let Some(error) = completion.error {
+            chunks.push(Err(BackendError::GenericError(error)));
+        }
+        Ok(ChatCompletionStream::from_chunks(
+            chunks,
+            cancellation_token,
+        ))
+    }
+}
+
+// what the js tool registry does with the js tool results
+#[derive(Clone)]
+pub enum JSToolFixture {
+    Record(Arc<FixtureRecorder>),
+    Replay(Arc<FixtureReplayer>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::brain::js_tools::JSToolRegistry;
+
+    struct ScriptedProvider;
+
+    impl ChatCompletionProvider for ScriptedProvider {
+        fn create_chat_completion(
+            &self,
+            messages: Vec<Message>,
+            _model: &Model,
+            _custom_key: Option<&str>,
+            _response_format: Option<serde_json::Value>,
+            _cancellation_token: CancellationToken,
+        ) -> BackendResult<String> {
+            Ok(format!("{} messages", messages.len()))
+        }
+
+        fn create_streaming_chat_completion(
+            &self,
+            _messages: Vec<Message>,
+            _model: &Model,
+            _custom_key: Option<&str>,
+            _response_format: Option<serde_json::Value>,
+            cancellation_token: CancellationToken,
+        ) -> BackendResult<ChatCompletionStream> {
+            Ok(ChatCompletionStream::from_chunks(
+                vec![
+                    Ok("<final_".to_string()),
+                    Ok("answer>hi</final_answer>".to_string()),
+                    Err(BackendError::GenericError("connection reset".to_string())),
+                ],
+                cancellation_token,
+            ))
+        }
+    }
+
+    fn stream_chunks(stream: ChatCompletionStream) -> Vec<String> {
+        stream
+            .map(|item| item.unwrap_or_else(|e| format!("error: {}", e)))
+            .collect()
+    }
+
+    #[test]
+    fn test_record_and_replay_completions() {
+        let recorder = FixtureRecorder::new(Arc::new(ScriptedProvider));
+        let model = Model::GPT4oMini;
+        let first = vec![Message::new_user("first")];
+        let second = vec![Message::new_system("system"), Message::new_user("second")];
+
+        let recorded_stream = stream_chunks(
+            recorder
+                .create_streaming_chat_completion(
+                    first.clone(),
+                    &model,
+                    None,
+                    None,
+                    CancellationToken::new(),
+                )
+                .unwrap(),
+        );
+        let recorded_completion = recorder
+            .create_chat_completion(second.clone(), &model, None, None, CancellationToken::new())
+            .unwrap();
+
+        recorder.set_recording(false);
+        recorder
+            .create_chat_completion(first.clone(), &model, None, None, CancellationToken::new())
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixture.json");
+        recorder.take().unwrap().save(&path).unwrap();
+        assert!(recorder.take().unwrap().completions.is_empty());
+
+        let replayer = FixtureReplayer::load(&path).unwrap();
+        // matched on the request, not on the order of the calls
+        assert_eq!(
+            replayer
+                .create_chat_completion(
+                    second.clone(),
+                    &model,
+                    None,
+                    None,
+                    CancellationToken::new()
+                )
+                .unwrap(),
+            recorded_completion
+        );
+        let replayed_stream = stream_chunks(
+            replayer
+                .create_streaming_chat_completion(
+                    first,
+                    &model,
+                    None,
+                    None,
+                    CancellationToken::new(),
+                )
+                .unwrap(),
+        );
+        assert_eq!(replayed_stream, recorded_stream);
+        assert_eq!(replayer.remaining().unwrap(), 0);
+
+        assert!(replayer
+            .create_chat_completion(second, &model, None, None, CancellationToken::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_replay_js_tools() {
+        let replayer = Arc::new(FixtureReplayer::new(Fixture {
+            completions: vec![],
+            js_tools: vec![
+                RecordedJSTool {
+                    tool: "scrape_url".to_string(),
+                    result: Some(serde_json::json!({"title": "Rust"})),
+                    error: None,
+                },
+                RecordedJSTool {
+                    tool: "scrape_url".to_string(),
+                    result: None,
+                    error: Some("page not found".to_string()),
+                },
+            ],
+        }));
+        let registry = JSToolRegistry::new();
+        registry
+            .set_fixture(Some(JSToolFixture::Replay(replayer.clone())))
+            .unwrap();
+
+        let result: serde_json::Value = registry
+            .execute_tool(
+                &ToolName::ScrapeURL,
+                Some(vec!["https://rust-lang.org".to_string()]),
+            )
+            .unwrap();
+        assert_eq!(result["title"], "Rust");
+        let result: BackendResult<serde_json::Value> = registry.execute_tool(
+            &ToolName::ScrapeURL,
+            Some(vec!["https://rust-lang.org/404".to_string()]),
+        );
+        assert!(result.is_err());
+        let result: BackendResult<serde_json::Value> = registry.execute_tool(
+            &ToolName::SearchDoneCallback,
+            Some(vec!["rust".to_string()]),
+        );
+        assert!(result.is_err());
+        assert_eq!(replayer.remaining().unwrap(), 0);
+    }
+}
@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::ai::brain::agents::budget::BudgetUsage;
+use crate::ai::brain::agents::context::{ContextManager, FetchedUrl, UrlFetcher};
+use crate::ai::brain::agents::io::{AgentIO, StatusMessage};
+use crate::ai::brain::agents::run_log::{RecordedIteration, RunRecorder};
+use crate::ai::brain::agents::tools::{ToolCall, ToolResult};
+use crate::ai::llm::models::{Message, MessageRole};
+use crate::store::db::Database;
+use crate::store::models::{
+    current_time, AgentRunEvent, AgentRunEventType, AgentRunIteration, AgentRunIterationOutcome,
+    AgentRunToolCall, AgentRunTranscript,
+};
+use crate::BackendResult;
+
+// persists an agent run to the agent_runs tables as it happens
+pub struct AgentRunRecorder {
+    run_id: String,
+    // TODO: not have a new database connection for each run
+    db: Mutex<Database>,
+    iteration: AtomicUsize,
+    // written together with their iteration
+    pending_tool_calls: Mutex<Vec<AgentRunToolCall>>,
+}
+
+impl AgentRunRecorder {
+    pub fn new(db_path: &str, run_id: String) -> BackendResult<Self> {
+        Ok(Self {
+            run_id,
+            db: Mutex::new(Database::new(db_path, false)?),
+            iteration: AtomicUsize::new(0),
+            pending_tool_calls: Mutex::new(vec![]),
+        })
+    }
+
+    // failing to record must not fail the run, so errors are only logged
+    fn with_db(&self, what: &str, f: impl FnOnce(&mut Database) -> BackendResult<()>) {
+        let result = match self.db.lock() {
+            Ok(mut db) => f(&mut db),
+            Err(_) => Ok(()),
+        };
+        if let Err(e) = result {
+            tracing::warn!(
+                "failed to record {} of agent run {}: {}",
+                what,
+                self.run_id,
+                e
+            );
+        }
+    }
+
+    fn record_event(&self, event_type: AgentRunEventType, payload: &impl Serialize) {
+        let payload = match serde_json::to_string(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("failed to serialize agent run event: {}", e);
+                return;
+            }
+        };
+        let event = AgentRunEvent {
+            run_id: self.run_id.clone(),
+            iteration: self.iteration.load(Ordering::SeqCst) as i64,
+            event_type,
+            payload,
+            created_at: current_time(),
+        };
+        self.with_db("event", |db| db.create_agent_run_event(&event));
+    }
+}
+
+impl RunRecorder for AgentRunRecorder {
+    fn begin_iteration(&self, iteration: usize) {
+        self.iteration.store(iteration, Ordering::SeqCst);
+        if let Ok(mut pending) = self.pending_tool_calls.lock() {
+            pending.clear();
+        }
+    }
+
+    fn record_tool_call(
+        &self,
+        iteration: usize,
+        position: usize,
+        tool_call: &ToolCall,
+        result: &ToolResult,
+        started_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        if let Ok(mut pending) = self.pending_tool_calls.lock() {
+            pending.push(AgentRunToolCall {
+                run_id: self.run_id.clone(),
+                iteration: iteration as i64,
+                position: position as i64,
+                tool_name: tool_call.function.name.clone(),
+                arguments: tool_call.function.arguments.clone(),
+                result: result.status.clone(),
+                started_at,
+                finished_at: current_time(),
+            });
+        }
+    }
+
+    fn record_iteration(
+        &self,
+        iteration: usize,
+        outcome: AgentRunIterationOutcome,
+        response: &str,
+        started_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        let tool_calls = self
+            .pending_tool_calls
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default();
+        let iteration = AgentRunIteration {
+            run_id: self.run_id.clone(),
+            iteration: iteration as i64,
+            outcome,
+            response: response.to_string(),
+            started_at,
+            finished_at: current_time(),
+        };
+        self.with_db("iteration", |db| {
+            db.create_agent_run_iteration(&iteration, &tool_calls)
+        });
+    }
+}
+
+// context mutations of a run, replayed to rebuild the context when resuming it
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ContextEvent {
+    RemoveContextMessages { message_ids: Vec<String> },
+    PopulateContextContent { message_ids: Vec<String> },
+    AddResources { resource_ids: Vec<String> },
+    AddUrl { url: String },
+    AddUrls { urls: Vec<String> },
+    AddText { title: String, content: String },
+}
+
+impl ContextEvent {
+    pub fn replay(self, key: &str, context_manager: &mut dyn ContextManager) -> BackendResult<()> {
+        match self {
+            Self::RemoveContextMessages { message_ids } => {
+                context_manager.remove_context_messages(key, &message_ids)
+            }
+            Self::PopulateContextContent { message_ids } => {
+                context_manager.populate_context_content(key, &message_ids)
+            }
+            Self::AddResources { resource_ids } => {
+                context_manager.add_resources(key, &resource_ids)
+            }
+            Self::AddUrl { url } => context_manager.add_url(key, &url),
+            Self::AddUrls { urls } => context_manager.add_urls(key, &urls).map(|_| ()),
+            Self::AddText { title, content } => context_manager.add_text(key, &title, &content),
+        }
+    }
+}
+
+// records the status messages written during a run
+pub struct RecordingIO<'a> {
+    io: &'a dyn AgentIO,
+    recorder: &'a AgentRunRecorder,
+}
+
+impl<'a> RecordingIO<'a> {
+    pub fn new(io: &'a dyn AgentIO, recorder: &'a AgentRunRecorder) -> Self {
+        Self { io, recorder }
+    }
+}
+
+impl AgentIO for RecordingIO<'_> {
+    fn get_id(&self) -> String {
+        self.io.get_id()
+    }
+
+    fn write(&self, content: &str) -> BackendResult<()> {
+        self.io.write(content)
+    }
+
+    fn write_status(&self, message: StatusMessage) -> BackendResult<()> {
+        self.recorder
+            .record_event(AgentRunEventType::Status, &message);
+        self.io.write_status(message)
+    }
+
+    fn read(&self) -> BackendResult<String> {
+        self.io.read()
+    }
+
+    fn clear(&self) -> BackendResult<()> {
+        self.io.clear()
+    }
+
+    fn report_usage(&self, usage: &BudgetUsage) {
+        self.io.report_usage(usage)
+    }
+}
+
+// records the successful context mutations of a run, e.g. which pages were read
+pub struct RecordingContextManager<'a> {
+    inner: &'a mut dyn ContextManager,
+    recorder: &'a AgentRunRecorder,
+}
+
+impl<'a> RecordingContextManager<'a> {
+    pub fn new(inner: &'a mut dyn ContextManager, recorder: &'a AgentRunRecorder) -> Self {
+        Self { inner, recorder }
+    }
+
+    fn record<T>(&self, result: BackendResult<T>, event: ContextEvent) -> BackendResult<T> {
+        if result.is_ok() {
+            self.recorder
+                .record_event(AgentRunEventType::Context, &event);
+        }
+        result
+    }
+}
+
+impl ContextManager for RecordingContextManager<'_> {
+    fn get_context(&self, key: &str) -> BackendResult<Vec<Message>> {
+        self.inner.get_context(key)
+    }
+
+    fn remove_context_messages(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()> {
+        let result = self.inner.remove_context_messages(key, message_ids);
+        self.record(
+            result,
+            ContextEvent::RemoveContextMessages {
+                message_ids: message_ids.to_vec(),
+            },
+        )
+    }
+
+    fn populate_context_content(&mut self, key: &str, message_ids: &[String]) -> BackendResult<()> {
+        let result = self.inner.populate_context_content(key, message_ids);
+        self.record(
+            result,
+            ContextEvent::PopulateContextContent {
+                message_ids: message_ids.to_vec(),
+            },
+        )
+    }
+
+    fn add_resources(&mut self, key: &str, resource_ids: &[String]) -> BackendResult<()> {
+        let result = self.inner.add_resources(key, resource_ids);
+        self.record(
+            result,
+            ContextEvent::AddResources {
+                resource_ids: resource_ids.to_vec(),
+            },
+        )
+    }
+
+    fn add_url(&mut self, key: &str, url: &str) -> BackendResult<()> {
+        let result = self.inner.add_url(key, url);
+        self.record(
+            result,
+            ContextEvent::AddUrl {
+                url: url.to_string(),
+            },
+        )
+    }
+
+    fn add_urls(&mut self, key: &str, urls: &[String]) -> BackendResult<Vec<BackendResult<()>>> {
+        let result = self.inner.add_urls(key, urls);
+        // only the urls that were actually added
+        let added = match &result {
+            Ok(results) => urls
+                .iter()
+                .zip(results)
+                .filter(|(_, r)| r.is_ok())
+                .map(|(url, _)| url.clone())
+                .collect(),
+            Err(_) => vec![],
+        };
+        self.record(result, ContextEvent::AddUrls { urls: added })
+    }
+
+    fn url_fetcher(&self) -> Option<Arc<dyn UrlFetcher>> {
+        self.inner.url_fetcher()
+    }
+
+    fn add_fetched_url(&mut self, key: &str, fetched: FetchedUrl) -> BackendResult<()> {
+        let url = fetched.url.clone();
+        let result = self.inner.add_fetched_url(key, fetched);
+        self.record(result, ContextEvent::AddUrl { url })
+    }
+
+    fn add_text(&mut self, key: &str, title: &str, content: &str) -> BackendResult<()> {
+        let result = self.inner.add_text(key, title, content);
+        self.record(
+            result,
+            ContextEvent::AddText {
+                title: title.to_string(),
+                content: content.to_string(),
+            },
+        )
+    }
+
+    fn get_sources_xml(&self, key: &str) -> BackendResult<String> {
+        self.inner.get_sources_xml(key)
+    }
+
+    fn get_citation(&self, key: &str, message_id: &str, cited_text: &str) -> BackendResult<String> {
+        self.inner.get_citation(key, message_id, cited_text)
+    }
+}
+
+// the finished iterations of a run and the context events they caused
+pub fn resume_state(
+    transcript: &AgentRunTranscript,
+) -> BackendResult<(Vec<RecordedIteration>, Vec<ContextEvent>)> {
+    let history: Vec<RecordedIteration> = transcript
+        .iterations
+        .iter()
+        .map(|iteration| RecordedIteration {
+            outcome: iteration.outcome.clone(),
+            response: iteration.response.clone(),
+            tool_results: transcript
+                .tool_calls
+                .iter()
+                .filter(|tool_call| tool_call.iteration == iteration.iteration)
+                .map(|tool_call| ToolResult {
+                    role: MessageRole::Assistant.to_string(),
+                    name: tool_call.tool_name.clone(),
+                    status: tool_call.result.clone(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let mut context_events = vec![];
+    for event in &transcript.events {
+        if event.event_type == AgentRunEventType::Context && event.iteration < history.len() as i64
+        {
+            context_events.push(serde_json::from_str(&event.payload)?);
+        }
+    }
+    Ok((history, context_events))
+}
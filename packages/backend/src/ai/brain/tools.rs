@@ -1,5 +1,6 @@
 use serde_json::json;
 
+use crate::ai::brain::agents::budget::AgentBudget;
 use crate::ai::brain::agents::context::ContextManager;
 use crate::ai::brain::agents::{io::AgentIO, tools::Tool, Agent};
 use crate::ai::brain::agents::{AgentResult, ExecuteConfig};
@@ -75,6 +76,10 @@ impl Tool for WebSearchAgentTool {
             custom_key: custom_key,
             system_message_preamble: Some(current_time_prompt()),
             allowed_tools: None,
+            recorder: None,
+            history: vec![],
+            budget: AgentBudget::default(),
+            fixture_dir: None,
         };
         let result = self
             .agent
@@ -84,7 +89,7 @@ impl Tool for WebSearchAgentTool {
             AgentResult::MaxIterationsReached(response) => Err(BackendError::GenericError(
                 format!("WebSearch agent max iterations reached: {}", response),
             )),
-            AgentResult::Cancelled => Err(BackendError::CancelledError),
+            AgentResult::Cancelled => Err(BackendError::LLMClientErrorCancelled),
             AgentResult::Error(error) => Err(BackendError::GenericError(format!(
                 "WebSearch agent error: {}",
                 error
@@ -174,6 +179,10 @@ impl Tool for SurfletAgentTool {
             custom_key,
             system_message_preamble: Some(current_time_prompt()),
             allowed_tools: None,
+            recorder: None,
+            history: vec![],
+            budget: AgentBudget::default(),
+            fixture_dir: None,
         };
         let result = self
             .agent
@@ -183,7 +192,7 @@ impl Tool for SurfletAgentTool {
             AgentResult::MaxIterationsReached(response) => Err(BackendError::GenericError(
                 format!("Surflet agent max iterations reached: {}", response),
             )),
-            AgentResult::Cancelled => Err(BackendError::CancelledError),
+            AgentResult::Cancelled => Err(BackendError::LLMClientErrorCancelled),
             AgentResult::Error(error) => Err(BackendError::GenericError(format!(
                 "Surflet agent error: {}",
                 error
@@ -252,6 +261,10 @@ impl Tool for ContextManagementTool {
             custom_key,
             system_message_preamble: None,
             allowed_tools: None,
+            recorder: None,
+            history: vec![],
+            budget: AgentBudget::default(),
+            fixture_dir: None,
         };
         let result = self
             .agent
@@ -265,7 +278,7 @@ impl Tool for ContextManagementTool {
                     response
                 )))
             }
-            AgentResult::Cancelled => Err(BackendError::CancelledError),
+            AgentResult::Cancelled => Err(BackendError::LLMClientErrorCancelled),
             AgentResult::Error(error) => Err(BackendError::GenericError(format!(
                 "Context Management agent error: {}",
                 error
@@ -0,0 +1,147 @@
+use crate::{BackendError, BackendResult};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::runtime::Runtime;
+
+// how often a blocked caller checks whether its token fired
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// shared flag to stop in-flight llm work, optionally with a deadline
+//
+// clones share the cancelled flag, so cancelling any clone cancels all of them,
+// while deadlines are per clone so a single call can be given a tighter one
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // returns a clone which also counts as cancelled once `timeout` has elapsed,
+    // an earlier existing deadline is kept
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        Self {
+            cancelled: self.cancelled.clone(),
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst) || self.is_expired()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    // time left until the deadline, `None` if there is no deadline
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn check(&self) -> BackendResult<()> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(BackendError::LLMClientErrorCancelled);
+        }
+        if self.is_expired() {
+            return Err(BackendError::LLMClientErrorDeadlineExceeded);
+        }
+        Ok(())
+    }
+
+    // resolves with the error of `check` once the token fires
+    async fn cancelled(&self) -> BackendError {
+        loop {
+            if let Err(err) = self.check() {
+                return err;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    // true if no one but the holder of this clone is interested in the token anymore
+    pub(crate) fn is_orphaned(&self) -> bool {
+        Arc::strong_count(&self.cancelled) == 1
+    }
+}
+
+// drives `future` on the runtime until it completes or the token fires
+//
+// a cancelled future is dropped right away, which aborts an in-flight request
+// and closes its connection, nothing is left running in the background
+pub(crate) fn block_on_cancellable<F: Future>(
+    runtime: &Runtime,
+    token: &CancellationToken,
+    future: F,
+) -> BackendResult<F::Output> {
+    token.check()?;
+    runtime.block_on(async {
+        tokio::select! {
+            output = future => Ok(output),
+            err = token.cancelled() => Err(err),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let call_token = token.with_timeout(Duration::from_secs(60));
+        assert!(!call_token.is_cancelled());
+
+        token.cancel();
+        assert!(call_token.is_cancelled());
+        assert!(matches!(
+            call_token.check(),
+            Err(BackendError::LLMClientErrorCancelled)
+        ));
+    }
+
+    #[test]
+    fn test_block_on_cancellable_drops_future_on_deadline() {
+        struct DropFlag(Arc<AtomicBool>);
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let runtime = Runtime::new().unwrap();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let token = CancellationToken::new().with_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let result = block_on_cancellable(&runtime, &token, async move {
+            let _flag = flag;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        assert!(matches!(
+            result,
+            Err(BackendError::LLMClientErrorDeadlineExceeded)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // the request is not left running after the caller returned
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
@@ -1,11 +1,21 @@
+pub mod cancellation;
+pub mod structured;
 pub mod tokens;
 
-use reqwest::{blocking::Response, header};
+pub use cancellation::CancellationToken;
+
+use bytes::Bytes;
+use cancellation::block_on_cancellable;
+use futures::{Stream, StreamExt};
+use reqwest::{header, Response};
 use serde::{Deserialize, Serialize};
 use std::{
-    io::{BufRead, BufReader},
+    collections::HashSet,
+    pin::Pin,
+    sync::Arc,
     time::{Duration, Instant},
 };
+use tokio::runtime::Runtime;
 
 use crate::{
     ai::llm::models::{Message, MessageContent, MessageRole},
@@ -13,15 +23,84 @@ use crate::{
 };
 
 pub struct ChatCompletionStream {
-    reader: BufReader<Response>,
+    source: StreamSource,
+    // sees every item before it is returned, used to record streams
+    tap: Option<StreamTap>,
     buffer: String,
     provider: Provider,
+    cancellation_token: CancellationToken,
+    cancelled: bool,
     last_update: Instant,
     update_interval: Duration,
+    usage: Option<CompletionUsage>,
+}
+
+type StreamTap = Box<dyn FnMut(&BackendResult<String>) + Send>;
+
+enum StreamSource {
+    // lines of the response body, dropping the stream closes the connection
+    Response(ResponseLines),
+    // chunks that were recorded before, replayed as they are
+    Chunks(std::vec::IntoIter<BackendResult<String>>),
+}
+
+struct ResponseLines {
+    runtime: Arc<Runtime>,
+    body: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+    // bytes of the line that is not complete yet
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl ResponseLines {
+    // blocks until the next line arrives, `None` once the body is exhausted
+    fn next_line(&mut self, token: &CancellationToken) -> Option<BackendResult<String>> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                return Some(Ok(String::from_utf8_lossy(&line).into_owned()));
+            }
+            if self.done {
+                if self.pending.is_empty() {
+                    return None;
+                }
+                let line = std::mem::take(&mut self.pending);
+                return Some(Ok(String::from_utf8_lossy(&line).into_owned()));
+            }
+            match block_on_cancellable(&self.runtime, token, self.body.next()) {
+                Ok(Some(Ok(chunk))) => self.pending.extend_from_slice(&chunk),
+                Ok(Some(Err(err))) => return Some(Err(err.into())),
+                Ok(None) => self.done = true,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
 }
 
 pub struct LLMClient {
-    client: reqwest::blocking::Client,
+    client: reqwest::Client,
+    // requests are driven here so that a cancelled one is dropped instead of left running
+    runtime: Arc<Runtime>,
+}
+
+pub trait ChatCompletionProvider: Send + Sync {
+    fn create_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String>;
+
+    fn create_streaming_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<ChatCompletionStream>;
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -78,6 +157,58 @@ pub trait TokenModel {
     fn max_tokens(&self) -> usize;
 }
 
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CompletionUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    // prompt tokens served from the provider's prompt cache
+    pub cache_read_tokens: u32,
+    // prompt tokens written to the provider's prompt cache, only reported by anthropic
+    pub cache_write_tokens: u32,
+}
+
+impl CompletionUsage {
+    // streamed responses report usage in pieces, e.g. input tokens at the start
+    // and output tokens at the end of the stream
+    fn merge(&mut self, other: &CompletionUsage) {
+        let merge_field = |current: &mut u32, new: u32| {
+            if new > 0 {
+                *current = new;
+            }
+        };
+        merge_field(&mut self.input_tokens, other.input_tokens);
+        merge_field(&mut self.output_tokens, other.output_tokens);
+        merge_field(&mut self.cache_read_tokens, other.cache_read_tokens);
+        merge_field(&mut self.cache_write_tokens, other.cache_write_tokens);
+    }
+
+    // sums the usage of separate completions
+    pub fn add(&mut self, other: &CompletionUsage) {
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+        self.cache_read_tokens = self
+            .cache_read_tokens
+            .saturating_add(other.cache_read_tokens);
+        self.cache_write_tokens = self
+            .cache_write_tokens
+            .saturating_add(other.cache_write_tokens);
+    }
+}
+
+// list prices in usd per million tokens
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_read: f64,
+    pub cache_write: f64,
+}
+
+pub struct ChatCompletion {
+    pub content: String,
+    pub usage: Option<CompletionUsage>,
+}
+
 mod response_types {
     use serde::{Deserialize, Serialize};
 
@@ -113,9 +244,24 @@ mod response_types {
             pub delta: Option<ChatCompletionChoiceDelta>,
         }
 
+        #[derive(Debug, Serialize, Deserialize)]
+        pub(crate) struct PromptTokensDetails {
+            pub cached_tokens: Option<u32>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        pub(crate) struct Usage {
+            #[serde(default)]
+            pub prompt_tokens: u32,
+            #[serde(default)]
+            pub completion_tokens: u32,
+            pub prompt_tokens_details: Option<PromptTokensDetails>,
+        }
+
         #[derive(Debug, Serialize, Deserialize)]
         pub(crate) struct ChatCompletionChunkResponse {
             pub choices: Vec<ChatCompletionChoice>,
+            pub usage: Option<Usage>,
         }
     }
 
@@ -160,8 +306,25 @@ mod response_types {
 
         #[derive(Debug, Serialize, Deserialize)]
         pub struct Usage {
+            #[serde(default)]
             pub input_tokens: u32,
+            #[serde(default)]
             pub output_tokens: u32,
+            pub cache_creation_input_tokens: Option<u32>,
+            pub cache_read_input_tokens: Option<u32>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        pub struct UsageMessage {
+            pub usage: Option<Usage>,
+        }
+
+        // usage is reported at the top level of full responses and `message_delta` events
+        // and inside the message of `message_start` events
+        #[derive(Debug, Serialize, Deserialize)]
+        pub struct UsageResponse {
+            pub usage: Option<Usage>,
+            pub message: Option<UsageMessage>,
         }
 
         #[derive(Debug, Serialize, Deserialize)]
@@ -177,21 +340,40 @@ mod response_types {
     }
 }
 
-fn filter_unsupported_content(messages: Vec<Message>, model: &Model) -> Vec<Message> {
-    if model.supports_images() {
-        messages
-    } else {
-        messages
-            .into_iter()
-            .map(|mut msg| {
-                msg.content.retain(|c| matches!(c, MessageContent::Text(_)));
-                msg
-            })
-            .filter(|msg| !msg.content.is_empty())
-            .collect()
+// replaces content the model can't handle with its text fallback, if any
+// openai only accepts `wav` and `mp3` audio
+fn audio_format(media_type: &str) -> &'static str {
+    match media_type {
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        _ => "mp3",
     }
 }
 
+fn filter_unsupported_content(messages: Vec<Message>, model: &Model) -> Vec<Message> {
+    let supported = |content: &MessageContent| match content {
+        MessageContent::Text(_) => true,
+        MessageContent::Image(_) => model.supports_images(),
+        MessageContent::Document(_) => model.supports_documents(),
+        MessageContent::Audio(_) => model.supports_audio(),
+    };
+
+    messages
+        .into_iter()
+        .map(|mut msg| {
+            msg.content = msg
+                .content
+                .into_iter()
+                .filter_map(|c| match supported(&c) {
+                    true => Some(c),
+                    false => c.to_text_fallback(),
+                })
+                .collect();
+            msg
+        })
+        .filter(|msg| !msg.content.is_empty())
+        .collect()
+}
+
 fn truncate_messages(messages: Vec<Message>, model: &Model) -> Vec<Message> {
     if messages.is_empty() {
         return messages;
@@ -203,16 +385,62 @@ fn truncate_messages(messages: Vec<Message>, model: &Model) -> Vec<Message> {
 }
 
 impl ChatCompletionStream {
-    fn new(reader: BufReader<Response>, provider: Provider, packets_per_second: u32) -> Self {
+    fn new(
+        response: Response,
+        runtime: Arc<Runtime>,
+        provider: Provider,
+        packets_per_second: u32,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        let lines = ResponseLines {
+            runtime,
+            body: Box::pin(response.bytes_stream()),
+            pending: Vec::new(),
+            done: false,
+        };
+
         Self {
-            reader,
+            source: StreamSource::Response(lines),
+            tap: None,
             buffer: String::new(),
             provider,
+            cancellation_token,
+            cancelled: false,
             last_update: Instant::now(),
             update_interval: Duration::from_secs_f64(1.0 / packets_per_second as f64),
+            usage: None,
         }
     }
 
+    // a stream that yields the given chunks without any request or pacing
+    pub fn from_chunks(
+        chunks: Vec<BackendResult<String>>,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        Self {
+            source: StreamSource::Chunks(chunks.into_iter()),
+            tap: None,
+            buffer: String::new(),
+            provider: Provider::Custom(String::new()),
+            cancellation_token,
+            cancelled: false,
+            last_update: Instant::now(),
+            update_interval: Duration::ZERO,
+            usage: None,
+        }
+    }
+
+    // calls `tap` with every item the stream yields
+    pub fn with_tap(mut self, tap: impl FnMut(&BackendResult<String>) + Send + 'static) -> Self {
+        self.tap = Some(Box::new(tap));
+        self
+    }
+
+    // token usage reported by the provider so far, complete once the stream is exhausted
+    pub fn usage(&self) -> Option<&CompletionUsage> {
+        self.usage.as_ref()
+    }
+
     pub fn set_packets_per_second(&mut self, pps: u32) {
         self.update_interval = Duration::from_secs_f64(1.0 / pps as f64);
     }
@@ -321,11 +549,15 @@ impl Provider {
         let mut json_obj = serde_json::json!({
             "model": model,
             "stream": stream,
-            "messages": messages,
+            "messages": self.transform_messages_for_openai(messages)?,
         });
         if let Some(format) = response_format {
             json_obj["response_format"] = serde_json::json!(format);
         }
+        // usage is only sent in the last chunk of a stream if requested
+        if stream && matches!(self, Self::OpenAI) {
+            json_obj["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
 
         serde_json::to_string(&json_obj).map_err(|err| {
             BackendError::GenericError(format!(
@@ -334,6 +566,54 @@ impl Provider {
         })
     }
 
+    fn transform_messages_for_openai(
+        &self,
+        messages: &[Message],
+    ) -> BackendResult<Vec<serde_json::Value>> {
+        messages
+            .iter()
+            .map(|m| {
+                let content = m
+                    .content
+                    .iter()
+                    .map(|content| self.transform_content_for_openai(content))
+                    .collect::<BackendResult<Vec<_>>>()?;
+                Ok(serde_json::json!({
+                    "role": m.role,
+                    "content": content,
+                }))
+            })
+            .collect()
+    }
+
+    fn transform_content_for_openai(
+        &self,
+        content: &MessageContent,
+    ) -> BackendResult<serde_json::Value> {
+        match content {
+            MessageContent::Text(_) | MessageContent::Image(_) => {
+                Ok(serde_json::to_value(content)?)
+            }
+            MessageContent::Document(document) => Ok(serde_json::json!({
+                "type": "file",
+                "file": {
+                    "filename": document.filename.as_deref().unwrap_or("document.pdf"),
+                    "file_data": document.data_url,
+                }
+            })),
+            MessageContent::Audio(audio) => {
+                let (media_type, data) = self.extract_image_data(&audio.data_url);
+                Ok(serde_json::json!({
+                    "type": "input_audio",
+                    "input_audio": {
+                        "data": data,
+                        "format": audio_format(media_type),
+                    }
+                }))
+            }
+        }
+    }
+
     fn prepare_anthropic_request(
         &self,
         model: &str,
@@ -342,10 +622,11 @@ impl Provider {
         messages: &[Message],
         _response_format: Option<&serde_json::Value>,
     ) -> BackendResult<String> {
+        // the system prompt is the most stable prefix of every request
         let system_message = messages
             .first()
             .filter(|m| m.role == MessageRole::System)
-            .map(|m| m.content.clone());
+            .map(|m| self.transform_content_for_anthropic(&m.content, true));
         let transformed_messages = self.transform_messages_for_anthropic(messages);
 
         serde_json::to_string(&serde_json::json!({
@@ -363,43 +644,99 @@ impl Provider {
     }
 
     fn transform_messages_for_anthropic(&self, messages: &[Message]) -> Vec<serde_json::Value> {
-        messages
+        let messages = messages
             .iter()
             .filter(|m| m.role != MessageRole::System)
-            .map(|m| {
-                let transformed_content = m
-                    .content
-                    .iter()
-                    .map(|content| match content {
-                        MessageContent::Text(text_content) => {
-                            serde_json::json!({
-                                "type": "text",
-                                "text": text_content.text
-                            })
-                        }
-                        MessageContent::Image(image_content) => {
-                            let (media_type, base64_data) =
-                                self.extract_image_data(&image_content.image_url.url);
-                            serde_json::json!({
-                                "type": "image",
-                                "source": {
-                                    "type": "base64",
-                                    "media_type": media_type,
-                                    "data": base64_data
-                                }
-                            })
-                        }
-                    })
-                    .collect::<Vec<_>>();
+            .collect::<Vec<_>>();
+        let cache_breakpoints = self.anthropic_cache_breakpoints(&messages);
 
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
                 serde_json::json!({
                     "role": m.role.to_string(),
-                    "content": transformed_content
+                    "content": self.transform_content_for_anthropic(
+                        &m.content,
+                        cache_breakpoints.contains(&i),
+                    )
                 })
             })
             .collect()
     }
 
+    fn transform_content_for_anthropic(
+        &self,
+        content: &[MessageContent],
+        cache_breakpoint: bool,
+    ) -> Vec<serde_json::Value> {
+        let mut transformed_content = content
+            .iter()
+            .map(|content| match content {
+                MessageContent::Text(text_content) => {
+                    serde_json::json!({
+                        "type": "text",
+                        "text": text_content.text
+                    })
+                }
+                MessageContent::Image(image_content) => {
+                    let (media_type, base64_data) =
+                        self.extract_image_data(&image_content.image_url.url);
+                    serde_json::json!({
+                        "type": "image",
+                        "source": {
+                            "type": "base64",
+                            "media_type": media_type,
+                            "data": base64_data
+                        }
+                    })
+                }
+                MessageContent::Document(document) => {
+                    let (media_type, base64_data) = self.extract_image_data(&document.data_url);
+                    serde_json::json!({
+                        "type": "document",
+                        "source": {
+                            "type": "base64",
+                            "media_type": media_type,
+                            "data": base64_data
+                        }
+                    })
+                }
+                // unsupported content is replaced with its fallback before the request is built
+                MessageContent::Audio(_) => serde_json::json!({
+                    "type": "text",
+                    "text": content
+                        .to_text_fallback()
+                        .map(|c| c.get_content())
+                        .unwrap_or_default()
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        // the prefix up to and including the marked block is cached
+        if cache_breakpoint {
+            if let Some(last) = transformed_content.last_mut() {
+                last["cache_control"] = serde_json::json!({ "type": "ephemeral" });
+            }
+        }
+        transformed_content
+    }
+
+    // anthropic only caches prefixes that end in a `cache_control` breakpoint (max 4 per request)
+    //
+    // besides the system prompt, the stable prefixes are the context messages
+    // and the conversation up to the latest message
+    fn anthropic_cache_breakpoints(&self, messages: &[&Message]) -> HashSet<usize> {
+        let mut breakpoints = HashSet::new();
+        if let Some(last_context) = messages.iter().rposition(|m| m.is_context) {
+            breakpoints.insert(last_context);
+        }
+        if messages.len() > 1 {
+            breakpoints.insert(messages.len() - 2);
+        }
+        breakpoints
+    }
+
     fn extract_image_data<'a>(&self, url: &'a str) -> (&'a str, &'a str) {
         if let Some(stripped) = url.strip_prefix("data:") {
             let parts: Vec<&str> = stripped.split(";base64,").collect();
@@ -468,6 +805,36 @@ impl Provider {
         }
     }
 
+    fn parse_usage(&self, data: &str) -> Option<CompletionUsage> {
+        use response_types::*;
+        match self {
+            Self::OpenAI | Self::Google | Self::Custom(_) => {
+                let usage = serde_json::from_str::<openai::ChatCompletionChunkResponse>(data)
+                    .ok()?
+                    .usage?;
+                Some(CompletionUsage {
+                    input_tokens: usage.prompt_tokens,
+                    output_tokens: usage.completion_tokens,
+                    cache_read_tokens: usage
+                        .prompt_tokens_details
+                        .and_then(|d| d.cached_tokens)
+                        .unwrap_or_default(),
+                    cache_write_tokens: 0,
+                })
+            }
+            Self::Anthropic => {
+                let resp = serde_json::from_str::<anthropic::UsageResponse>(data).ok()?;
+                let usage = resp.usage.or(resp.message.and_then(|m| m.usage))?;
+                Some(CompletionUsage {
+                    input_tokens: usage.input_tokens,
+                    output_tokens: usage.output_tokens,
+                    cache_read_tokens: usage.cache_read_input_tokens.unwrap_or_default(),
+                    cache_write_tokens: usage.cache_creation_input_tokens.unwrap_or_default(),
+                })
+            }
+        }
+    }
+
     fn parse_response(&self, data: &str) -> BackendResult<Option<String>> {
         self.parse_potential_error(data)?;
 
@@ -495,6 +862,18 @@ impl Provider {
 }
 
 impl Model {
+    // pdf input
+    fn supports_documents(&self) -> bool {
+        !matches!(
+            self,
+            Self::O3Mini | Self::Claude35Haiku | Self::Gemini20Flash | Self::Custom { .. }
+        )
+    }
+
+    pub fn supports_audio(&self) -> bool {
+        matches!(self, Self::Gemini20Flash)
+    }
+
     fn supports_images(&self) -> bool {
         match self {
             Self::Claude35Haiku => false,
@@ -525,6 +904,68 @@ impl Model {
         .to_string()
     }
 
+    // cheap model from the same provider used for background tasks like history compaction
+    // custom models don't have a cheaper alternative so they are used as is
+    pub fn compaction_model(&self) -> Model {
+        match self.provider() {
+            Provider::OpenAI => Self::GPT4oMini,
+            Provider::Anthropic => Self::Claude35Haiku,
+            Provider::Google => Self::Gemini20Flash,
+            Provider::Custom(_) => self.clone(),
+        }
+    }
+
+    // reference: https://openai.com/api/pricing, https://www.anthropic.com/pricing
+    // and https://ai.google.dev/gemini-api/docs/pricing
+    pub fn price(&self) -> Option<ModelPrice> {
+        let price = |input, output, cache_read, cache_write| ModelPrice {
+            input,
+            output,
+            cache_read,
+            cache_write,
+        };
+        match self {
+            Self::GPT5 => Some(price(1.25, 10.0, 0.125, 0.0)),
+            Self::GPT5_Mini => Some(price(0.25, 2.0, 0.025, 0.0)),
+            Self::GPT4_1 => Some(price(2.0, 8.0, 0.5, 0.0)),
+            Self::GPT4_1Mini => Some(price(0.4, 1.6, 0.1, 0.0)),
+            Self::GPT4o => Some(price(2.5, 10.0, 1.25, 0.0)),
+            Self::GPT4oMini => Some(price(0.15, 0.6, 0.075, 0.0)),
+            Self::O3Mini => Some(price(1.1, 4.4, 0.55, 0.0)),
+            Self::Claude45Sonnet
+            | Self::Claude4Sonnet
+            | Self::Claude37Sonnet
+            | Self::Claude35Sonnet => Some(price(3.0, 15.0, 0.3, 3.75)),
+            Self::Claude35Haiku => Some(price(0.8, 4.0, 0.08, 1.0)),
+            Self::Gemini20Flash => Some(price(0.1, 0.4, 0.025, 0.0)),
+            Self::Custom { .. } => None,
+        }
+    }
+
+    // all tokens the completion was billed for
+    pub fn total_tokens(&self, usage: &CompletionUsage) -> u64 {
+        let tokens = usage.input_tokens as u64 + usage.output_tokens as u64;
+        match self.provider() {
+            // anthropic reports cached prompt tokens separately from the input tokens
+            Provider::Anthropic => {
+                tokens + usage.cache_read_tokens as u64 + usage.cache_write_tokens as u64
+            }
+            _ => tokens,
+        }
+    }
+
+    pub fn cost(&self, usage: &CompletionUsage, price: &ModelPrice) -> f64 {
+        let uncached_input_tokens = match self.provider() {
+            Provider::Anthropic => usage.input_tokens,
+            _ => usage.input_tokens.saturating_sub(usage.cache_read_tokens),
+        };
+        (uncached_input_tokens as f64 * price.input
+            + usage.output_tokens as f64 * price.output
+            + usage.cache_read_tokens as f64 * price.cache_read
+            + usage.cache_write_tokens as f64 * price.cache_write)
+            / 1_000_000.0
+    }
+
     fn provider(&self) -> &Provider {
         match self {
             Self::GPT5
@@ -572,14 +1013,33 @@ impl Iterator for ChatCompletionStream {
     type Item = BackendResult<String>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.buffer.clear();
+        let item = self.next_chunk();
+        if let (Some(tap), Some(item)) = (self.tap.as_mut(), item.as_ref()) {
+            tap(item);
+        }
+        item
+    }
+}
 
-        match self.reader.read_line(&mut self.buffer) {
-            Ok(0) => None,
-            Ok(_) => {
-                self.buffer = self.buffer.trim().to_string();
+impl ChatCompletionStream {
+    fn next_chunk(&mut self) -> Option<BackendResult<String>> {
+        if self.cancelled {
+            return None;
+        }
+        if let Err(err) = self.cancellation_token.check() {
+            self.cancelled = true;
+            return Some(Err(err));
+        }
+
+        let line = match &mut self.source {
+            StreamSource::Response(lines) => lines.next_line(&self.cancellation_token)?,
+            StreamSource::Chunks(chunks) => return chunks.next(),
+        };
+        match line {
+            Ok(line) => {
+                self.buffer = line.trim().to_string();
                 if self.buffer.is_empty() {
-                    return self.next();
+                    return self.next_chunk();
                 }
 
                 let data = match self.buffer.strip_prefix("data: ") {
@@ -588,15 +1048,26 @@ impl Iterator for ChatCompletionStream {
                     Some(data) => data,
                 };
 
+                if let Some(usage) = self.provider.parse_usage(data) {
+                    self.usage
+                        .get_or_insert_with(Default::default)
+                        .merge(&usage);
+                }
+
                 match self.provider.parse_response_chunk(data, true).transpose() {
                     Some(Ok(content)) => {
                         self.wait_for_next_update();
                         Some(Ok(content))
                     }
                     Some(Err(e)) => Some(Err(e)),
-                    None => self.next(),
+                    None => self.next_chunk(),
                 }
             }
+            Err(err @ BackendError::LLMClientErrorCancelled)
+            | Err(err @ BackendError::LLMClientErrorDeadlineExceeded) => {
+                self.cancelled = true;
+                Some(Err(err))
+            }
             Err(e) => Some(Err(BackendError::GenericError(e.to_string()))),
         }
     }
@@ -605,20 +1076,53 @@ impl Iterator for ChatCompletionStream {
 impl LLMClient {
     pub fn new() -> BackendResult<Self> {
         Ok(Self {
-            client: reqwest::blocking::Client::builder()
+            client: reqwest::Client::builder()
                 .timeout(std::time::Duration::from_secs(300))
                 .build()?,
+            runtime: Arc::new(
+                tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("llm-client")
+                    .enable_all()
+                    .build()?,
+            ),
         })
     }
 
-    #[tracing::instrument(level = "trace", skip(self, messages, response_format))]
+    #[tracing::instrument(
+        level = "trace",
+        skip(self, messages, response_format, cancellation_token)
+    )]
     pub fn create_chat_completion(
         &self,
         messages: Vec<Message>,
         model: &Model,
         custom_key: Option<String>,
         response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<String> {
+        self.create_chat_completion_with_usage(
+            messages,
+            model,
+            custom_key,
+            response_format,
+            cancellation_token,
+        )
+        .map(|completion| completion.content)
+    }
+
+    #[tracing::instrument(
+        level = "trace",
+        skip(self, messages, response_format, cancellation_token)
+    )]
+    pub fn create_chat_completion_with_usage(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<String>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<ChatCompletion> {
         let provider = model.provider();
         let response = self.send_completion_request(
             messages,
@@ -626,18 +1130,28 @@ impl LLMClient {
             custom_key,
             response_format.as_ref(),
             false,
+            &cancellation_token,
         )?;
 
-        self.handle_completion_response(response, provider, response_format.is_some())
+        self.handle_completion_response(
+            response,
+            provider,
+            response_format.is_some(),
+            &cancellation_token,
+        )
     }
 
-    #[tracing::instrument(level = "trace", skip(self, messages, response_format))]
+    #[tracing::instrument(
+        level = "trace",
+        skip(self, messages, response_format, cancellation_token)
+    )]
     pub fn create_streaming_chat_completion(
         &self,
         messages: Vec<Message>,
         model: &Model,
         custom_key: Option<String>,
         response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<ChatCompletionStream> {
         let response = self.send_completion_request(
             messages,
@@ -645,9 +1159,10 @@ impl LLMClient {
             custom_key,
             response_format.as_ref(),
             true,
+            &cancellation_token,
         )?;
 
-        self.handle_streaming_response(response, model.provider())
+        self.handle_streaming_response(response, model.provider(), cancellation_token)
     }
 
     fn send_completion_request(
@@ -657,6 +1172,7 @@ impl LLMClient {
         custom_key: Option<String>,
         response_format: Option<&serde_json::Value>,
         stream: bool,
+        cancellation_token: &CancellationToken,
     ) -> BackendResult<Response> {
         let messages = truncate_messages(filter_unsupported_content(messages, model), model);
         let provider = model.provider();
@@ -678,7 +1194,8 @@ impl LLMClient {
             }
         }
 
-        let response = builder.body(body).send()?;
+        let response =
+            block_on_cancellable(&self.runtime, cancellation_token, builder.body(body).send())??;
         tracing::debug!(
             "completion request - url: {:?}, stream: {}, status: {:?}, model: {:?}",
             url,
@@ -694,13 +1211,16 @@ impl LLMClient {
                 }
                 // TODO: are there other cases of bad request
                 if status == reqwest::StatusCode::BAD_REQUEST {
-                    return Err(BackendError::LLMClientErrorBadRequest(response.text()?));
+                    let error_text =
+                        block_on_cancellable(&self.runtime, cancellation_token, response.text())??;
+                    return Err(BackendError::LLMClientErrorBadRequest(error_text));
                 }
                 if status == reqwest::StatusCode::UNAUTHORIZED {
                     return Err(BackendError::LLMClientErrorUnauthorized);
                 }
                 if status.is_client_error() {
-                    let error_text = response.text()?;
+                    let error_text =
+                        block_on_cancellable(&self.runtime, cancellation_token, response.text())??;
                     model.provider().parse_potential_error(&error_text)?;
                 }
             }
@@ -715,28 +1235,245 @@ impl LLMClient {
         response: Response,
         provider: &Provider,
         has_response_format: bool,
-    ) -> BackendResult<String> {
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<ChatCompletion> {
+        let text = block_on_cancellable(&self.runtime, cancellation_token, response.text())??;
         let resp = provider
-            .parse_response(&response.text()?)
+            .parse_response(&text)
             .map(|m| m.unwrap_or_default());
 
-        match provider {
+        let content = match provider {
             Provider::Anthropic | Provider::Custom(_) if has_response_format => {
                 resp.map(|r| format!("{{{r}"))
             }
             _ => resp,
-        }
+        }?;
+
+        Ok(ChatCompletion {
+            content,
+            usage: provider.parse_usage(&text),
+        })
     }
 
     fn handle_streaming_response(
         &self,
         response: Response,
         provider: &Provider,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<ChatCompletionStream> {
         Ok(ChatCompletionStream::new(
-            BufReader::new(response),
+            response,
+            self.runtime.clone(),
             provider.clone(),
             120,
+            cancellation_token,
         ))
     }
 }
+
+impl ChatCompletionProvider for LLMClient {
+    fn create_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        LLMClient::create_chat_completion(
+            self,
+            messages,
+            model,
+            custom_key.map(String::from),
+            response_format,
+            cancellation_token,
+        )
+    }
+
+    fn create_streaming_chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<&str>,
+        response_format: Option<serde_json::Value>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<ChatCompletionStream> {
+        LLMClient::create_streaming_chat_completion(
+            self,
+            messages,
+            model,
+            custom_key.map(String::from),
+            response_format,
+            cancellation_token,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::llm::models::{ContextMessage, MessageContentAudio, MessageContentDocument};
+
+    fn context_message(id: &str) -> Message {
+        Message::new_context(&ContextMessage {
+            id: id.to_string(),
+            content: Some("content".to_string()),
+            content_type: "text".to_string(),
+            title: None,
+            author: None,
+            source_url: None,
+            page: None,
+            description: None,
+            created_at: None,
+            timestamp: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_anthropic_request_cache_breakpoints() {
+        let messages = vec![
+            Message::new_system("system"),
+            context_message("1"),
+            context_message("2"),
+            Message::new_user("first question"),
+            Message::new_assistant("first answer"),
+            Message::new_user("second question"),
+        ];
+        let request = Provider::Anthropic
+            .prepare_anthropic_request("model", false, 1024, &messages, None)
+            .unwrap();
+        let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+
+        let is_breakpoint = |block: &serde_json::Value| block.get("cache_control").is_some();
+        assert!(is_breakpoint(&request["system"][0]));
+
+        let breakpoints = request["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| is_breakpoint(&m["content"][0]))
+            .collect::<Vec<_>>();
+        assert_eq!(breakpoints, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_unsupported_content_falls_back_to_text() {
+        let document = MessageContentDocument {
+            data_url: "data:application/pdf;base64,JVBERi0=".to_string(),
+            filename: Some("paper.pdf".to_string()),
+            text: Some("abstract".to_string()),
+            pages: Some(1),
+        };
+        let messages = vec![
+            Message::new_document(document.clone()),
+            Message::new_audio(MessageContentAudio {
+                data_url: "data:audio/mpeg;base64,SUQz".to_string(),
+                filename: Some("memo.mp3".to_string()),
+                transcript: Some("hello".to_string()),
+            }),
+        ];
+
+        let filtered = filter_unsupported_content(messages.clone(), &Model::Claude35Haiku);
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(
+            filtered[0].content[0],
+            MessageContent::new_text("text extracted from paper.pdf:\nabstract".to_string())
+        );
+        assert_eq!(
+            filtered[1].content[0],
+            MessageContent::new_text("transcript of memo.mp3:\nhello".to_string())
+        );
+
+        let filtered = filter_unsupported_content(messages, &Model::GPT4o);
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered[0].content[0], MessageContent::Document(document));
+    }
+
+    #[test]
+    fn test_openai_request_content_types() {
+        let document = MessageContentDocument {
+            data_url: "data:application/pdf;base64,JVBERi0=".to_string(),
+            filename: None,
+            text: None,
+            pages: None,
+        };
+        let messages = vec![
+            Message::new_document(document),
+            Message::new_audio(MessageContentAudio {
+                data_url: "data:audio/wav;base64,UklG".to_string(),
+                filename: None,
+                transcript: None,
+            }),
+        ];
+        let request = Provider::OpenAI
+            .prepare_openai_request("model", false, &messages, None)
+            .unwrap();
+        let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+
+        let document = &request["messages"][0]["content"][0];
+        assert_eq!(document["type"], "file");
+        assert_eq!(
+            document["file"]["file_data"],
+            "data:application/pdf;base64,JVBERi0="
+        );
+        let audio = &request["messages"][1]["content"][0];
+        assert_eq!(audio["type"], "input_audio");
+        assert_eq!(audio["input_audio"]["data"], "UklG");
+        assert_eq!(audio["input_audio"]["format"], "wav");
+    }
+
+    #[test]
+    fn test_parse_streamed_usage() {
+        let mut usage = CompletionUsage::default();
+        let events = [
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"cache_creation_input_tokens":0,"cache_read_input_tokens":2000,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"hi"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#,
+        ];
+        for event in events {
+            if let Some(u) = Provider::Anthropic.parse_usage(event) {
+                usage.merge(&u);
+            }
+        }
+        assert_eq!(
+            usage,
+            CompletionUsage {
+                input_tokens: 10,
+                output_tokens: 15,
+                cache_read_tokens: 2000,
+                cache_write_tokens: 0,
+            }
+        );
+
+        let chunk = r#"{"choices":[],"usage":{"prompt_tokens":1500,"completion_tokens":20,"prompt_tokens_details":{"cached_tokens":1024}}}"#;
+        let usage = Provider::OpenAI.parse_usage(chunk).unwrap();
+        assert_eq!(usage.cache_read_tokens, 1024);
+        assert_eq!(usage.input_tokens, 1500);
+    }
+
+    #[test]
+    fn test_usage_cost() {
+        let usage = CompletionUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_tokens: 500_000,
+            cache_write_tokens: 0,
+        };
+        // openai counts the cached tokens as input tokens as well
+        let model = Model::GPT4o;
+        assert_eq!(model.total_tokens(&usage), 1_100_000);
+        let cost = model.cost(&usage, &model.price().unwrap());
+        assert!((cost - (1.25 + 1.0 + 0.625)).abs() < 1e-9);
+
+        let model = Model::Claude4Sonnet;
+        assert_eq!(model.total_tokens(&usage), 1_600_000);
+        let cost = model.cost(&usage, &model.price().unwrap());
+        assert!((cost - (3.0 + 1.5 + 0.15)).abs() < 1e-9);
+
+        let mut total = usage.clone();
+        total.add(&usage);
+        assert_eq!(total.input_tokens, 2_000_000);
+        assert_eq!(total.cache_read_tokens, 1_000_000);
+    }
+}
@@ -0,0 +1,149 @@
+use super::{CancellationToken, LLMClient, Model};
+use crate::ai::llm::models::Message;
+use crate::{BackendError, BackendResult};
+use serde::de::DeserializeOwned;
+
+const MAX_ATTEMPTS: usize = 3;
+
+// a type the llm can be asked to produce as json
+//
+// openai and google enforce the schema natively, for the other providers
+// the schema is injected into the prompt and the answer is validated here
+pub trait StructuredOutput: DeserializeOwned {
+    // name of the schema, must match `^[a-zA-Z0-9_-]+$`
+    const NAME: &'static str;
+
+    // json schema of the type, in the strict subset supported by openai:
+    // every property required and `additionalProperties: false`
+    fn json_schema() -> serde_json::Value;
+
+    // checks the schema can't express, the error is sent back to the llm on retry
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn response_format<T: StructuredOutput>() -> serde_json::Value {
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": T::NAME,
+            "strict": true,
+            "schema": T::json_schema(),
+        }
+    })
+}
+
+// models without native structured output like to wrap json in markdown code blocks
+fn strip_code_fence(answer: &str) -> &str {
+    let answer = answer.trim();
+    match answer
+        .strip_prefix("```json")
+        .or_else(|| answer.strip_prefix("```"))
+        .and_then(|a| a.strip_suffix("```"))
+    {
+        Some(inner) => inner.trim(),
+        None => answer,
+    }
+}
+
+pub fn parse_structured_output<T: StructuredOutput>(answer: &str) -> Result<T, String> {
+    let output: T = serde_json::from_str(strip_code_fence(answer))
+        .map_err(|e| format!("response is not valid JSON for the schema: {e}"))?;
+    output.validate()?;
+    Ok(output)
+}
+
+impl LLMClient {
+    // requests a completion that deserializes into `T`
+    //
+    // answers that fail to parse or validate are retried with the error
+    // appended to the conversation, up to `MAX_ATTEMPTS` requests in total
+    #[tracing::instrument(level = "trace", skip(self, messages, cancellation_token))]
+    pub fn create_structured_completion<T: StructuredOutput>(
+        &self,
+        mut messages: Vec<Message>,
+        model: &Model,
+        custom_key: Option<String>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<T> {
+        let mut last_error = String::new();
+        for attempt in 1..=MAX_ATTEMPTS {
+            let answer = self.create_chat_completion(
+                messages.clone(),
+                model,
+                custom_key.clone(),
+                Some(response_format::<T>()),
+                cancellation_token.clone(),
+            )?;
+            match parse_structured_output::<T>(&answer) {
+                Ok(output) => return Ok(output),
+                Err(err) => {
+                    tracing::warn!(
+                        "invalid {} structured output (attempt {attempt}): {err}",
+                        T::NAME
+                    );
+                    messages.push(Message::new_assistant(&answer));
+                    messages.push(Message::new_user(&format!(
+                        "Your previous response was invalid: {err}\nFix it and respond again."
+                    )));
+                    last_error = err;
+                }
+            }
+        }
+
+        Err(BackendError::GenericError(format!(
+            "failed to get valid {} output after {MAX_ATTEMPTS} attempts: {last_error}",
+            T::NAME
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Rating {
+        score: u32,
+    }
+
+    impl StructuredOutput for Rating {
+        const NAME: &'static str = "rating";
+
+        fn json_schema() -> serde_json::Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": { "score": { "type": "integer" } },
+                "required": ["score"],
+                "additionalProperties": false
+            })
+        }
+
+        fn validate(&self) -> Result<(), String> {
+            if self.score > 10 {
+                return Err(format!("score must be at most 10, got {}", self.score));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_structured_output() {
+        let rating = parse_structured_output::<Rating>(r#"{"score": 7}"#).unwrap();
+        assert_eq!(rating.score, 7);
+
+        let rating = parse_structured_output::<Rating>("```json\n{\"score\": 3}\n```").unwrap();
+        assert_eq!(rating.score, 3);
+    }
+
+    #[test]
+    fn test_parse_structured_output_errors() {
+        assert!(parse_structured_output::<Rating>(r#"{"rating": 7}"#).is_err());
+        assert!(parse_structured_output::<Rating>("seven").is_err());
+
+        let err = parse_structured_output::<Rating>(r#"{"score": 11}"#).unwrap_err();
+        assert_eq!(err, "score must be at most 10, got 11");
+    }
+}
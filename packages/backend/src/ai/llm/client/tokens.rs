@@ -1,6 +1,8 @@
 use super::TokenModel;
-use crate::ai::llm::models::{Message, MessageContent};
-use std::collections::HashSet;
+use crate::ai::llm::models::{
+    Message, MessageContent, MessageContentAudio, MessageContentDocument,
+};
+use std::{collections::HashSet, ops::Range};
 
 // reference: https://help.openai.com/en/articles/4936856-what-are-tokens-and-how-to-count-them
 fn estimate_text_tokens(text: &str) -> usize {
@@ -15,10 +17,30 @@ fn estimate_image_tokens(_image_url: &str) -> usize {
     1105
 }
 
+// providers send both the extracted text and an image of every page
+// reference: https://docs.anthropic.com/en/docs/build-with-claude/pdf-support
+fn estimate_document_tokens(document: &MessageContentDocument) -> usize {
+    let text_tokens = document
+        .text
+        .as_deref()
+        .map(estimate_text_tokens)
+        .unwrap_or_default();
+    text_tokens + document.pages.unwrap_or(1) * estimate_image_tokens(&document.data_url)
+}
+
+// ~32 tokens per second of audio and ~16kB per second of compressed audio
+// reference: https://ai.google.dev/gemini-api/docs/audio
+fn estimate_audio_tokens(audio: &MessageContentAudio) -> usize {
+    let bytes = audio.data_url.len() / 4 * 3;
+    bytes.div_ceil(500)
+}
+
 pub fn estimate_message_content_tokens(content: &MessageContent) -> usize {
     match content {
         MessageContent::Text(text) => estimate_text_tokens(&text.text),
         MessageContent::Image(image) => estimate_image_tokens(&image.image_url.url),
+        MessageContent::Document(document) => estimate_document_tokens(document),
+        MessageContent::Audio(audio) => estimate_audio_tokens(audio),
     }
 }
 
@@ -127,6 +149,33 @@ pub fn truncate_messages(
     (truncated, truncated_messages)
 }
 
+// number of messages from the start of `range` that need to be compacted
+// for all messages to fit the max tokens while leaving `reserved_tokens` for the summary
+//
+// returns 0 if the messages already fit
+pub fn count_messages_to_compact(
+    messages: &[Message],
+    range: Range<usize>,
+    model: &impl TokenModel,
+    reserved_tokens: usize,
+) -> usize {
+    let max_tokens = model.max_tokens();
+    let mut total_tokens = estimate_messages_tokens(messages);
+    if total_tokens <= max_tokens {
+        return 0;
+    }
+
+    let mut count = 0;
+    for message in messages[range].iter() {
+        if total_tokens + reserved_tokens <= max_tokens {
+            break;
+        }
+        total_tokens -= estimate_message_token(message);
+        count += 1;
+    }
+    count
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +268,7 @@ mod tests {
             page: None,
             description: None,
             created_at: None,
+            timestamp: None,
         })
         .expect("Failed to create context message");
 
@@ -378,4 +428,45 @@ mod tests {
         assert_eq!(truncated_messages[1], third);
         assert_eq!(truncated_messages[2], fourth);
     }
+
+    #[test]
+    fn test_count_messages_to_compact_under_limit() {
+        let messages = vec![
+            Message::new_system("system"),
+            Message::new_user("First"),
+            Message::new_assistant("Second"),
+            Message::new_user("Third"),
+        ];
+        let model = MockModel { max_tokens: 100 };
+
+        assert_eq!(count_messages_to_compact(&messages, 1..3, &model, 10), 0);
+    }
+
+    #[test]
+    fn test_count_messages_to_compact_oldest_first() {
+        let messages = vec![
+            Message::new_system(&"s".repeat(40)),
+            Message::new_user(&"a".repeat(40)),
+            Message::new_assistant(&"b".repeat(40)),
+            Message::new_user(&"c".repeat(40)),
+            Message::new_user(&"d".repeat(40)),
+        ];
+        // 50 tokens in total, 5 reserved for the summary
+        let model = MockModel { max_tokens: 35 };
+
+        assert_eq!(count_messages_to_compact(&messages, 1..4, &model, 5), 2);
+    }
+
+    #[test]
+    fn test_count_messages_to_compact_limited_to_range() {
+        let messages = vec![
+            Message::new_system(&"s".repeat(40)),
+            Message::new_user(&"a".repeat(40)),
+            Message::new_assistant(&"b".repeat(40)),
+            Message::new_user(&"c".repeat(40)),
+        ];
+        let model = MockModel { max_tokens: 10 };
+
+        assert_eq!(count_messages_to_compact(&messages, 1..3, &model, 5), 2);
+    }
 }
@@ -1,3 +1,4 @@
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
@@ -18,6 +19,68 @@ pub struct MessageContentImage {
     pub image_url: MessageContentImageURL,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageContentDocument {
+    // base64 data url, e.g. `data:application/pdf;base64,...`
+    pub data_url: String,
+    pub filename: Option<String>,
+    // extracted text, sent instead of the document to models that can't read documents
+    pub text: Option<String>,
+    pub pages: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageContentAudio {
+    // base64 data url, e.g. `data:audio/mpeg;base64,...`
+    pub data_url: String,
+    #[serde(default)]
+    pub filename: Option<String>,
+    // sent instead of the audio to models that can't listen to audio
+    pub transcript: Option<String>,
+}
+
+// splits a base64 data url into its media type and data
+pub fn split_data_url(url: &str) -> Option<(&str, &str)> {
+    url.strip_prefix("data:")?.split_once(";base64,")
+}
+
+impl MessageContentDocument {
+    // decodes the pdf to extract the fallback text and page count
+    pub fn from_pdf_data_url(data_url: String, filename: Option<String>) -> BackendResult<Self> {
+        let data = split_data_url(&data_url)
+            .map(|(_, data)| data)
+            .ok_or_else(|| BackendError::GenericError("invalid document data url".to_string()))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| BackendError::GenericError(format!("invalid document data: {e}")))?;
+        let doc = lopdf::Document::load_mem(&bytes)
+            .map_err(|e| BackendError::GenericError(format!("failed to load pdf: {e}")))?;
+
+        let page_numbers = doc.get_pages().into_keys().collect::<Vec<_>>();
+        // a document without extractable text (e.g. a scan) is still useful to models
+        // that can read documents
+        let text = doc
+            .extract_text(&page_numbers)
+            .ok()
+            .filter(|text| !text.trim().is_empty());
+
+        Ok(Self {
+            data_url,
+            filename,
+            text,
+            pages: Some(page_numbers.len()),
+        })
+    }
+
+    // the chat history only keeps the extracted text so the pdf isn't parsed again on every load
+    pub fn without_data(&self) -> Self {
+        Self {
+            data_url: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum MessageContent {
@@ -25,6 +88,10 @@ pub enum MessageContent {
     Text(MessageContentText),
     #[serde(rename = "image_url")]
     Image(MessageContentImage),
+    #[serde(rename = "document")]
+    Document(MessageContentDocument),
+    #[serde(rename = "audio")]
+    Audio(MessageContentAudio),
 }
 
 impl MessageContent {
@@ -40,6 +107,24 @@ impl MessageContent {
         match self {
             MessageContent::Text(text) => text.text.clone(),
             MessageContent::Image(image) => image.image_url.url.clone(),
+            MessageContent::Document(document) => document.data_url.clone(),
+            MessageContent::Audio(audio) => audio.data_url.clone(),
+        }
+    }
+
+    // text stand-in for documents and audio sent to models that don't support them
+    pub fn to_text_fallback(&self) -> Option<MessageContent> {
+        match self {
+            MessageContent::Text(_) => Some(self.clone()),
+            MessageContent::Image(_) => None,
+            MessageContent::Document(document) => document.text.as_ref().map(|text| {
+                let name = document.filename.as_deref().unwrap_or("document");
+                MessageContent::new_text(format!("text extracted from {name}:\n{text}"))
+            }),
+            MessageContent::Audio(audio) => audio.transcript.as_ref().map(|transcript| {
+                let name = audio.filename.as_deref().unwrap_or("audio");
+                MessageContent::new_text(format!("transcript of {name}:\n{transcript}"))
+            }),
         }
     }
 }
@@ -67,6 +152,9 @@ pub struct ContextMessage {
     pub page: Option<u32>,
     pub description: Option<String>,
     pub created_at: Option<String>,
+    // position in the source, e.g. for transcripts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -135,6 +223,61 @@ impl Message {
         }
     }
 
+    // summary of compacted chat history and context, never truncated
+    pub fn new_summary(summary: &str) -> Message {
+        let content_str = format!(
+            "summary of the earlier conversation and context:\n{}",
+            summary
+        );
+        Message {
+            role: MessageRole::User,
+            content: vec![MessageContent::new_text(content_str)],
+            truncatable: false,
+            is_context: true,
+        }
+    }
+
+    pub fn new_document(document: MessageContentDocument) -> Message {
+        Message {
+            role: MessageRole::User,
+            content: vec![MessageContent::Document(document)],
+            truncatable: true,
+            is_context: true,
+        }
+    }
+
+    pub fn new_audio(audio: MessageContentAudio) -> Message {
+        Message {
+            role: MessageRole::User,
+            content: vec![MessageContent::Audio(audio)],
+            truncatable: true,
+            is_context: true,
+        }
+    }
+
+    // tool results are sent as user messages, the agents parse tool calls from text
+    // so there is no provider tool call id to attach them to
+    pub fn new_tool(name: &str, status: &str) -> Message {
+        let content_str = format!("result of tool `{}`: {}", name, status);
+        Message {
+            role: MessageRole::User,
+            content: vec![MessageContent::new_text(content_str)],
+            truncatable: false,
+            is_context: false,
+        }
+    }
+
+    // memories change with every query, they are sent after the history
+    // so that the system prompt and the history can be cached
+    pub fn new_memories(prompt: &str) -> Message {
+        Message {
+            role: MessageRole::User,
+            content: vec![MessageContent::new_text(prompt.to_string())],
+            truncatable: false,
+            is_context: true,
+        }
+    }
+
     pub fn new_note(content: &str) -> Message {
         let content_str = format!("working user document:\n{}", content);
         Message {
@@ -0,0 +1,137 @@
+use crate::ai::AI;
+use crate::store::db::Database;
+use crate::store::models::{current_time, random_uuid, Memory};
+use crate::{BackendError, BackendResult};
+
+// most memories added to a single prompt
+pub const MAX_PROMPT_MEMORIES: usize = 8;
+// memories less similar to the request than this are left out,
+// unless there are so few memories that all of them are used
+const MEMORY_SIMILARITY_THRESHOLD: f32 = 0.3;
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+// the `limit` memories most similar to the query, most similar first
+pub fn rank_memories(memories: Vec<Memory>, query_embedding: &[f32], limit: usize) -> Vec<Memory> {
+    if memories.len() <= limit {
+        return memories;
+    }
+    let mut scored: Vec<(f32, Memory)> = memories
+        .into_iter()
+        .map(|memory| {
+            (
+                cosine_similarity(&memory.embedding, query_embedding),
+                memory,
+            )
+        })
+        .filter(|(similarity, _)| *similarity >= MEMORY_SIMILARITY_THRESHOLD)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, memory)| memory)
+        .collect()
+}
+
+impl AI {
+    fn embed_memory(&self, content: &str) -> BackendResult<Vec<f32>> {
+        self.encode_sentences(&vec![content.to_string()])?
+            .pop()
+            .ok_or_else(|| BackendError::GenericError("no embedding for memory".to_string()))
+    }
+
+    pub fn save_memory(&self, db: &mut Database, content: &str) -> BackendResult<Memory> {
+        let memory = Memory {
+            id: random_uuid(),
+            content: content.trim().to_string(),
+            embedding: self.embed_memory(content.trim())?,
+            created_at: current_time(),
+            updated_at: current_time(),
+        };
+        db.upsert_memory(&memory)?;
+        Ok(memory)
+    }
+
+    pub fn update_memory(
+        &self,
+        db: &mut Database,
+        id: &str,
+        content: &str,
+    ) -> BackendResult<Memory> {
+        let mut memory = db
+            .get_memory(id)?
+            .ok_or_else(|| BackendError::GenericError(format!("memory {} not found", id)))?;
+        memory.content = content.trim().to_string();
+        memory.embedding = self.embed_memory(&memory.content)?;
+        memory.updated_at = current_time();
+        db.upsert_memory(&memory)?;
+        Ok(memory)
+    }
+
+    // the memories to add to the prompt of a request
+    pub fn relevant_memories(&self, db: &Database, query: &str) -> BackendResult<Vec<Memory>> {
+        let memories = db.list_memories()?;
+        // no need to embed the query if all memories fit
+        if memories.len() <= MAX_PROMPT_MEMORIES {
+            return Ok(memories);
+        }
+        let query_embedding = self.embed_memory(query)?;
+        Ok(rank_memories(
+            memories,
+            &query_embedding,
+            MAX_PROMPT_MEMORIES,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(content: &str, embedding: Vec<f32>) -> Memory {
+        Memory {
+            id: random_uuid(),
+            content: content.to_string(),
+            embedding,
+            created_at: current_time(),
+            updated_at: current_time(),
+        }
+    }
+
+    #[test]
+    fn test_rank_memories() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+
+        let memories = vec![
+            memory("british english", vec![1.0, 0.1, 0.0]),
+            memory("thesis about bees", vec![0.0, 1.0, 0.0]),
+            memory("papers after 2020", vec![0.7, 0.7, 0.0]),
+        ];
+        // everything is used while it fits
+        assert_eq!(
+            rank_memories(memories.clone(), &[0.0, 0.0, 1.0], 3).len(),
+            3
+        );
+
+        let ranked = rank_memories(memories.clone(), &[1.0, 0.0, 0.0], 2);
+        let contents: Vec<&str> = ranked.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["british english", "papers after 2020"]);
+
+        // unrelated memories are left out
+        assert!(rank_memories(memories, &[0.0, 0.0, 1.0], 2).is_empty());
+    }
+}
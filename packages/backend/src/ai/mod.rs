@@ -1,5 +1,6 @@
 pub mod embeddings;
 pub mod llm;
+pub mod memory;
 pub mod youtube;
 
 #[cfg(feature = "wip")]
@@ -11,12 +12,25 @@ mod prompts;
 pub const _MODULE_PREFIX: &str = "ai";
 pub const _AI_API_ENDPOINT: &str = "v1/deta-os-ai";
 
+// number of most recent history messages that are never compacted
+const COMPACTION_KEEP_RECENT_MESSAGES: usize = 6;
+// tokens left free for the summary when deciding how much history to compact
+const COMPACTION_SUMMARY_RESERVED_TOKENS: usize = 2048;
+// deadlines for the short non-streaming calls that gate a chat response
+const COMPACTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+const SHOULD_CLUSTER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const SQL_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
 use std::str::FromStr;
 
 use crate::ai::embeddings::chunking::ContentChunker;
 use crate::ai::llm::client;
-use crate::ai::llm::client::{ChatCompletionStream, Model};
-use crate::ai::llm::models::{ContextMessage, Message, MessageContent, MessageRole};
+use crate::ai::llm::client::structured::StructuredOutput;
+use crate::ai::llm::client::{tokens, CancellationToken, ChatCompletionStream, Model};
+use crate::ai::llm::models::{
+    ContextMessage, Message, MessageContent, MessageContentAudio, MessageContentDocument,
+    MessageRole,
+};
 use crate::ai::local::client::{
     DocsSimilarityRequest, FilteredSearchRequest, LocalAIClient, UpsertEmbeddingsRequest,
 };
@@ -26,8 +40,9 @@ use crate::{BackendError, BackendResult};
 use serde::{Deserialize, Serialize};
 
 use prompts::{
-    chat_prompt, create_app_prompt, general_chat_prompt, note_prompt, should_narrow_search_prompt,
-    should_narrow_search_prompt_simple, sql_query_generator_prompt,
+    chat_history_summary_prompt, chat_prompt, create_app_prompt, general_chat_prompt,
+    memories_prompt, note_prompt, should_narrow_search_prompt, should_narrow_search_prompt_simple,
+    sql_query_generator_prompt,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,6 +70,51 @@ pub struct YoutubeTranscript {
     pub metadata: YoutubeTranscriptMetadata,
 }
 
+// a file attached to a chat message, the frontend can also send just the data url
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "InlineFileInput")]
+pub struct InlineFile {
+    // base64 data url
+    pub data_url: String,
+    pub filename: Option<String>,
+    // of audio files, sent instead of the audio to models that can't listen to audio
+    pub transcript: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InlineFileInput {
+    DataUrl(String),
+    File {
+        data_url: String,
+        #[serde(default)]
+        filename: Option<String>,
+        #[serde(default)]
+        transcript: Option<String>,
+    },
+}
+
+impl From<InlineFileInput> for InlineFile {
+    fn from(input: InlineFileInput) -> Self {
+        match input {
+            InlineFileInput::DataUrl(data_url) => InlineFile {
+                data_url,
+                filename: None,
+                transcript: None,
+            },
+            InlineFileInput::File {
+                data_url,
+                filename,
+                transcript,
+            } => InlineFile {
+                data_url,
+                filename,
+                transcript,
+            },
+        }
+    }
+}
+
 pub struct ChatInput {
     pub query: String,
     pub model: Model,
@@ -63,9 +123,52 @@ pub struct ChatInput {
     pub note_resource_id: Option<String>,
     pub number_documents: i32,
     pub inline_images: Option<Vec<String>>,
+    // pdfs
+    pub inline_documents: Option<Vec<InlineFile>>,
+    pub inline_audio: Option<Vec<InlineFile>>,
     pub general: bool,
     pub websearch: bool,
     pub surflet: bool,
+    // summarise older history instead of dropping it when over the token budget
+    pub compact_history: bool,
+    pub cancellation_token: CancellationToken,
+}
+
+// summary of the first `covered_messages` history messages
+pub struct ChatSummary {
+    pub content: String,
+    pub covered_messages: usize,
+}
+
+// a saved chat history parsed into messages
+pub struct ChatHistory {
+    pub messages: Vec<Message>,
+    // `created_at` of the saved message each message was parsed from, saved messages
+    // that don't make a message (e.g. documents without text) are left out
+    pub created_at: Vec<chrono::DateTime<chrono::Utc>>,
+}
+
+// the summary is saved with the timestamp of the last message it covers
+// so that it replaces the covered messages when the history is loaded again
+pub fn chat_summary_message(
+    session_id: &str,
+    summary: ChatSummary,
+    history_created_at: &[chrono::DateTime<chrono::Utc>],
+) -> Option<AIChatSessionMessage> {
+    let created_at = summary
+        .covered_messages
+        .checked_sub(1)
+        .and_then(|i| history_created_at.get(i))?;
+    Some(AIChatSessionMessage {
+        ai_session_id: session_id.to_string(),
+        role: "user".to_owned(),
+        content: summary.content,
+        truncatable: false,
+        is_context: true,
+        msg_type: "summary".to_owned(),
+        created_at: *created_at,
+        sources: None,
+    })
 }
 
 // TODO: fix sources vs messages
@@ -74,6 +177,7 @@ pub struct ChatResult {
     pub sources: Vec<AIChatSessionMessageSource>,
     pub sources_xml: String,
     pub stream: ChatCompletionStream,
+    pub summary: Option<ChatSummary>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,17 +186,86 @@ pub struct ShouldClusterResult {
     pub relevant_context_ids: Option<Vec<String>>,
 }
 
+impl StructuredOutput for ShouldClusterResult {
+    const NAME: &'static str = "should_cluster_response";
+
+    fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "embeddings_search_needed": {
+                    "type": "boolean"
+                },
+                "relevant_context_ids": {
+                    "type": "array",
+                    "items": {
+                        "type": "string"
+                    }
+                }
+            },
+            "required": ["embeddings_search_needed", "relevant_context_ids"],
+            "additionalProperties": false
+        })
+    }
+}
+
 pub struct AI {
     pub client: client::LLMClient,
     pub chunker: ContentChunker,
     local_ai_client: LocalAIClient,
 }
 
+// the prompts don't need more than the hour, it's truncated only to keep the prompt
+// prefix stable across chat turns, otherwise every request would miss the provider's
+// prompt cache
 fn human_readable_current_time() -> String {
-    // 2023-09-13 21:00:00 Tuesday
-    chrono::Utc::now()
-        .format("%Y-%m-%d %H:%M:%S %A")
-        .to_string()
+    // 2023-09-13 21:00 Tuesday
+    chrono::Utc::now().format("%Y-%m-%d %H:00 %A").to_string()
+}
+
+// documents that can't be parsed are still sent to models that read documents natively
+fn parse_document(data_url: String, filename: Option<String>) -> MessageContentDocument {
+    MessageContentDocument::from_pdf_data_url(data_url.clone(), filename.clone()).unwrap_or_else(
+        |err| {
+            tracing::warn!("failed to extract text from document: {err}");
+            MessageContentDocument {
+                data_url,
+                filename,
+                text: None,
+                pages: None,
+            }
+        },
+    )
+}
+
+// documents are stored with their extracted text only, `None` if there is no text
+//
+// older chats stored the data url of the document
+fn parse_history_document(content: String) -> BackendResult<Option<MessageContent>> {
+    if content.starts_with("data:") {
+        return Ok(Some(MessageContent::Document(parse_document(
+            content, None,
+        ))));
+    }
+    let document: MessageContentDocument = serde_json::from_str(&content).map_err(|e| {
+        BackendError::GenericError(format!("failed to parse chat message document: {e}"))
+    })?;
+    Ok(MessageContent::Document(document).to_text_fallback())
+}
+
+// older chats stored the data url of the audio
+fn parse_history_audio(content: String) -> BackendResult<MessageContent> {
+    if content.starts_with("data:") {
+        return Ok(MessageContent::Audio(MessageContentAudio {
+            data_url: content,
+            filename: None,
+            transcript: None,
+        }));
+    }
+    let audio = serde_json::from_str(&content).map_err(|e| {
+        BackendError::GenericError(format!("failed to parse chat message audio: {e}"))
+    })?;
+    Ok(MessageContent::Audio(audio))
 }
 
 impl AI {
@@ -107,12 +280,23 @@ impl AI {
     pub fn parse_chat_history(
         &self,
         history: Vec<AIChatSessionMessage>,
-    ) -> BackendResult<Vec<Message>> {
+    ) -> BackendResult<ChatHistory> {
         let mut messages = Vec::new();
+        let mut created_at = Vec::new();
         for msg in history {
             let content = match msg.msg_type.as_ref() {
                 "text" => MessageContent::new_text(msg.content),
                 "image" => MessageContent::new_image(msg.content),
+                "document" => match parse_history_document(msg.content)? {
+                    Some(content) => content,
+                    None => continue,
+                },
+                "audio" => parse_history_audio(msg.content)?,
+                "summary" => {
+                    messages.push(Message::new_summary(&msg.content));
+                    created_at.push(msg.created_at);
+                    continue;
+                }
                 _ => {
                     return Err(BackendError::GenericError(format!(
                         "unknown chat message type: {}",
@@ -129,8 +313,90 @@ impl AI {
                 truncatable: msg.truncatable,
                 is_context: msg.is_context,
             });
+            created_at.push(msg.created_at);
         }
-        Ok(messages)
+        Ok(ChatHistory {
+            messages,
+            created_at,
+        })
+    }
+
+    // replaces the history messages covered by the latest cached summary with the summary
+    //
+    // a summary covers every message created at or before its own `created_at`
+    pub fn apply_chat_summary(
+        &self,
+        history: Vec<AIChatSessionMessage>,
+    ) -> Vec<AIChatSessionMessage> {
+        let latest_summary = history
+            .iter()
+            .filter(|msg| msg.msg_type == "summary")
+            .max_by_key(|msg| msg.created_at)
+            .cloned();
+        let summary = match latest_summary {
+            Some(summary) => summary,
+            None => return history,
+        };
+
+        let mut messages = vec![summary.clone()];
+        messages.extend(
+            history
+                .into_iter()
+                .filter(|msg| msg.msg_type != "summary" && msg.created_at > summary.created_at),
+        );
+        messages
+    }
+
+    // summarises the oldest history messages into a single context message
+    // if the messages don't fit the model's token budget
+    //
+    // history messages are expected at `messages[1..=history_len]`
+    fn compact_chat_history(
+        &self,
+        messages: &mut Vec<Message>,
+        history_len: usize,
+        model: &Model,
+        custom_key: Option<String>,
+        cancellation_token: &CancellationToken,
+    ) -> BackendResult<Option<ChatSummary>> {
+        let compactable = history_len.saturating_sub(COMPACTION_KEEP_RECENT_MESSAGES);
+        let covered_messages = tokens::count_messages_to_compact(
+            messages,
+            1..compactable + 1,
+            model,
+            COMPACTION_SUMMARY_RESERVED_TOKENS,
+        );
+        if covered_messages == 0 {
+            return Ok(None);
+        }
+
+        let mut summary_messages = vec![Message::new_system(&chat_history_summary_prompt())];
+        summary_messages.extend(
+            messages[1..covered_messages + 1]
+                .iter()
+                .cloned()
+                .map(|msg| msg.with_truncatable(true)),
+        );
+        summary_messages.push(Message::new_user(
+            "Summarize the conversation and the context above.",
+        ));
+
+        let content = self.client.create_chat_completion(
+            summary_messages,
+            &model.compaction_model(),
+            custom_key,
+            None,
+            cancellation_token.with_timeout(COMPACTION_TIMEOUT),
+        )?;
+        messages.splice(
+            1..covered_messages + 1,
+            std::iter::once(Message::new_summary(&content)),
+        );
+
+        Ok(Some(ChatSummary {
+            content,
+            covered_messages,
+        }))
     }
 
     pub fn get_docs_similarity(
@@ -161,36 +427,13 @@ impl AI {
         model: &Model,
         custom_key: Option<String>,
         context: Vec<ContextMessage>,
+        cancellation_token: &CancellationToken,
     ) -> BackendResult<ShouldClusterResult> {
+        let cancellation_token = cancellation_token.with_timeout(SHOULD_CLUSTER_TIMEOUT);
         // TODO(@nullptropy): temporary measure to make local model UX better
-        let (prompt, response_format) = match model {
-            Model::Custom { .. } => (should_narrow_search_prompt_simple(), None),
-            _ => (
-                should_narrow_search_prompt(&human_readable_current_time()),
-                Some(serde_json::json!({
-                    "type": "json_schema",
-                    "json_schema": {
-                        "name": "should_cluster_response",
-                        "strict": true,
-                        "schema": {
-                            "type": "object",
-                            "properties": {
-                                "embeddings_search_needed": {
-                                    "type": "boolean"
-                                },
-                                "relevant_context_ids": {
-                                    "type": "array",
-                                    "items": {
-                                        "type": "string"
-                                    }
-                                }
-                            },
-                            "required": ["embeddings_search_needed", "relevant_context_ids"],
-                            "additionalProperties": false
-                        }
-                    }
-                })),
-            ),
+        let prompt = match model {
+            Model::Custom { .. } => should_narrow_search_prompt_simple(),
+            _ => should_narrow_search_prompt(&human_readable_current_time()),
         };
 
         let mut messages = vec![Message::new_system(&prompt)];
@@ -202,28 +445,30 @@ impl AI {
             prompt, query
         )));
 
-        let answer =
-            self.client
-                .create_chat_completion(messages, model, custom_key, response_format)?;
-
         if let Model::Custom { .. } = model {
+            let answer = self.client.create_chat_completion(
+                messages,
+                model,
+                custom_key,
+                None,
+                cancellation_token,
+            )?;
             Ok(ShouldClusterResult {
                 embeddings_search_needed: answer.trim().to_lowercase() == "true",
                 relevant_context_ids: Some(vec![]),
             })
         } else {
-            let response: ShouldClusterResult = serde_json::from_str(&answer).map_err(|e| {
-                BackendError::GenericError(format!(
-                    "failed to parse should_cluster response: {}",
-                    e
-                ))
-            })?;
-            Ok(response)
+            self.client.create_structured_completion(
+                messages,
+                model,
+                custom_key,
+                cancellation_token,
+            )
         }
     }
 
     pub fn upsert_embeddings(
-        &mut self,
+        &self,
         old_keys: Vec<i64>,
         new_keys: Vec<i64>,
         chunks: Vec<String>,
@@ -294,6 +539,7 @@ impl AI {
                 source_url: None,
                 author: None,
                 description: None,
+                timestamp: None,
             };
             // TODO: is author info easily retreivable or stored?
             if let Some(metadata) = &resource.metadata {
@@ -333,6 +579,7 @@ impl AI {
                 source_url: None,
                 author: None,
                 description: None,
+                timestamp: None,
             };
             // TODO: is author info easily retreivable or stored?
             if let Some(metadata) = &resource.metadata {
@@ -384,6 +631,18 @@ impl AI {
                 "Resource IDs must be provided if not general query".to_string(),
             ));
         }
+        // the audio would be dropped without a word otherwise
+        let unplayable_audio = input
+            .inline_audio
+            .iter()
+            .flatten()
+            .any(|audio| audio.transcript.is_none());
+        if unplayable_audio && !input.model.supports_audio() {
+            return Err(BackendError::GenericError(
+                "the model can't listen to audio, send a transcript of the audio instead"
+                    .to_string(),
+            ));
+        }
 
         let mut rag_results = match should_cluster {
             true => self.vector_search(
@@ -431,6 +690,18 @@ impl AI {
         // history if any
         messages.extend(history);
 
+        // the chat still works without memories, e.g. when the embeddings model is down
+        match self.relevant_memories(contents_store, &input.query) {
+            Ok(memories) => {
+                if let Some(prompt) = memories_prompt(&memories, false) {
+                    messages.push(Message::new_memories(&prompt));
+                }
+            }
+            Err(e) => tracing::warn!("failed to get relevant memories: {}", e),
+        }
+        // the memories are looked up again for every query, they are not saved
+        let new_messages_start = messages.len();
+
         // context messages
         for msg in contexts {
             messages.push(Message::new_context(&msg)?);
@@ -441,6 +712,23 @@ impl AI {
                 messages.push(Message::new_image(&image));
             }
         }
+        if let Some(inline_documents) = input.inline_documents {
+            for document in inline_documents {
+                messages.push(Message::new_document(parse_document(
+                    document.data_url,
+                    document.filename,
+                )));
+            }
+        }
+        if let Some(inline_audio) = input.inline_audio {
+            for audio in inline_audio {
+                messages.push(Message::new_audio(MessageContentAudio {
+                    data_url: audio.data_url,
+                    filename: audio.filename,
+                    transcript: audio.transcript,
+                }));
+            }
+        }
 
         if let Some(note_resource_id) = input.note_resource_id {
             let resource_text_contents =
@@ -455,12 +743,31 @@ impl AI {
         }
 
         messages.push(Message::new_user(&input.query));
-        let messages_slice = messages[history_len + 1..].to_vec().clone();
+        let messages_slice = messages[new_messages_start..].to_vec().clone();
+
+        let mut summary = None;
+        if input.compact_history {
+            // fall back to plain truncation if the summary can't be generated
+            summary = self
+                .compact_chat_history(
+                    &mut messages,
+                    history_len,
+                    &input.model,
+                    input.custom_key.clone(),
+                    &input.cancellation_token,
+                )
+                .unwrap_or_else(|err| {
+                    tracing::warn!("failed to compact chat history: {err}");
+                    None
+                });
+        }
+
         let stream = self.client.create_streaming_chat_completion(
             messages,
             &input.model,
             input.custom_key,
             None,
+            input.cancellation_token,
         )?;
 
         Ok(ChatResult {
@@ -468,6 +775,7 @@ impl AI {
             sources,
             sources_xml,
             stream,
+            summary,
         })
     }
 
@@ -477,13 +785,19 @@ impl AI {
         prompt: String,
         model: &Model,
         custom_key: Option<String>,
+        cancellation_token: &CancellationToken,
     ) -> BackendResult<String> {
         let messages = vec![
             Message::new_system(&sql_query_generator_prompt()),
             Message::new_user(&prompt),
         ];
-        self.client
-            .create_chat_completion(messages, model, custom_key, None)
+        self.client.create_chat_completion(
+            messages,
+            model,
+            custom_key,
+            None,
+            cancellation_token.with_timeout(SQL_QUERY_TIMEOUT),
+        )
     }
 
     pub fn create_app(
@@ -492,6 +806,7 @@ impl AI {
         model: &Model,
         custom_key: Option<String>,
         inline_images: Option<Vec<String>>,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<ChatCompletionStream> {
         let mut messages = vec![
             Message::new_system(&create_app_prompt(&human_readable_current_time())),
@@ -502,7 +817,173 @@ impl AI {
                 messages.push(Message::new_image(&image));
             }
         }
-        self.client
-            .create_streaming_chat_completion(messages, model, custom_key, None)
+        self.client.create_streaming_chat_completion(
+            messages,
+            model,
+            custom_key,
+            None,
+            cancellation_token,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_file_from_data_url_or_object() {
+        let files: Vec<InlineFile> = serde_json::from_str(
+            r#"["data:audio/mpeg;base64,SUQz", {"data_url": "data:audio/wav;base64,UklG", "filename": "memo.wav", "transcript": "hello"}]"#,
+        )
+        .unwrap();
+        assert_eq!(files[0].data_url, "data:audio/mpeg;base64,SUQz");
+        assert_eq!(files[0].filename, None);
+        assert_eq!(files[1].filename.as_deref(), Some("memo.wav"));
+        assert_eq!(files[1].transcript.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn test_history_document_keeps_only_the_text() {
+        let document = MessageContentDocument {
+            data_url: "data:application/pdf;base64,JVBERi0=".to_string(),
+            filename: Some("paper.pdf".to_string()),
+            text: Some("abstract".to_string()),
+            pages: Some(1),
+        };
+        let stored = serde_json::to_string(&document.without_data()).unwrap();
+        assert!(!stored.contains("JVBERi0"));
+
+        assert_eq!(
+            parse_history_document(stored).unwrap(),
+            Some(MessageContent::new_text(
+                "text extracted from paper.pdf:\nabstract".to_string()
+            ))
+        );
+
+        let scan = MessageContentDocument {
+            text: None,
+            ..document
+        };
+        let stored = serde_json::to_string(&scan.without_data()).unwrap();
+        assert_eq!(parse_history_document(stored).unwrap(), None);
+    }
+
+    fn session_message(
+        session_id: &str,
+        role: &str,
+        msg_type: &str,
+        content: &str,
+        created_at: chrono::DateTime<chrono::Utc>,
+    ) -> AIChatSessionMessage {
+        AIChatSessionMessage {
+            ai_session_id: session_id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            truncatable: false,
+            is_context: msg_type != "text",
+            msg_type: msg_type.to_string(),
+            created_at,
+            sources: None,
+        }
+    }
+
+    fn save_session_messages(db: &mut Database, messages: &[AIChatSessionMessage]) {
+        let mut tx = db.begin().unwrap();
+        for message in messages {
+            Database::create_ai_session_message_tx(&mut tx, message).unwrap();
+        }
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_chat_summary_round_trip() {
+        use crate::store::models::AIChatSession;
+        use chrono::TimeZone;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        let start = chrono::Utc.with_ymd_and_hms(2024, 5, 6, 12, 0, 0).unwrap();
+        let at = |minutes| start + chrono::Duration::minutes(minutes);
+        let session = AIChatSession {
+            id: "session".to_string(),
+            system_prompt: String::new(),
+            title: "chat".to_string(),
+            created_at: start,
+            updated_at: start,
+        };
+        let mut tx = db.begin().unwrap();
+        Database::create_ai_session_tx(&mut tx, &session).unwrap();
+        tx.commit().unwrap();
+
+        // a scan without text is left out of the parsed history
+        let scan = serde_json::to_string(&MessageContentDocument {
+            data_url: String::new(),
+            filename: Some("scan.pdf".to_string()),
+            text: None,
+            pages: Some(1),
+        })
+        .unwrap();
+        save_session_messages(
+            &mut db,
+            &[
+                session_message(&session.id, "user", "document", &scan, at(0)),
+                session_message(&session.id, "user", "text", "first question", at(1)),
+                session_message(&session.id, "assistant", "text", "first answer", at(2)),
+                session_message(&session.id, "user", "text", "second question", at(3)),
+                session_message(&session.id, "assistant", "text", "second answer", at(4)),
+            ],
+        );
+
+        let ai = AI::new(String::new()).unwrap();
+        let saved = db
+            .list_ai_session_messages_skip_sources(&session.id)
+            .unwrap();
+        let history = ai.parse_chat_history(ai.apply_chat_summary(saved)).unwrap();
+        assert_eq!(history.messages.len(), 4);
+
+        // the first question and answer are summarised
+        let summary = ChatSummary {
+            content: "summary".to_string(),
+            covered_messages: 2,
+        };
+        let summary = chat_summary_message(&session.id, summary, &history.created_at).unwrap();
+        assert_eq!(summary.created_at, at(2));
+        save_session_messages(&mut db, &[summary]);
+
+        let saved = db
+            .list_ai_session_messages_skip_sources(&session.id)
+            .unwrap();
+        let reloaded = ai.apply_chat_summary(saved);
+        let contents: Vec<_> = reloaded.iter().map(|msg| msg.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["summary", "second question", "second answer"]
+        );
+        let history = ai.parse_chat_history(reloaded).unwrap();
+        assert_eq!(history.created_at, vec![at(2), at(3), at(4)]);
+    }
+
+    #[test]
+    fn test_history_audio_with_transcript() {
+        let audio = MessageContentAudio {
+            data_url: "data:audio/mpeg;base64,SUQz".to_string(),
+            filename: Some("memo.mp3".to_string()),
+            transcript: Some("hello".to_string()),
+        };
+        let stored = serde_json::to_string(&audio).unwrap();
+        assert_eq!(
+            parse_history_audio(stored).unwrap(),
+            MessageContent::Audio(audio)
+        );
+
+        // older chats stored just the data url
+        assert!(matches!(
+            parse_history_audio("data:audio/mpeg;base64,SUQz".to_string()).unwrap(),
+            MessageContent::Audio(MessageContentAudio {
+                transcript: None,
+                ..
+            })
+        ));
     }
 }
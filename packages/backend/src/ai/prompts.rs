@@ -1,3 +1,5 @@
+use crate::store::models::Memory;
+
 pub fn should_narrow_search_prompt(current_time: &str) -> String {
     format!("You are a helpful assistant that is being used in a question answering pipeline during the search step.
 A user has some metadata about the context and a query, and you need to determine whether the query should lead to an embeddings search over the content of the context to narrow down the search space or not.
@@ -19,6 +21,19 @@ pub fn should_narrow_search_prompt_simple() -> String {
     "Evaluate if this query needs a search for specific information. Return only 'true' for specific questions (like 'what did X say about Y'), or 'false' for general analysis (like 'summarize this', 'what are the key points').".to_string()
 }
 
+pub fn chat_history_summary_prompt() -> String {
+    "You are a helpful assistant that compacts the history of a conversation between a user and an AI assistant.
+The conversation may also contain context messages with content from the user's documents, web pages, videos and other resources.
+
+Write a single concise summary of the conversation and the context so far that preserves:
+- the questions the user asked and the answers they were given
+- facts, numbers, names, dates and quotes from the context that were relevant to the conversation
+- the titles and source urls of the resources the information came from
+- any preferences or instructions the user gave
+
+Do not add any information that is not present in the conversation. Do not address the user, only return the summary.".to_string()
+}
+
 pub fn create_app_prompt(current_time: &str) -> String {
     format!("
 You are an AI that creates self-contained web applications called \"Surflets\" using only HTML code.
@@ -667,3 +682,18 @@ When it makes sense, combine approaches for queries with both specific terms and
 Note: Always ensure that your SQL queries only return resources where deleted = 0, unless the query explicitly includes deleted resources.
 "#.to_string()
 }
+
+// ids are only needed when the model can update or forget the memories
+pub fn memories_prompt(memories: &[Memory], with_ids: bool) -> Option<String> {
+    if memories.is_empty() {
+        return None;
+    }
+    let mut prompt = "Things you remember about the user from earlier conversations, follow their preferences and use the facts when they are relevant, but don't mention them otherwise:".to_string();
+    for memory in memories {
+        match with_ids {
+            true => prompt.push_str(&format!("\n- [{}] {}", memory.id, memory.content)),
+            false => prompt.push_str(&format!("\n- {}", memory.content)),
+        }
+    }
+    Some(prompt)
+}
@@ -38,6 +38,10 @@ pub fn extract_youtube_video_id(url: &str) -> Option<String> {
     None
 }
 
+pub fn is_youtube_video_url(url: &str) -> bool {
+    extract_youtube_video_id(url).is_some()
+}
+
 pub fn fetch_transcript(
     video_url: &str,
     preferred_lang: Option<&str>,
@@ -1,6 +1,10 @@
 use crate::{
-    ai::llm::{client::Model, models::Message},
+    ai::{
+        llm::{client::Model, models::Message},
+        InlineFile,
+    },
     api::message::*,
+    store::models::{CustomAgent, CustomTool},
     worker::tunnel::WorkerTunnel,
 };
 use neon::prelude::*;
@@ -16,9 +20,174 @@ pub fn register_exported_functions(cx: &mut ModuleContext) -> NeonResult<()> {
     cx.export_function("js__ai_get_docs_similarity", js_get_ai_docs_similarity)?;
     cx.export_function("js__ai_get_youtube_transcript", js_get_youtube_transcript)?;
     cx.export_function("js__ai_search_chat_resources", js_search_chat_resources)?;
+    cx.export_function("js__ai_cancel_request", js_cancel_request)?;
+    cx.export_function("js__ai_list_agent_runs", js_list_agent_runs)?;
+    cx.export_function("js__ai_get_agent_run", js_get_agent_run)?;
+    #[cfg(feature = "wip")]
+    cx.export_function("js__ai_resume_agent_run", js_resume_agent_run)?;
+    cx.export_function("js__ai_upsert_custom_agent", js_upsert_custom_agent)?;
+    cx.export_function("js__ai_delete_custom_agent", js_delete_custom_agent)?;
+    cx.export_function("js__ai_list_custom_agents", js_list_custom_agents)?;
+    cx.export_function("js__ai_upsert_custom_tool", js_upsert_custom_tool)?;
+    cx.export_function("js__ai_delete_custom_tool", js_delete_custom_tool)?;
+    cx.export_function("js__ai_list_custom_tools", js_list_custom_tools)?;
     Ok(())
 }
 
+fn js_cancel_request(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let request_id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let cancelled = tunnel.cancellation_registry.cancel(&request_id);
+    Ok(cx.boolean(cancelled))
+}
+
+fn js_list_agent_runs(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let note_id = cx.argument_opt(1).and_then(|arg| {
+        arg.downcast::<JsString, FunctionContext>(&mut cx)
+            .ok()
+            .map(|js_string| js_string.value(&mut cx))
+    });
+    let limit = cx.argument_opt(2).and_then(|arg| {
+        arg.downcast::<JsNumber, FunctionContext>(&mut cx)
+            .ok()
+            .map(|js_number| js_number.value(&mut cx) as i64)
+    });
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::AgentRunMessage(AgentRunMessage::ListAgentRuns(note_id, limit)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_get_agent_run(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let run_id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::AgentRunMessage(AgentRunMessage::GetAgentRun(run_id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+#[cfg(feature = "wip")]
+fn js_resume_agent_run(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct ResumeAgentRunOptions {
+        pub run_id: String,
+        pub custom_key: Option<String>,
+        pub request_id: Option<String>,
+    }
+
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let json_opt = cx.argument::<JsString>(1)?.value(&mut cx);
+    let mut opts: ResumeAgentRunOptions = match serde_json::from_str(&json_opt) {
+        Ok(opts) => opts,
+        Err(err) => return cx.throw_error(format!("failed to parse options: {err}")),
+    };
+    opts.custom_key = opts.custom_key.filter(|k| !k.is_empty());
+    let stream_callback = cx.argument::<JsFunction>(2)?.root(&mut cx);
+    let status_callback = cx.argument::<JsFunction>(3)?.root(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::AgentRunMessage(AgentRunMessage::ResumeAgentRun {
+            run_id: opts.run_id,
+            custom_key: opts.custom_key,
+            stream_callback,
+            status_callback,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
+        }),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_upsert_custom_agent(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let agent_json = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let agent: CustomAgent = match serde_json::from_str(&agent_json) {
+        Ok(agent) => agent,
+        Err(err) => return cx.throw_error(err.to_string()),
+    };
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::UpsertCustomAgent(agent)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_delete_custom_agent(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let agent_id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::DeleteCustomAgent(agent_id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_list_custom_agents(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::ListCustomAgents),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_upsert_custom_tool(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let tool_json = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let tool: CustomTool = match serde_json::from_str(&tool_json) {
+        Ok(tool) => tool,
+        Err(err) => return cx.throw_error(err.to_string()),
+    };
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::UpsertCustomTool(tool)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_delete_custom_tool(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let tool_id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::DeleteCustomTool(tool_id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_list_custom_tools(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::CustomAgentMessage(CustomAgentMessage::ListCustomTools),
+        deferred,
+    );
+    Ok(promise)
+}
+
 fn js_get_ai_chat_data_source(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
     let source_uid = cx.argument::<JsString>(1)?.value(&mut cx);
@@ -86,6 +255,7 @@ fn js_query_sffs_resources(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub sql_query: Option<String>,
         pub embedding_query: Option<String>,
         pub embedding_distance_threshold: Option<f32>,
+        pub request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -105,6 +275,7 @@ fn js_query_sffs_resources(mut cx: FunctionContext) -> JsResult<JsPromise> {
             opts.sql_query,
             opts.embedding_query,
             opts.embedding_distance_threshold,
+            tunnel.cancellation_registry.register(opts.request_id),
         )),
         deferred,
     );
@@ -119,6 +290,7 @@ fn js_create_app(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub model: Model,
         pub custom_key: Option<String>,
         pub inline_images: Option<Vec<String>>,
+        pub request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -142,6 +314,7 @@ fn js_create_app(mut cx: FunctionContext) -> JsResult<JsPromise> {
             model: opts.model,
             custom_key: opts.custom_key,
             inline_images: opts.inline_images,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
         }),
         deferred,
     );
@@ -155,6 +328,7 @@ fn js_create_chat_completion(mut cx: FunctionContext) -> JsResult<JsPromise> {
         model: Model,
         custom_key: Option<String>,
         response_format: Option<String>,
+        request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -172,6 +346,7 @@ fn js_create_chat_completion(mut cx: FunctionContext) -> JsResult<JsPromise> {
             model: opts.model,
             custom_key: opts.custom_key,
             response_format: opts.response_format,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
         }),
         deferred,
     );
@@ -192,6 +367,8 @@ fn js_send_note_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub custom_key: Option<String>,
         pub resource_ids: Option<Vec<String>>,
         pub inline_images: Option<Vec<String>>,
+        pub inline_documents: Option<Vec<InlineFile>>,
+        pub inline_audio: Option<Vec<InlineFile>>,
         #[serde(default = "default_limit")]
         pub limit: i32,
         #[serde(default)]
@@ -200,6 +377,7 @@ fn js_send_note_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub websearch: bool,
         #[serde(default)]
         pub surflet: bool,
+        pub request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -222,10 +400,13 @@ fn js_send_note_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
             custom_key: opts.custom_key,
             resource_ids: opts.resource_ids.unwrap_or_default(),
             inline_images: opts.inline_images,
+            inline_documents: opts.inline_documents,
+            inline_audio: opts.inline_audio,
             number_documents: opts.limit,
             general: opts.general,
             websearch: opts.websearch,
             surflet: opts.surflet,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
         }),
         deferred,
     );
@@ -246,6 +427,8 @@ fn js_send_chat_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub custom_key: Option<String>,
         pub resource_ids: Option<Vec<String>>,
         pub inline_images: Option<Vec<String>>,
+        pub inline_documents: Option<Vec<InlineFile>>,
+        pub inline_audio: Option<Vec<InlineFile>>,
         #[serde(default = "default_limit")]
         pub limit: i32,
         #[serde(default)]
@@ -254,6 +437,9 @@ fn js_send_chat_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
         pub general: bool,
         #[serde(default)]
         pub app_creation: bool,
+        #[serde(default)]
+        pub compact_history: bool,
+        pub request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -276,10 +462,14 @@ fn js_send_chat_message(mut cx: FunctionContext) -> JsResult<JsPromise> {
             custom_key: opts.custom_key,
             resource_ids: opts.resource_ids.unwrap_or_default(),
             inline_images: opts.inline_images,
+            inline_documents: opts.inline_documents,
+            inline_audio: opts.inline_audio,
             number_documents: opts.limit,
             search_only: opts.rag_only,
             general: opts.general,
             app_creation: opts.app_creation,
+            compact_history: opts.compact_history,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
         }),
         deferred,
     );
@@ -300,6 +490,7 @@ fn js_search_chat_resources(mut cx: FunctionContext) -> JsResult<JsPromise> {
         #[serde(default = "default_limit")]
         pub number_documents: i32,
         pub resource_ids: Option<Vec<String>>,
+        pub request_id: Option<String>,
     }
 
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
@@ -318,6 +509,7 @@ fn js_search_chat_resources(mut cx: FunctionContext) -> JsResult<JsPromise> {
             custom_key: opts.custom_key,
             number_documents: opts.number_documents,
             resource_ids: opts.resource_ids,
+            cancellation_token: tunnel.cancellation_registry.register(opts.request_id),
         }),
         deferred,
     );
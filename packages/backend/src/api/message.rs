@@ -1,5 +1,11 @@
 use crate::{
-    ai::llm::{client::Model, models::Message},
+    ai::{
+        llm::{
+            client::{CancellationToken, Model},
+            models::Message,
+        },
+        InlineFile,
+    },
     store::models::*,
     BackendResult,
 };
@@ -35,6 +41,13 @@ pub enum WorkerMessage {
     SpaceMessage(SpaceMessage),
     AppMessage(AppMessage),
     KVStoreMessage(KVStoreMessage),
+    ImportMessage(ImportMessage),
+    ArchiveMessage(ArchiveMessage),
+    BackupMessage(BackupMessage),
+    VaultMessage(VaultMessage),
+    WatchFolderMessage(WatchFolderMessage),
+    AgentRunMessage(AgentRunMessage),
+    CustomAgentMessage(CustomAgentMessage),
 }
 
 #[derive(Debug)]
@@ -47,8 +60,19 @@ pub enum HistoryMessage {
     SearchHistoryEntriesByHostnamePrefix(String, Option<f64>),
     SearchHistoryEntriesByHostname(String),
     SearchHistoryEntriesByUrlAndTitle(String, Option<f64>),
-    ImportBrowserHistory(String),
-    ImportBrowserBookmarks(String),
+    // top domains, urls and searches and the activity over time
+    GetHistoryStats(crate::store::models::HistoryStatsQuery),
+    ListBrowserProfiles(String),
+    // browser type and profile id, `None` for the default profile
+    ImportBrowserHistory(String, Option<String>),
+    ImportBrowserBookmarks(String, Option<String>),
+    // one space per bookmark folder, nested folders as sub-spaces
+    ImportBrowserBookmarksAsSpaces(String, Option<String>),
+    // path of a Netscape bookmark file, i.e. a bookmarks.html export
+    ImportBookmarksHtml(String),
+    ImportBookmarksHtmlAsSpaces(String),
+    // space ids, all top level spaces if empty, and the path to write to
+    ExportBookmarksHtml(Vec<String>, String),
     RemoveAllHistoryEntries,
 }
 
@@ -160,6 +184,7 @@ pub enum MiscMessage {
         model: Model,
         custom_key: Option<String>,
         response_format: Option<String>,
+        cancellation_token: CancellationToken,
     },
     ChatQuery {
         callback: Root<JsFunction>,
@@ -171,8 +196,12 @@ pub enum MiscMessage {
         search_only: bool,
         resource_ids: Vec<String>,
         inline_images: Option<Vec<String>>,
+        inline_documents: Option<Vec<InlineFile>>,
+        inline_audio: Option<Vec<InlineFile>>,
         general: bool,
         app_creation: bool,
+        compact_history: bool,
+        cancellation_token: CancellationToken,
     },
     NoteQuery {
         callback: Root<JsFunction>,
@@ -183,9 +212,12 @@ pub enum MiscMessage {
         note_resource_id: String,
         resource_ids: Vec<String>,
         inline_images: Option<Vec<String>>,
+        inline_documents: Option<Vec<InlineFile>>,
+        inline_audio: Option<Vec<InlineFile>>,
         general: bool,
         websearch: bool,
         surflet: bool,
+        cancellation_token: CancellationToken,
     },
     CreateAppQuery {
         chunk_callback: Root<JsFunction>,
@@ -194,6 +226,7 @@ pub enum MiscMessage {
         model: Model,
         custom_key: Option<String>,
         inline_images: Option<Vec<String>>,
+        cancellation_token: CancellationToken,
     },
     Print(String),
     CreateAIChatMessage(String, String),
@@ -202,6 +235,8 @@ pub enum MiscMessage {
     SearchAIChats(String, Option<i64>),
     GetAIChatMessage(String),
     DeleteAIChatMessage(String),
+    ListMemories,
+    DeleteMemory(String),
     QuerySFFSResources(
         String,
         Model,
@@ -209,6 +244,7 @@ pub enum MiscMessage {
         Option<String>,
         Option<String>,
         Option<f32>,
+        CancellationToken,
     ),
     GetAIChatDataSource(String),
     GetAIDocsSimilarity {
@@ -226,6 +262,7 @@ pub enum MiscMessage {
         custom_key: Option<String>,
         number_documents: i32,
         resource_ids: Option<Vec<String>>,
+        cancellation_token: CancellationToken,
     },
 }
 
@@ -252,6 +289,76 @@ pub enum AppMessage {
     UpdateAppContentMessage(String, String),
 }
 
+#[derive(Debug)]
+pub enum ImportMessage {
+    // service name, e.g. `pocket`, and the path of its export file
+    ImportServiceExport(String, String),
+}
+
+#[derive(Debug)]
+pub enum ArchiveMessage {
+    // path of the archive to write
+    ExportLibrary(String),
+    // path of the archive and the conflict strategy, `skip` or `duplicate`
+    ImportLibrary(String, Option<String>),
+}
+
+#[derive(Debug)]
+pub enum BackupMessage {
+    CreateBackup,
+    // sent periodically, only backs up when a backup is due
+    RunScheduledBackup,
+    ListBackups,
+    RestoreBackup(String),
+    GetBackupPolicy,
+    SetBackupPolicy(crate::store::backup::BackupPolicy),
+}
+
+#[derive(Debug)]
+pub enum VaultMessage {
+    // space id and the folder to write the vault into
+    ExportSpaceAsVault(String, String),
+    // path of the vault folder
+    ImportVault(String),
+}
+
+#[derive(Debug)]
+pub enum WatchFolderMessage {
+    UpsertWatchFolder(crate::store::models::WatchFolder),
+    RemoveWatchFolder(String),
+    ListWatchFolders,
+    // sent periodically, adds new and changed files of every enabled folder
+    ScanWatchFolders,
+}
+
+#[derive(Debug)]
+pub enum AgentRunMessage {
+    // note id to only list the runs of one note and the limit
+    ListAgentRuns(Option<String>, Option<i64>),
+    // the run with its iterations, tool calls and events
+    GetAgentRun(String),
+    // continues a cancelled, failed or crashed run of the lead agent
+    #[cfg(feature = "wip")]
+    ResumeAgentRun {
+        run_id: String,
+        custom_key: Option<String>,
+        stream_callback: Root<JsFunction>,
+        status_callback: Root<JsFunction>,
+        cancellation_token: CancellationToken,
+    },
+}
+
+// user defined agents and tools, picked up by the lead agent on its next start
+#[derive(Debug)]
+pub enum CustomAgentMessage {
+    UpsertCustomAgent(crate::store::models::CustomAgent),
+    DeleteCustomAgent(String),
+    ListCustomAgents,
+    UpsertCustomTool(crate::store::models::CustomTool),
+    DeleteCustomTool(String),
+    ListCustomTools,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub enum KVStoreMessage {
     CreateTable(String),
@@ -70,6 +70,8 @@ pub fn register_exported_functions(cx: &mut ModuleContext) -> NeonResult<()> {
         "js__store_search_history_entries_by_url_and_title",
         js_search_history_entries_by_url_and_title,
     )?;
+    cx.export_function("js__store_list_browser_profiles", js_list_browser_profiles)?;
+    cx.export_function("js__store_get_history_stats", js_get_history_stats)?;
     cx.export_function(
         "js__store_import_browser_history",
         js_import_browser_history,
@@ -78,6 +80,30 @@ pub fn register_exported_functions(cx: &mut ModuleContext) -> NeonResult<()> {
         "js__store_import_browser_bookmarks",
         js_import_browser_bookmarks,
     )?;
+    cx.export_function(
+        "js__store_import_browser_bookmarks_as_spaces",
+        js_import_browser_bookmarks_as_spaces,
+    )?;
+    cx.export_function("js__store_import_bookmarks_html", js_import_bookmarks_html)?;
+    cx.export_function(
+        "js__store_import_bookmarks_html_as_spaces",
+        js_import_bookmarks_html_as_spaces,
+    )?;
+    cx.export_function("js__store_export_bookmarks_html", js_export_bookmarks_html)?;
+    cx.export_function("js__store_import_service_export", js_import_service_export)?;
+    cx.export_function("js__store_export_library", js_export_library)?;
+    cx.export_function("js__store_import_library", js_import_library)?;
+    cx.export_function("js__store_create_backup", js_create_backup)?;
+    cx.export_function("js__store_list_backups", js_list_backups)?;
+    cx.export_function("js__store_restore_backup", js_restore_backup)?;
+    cx.export_function("js__store_get_backup_policy", js_get_backup_policy)?;
+    cx.export_function("js__store_set_backup_policy", js_set_backup_policy)?;
+    cx.export_function("js__store_export_space_as_vault", js_export_space_as_vault)?;
+    cx.export_function("js__store_import_vault", js_import_vault)?;
+    cx.export_function("js__store_upsert_watch_folder", js_upsert_watch_folder)?;
+    cx.export_function("js__store_remove_watch_folder", js_remove_watch_folder)?;
+    cx.export_function("js__store_list_watch_folders", js_list_watch_folders)?;
+    cx.export_function("js__store_scan_watch_folders", js_scan_watch_folders)?;
 
     cx.export_function("js__store_create_ai_chat", js_create_ai_chat)?;
     cx.export_function("js__store_update_ai_chat", js_update_ai_chat)?;
@@ -85,6 +111,8 @@ pub fn register_exported_functions(cx: &mut ModuleContext) -> NeonResult<()> {
     cx.export_function("js__store_search_ai_chats", js_search_ai_chats)?;
     cx.export_function("js__store_get_ai_chat", js_get_ai_chat)?;
     cx.export_function("js__store_remove_ai_chat", js_remove_ai_chat)?;
+    cx.export_function("js__store_list_memories", js_list_memories)?;
+    cx.export_function("js__store_remove_memory", js_remove_memory)?;
 
     cx.export_function("js__store_create_space", js_create_space)?;
     cx.export_function("js__store_get_space", js_get_space)?;
@@ -730,6 +758,23 @@ fn js_search_history_entries_by_hostname_prefix(mut cx: FunctionContext) -> JsRe
     Ok(promise)
 }
 
+fn js_get_history_stats(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let query_json = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let query = match serde_json::from_str(&query_json) {
+        Ok(query) => query,
+        Err(err) => return cx.throw_error(err.to_string()),
+    };
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::GetHistoryStats(query)),
+        deferred,
+    );
+    Ok(promise)
+}
+
 fn js_search_history_entries_by_hostname(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
     let url = cx.argument::<JsString>(1)?.value(&mut cx);
@@ -763,13 +808,33 @@ fn js_search_history_entries_by_url_and_title(mut cx: FunctionContext) -> JsResu
     Ok(promise)
 }
 
+fn js_list_browser_profiles(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let browser_type = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ListBrowserProfiles(browser_type)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
 fn js_import_browser_history(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
     let browser_type = cx.argument::<JsString>(1)?.value(&mut cx);
+    let profile_id = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString, FunctionContext>(&mut cx).ok())
+        .map(|js_string| js_string.value(&mut cx));
 
     let (deferred, promise) = cx.promise();
     tunnel.worker_send_js(
-        WorkerMessage::HistoryMessage(HistoryMessage::ImportBrowserHistory(browser_type)),
+        WorkerMessage::HistoryMessage(HistoryMessage::ImportBrowserHistory(
+            browser_type,
+            profile_id,
+        )),
         deferred,
     );
 
@@ -779,13 +844,264 @@ fn js_import_browser_history(mut cx: FunctionContext) -> JsResult<JsPromise> {
 fn js_import_browser_bookmarks(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
     let browser_type = cx.argument::<JsString>(1)?.value(&mut cx);
+    let profile_id = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString, FunctionContext>(&mut cx).ok())
+        .map(|js_string| js_string.value(&mut cx));
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ImportBrowserBookmarks(
+            browser_type,
+            profile_id,
+        )),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_import_browser_bookmarks_as_spaces(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let browser_type = cx.argument::<JsString>(1)?.value(&mut cx);
+    let profile_id = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString, FunctionContext>(&mut cx).ok())
+        .map(|js_string| js_string.value(&mut cx));
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ImportBrowserBookmarksAsSpaces(
+            browser_type,
+            profile_id,
+        )),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_import_bookmarks_html(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let path = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ImportBookmarksHtml(path)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_import_bookmarks_html_as_spaces(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let path = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ImportBookmarksHtmlAsSpaces(path)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_export_bookmarks_html(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let space_ids = cx.argument::<JsArray>(1)?.to_vec(&mut cx)?;
+    let space_ids = space_ids
+        .iter()
+        .map(|value| {
+            Ok(value
+                .downcast_or_throw::<JsString, FunctionContext>(&mut cx)?
+                .value(&mut cx))
+        })
+        .collect::<NeonResult<Vec<String>>>()?;
+    let path = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::HistoryMessage(HistoryMessage::ExportBookmarksHtml(space_ids, path)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_import_service_export(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let service = cx.argument::<JsString>(1)?.value(&mut cx);
+    let path = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::ImportMessage(ImportMessage::ImportServiceExport(service, path)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_export_library(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let path = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::ArchiveMessage(ArchiveMessage::ExportLibrary(path)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_import_library(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let path = cx.argument::<JsString>(1)?.value(&mut cx);
+    let strategy = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString, FunctionContext>(&mut cx).ok())
+        .map(|js_string| js_string.value(&mut cx));
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::ArchiveMessage(ArchiveMessage::ImportLibrary(path, strategy)),
+        deferred,
+    );
+
+    Ok(promise)
+}
+
+fn js_create_backup(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::BackupMessage(BackupMessage::CreateBackup),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_list_backups(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::BackupMessage(BackupMessage::ListBackups),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_restore_backup(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let backup_id = cx.argument::<JsString>(1)?.value(&mut cx);
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::BackupMessage(BackupMessage::RestoreBackup(backup_id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_get_backup_policy(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::BackupMessage(BackupMessage::GetBackupPolicy),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_set_backup_policy(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let policy_json = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let policy = match serde_json::from_str(&policy_json) {
+        Ok(policy) => policy,
+        Err(err) => return cx.throw_error(err.to_string()),
+    };
 
     let (deferred, promise) = cx.promise();
     tunnel.worker_send_js(
-        WorkerMessage::HistoryMessage(HistoryMessage::ImportBrowserBookmarks(browser_type)),
+        WorkerMessage::BackupMessage(BackupMessage::SetBackupPolicy(policy)),
         deferred,
     );
+    Ok(promise)
+}
+
+fn js_export_space_as_vault(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let space_id = cx.argument::<JsString>(1)?.value(&mut cx);
+    let path = cx.argument::<JsString>(2)?.value(&mut cx);
 
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::VaultMessage(VaultMessage::ExportSpaceAsVault(space_id, path)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_import_vault(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let path = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::VaultMessage(VaultMessage::ImportVault(path)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_upsert_watch_folder(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let folder_json = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let folder: models::WatchFolder = match serde_json::from_str(&folder_json) {
+        Ok(folder) => folder,
+        Err(err) => return cx.throw_error(err.to_string()),
+    };
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::WatchFolderMessage(WatchFolderMessage::UpsertWatchFolder(folder)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_remove_watch_folder(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let folder_id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::WatchFolderMessage(WatchFolderMessage::RemoveWatchFolder(folder_id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_list_watch_folders(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::WatchFolderMessage(WatchFolderMessage::ListWatchFolders),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_scan_watch_folders(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::WatchFolderMessage(WatchFolderMessage::ScanWatchFolders),
+        deferred,
+    );
     Ok(promise)
 }
 
@@ -972,6 +1288,29 @@ fn js_remove_ai_chat(mut cx: FunctionContext) -> JsResult<JsPromise> {
     Ok(promise)
 }
 
+fn js_list_memories(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::MiscMessage(MiscMessage::ListMemories),
+        deferred,
+    );
+    Ok(promise)
+}
+
+fn js_remove_memory(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
+    let id = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let (deferred, promise) = cx.promise();
+    tunnel.worker_send_js(
+        WorkerMessage::MiscMessage(MiscMessage::DeleteMemory(id)),
+        deferred,
+    );
+    Ok(promise)
+}
+
 fn js_get_ai_chat(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let tunnel = cx.argument::<JsBox<WorkerTunnel>>(0)?;
     let session_id = cx.argument::<JsString>(1)?.value(&mut cx);
@@ -18,6 +18,8 @@ pub enum BackendError {
     ReqwestError(#[from] reqwest::Error),
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Zip error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
     #[error("Key-value store error: {0}")]
     KeyValueStoreError(#[from] crate::store::kv::KeyValueStoreError),
     #[error("LLM Error: {r#type}: {message}")]
@@ -30,6 +32,10 @@ pub enum BackendError {
     LLMClientErrorTooManyRequests,
     #[error("LLM Unauthorized error")]
     LLMClientErrorUnauthorized,
+    #[error("LLM Request Cancelled error")]
+    LLMClientErrorCancelled,
+    #[error("LLM Request Deadline Exceeded error")]
+    LLMClientErrorDeadlineExceeded,
     // TODO: fix this monstrosity
     #[error("LLM Quota Depleted error: {quotas}")]
     LLMClientErrorQuotasDepleted { quotas: serde_json::Value },
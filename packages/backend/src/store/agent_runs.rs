@@ -0,0 +1,348 @@
+use super::models::*;
+use crate::{store::db::Database, BackendResult};
+
+impl Database {
+    pub fn create_agent_run(&mut self, run: &AgentRun) -> BackendResult<()> {
+        self.conn.execute(
+            "INSERT INTO agent_runs (id, note_id, model, user_message, status, final_answer, error, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![
+                run.id,
+                run.note_id,
+                run.model,
+                run.user_message,
+                run.status,
+                run.final_answer,
+                run.error,
+                run.created_at,
+                run.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_agent_run_status(
+        &mut self,
+        id: &str,
+        status: &AgentRunStatus,
+        final_answer: Option<&str>,
+        error: Option<&str>,
+    ) -> BackendResult<()> {
+        self.conn.execute(
+            "UPDATE agent_runs SET status = ?2, final_answer = ?3, error = ?4, updated_at = ?5 WHERE id = ?1",
+            rusqlite::params![id, status, final_answer, error, current_time()],
+        )?;
+        Ok(())
+    }
+
+    // the iteration and its tool calls are written together
+    // so a recorded iteration is always complete
+    pub fn create_agent_run_iteration(
+        &mut self,
+        iteration: &AgentRunIteration,
+        tool_calls: &[AgentRunToolCall],
+    ) -> BackendResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO agent_run_iterations (run_id, iteration, outcome, response, started_at, finished_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                iteration.run_id,
+                iteration.iteration,
+                iteration.outcome,
+                iteration.response,
+                iteration.started_at,
+                iteration.finished_at
+            ],
+        )?;
+        for tool_call in tool_calls {
+            tx.execute(
+                "INSERT OR REPLACE INTO agent_run_tool_calls (run_id, iteration, position, tool_name, arguments, result, started_at, finished_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![
+                    tool_call.run_id,
+                    tool_call.iteration,
+                    tool_call.position,
+                    tool_call.tool_name,
+                    tool_call.arguments,
+                    tool_call.result,
+                    tool_call.started_at,
+                    tool_call.finished_at
+                ],
+            )?;
+        }
+        tx.execute(
+            "UPDATE agent_runs SET updated_at = ?2 WHERE id = ?1",
+            rusqlite::params![iteration.run_id, current_time()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn create_agent_run_event(&mut self, event: &AgentRunEvent) -> BackendResult<()> {
+        self.conn.execute(
+            "INSERT INTO agent_run_events (run_id, iteration, event_type, payload, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                event.run_id,
+                event.iteration,
+                event.event_type,
+                event.payload,
+                event.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    // drops everything recorded from `iteration` on, used before resuming a run
+    // so the redone iterations don't leave duplicates behind
+    pub fn truncate_agent_run(&mut self, run_id: &str, iteration: i64) -> BackendResult<()> {
+        let tx = self.conn.transaction()?;
+        for table in [
+            "agent_run_iterations",
+            "agent_run_tool_calls",
+            "agent_run_events",
+        ] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE run_id = ?1 AND iteration >= ?2",
+                    table
+                ),
+                rusqlite::params![run_id, iteration],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_agent_run(&mut self, id: &str) -> BackendResult<()> {
+        self.truncate_agent_run(id, 0)?;
+        self.conn.execute(
+            "DELETE FROM agent_runs WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+        Ok(())
+    }
+
+    fn agent_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentRun> {
+        Ok(AgentRun {
+            id: row.get(0)?,
+            note_id: row.get(1)?,
+            model: row.get(2)?,
+            user_message: row.get(3)?,
+            status: row.get(4)?,
+            final_answer: row.get(5)?,
+            error: row.get(6)?,
+            created_at: row.get(7)?,
+            updated_at: row.get(8)?,
+        })
+    }
+
+    pub fn get_agent_run(&self, id: &str) -> BackendResult<Option<AgentRun>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, note_id, model, user_message, status, final_answer, error, created_at, updated_at
+            FROM agent_runs WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(rusqlite::params![id], Self::agent_run_from_row)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    // most recent first, optionally only the runs of one note
+    pub fn list_agent_runs(
+        &self,
+        note_id: Option<&str>,
+        limit: Option<i64>,
+    ) -> BackendResult<Vec<AgentRun>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, note_id, model, user_message, status, final_answer, error, created_at, updated_at
+            FROM agent_runs
+            WHERE ?1 IS NULL OR note_id = ?1
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?2",
+        )?;
+        let runs = stmt.query_map(
+            rusqlite::params![note_id, limit.unwrap_or(-1)],
+            Self::agent_run_from_row,
+        )?;
+        let mut result = Vec::new();
+        for run in runs {
+            result.push(run?);
+        }
+        Ok(result)
+    }
+
+    pub fn get_agent_run_transcript(&self, id: &str) -> BackendResult<Option<AgentRunTranscript>> {
+        let run = match self.get_agent_run(id)? {
+            Some(run) => run,
+            None => return Ok(None),
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT run_id, iteration, outcome, response, started_at, finished_at
+            FROM agent_run_iterations WHERE run_id = ?1 ORDER BY iteration ASC",
+        )?;
+        let iterations = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok(AgentRunIteration {
+                    run_id: row.get(0)?,
+                    iteration: row.get(1)?,
+                    outcome: row.get(2)?,
+                    response: row.get(3)?,
+                    started_at: row.get(4)?,
+                    finished_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT run_id, iteration, position, tool_name, arguments, result, started_at, finished_at
+            FROM agent_run_tool_calls WHERE run_id = ?1 ORDER BY iteration ASC, position ASC",
+        )?;
+        let tool_calls = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok(AgentRunToolCall {
+                    run_id: row.get(0)?,
+                    iteration: row.get(1)?,
+                    position: row.get(2)?,
+                    tool_name: row.get(3)?,
+                    arguments: row.get(4)?,
+                    result: row.get(5)?,
+                    started_at: row.get(6)?,
+                    finished_at: row.get(7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT run_id, iteration, event_type, payload, created_at
+            FROM agent_run_events WHERE run_id = ?1 ORDER BY id ASC",
+        )?;
+        let events = stmt
+            .query_map(rusqlite::params![id], |row| {
+                Ok(AgentRunEvent {
+                    run_id: row.get(0)?,
+                    iteration: row.get(1)?,
+                    event_type: row.get(2)?,
+                    payload: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(AgentRunTranscript {
+            run,
+            iterations,
+            tool_calls,
+            events,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use tempfile::tempdir;
+
+    fn setup_test_db() -> (Database, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = Database::new(&db_path.to_string_lossy(), true).unwrap();
+        (db, dir)
+    }
+
+    fn create_test_run(db: &mut Database, id: &str, note_id: &str) {
+        let now = current_time();
+        db.create_agent_run(&AgentRun {
+            id: id.to_string(),
+            note_id: note_id.to_string(),
+            model: "\"gpt-4o\"".to_string(),
+            user_message: "what did I save about rust?".to_string(),
+            status: AgentRunStatus::Running,
+            final_answer: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        })
+        .unwrap();
+    }
+
+    fn record_iteration(db: &mut Database, run_id: &str, iteration: i64) {
+        let now = current_time();
+        db.create_agent_run_iteration(
+            &AgentRunIteration {
+                run_id: run_id.to_string(),
+                iteration,
+                outcome: AgentRunIterationOutcome::ToolCalls,
+                response: "<tool_calls></tool_calls>".to_string(),
+                started_at: now,
+                finished_at: now,
+            },
+            &[AgentRunToolCall {
+                run_id: run_id.to_string(),
+                iteration,
+                position: 0,
+                tool_name: "library_search".to_string(),
+                arguments: r#"{"query": "rust"}"#.to_string(),
+                result: "Success".to_string(),
+                started_at: now,
+                finished_at: now,
+            }],
+        )
+        .unwrap();
+        db.create_agent_run_event(&AgentRunEvent {
+            run_id: run_id.to_string(),
+            iteration,
+            event_type: AgentRunEventType::Status,
+            payload: r#"{"type":"status","value":"Searching"}"#.to_string(),
+            created_at: now,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_agent_run_transcript() {
+        let (mut db, _dir) = setup_test_db();
+        create_test_run(&mut db, "run1", "note1");
+        record_iteration(&mut db, "run1", 0);
+        record_iteration(&mut db, "run1", 1);
+        db.update_agent_run_status("run1", &AgentRunStatus::Completed, Some("answer"), None)
+            .unwrap();
+
+        let transcript = db.get_agent_run_transcript("run1").unwrap().unwrap();
+        assert_eq!(transcript.run.status, AgentRunStatus::Completed);
+        assert_eq!(transcript.run.final_answer.as_deref(), Some("answer"));
+        assert_eq!(transcript.iterations.len(), 2);
+        assert_eq!(transcript.tool_calls.len(), 2);
+        assert_eq!(transcript.tool_calls[1].iteration, 1);
+        assert_eq!(transcript.events.len(), 2);
+
+        db.truncate_agent_run("run1", 1).unwrap();
+        let transcript = db.get_agent_run_transcript("run1").unwrap().unwrap();
+        assert_eq!(transcript.iterations.len(), 1);
+        assert_eq!(transcript.tool_calls.len(), 1);
+        assert_eq!(transcript.events.len(), 1);
+
+        assert!(db.get_agent_run_transcript("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_list_agent_runs() {
+        let (mut db, _dir) = setup_test_db();
+        create_test_run(&mut db, "run1", "note1");
+        create_test_run(&mut db, "run2", "note2");
+        create_test_run(&mut db, "run3", "note1");
+
+        assert_eq!(db.list_agent_runs(None, None).unwrap().len(), 3);
+        assert_eq!(db.list_agent_runs(None, Some(2)).unwrap().len(), 2);
+        let ids: Vec<String> = db
+            .list_agent_runs(Some("note1"), None)
+            .unwrap()
+            .into_iter()
+            .map(|run| run.id)
+            .collect();
+        assert_eq!(ids, vec!["run3", "run1"]);
+
+        db.delete_agent_run("run3").unwrap();
+        assert_eq!(db.list_agent_runs(Some("note1"), None).unwrap().len(), 1);
+    }
+}
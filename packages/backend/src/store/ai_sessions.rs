@@ -210,6 +210,39 @@ impl Database {
         Ok(result)
     }
 
+    pub fn list_ai_session_messages(
+        &self,
+        session_id: &str,
+    ) -> BackendResult<Vec<AIChatSessionMessage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ai_session_id, role, content, truncatable, is_context, msg_type, sources, created_at
+            FROM ai_session_messages
+            WHERE ai_session_id = ?1
+            ORDER BY created_at ASC",
+        )?;
+        let messages = stmt.query_map(rusqlite::params![session_id], |row| {
+            let sources_raw: String = row.get(6)?;
+            let parsed_sources: Option<Vec<AIChatSessionMessageSource>> =
+                serde_json::from_str(&sources_raw).ok();
+
+            Ok(AIChatSessionMessage {
+                ai_session_id: row.get(0)?,
+                role: row.get(1)?,
+                content: row.get(2)?,
+                truncatable: row.get(3)?,
+                is_context: row.get(4)?,
+                msg_type: row.get(5)?,
+                sources: parsed_sources,
+                created_at: row.get(7)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for message in messages {
+            result.push(message?);
+        }
+        Ok(result)
+    }
+
     pub fn list_non_context_ai_session_messages(
         &self,
         session_id: &str,
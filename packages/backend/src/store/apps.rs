@@ -19,6 +19,24 @@ impl Database {
         Ok(())
     }
 
+    pub fn create_app_tx(tx: &mut rusqlite::Transaction, app: &App) -> BackendResult<()> {
+        tx.execute(
+            "INSERT INTO apps (id, app_type, content, created_at, updated_at, name, icon, meta) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                app.id,
+                app.app_type,
+                app.content,
+                app.created_at,
+                app.updated_at,
+                app.name,
+                app.icon,
+                app.meta
+            ],
+        )?;
+        Ok(())
+    }
+
     pub fn delete_app(&self, app_id: &str) -> BackendResult<()> {
         self.conn
             .execute("DELETE FROM apps WHERE id = ?1", rusqlite::params![app_id])?;
@@ -0,0 +1,623 @@
+use chrono::{DateTime, Datelike, Duration, Utc};
+use rusqlite::{backup, Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration as StdDuration, UNIX_EPOCH};
+
+use super::{db::Database, kv::KeyValueStore, migrations::migrate, models::current_time};
+use crate::{BackendError, BackendResult};
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const POLICY_FILE: &str = "policy.json";
+const DB_FILE: &str = "surf.sqlite";
+const KV_DB_FILE: &str = "kv.sqlite";
+const RESOURCES_DIR: &str = "resources";
+const PARTIAL_SUFFIX: &str = ".partial";
+const BACKUPS_DIR: &str = "backups";
+
+// snapshots taken before migrations and restores don't count towards the schedule
+const KEEP_SAFETY_SNAPSHOTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum SnapshotReason {
+    Scheduled,
+    Manual,
+    PreMigration,
+    PreRestore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    // seconds since the unix epoch
+    pub modified: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub reason: SnapshotReason,
+    pub created_at: DateTime<Utc>,
+    pub has_kv: bool,
+    // the resource files when they were backed up, snapshots taken before
+    // migrations only hold the main database
+    pub resources: Option<BTreeMap<String, FileStamp>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BackupPolicy {
+    pub enabled: bool,
+    // the newest snapshot of each of the last days and weeks with snapshots is kept
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            enabled: true,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+// copies the database through the sqlite backup api, so it can be written to while
+// the snapshot is taken, the snapshot is taken in a single step and is consistent
+pub fn backup_db(source_conn: &Connection, backup_db_path: &str) -> BackendResult<()> {
+    let mut backup_conn = Connection::open(backup_db_path)?;
+    let bk = backup::Backup::new(source_conn, &mut backup_conn)?;
+    bk.step(-1)?;
+    Ok(())
+}
+
+pub fn restore_db(target_conn: &mut Connection, backup_db_path: &str) -> BackendResult<()> {
+    target_conn.restore(
+        DatabaseName::Main,
+        backup_db_path,
+        None::<fn(backup::Progress)>,
+    )?;
+    Ok(())
+}
+
+fn path_string(path: &Path) -> String {
+    path.as_os_str().to_string_lossy().to_string()
+}
+
+// the backups are kept next to the database they are taken of
+pub fn backups_path(db_path: &str) -> String {
+    path_string(&Path::new(db_path).with_file_name(BACKUPS_DIR))
+}
+
+fn file_stamp(metadata: &fs::Metadata) -> FileStamp {
+    FileStamp {
+        size: metadata.len(),
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+    }
+}
+
+// the files directly in the directory with their stamps, a missing directory has none
+fn list_files(dir: &Path) -> BackendResult<BTreeMap<String, FileStamp>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = BTreeMap::new();
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.insert(
+                entry.file_name().to_string_lossy().to_string(),
+                file_stamp(&metadata),
+            );
+        }
+    }
+    Ok(files)
+}
+
+// copies the resource files, files that are unchanged in one of the previous snapshots
+// are hard linked to it instead, snapshots are never written to so they can share files
+fn copy_resources(
+    resources_path: &Path,
+    target: &Path,
+    previous: &[(PathBuf, &BTreeMap<String, FileStamp>)],
+) -> BackendResult<BTreeMap<String, FileStamp>> {
+    fs::create_dir_all(target)?;
+    let files = list_files(resources_path)?;
+    for (name, stamp) in &files {
+        let linked = previous.iter().any(|(previous_dir, previous_files)| {
+            previous_files.get(name) == Some(stamp)
+                && fs::hard_link(previous_dir.join(name), target.join(name)).is_ok()
+        });
+        if !linked {
+            fs::copy(resources_path.join(name), target.join(name))?;
+        }
+    }
+    Ok(files)
+}
+
+fn same_contents(a: &Path, b: &Path) -> BackendResult<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut a = BufReader::new(fs::File::open(a)?);
+    let mut b = BufReader::new(fs::File::open(b)?);
+    let mut a_chunk = [0; 8192];
+    let mut b_chunk = [0; 8192];
+    loop {
+        let read = a.read(&mut a_chunk)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut b_chunk[..read])?;
+        if a_chunk[..read] != b_chunk[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+// copies back the files whose contents differ from the snapshot and removes the ones
+// that aren't in it, restored files keep the modification time they had in the snapshot
+// so the next snapshot can link them to it
+fn restore_resources(
+    snapshot_dir: &Path,
+    files: &BTreeMap<String, FileStamp>,
+    resources_path: &Path,
+) -> BackendResult<()> {
+    fs::create_dir_all(resources_path)?;
+    let current = list_files(resources_path)?;
+    for (name, stamp) in files {
+        let source = snapshot_dir.join(name);
+        let target = resources_path.join(name);
+        if current.contains_key(name) && same_contents(&source, &target)? {
+            continue;
+        }
+        fs::copy(&source, &target)?;
+        fs::File::options()
+            .write(true)
+            .open(&target)?
+            .set_modified(UNIX_EPOCH + StdDuration::from_secs(stamp.modified))?;
+    }
+    for name in current.keys().filter(|name| !files.contains_key(*name)) {
+        fs::remove_file(resources_path.join(name))?;
+    }
+    Ok(())
+}
+
+// the snapshots that completed, newest first
+pub fn list_snapshots(backups_path: &str) -> BackendResult<Vec<Snapshot>> {
+    let entries = match fs::read_dir(backups_path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(PARTIAL_SUFFIX)
+        {
+            continue;
+        }
+        let path = entry.path().join(SNAPSHOT_FILE);
+        // the snapshot file is written last, a directory without one is incomplete
+        if let Ok(data) = fs::read(&path) {
+            match serde_json::from_slice::<Snapshot>(&data) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(e) => tracing::warn!("skipping unreadable snapshot {:?}: {}", path, e),
+            }
+        }
+    }
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+    Ok(snapshots)
+}
+
+fn find_snapshot(backups_path: &str, id: &str) -> BackendResult<Snapshot> {
+    list_snapshots(backups_path)?
+        .into_iter()
+        .find(|snapshot| snapshot.id == id)
+        .ok_or_else(|| BackendError::GenericError(format!("Backup not found: {}", id)))
+}
+
+// writes the snapshot into a partial directory that is only renamed once it's complete
+fn write_snapshot(
+    backups_path: &str,
+    reason: SnapshotReason,
+    write: impl FnOnce(&Path, &mut Snapshot) -> BackendResult<()>,
+) -> BackendResult<Snapshot> {
+    let created_at = current_time();
+    let mut snapshot = Snapshot {
+        id: format!(
+            "{}-{}",
+            created_at.format("%Y%m%dT%H%M%S%3fZ"),
+            reason.as_ref()
+        ),
+        reason,
+        created_at,
+        has_kv: false,
+        resources: None,
+    };
+    let dir = Path::new(backups_path).join(&snapshot.id);
+    let partial_dir = Path::new(backups_path).join(format!("{}{}", snapshot.id, PARTIAL_SUFFIX));
+    fs::create_dir_all(&partial_dir)?;
+
+    let result = write(&partial_dir, &mut snapshot).and_then(|_| {
+        fs::write(
+            partial_dir.join(SNAPSHOT_FILE),
+            serde_json::to_vec_pretty(&snapshot)?,
+        )?;
+        fs::rename(&partial_dir, &dir)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(&partial_dir);
+        return Err(e);
+    }
+    Ok(snapshot)
+}
+
+// snapshots both databases and the resource files
+pub fn create_snapshot(
+    db: &Database,
+    kv: &KeyValueStore,
+    resources_path: &str,
+    backups_path: &str,
+    reason: SnapshotReason,
+) -> BackendResult<Snapshot> {
+    // a restore brings back files of older snapshots, not only of the newest one
+    let snapshots = list_snapshots(backups_path)?;
+    let previous: Vec<_> = snapshots
+        .iter()
+        .filter_map(|snapshot| {
+            let dir = Path::new(backups_path)
+                .join(&snapshot.id)
+                .join(RESOURCES_DIR);
+            Some((dir, snapshot.resources.as_ref()?))
+        })
+        .collect();
+
+    write_snapshot(backups_path, reason, |dir, snapshot| {
+        backup_db(&db.conn, &path_string(&dir.join(DB_FILE)))?;
+        kv.backup(&path_string(&dir.join(KV_DB_FILE)))?;
+        snapshot.has_kv = true;
+        snapshot.resources = Some(copy_resources(
+            Path::new(resources_path),
+            &dir.join(RESOURCES_DIR),
+            &previous,
+        )?);
+        Ok(())
+    })
+}
+
+// only the main database is changed by migrations
+pub fn create_pre_migration_snapshot(
+    conn: &Connection,
+    backups_path: &str,
+) -> BackendResult<Snapshot> {
+    let snapshot = write_snapshot(backups_path, SnapshotReason::PreMigration, |dir, _| {
+        backup_db(conn, &path_string(&dir.join(DB_FILE)))
+    })?;
+    prune_snapshots(backups_path, &load_policy(backups_path))?;
+    Ok(snapshot)
+}
+
+// restores the parts of the library the snapshot holds, the current state is
+// snapshotted first so the restore can be undone
+pub fn restore_snapshot(
+    db: &mut Database,
+    kv: &mut KeyValueStore,
+    resources_path: &str,
+    backups_path: &str,
+    id: &str,
+) -> BackendResult<Snapshot> {
+    let snapshot = find_snapshot(backups_path, id)?;
+    let dir = Path::new(backups_path).join(&snapshot.id);
+    create_snapshot(
+        db,
+        kv,
+        resources_path,
+        backups_path,
+        SnapshotReason::PreRestore,
+    )?;
+
+    restore_db(&mut db.conn, &path_string(&dir.join(DB_FILE)))?;
+    // the snapshot can be from an older version
+    migrate(&mut db.conn, backups_path)?;
+    if snapshot.has_kv {
+        kv.restore(&path_string(&dir.join(KV_DB_FILE)))?;
+    }
+
+    if let Some(files) = &snapshot.resources {
+        restore_resources(&dir.join(RESOURCES_DIR), files, Path::new(resources_path))?;
+    }
+    Ok(snapshot)
+}
+
+// a snapshot is due once the last scheduled or manual one is a day old
+pub fn is_backup_due(snapshots: &[Snapshot], now: DateTime<Utc>) -> bool {
+    !snapshots.iter().any(|snapshot| {
+        matches!(
+            snapshot.reason,
+            SnapshotReason::Scheduled | SnapshotReason::Manual
+        ) && now - snapshot.created_at < Duration::days(1)
+    })
+}
+
+// the ids of the snapshots to keep, the snapshots have to be sorted newest first
+fn snapshots_to_keep<'a>(snapshots: &'a [Snapshot], policy: &BackupPolicy) -> HashSet<&'a str> {
+    let mut keep = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut safety_snapshots: HashMap<SnapshotReason, usize> = HashMap::new();
+
+    for snapshot in snapshots {
+        match snapshot.reason {
+            SnapshotReason::PreMigration | SnapshotReason::PreRestore => {
+                let count = safety_snapshots.entry(snapshot.reason).or_default();
+                *count += 1;
+                if *count <= KEEP_SAFETY_SNAPSHOTS {
+                    keep.insert(snapshot.id.as_str());
+                }
+            }
+            SnapshotReason::Scheduled | SnapshotReason::Manual => {
+                let day = snapshot.created_at.date_naive();
+                if days.len() < policy.keep_daily && days.insert(day) {
+                    keep.insert(snapshot.id.as_str());
+                }
+                let week = snapshot.created_at.iso_week();
+                if weeks.len() < policy.keep_weekly && weeks.insert((week.year(), week.week())) {
+                    keep.insert(snapshot.id.as_str());
+                }
+            }
+        }
+    }
+    keep
+}
+
+// removes the snapshots the policy doesn't keep and leftovers of failed snapshots,
+// returns the ids of the removed snapshots
+pub fn prune_snapshots(backups_path: &str, policy: &BackupPolicy) -> BackendResult<Vec<String>> {
+    let snapshots = list_snapshots(backups_path)?;
+    let keep = snapshots_to_keep(&snapshots, policy);
+    let mut removed = Vec::new();
+    for snapshot in &snapshots {
+        if !keep.contains(snapshot.id.as_str()) {
+            fs::remove_dir_all(Path::new(backups_path).join(&snapshot.id))?;
+            removed.push(snapshot.id.clone());
+        }
+    }
+
+    // partial directories that are still being written are recent
+    let cutoff = std::time::SystemTime::now() - std::time::Duration::from_secs(24 * 60 * 60);
+    for entry in fs::read_dir(backups_path)? {
+        let entry = entry?;
+        let is_stale = entry
+            .metadata()?
+            .modified()
+            .is_ok_and(|modified| modified < cutoff);
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(PARTIAL_SUFFIX)
+            && is_stale
+        {
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(removed)
+}
+
+fn policy_path(backups_path: &str) -> PathBuf {
+    Path::new(backups_path).join(POLICY_FILE)
+}
+
+pub fn load_policy(backups_path: &str) -> BackupPolicy {
+    fs::read(policy_path(backups_path))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_policy(backups_path: &str, policy: &BackupPolicy) -> BackendResult<()> {
+    fs::create_dir_all(backups_path)?;
+    fs::write(
+        policy_path(backups_path),
+        serde_json::to_vec_pretty(policy)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn snapshot(reason: SnapshotReason, day: u32, hour: u32) -> Snapshot {
+        let created_at = Utc.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap();
+        Snapshot {
+            id: format!("{}-{}-{}", day, hour, reason.as_ref()),
+            reason,
+            created_at,
+            has_kv: true,
+            resources: None,
+        }
+    }
+
+    #[test]
+    fn test_snapshots_to_keep() {
+        // may 1st 2024 is a wednesday
+        let mut snapshots: Vec<Snapshot> = (1..=20)
+            .flat_map(|day| {
+                vec![
+                    snapshot(SnapshotReason::Scheduled, day, 8),
+                    snapshot(SnapshotReason::Manual, day, 18),
+                ]
+            })
+            .collect();
+        snapshots.extend((1..=5).map(|day| snapshot(SnapshotReason::PreMigration, day, 9)));
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+
+        let policy = BackupPolicy {
+            enabled: true,
+            keep_daily: 3,
+            keep_weekly: 3,
+        };
+        let mut keep: Vec<_> = snapshots_to_keep(&snapshots, &policy).into_iter().collect();
+        keep.sort();
+        assert_eq!(
+            keep,
+            vec![
+                "12-18-manual",
+                "18-18-manual",
+                "19-18-manual",
+                "20-18-manual",
+                "3-9-pre-migration",
+                "4-9-pre-migration",
+                "5-9-pre-migration",
+            ]
+        );
+
+        let now = Utc.with_ymd_and_hms(2024, 5, 21, 12, 0, 0).unwrap();
+        assert!(is_backup_due(&snapshots, now + Duration::days(1)));
+        assert!(!is_backup_due(&snapshots, now));
+    }
+
+    #[test]
+    fn test_copy_resources() {
+        let dir = tempdir().unwrap();
+        let resources = dir.path().join("resources");
+        fs::create_dir_all(&resources).unwrap();
+        fs::write(resources.join("a.md"), "a").unwrap();
+        fs::write(resources.join("b.md"), "b").unwrap();
+
+        let first = dir.path().join("first");
+        let files = copy_resources(&resources, &first, &[]).unwrap();
+        assert_eq!(files.len(), 2);
+
+        fs::write(resources.join("b.md"), "changed").unwrap();
+        let second = dir.path().join("second");
+        let changed = copy_resources(&resources, &second, &[(first.clone(), &files)]).unwrap();
+        assert_eq!(fs::read_to_string(second.join("a.md")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(second.join("b.md")).unwrap(), "changed");
+        assert_eq!(fs::read_to_string(first.join("b.md")).unwrap(), "b");
+        assert_ne!(changed["b.md"], files["b.md"]);
+    }
+
+    #[test]
+    fn test_restore_resources() {
+        let dir = tempdir().unwrap();
+        let resources = dir.path().join("resources");
+        fs::create_dir_all(&resources).unwrap();
+        fs::write(resources.join("a.md"), "a").unwrap();
+        fs::write(resources.join("b.md"), "b").unwrap();
+        let snapshot = dir.path().join("snapshot");
+        let files = copy_resources(&resources, &snapshot, &[]).unwrap();
+
+        // same size and modification time, only the contents tell them apart
+        fs::write(resources.join("b.md"), "c").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(resources.join("b.md"))
+            .unwrap()
+            .set_modified(UNIX_EPOCH + StdDuration::from_secs(files["b.md"].modified))
+            .unwrap();
+        fs::write(resources.join("new.md"), "new").unwrap();
+        fs::remove_file(resources.join("a.md")).unwrap();
+        // older than the snapshot's copy
+        fs::File::options()
+            .write(true)
+            .open(snapshot.join("a.md"))
+            .unwrap()
+            .set_modified(UNIX_EPOCH + StdDuration::from_secs(1_700_000_000))
+            .unwrap();
+        let files = list_files(&snapshot).unwrap();
+
+        restore_resources(&snapshot, &files, &resources).unwrap();
+        assert_eq!(fs::read_to_string(resources.join("a.md")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(resources.join("b.md")).unwrap(), "b");
+        assert!(!resources.join("new.md").exists());
+        // the restored files can be linked to the snapshot
+        assert_eq!(list_files(&resources).unwrap(), files);
+    }
+
+    #[test]
+    fn test_restore_waits_for_resource_writes() {
+        use crate::{store::models::Resource, worker::tunnel::LibraryLock};
+
+        let dir = tempdir().unwrap();
+        let db_path = path_string(&dir.path().join("surf.sqlite"));
+        let mut db = Database::new(&db_path, true).unwrap();
+        let mut kv = KeyValueStore::new(&path_string(&dir.path().join("kv.sqlite"))).unwrap();
+        let resources = dir.path().join("resources");
+        fs::create_dir_all(&resources).unwrap();
+        let backups_path = backups_path(&db_path);
+        let snapshot = create_snapshot(
+            &db,
+            &kv,
+            &path_string(&resources),
+            &backups_path,
+            SnapshotReason::Manual,
+        )
+        .unwrap();
+
+        let lock = LibraryLock::default();
+        let resource = Resource {
+            id: "new".to_string(),
+            resource_path: path_string(&resources.join("new.md")),
+            resource_type: "text/markdown".to_string(),
+            created_at: current_time(),
+            updated_at: current_time(),
+            deleted: 0,
+        };
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        // another worker thread creates a resource, its row is saved before its file
+        let writer = {
+            let lock = lock.clone();
+            let db_path = db_path.clone();
+            let resource = resource.clone();
+            std::thread::spawn(move || {
+                let _shared = lock.shared();
+                started_tx.send(()).unwrap();
+                let mut db = Database::new(&db_path, false).unwrap();
+                let mut tx = db.begin().unwrap();
+                Database::create_resource_tx(&mut tx, &resource).unwrap();
+                tx.commit().unwrap();
+                std::thread::sleep(StdDuration::from_millis(100));
+                fs::write(&resource.resource_path, "new").unwrap();
+            })
+        };
+
+        started_rx.recv().unwrap();
+        {
+            let _exclusive = lock.exclusive();
+            restore_snapshot(
+                &mut db,
+                &mut kv,
+                &path_string(&resources),
+                &backups_path,
+                &snapshot.id,
+            )
+            .unwrap();
+        }
+        writer.join().unwrap();
+
+        // the resource was created before the restore, it's gone from both
+        assert!(db.get_resource(&resource.id).unwrap().is_none());
+        assert!(!resources.join("new.md").exists());
+    }
+}
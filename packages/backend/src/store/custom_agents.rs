@@ -0,0 +1,211 @@
+use super::models::*;
+use crate::{store::db::Database, BackendError, BackendResult};
+
+const MAX_CUSTOM_AGENT_ITERATIONS: i64 = 20;
+
+fn validate_custom_agent(agent: &CustomAgent) -> BackendResult<()> {
+    validate_custom_name(&agent.name).map_err(BackendError::GenericError)?;
+    if !(1..=MAX_CUSTOM_AGENT_ITERATIONS).contains(&agent.max_iterations) {
+        return Err(BackendError::GenericError(format!(
+            "max_iterations must be between 1 and {}",
+            MAX_CUSTOM_AGENT_ITERATIONS
+        )));
+    }
+    if agent.system_prompt.trim().is_empty() {
+        return Err(BackendError::GenericError(
+            "system prompt must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_custom_tool(tool: &CustomTool) -> BackendResult<()> {
+    validate_custom_name(&tool.name).map_err(BackendError::GenericError)?;
+    let schema: serde_json::Value = serde_json::from_str(&tool.parameters_schema)?;
+    if !schema.is_object() {
+        return Err(BackendError::GenericError(
+            "parameters schema must be a json object".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl Database {
+    // replaces the agent with the same id
+    pub fn upsert_custom_agent(&mut self, agent: &CustomAgent) -> BackendResult<()> {
+        validate_custom_agent(agent)?;
+        self.conn.execute(
+            "INSERT INTO custom_agents (id, name, description, system_prompt, model, allowed_tools, max_iterations, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT(id) DO UPDATE SET name = ?2, description = ?3, system_prompt = ?4, model = ?5,
+                allowed_tools = ?6, max_iterations = ?7, updated_at = ?9",
+            rusqlite::params![
+                agent.id,
+                agent.name,
+                agent.description,
+                agent.system_prompt,
+                agent.model,
+                serde_json::to_string(&agent.allowed_tools)?,
+                agent.max_iterations,
+                agent.created_at,
+                current_time()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_custom_agent(&mut self, id: &str) -> BackendResult<()> {
+        self.conn.execute(
+            "DELETE FROM custom_agents WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_custom_agents(&self) -> BackendResult<Vec<CustomAgent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, description, system_prompt, model, allowed_tools, max_iterations, created_at, updated_at
+            FROM custom_agents ORDER BY name ASC",
+        )?;
+        let agents = stmt.query_map([], |row| {
+            let allowed_tools: String = row.get(5)?;
+            Ok(CustomAgent {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                system_prompt: row.get(3)?,
+                model: row.get(4)?,
+                allowed_tools: serde_json::from_str(&allowed_tools).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        5,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })?,
+                max_iterations: row.get(6)?,
+                created_at: row.get(7)?,
+                updated_at: row.get(8)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for agent in agents {
+            result.push(agent?);
+        }
+        Ok(result)
+    }
+
+    // replaces the tool with the same id
+    pub fn upsert_custom_tool(&mut self, tool: &CustomTool) -> BackendResult<()> {
+        validate_custom_tool(tool)?;
+        self.conn.execute(
+            "INSERT INTO custom_tools (id, name, description, parameters_schema, execution_message, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(id) DO UPDATE SET name = ?2, description = ?3, parameters_schema = ?4,
+                execution_message = ?5, updated_at = ?7",
+            rusqlite::params![
+                tool.id,
+                tool.name,
+                tool.description,
+                tool.parameters_schema,
+                tool.execution_message,
+                tool.created_at,
+                current_time()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_custom_tool(&mut self, id: &str) -> BackendResult<()> {
+        self.conn.execute(
+            "DELETE FROM custom_tools WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_custom_tools(&self) -> BackendResult<Vec<CustomTool>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, description, parameters_schema, execution_message, created_at, updated_at
+            FROM custom_tools ORDER BY name ASC",
+        )?;
+        let tools = stmt.query_map([], |row| {
+            Ok(CustomTool {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                parameters_schema: row.get(3)?,
+                execution_message: row.get(4)?,
+                created_at: row.get(5)?,
+                updated_at: row.get(6)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for tool in tools {
+            result.push(tool?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use tempfile::tempdir;
+
+    fn test_agent(name: &str) -> CustomAgent {
+        CustomAgent {
+            id: random_uuid(),
+            name: name.to_string(),
+            description: "Reviews papers".to_string(),
+            system_prompt: "You review scientific literature.".to_string(),
+            model: None,
+            allowed_tools: vec!["library_search".to_string()],
+            max_iterations: 5,
+            created_at: current_time(),
+            updated_at: current_time(),
+        }
+    }
+
+    #[test]
+    fn test_custom_agents_and_tools() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+
+        let mut agent = test_agent("literature_reviewer");
+        db.upsert_custom_agent(&agent).unwrap();
+        agent.max_iterations = 8;
+        db.upsert_custom_agent(&agent).unwrap();
+        let agents = db.list_custom_agents().unwrap();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].max_iterations, 8);
+        assert_eq!(agents[0].allowed_tools, vec!["library_search"]);
+
+        // names are unique and must be usable as tool names
+        assert!(db
+            .upsert_custom_agent(&test_agent("literature_reviewer"))
+            .is_err());
+        assert!(db
+            .upsert_custom_agent(&test_agent("Meeting Summariser"))
+            .is_err());
+
+        let mut tool = CustomTool {
+            id: random_uuid(),
+            name: "fetch_calendar".to_string(),
+            description: "Fetches the user's meetings".to_string(),
+            parameters_schema: r#"{"type": "object", "properties": {}}"#.to_string(),
+            execution_message: None,
+            created_at: current_time(),
+            updated_at: current_time(),
+        };
+        db.upsert_custom_tool(&tool).unwrap();
+        assert_eq!(db.list_custom_tools().unwrap().len(), 1);
+        tool.parameters_schema = "[]".to_string();
+        assert!(db.upsert_custom_tool(&tool).is_err());
+
+        db.delete_custom_agent(&agent.id).unwrap();
+        db.delete_custom_tool(&tool.id).unwrap();
+        assert!(db.list_custom_agents().unwrap().is_empty());
+        assert!(db.list_custom_tools().unwrap().is_empty());
+    }
+}
@@ -2,7 +2,7 @@ use crate::{BackendError, BackendResult};
 
 use rusqlite::Connection;
 
-use super::migrations::migrate;
+use super::{backup::backups_path, history_stats::register_history_functions, migrations::migrate};
 
 pub fn setup_connection_settings(conn: &rusqlite::Connection) -> BackendResult<()> {
     let exec_pragma = |pragma: &str| -> BackendResult<()> {
@@ -64,11 +64,12 @@ impl Database {
         setup_connection_settings(&read_only_conn)?;
 
         if run_migrations {
-            let backup_db_path = format!("{}.backup", db_path);
-            migrate(&mut conn, &backup_db_path)?
+            migrate(&mut conn, &backups_path(db_path))?
         }
         rusqlite::vtab::array::load_module(&conn)?;
         rusqlite::vtab::array::load_module(&read_only_conn)?;
+        register_history_functions(&conn)?;
+        register_history_functions(&read_only_conn)?;
 
         Ok(Database {
             conn,
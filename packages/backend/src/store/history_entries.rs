@@ -3,11 +3,17 @@ use crate::{store::db::Database, BackendError, BackendResult};
 use rusqlite::OptionalExtension;
 use std::str::FromStr;
 
+// the most visited domains first, the visits of imported entries included, and the
+// latest visits first within a domain
+const HOSTNAME_ORDER: &str = "
+    SUM(MAX(visit_count, 1)) OVER (PARTITION BY url_domain(url)) DESC,
+    COALESCE(last_visited_at, created_at) DESC";
+
 impl Database {
     pub fn create_history_entry(&self, entry: &HistoryEntry) -> BackendResult<()> {
         let query = "
-            INSERT INTO history_entries (id, entry_type, url, title, search_query, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+            INSERT INTO history_entries (id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)";
         self.conn.execute(
             query,
             rusqlite::params![
@@ -18,6 +24,11 @@ impl Database {
                 entry.search_query,
                 entry.created_at,
                 entry.updated_at,
+                entry.source_profile,
+                entry.visit_count,
+                entry.typed_count,
+                entry.last_visited_at,
+                entry.last_transition,
             ],
         )?;
         Ok(())
@@ -65,8 +76,8 @@ impl Database {
         // };
 
         let query = "
-            INSERT INTO history_entries (id, entry_type, url, title, search_query, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+            INSERT INTO history_entries (id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)";
 
         let mut inserted_entries = Vec::new();
         for entry in entries {
@@ -90,6 +101,11 @@ impl Database {
                     entry.search_query,
                     entry.created_at,
                     entry.updated_at,
+                    entry.source_profile,
+                    entry.visit_count,
+                    entry.typed_count,
+                    entry.last_visited_at,
+                    entry.last_transition,
                 ],
             )?;
 
@@ -102,7 +118,7 @@ impl Database {
 
     pub fn get_history_entry(&self, id: &str) -> BackendResult<Option<HistoryEntry>> {
         let query = "
-            SELECT id, entry_type, url, title, search_query, created_at, updated_at
+            SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition
             FROM history_entries
             WHERE id = ?1";
         self.conn
@@ -115,6 +131,11 @@ impl Database {
                     search_query: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    source_profile: row.get(7)?,
+                    visit_count: row.get(8)?,
+                    typed_count: row.get(9)?,
+                    last_visited_at: row.get(10)?,
+                    last_transition: row.get(11)?,
                 })
             })
             .optional()
@@ -152,9 +173,9 @@ impl Database {
         since: Option<f64>,
     ) -> BackendResult<Vec<HistoryEntry>> {
         let mut query = "
-            SELECT id, entry_type, url, title, search_query, created_at, updated_at
+            SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition
             FROM history_entries
-            WHERE url LIKE ?1 OR url LIKE ?2 OR url LIKE ?3 OR url LIKE ?4
+            WHERE (url LIKE ?1 OR url LIKE ?2 OR url LIKE ?3 OR url LIKE ?4)
         "
         .to_string();
 
@@ -168,8 +189,8 @@ impl Database {
         if let Some(mut since) = since {
             since /= 1000.0;
             query = format!(
-                "{} AND created_at >= datetime(?5, 'unixepoch') ORDER BY created_at DESC",
-                query
+                "{} AND julianday(COALESCE(last_visited_at, created_at)) >= julianday(?5, 'unixepoch') ORDER BY {}",
+                query, HOSTNAME_ORDER
             );
             let mut stmt = self.read_only_conn.prepare(&query)?;
             let items = stmt.query_map(
@@ -189,6 +210,11 @@ impl Database {
                         search_query: row.get(4)?,
                         created_at: row.get(5)?,
                         updated_at: row.get(6)?,
+                        source_profile: row.get(7)?,
+                        visit_count: row.get(8)?,
+                        typed_count: row.get(9)?,
+                        last_visited_at: row.get(10)?,
+                        last_transition: row.get(11)?,
                     })
                 },
             )?;
@@ -197,7 +223,7 @@ impl Database {
             }
             return Ok(results);
         }
-        query = format!("{} ORDER BY created_at DESC", query);
+        query = format!("{} ORDER BY {}", query, HOSTNAME_ORDER);
         let mut stmt = self.read_only_conn.prepare(&query)?;
         let items = stmt.query_map(
             rusqlite::params![https_prefix, http_prefix, www_https_prefix, www_http_prefix],
@@ -210,6 +236,11 @@ impl Database {
                     search_query: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    source_profile: row.get(7)?,
+                    visit_count: row.get(8)?,
+                    typed_count: row.get(9)?,
+                    last_visited_at: row.get(10)?,
+                    last_transition: row.get(11)?,
                 })
             },
         )?;
@@ -241,12 +272,14 @@ impl Database {
 
         if search_terms.is_empty() {
             let mut base_query = "
-                SELECT id, entry_type, url, title, search_query, created_at, updated_at, COUNT(url) as url_count
+                SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition, SUM(visit_count) as url_count
                 FROM history_entries
                 WHERE (url LIKE ?1 OR title LIKE ?1)".to_string();
 
             if since.is_some() {
-                base_query.push_str(" AND created_at >= datetime(?2, 'unixepoch')");
+                base_query.push_str(
+                    " AND julianday(COALESCE(last_visited_at, created_at)) >= julianday(?2, 'unixepoch')",
+                );
             }
 
             base_query.push_str(
@@ -260,7 +293,7 @@ impl Database {
                         ELSE 4
                     END,
                     url_count DESC,
-                    created_at DESC
+                    MAX(COALESCE(last_visited_at, created_at)) DESC
                 LIMIT 25",
             );
 
@@ -285,6 +318,11 @@ impl Database {
                                 search_query: row.get(4)?,
                                 created_at: row.get(5)?,
                                 updated_at: row.get(6)?,
+                                source_profile: row.get(7)?,
+                                visit_count: row.get(8)?,
+                                typed_count: row.get(9)?,
+                                last_visited_at: row.get(10)?,
+                                last_transition: row.get(11)?,
                             })
                         },
                     )?)
@@ -301,6 +339,11 @@ impl Database {
                             search_query: row.get(4)?,
                             created_at: row.get(5)?,
                             updated_at: row.get(6)?,
+                            source_profile: row.get(7)?,
+                            visit_count: row.get(8)?,
+                            typed_count: row.get(9)?,
+                            last_visited_at: row.get(10)?,
+                            last_transition: row.get(11)?,
                         })
                     })?)
                 };
@@ -313,7 +356,7 @@ impl Database {
         }
 
         let mut base_query = "
-            SELECT id, entry_type, url, title, search_query, created_at, updated_at, COUNT(url) as url_count, 
+            SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition, SUM(visit_count) as url_count, 
             CASE 
                 WHEN title IS NOT NULL THEN length(title) 
                 ELSE 9999
@@ -337,7 +380,7 @@ impl Database {
 
         if since.is_some() {
             base_query.push_str(&format!(
-                " AND created_at >= datetime(?{}, 'unixepoch')",
+                " AND julianday(COALESCE(last_visited_at, created_at)) >= julianday(?{}, 'unixepoch')",
                 timestamp_param_index
             ));
         }
@@ -356,7 +399,7 @@ impl Database {
                 END,
                 title_length ASC,
                 url_count DESC,
-                created_at DESC
+                MAX(COALESCE(last_visited_at, created_at)) DESC
             LIMIT 25",
         );
 
@@ -387,6 +430,11 @@ impl Database {
                 search_query: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                source_profile: row.get(7)?,
+                visit_count: row.get(8)?,
+                typed_count: row.get(9)?,
+                last_visited_at: row.get(10)?,
+                last_transition: row.get(11)?,
             })
         });
 
@@ -402,7 +450,7 @@ impl Database {
         limit: Option<usize>,
     ) -> BackendResult<Vec<HistoryEntry>> {
         let mut query = String::from(
-            "SELECT id, entry_type, url, title, search_query, created_at, updated_at
+            "SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition
             FROM history_entries
             ORDER BY created_at DESC",
         );
@@ -422,6 +470,11 @@ impl Database {
                 search_query: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                source_profile: row.get(7)?,
+                visit_count: row.get(8)?,
+                typed_count: row.get(9)?,
+                last_visited_at: row.get(10)?,
+                last_transition: row.get(11)?,
             })
         })?;
 
@@ -434,7 +487,7 @@ impl Database {
     }
 
     pub fn search_history_by_hostname(&self, url: &str) -> BackendResult<Vec<HistoryEntry>> {
-        let query = "SELECT id, entry_type, url, title, search_query, created_at, updated_at
+        let query = "SELECT id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition
                     FROM history_entries
                     WHERE url LIKE ?1 OR url LIKE ?2 OR url LIKE ?3 OR url LIKE ?4
                     ORDER BY created_at DESC";
@@ -457,6 +510,11 @@ impl Database {
                     search_query: row.get(4)?,
                     created_at: row.get(5)?,
                     updated_at: row.get(6)?,
+                    source_profile: row.get(7)?,
+                    visit_count: row.get(8)?,
+                    typed_count: row.get(9)?,
+                    last_visited_at: row.get(10)?,
+                    last_transition: row.get(11)?,
                 })
             },
         )?;
@@ -474,3 +532,106 @@ impl Database {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use chrono::{Duration, Utc};
+    use tempfile::tempdir;
+
+    fn history_entry(
+        url: &str,
+        created_at: chrono::DateTime<Utc>,
+        last_visited_at: Option<chrono::DateTime<Utc>>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            id: random_uuid(),
+            entry_type: HistoryEntryType::Navigation,
+            url: Some(url.to_string()),
+            title: None,
+            search_query: None,
+            created_at,
+            updated_at: created_at,
+            source_profile: None,
+            visit_count: 1,
+            typed_count: 0,
+            last_visited_at,
+            last_transition: None,
+        }
+    }
+
+    #[test]
+    fn test_search_history_since_last_visit() {
+        let dir = tempdir().unwrap();
+        let db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        let now = Utc::now();
+        for (url, created_at, last_visited_at) in [
+            ("https://deta.space/stale", now - Duration::days(30), None),
+            ("https://deta.space/new", now - Duration::hours(3), None),
+            // first visited a while ago, the visits are merged into one entry
+            (
+                "https://deta.space/revisited",
+                now - Duration::days(30),
+                Some(now - Duration::hours(1)),
+            ),
+        ] {
+            db.create_history_entry(&history_entry(url, created_at, last_visited_at))
+                .unwrap();
+        }
+
+        let since = Some((now - Duration::days(7)).timestamp_millis() as f64);
+        let urls = |entries: Vec<HistoryEntry>| -> Vec<String> {
+            entries.into_iter().filter_map(|e| e.url).collect()
+        };
+        let recent = vec!["https://deta.space/revisited", "https://deta.space/new"];
+        assert_eq!(
+            urls(db.search_history_by_hostname_prefix("deta", since).unwrap()),
+            recent
+        );
+        assert_eq!(
+            urls(db.search_history_by_url_and_title("deta", since).unwrap()),
+            recent
+        );
+        assert_eq!(
+            urls(db.search_history_by_hostname_prefix("deta", None).unwrap())[..2],
+            recent
+        );
+    }
+
+    #[test]
+    fn test_search_history_by_hostname_prefix_ranking() {
+        let dir = tempdir().unwrap();
+        let db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        let now = Utc::now();
+        let mut imported =
+            history_entry("https://www.deta.dev/docs", now - Duration::days(3), None);
+        imported.entry_type = HistoryEntryType::ImportChrome;
+        imported.visit_count = 4;
+        for entry in [
+            history_entry("https://deta.space/", now - Duration::hours(1), None),
+            history_entry("https://deta.space/docs", now - Duration::hours(2), None),
+            history_entry("https://deta.dev/", now - Duration::days(2), None),
+            imported,
+        ] {
+            db.create_history_entry(&entry).unwrap();
+        }
+
+        // deta.dev has more visits, even though deta.space was visited more recently
+        let urls: Vec<_> = db
+            .search_history_by_hostname_prefix("deta", None)
+            .unwrap()
+            .into_iter()
+            .filter_map(|e| e.url)
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://deta.dev/",
+                "https://www.deta.dev/docs",
+                "https://deta.space/",
+                "https://deta.space/docs",
+            ]
+        );
+    }
+}
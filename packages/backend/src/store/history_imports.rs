@@ -0,0 +1,337 @@
+use super::models::*;
+use crate::{store::db::Database, BackendResult};
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+impl Database {
+    pub fn get_history_import_mark(
+        &self,
+        browser_type: &str,
+        profile_id: &str,
+    ) -> BackendResult<Option<HistoryImportMark>> {
+        self.conn
+            .query_row(
+                "SELECT browser_type, profile_id, last_visit_id, last_visit_time, updated_at
+                FROM history_import_marks WHERE browser_type = ?1 AND profile_id = ?2",
+                rusqlite::params![browser_type, profile_id],
+                |row| {
+                    Ok(HistoryImportMark {
+                        browser_type: row.get(0)?,
+                        profile_id: row.get(1)?,
+                        last_visit_id: row.get(2)?,
+                        last_visit_time: row.get(3)?,
+                        updated_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    // the latest visit imported into the profile before it had a mark, entries imported
+    // before there were profiles have none and belong to the default profile
+    pub fn get_latest_imported_visit(
+        &self,
+        entry_type: &HistoryEntryType,
+        profile_id: &str,
+        is_default_profile: bool,
+    ) -> BackendResult<Option<DateTime<Utc>>> {
+        self.conn
+            .query_row(
+                "SELECT MAX(COALESCE(last_visited_at, created_at)) FROM history_entries
+                WHERE entry_type = ?1 AND (source_profile = ?2 OR (?3 AND source_profile IS NULL))",
+                rusqlite::params![entry_type.as_ref(), profile_id, is_default_profile],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.into())
+    }
+
+    // merges each entry into the existing entry for its url, type and profile,
+    // the mark is moved in the same transaction so no visit is counted twice
+    pub fn import_history_entries(
+        &mut self,
+        entries: &[HistoryEntry],
+        mark: &HistoryImportMark,
+        is_default_profile: bool,
+    ) -> BackendResult<Vec<HistoryEntry>> {
+        let tx = self.conn.transaction()?;
+        let mut imported = Vec::new();
+        for entry in entries {
+            let existing = tx
+                .query_row(
+                    "SELECT id, title, created_at, visit_count, typed_count, last_visited_at, last_transition
+                    FROM history_entries
+                    WHERE entry_type = ?1 AND url = ?2
+                        AND (source_profile IS ?3 OR (?4 AND source_profile IS NULL))
+                    ORDER BY source_profile IS NULL, created_at ASC
+                    LIMIT 1",
+                    rusqlite::params![
+                        entry.entry_type.as_ref(),
+                        entry.url,
+                        entry.source_profile,
+                        is_default_profile,
+                    ],
+                    |row| {
+                        Ok(HistoryEntry {
+                            id: row.get(0)?,
+                            title: row.get(1)?,
+                            created_at: row.get(2)?,
+                            visit_count: row.get(3)?,
+                            typed_count: row.get(4)?,
+                            last_visited_at: row.get(5)?,
+                            last_transition: row.get(6)?,
+                            ..entry.clone()
+                        })
+                    },
+                )
+                .optional()?;
+
+            // entries without a profile are moved to the default profile when they are merged
+            let entry = match existing {
+                Some(existing) => {
+                    let is_newer = entry.last_visited_at >= existing.last_visited_at;
+                    let merged = HistoryEntry {
+                        id: existing.id.clone(),
+                        title: if is_newer && entry.title.is_some() {
+                            entry.title.clone()
+                        } else {
+                            existing.title.clone()
+                        },
+                        created_at: existing.created_at.min(entry.created_at),
+                        updated_at: current_time(),
+                        visit_count: existing.visit_count + entry.visit_count,
+                        typed_count: existing.typed_count + entry.typed_count,
+                        last_visited_at: existing.last_visited_at.max(entry.last_visited_at),
+                        last_transition: if is_newer {
+                            entry.last_transition
+                        } else {
+                            existing.last_transition
+                        },
+                        ..entry.clone()
+                    };
+                    tx.execute(
+                        "UPDATE history_entries
+                        SET title = ?1, created_at = ?2, updated_at = ?3, visit_count = ?4,
+                            typed_count = ?5, last_visited_at = ?6, last_transition = ?7,
+                            source_profile = ?8
+                        WHERE id = ?9",
+                        rusqlite::params![
+                            merged.title,
+                            merged.created_at,
+                            merged.updated_at,
+                            merged.visit_count,
+                            merged.typed_count,
+                            merged.last_visited_at,
+                            merged.last_transition,
+                            merged.source_profile,
+                            merged.id,
+                        ],
+                    )?;
+                    merged
+                }
+                None => {
+                    tx.execute(
+                        "INSERT INTO history_entries (id, entry_type, url, title, search_query, created_at, updated_at, source_profile, visit_count, typed_count, last_visited_at, last_transition)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                        rusqlite::params![
+                            entry.id,
+                            entry.entry_type.as_ref(),
+                            entry.url,
+                            entry.title,
+                            entry.search_query,
+                            entry.created_at,
+                            entry.updated_at,
+                            entry.source_profile,
+                            entry.visit_count,
+                            entry.typed_count,
+                            entry.last_visited_at,
+                            entry.last_transition,
+                        ],
+                    )?;
+                    entry.clone()
+                }
+            };
+            imported.push(entry);
+        }
+
+        tx.execute(
+            "INSERT INTO history_import_marks (browser_type, profile_id, last_visit_id, last_visit_time, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(browser_type, profile_id) DO UPDATE SET
+                last_visit_id = ?3, last_visit_time = ?4, updated_at = ?5",
+            rusqlite::params![
+                mark.browser_type,
+                mark.profile_id,
+                mark.last_visit_id,
+                mark.last_visit_time,
+                mark.updated_at,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use chrono::{Duration, Utc};
+    use tempfile::tempdir;
+
+    fn imported_entry(
+        url: &str,
+        visit_count: u32,
+        last_visited_at: chrono::DateTime<Utc>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            id: random_uuid(),
+            entry_type: HistoryEntryType::ImportChrome,
+            url: Some(url.to_string()),
+            title: Some(format!("{} at {}", url, visit_count)),
+            search_query: None,
+            created_at: last_visited_at,
+            updated_at: current_time(),
+            source_profile: Some("Profile 1".to_string()),
+            visit_count,
+            typed_count: 1,
+            last_visited_at: Some(last_visited_at),
+            last_transition: Some(HistoryVisitTransition::Typed),
+        }
+    }
+
+    #[test]
+    fn test_import_history_entries() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        assert_eq!(
+            db.get_history_import_mark("chrome", "Profile 1").unwrap(),
+            None
+        );
+
+        let first_visit = Utc::now() - Duration::days(7);
+        let mut mark = HistoryImportMark {
+            browser_type: "chrome".to_string(),
+            profile_id: "Profile 1".to_string(),
+            last_visit_id: 10,
+            last_visit_time: 100,
+            updated_at: current_time(),
+        };
+        db.import_history_entries(
+            &[imported_entry("https://deta.space", 3, first_visit)],
+            &mark,
+            false,
+        )
+        .unwrap();
+
+        let mut newer = imported_entry("https://deta.space", 2, Utc::now());
+        newer.last_transition = Some(HistoryVisitTransition::Link);
+        mark.last_visit_id = 20;
+        let imported = db
+            .import_history_entries(
+                &[newer, imported_entry("https://example.com", 1, Utc::now())],
+                &mark,
+                false,
+            )
+            .unwrap();
+        assert_eq!(imported.len(), 2);
+
+        let entries = db.get_all_history_entries(None).unwrap();
+        assert_eq!(entries.len(), 2);
+        let merged = entries
+            .iter()
+            .find(|e| e.url.as_deref() == Some("https://deta.space"))
+            .unwrap();
+        assert_eq!(merged.id, imported[0].id);
+        assert_eq!(merged.visit_count, 5);
+        assert_eq!(merged.typed_count, 2);
+        assert_eq!(merged.title.as_deref(), Some("https://deta.space at 2"));
+        assert_eq!(merged.created_at, first_visit);
+        assert_eq!(merged.last_transition, Some(HistoryVisitTransition::Link));
+        assert_eq!(
+            db.get_history_import_mark("chrome", "Profile 1")
+                .unwrap()
+                .map(|m| m.last_visit_id),
+            Some(20)
+        );
+    }
+
+    #[test]
+    fn test_reimport_into_legacy_history() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+
+        // imported before there were profiles and visit counts, a row per visit
+        let first_visit = Utc::now() - Duration::days(7);
+        let legacy = [0, 1, 2, 3]
+            .iter()
+            .map(|&day| {
+                let url = if day < 3 {
+                    "https://deta.space"
+                } else {
+                    "https://example.com"
+                };
+                HistoryEntry {
+                    source_profile: None,
+                    typed_count: 0,
+                    last_visited_at: None,
+                    last_transition: None,
+                    ..imported_entry(url, 1, first_visit + Duration::days(day))
+                }
+            })
+            .collect::<Vec<_>>();
+        db.create_history_entries_batch(&legacy).unwrap();
+        db.conn
+            .execute_batch(include_str!("../../migrations/11_history_dedupe.sql"))
+            .unwrap();
+
+        let entries = db.get_all_history_entries(None).unwrap();
+        assert_eq!(entries.len(), 2);
+        let merged = entries
+            .iter()
+            .find(|e| e.url.as_deref() == Some("https://deta.space"))
+            .unwrap();
+        assert_eq!(merged.id, legacy[0].id);
+        assert_eq!(merged.visit_count, 3);
+        assert_eq!(merged.created_at, first_visit);
+        assert_eq!(
+            merged.last_visited_at,
+            Some(first_visit + Duration::days(2))
+        );
+
+        // entries without a profile belong to the default profile
+        assert_eq!(
+            db.get_latest_imported_visit(&HistoryEntryType::ImportChrome, "Default", true)
+                .unwrap(),
+            Some(first_visit + Duration::days(3))
+        );
+        assert_eq!(
+            db.get_latest_imported_visit(&HistoryEntryType::ImportChrome, "Profile 1", false)
+                .unwrap(),
+            None
+        );
+
+        let mark = HistoryImportMark {
+            browser_type: "chrome".to_string(),
+            profile_id: "Default".to_string(),
+            last_visit_id: 10,
+            last_visit_time: 100,
+            updated_at: current_time(),
+        };
+        let newer = HistoryEntry {
+            source_profile: Some("Default".to_string()),
+            ..imported_entry("https://deta.space", 2, Utc::now())
+        };
+        db.import_history_entries(&[newer], &mark, true).unwrap();
+
+        let entries = db.get_all_history_entries(None).unwrap();
+        assert_eq!(entries.len(), 2);
+        let merged = entries
+            .iter()
+            .find(|e| e.url.as_deref() == Some("https://deta.space"))
+            .unwrap();
+        assert_eq!(merged.id, legacy[0].id);
+        assert_eq!(merged.visit_count, 5);
+        assert_eq!(merged.source_profile.as_deref(), Some("Default"));
+    }
+}
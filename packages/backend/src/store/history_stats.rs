@@ -0,0 +1,485 @@
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use super::models::*;
+use crate::{store::db::Database, BackendResult};
+
+// the visits of the history entries in the range
+//
+// imported entries hold all the visits to their url but only know the first and the last
+// one, their visits are counted at the last visit if both are in the range, otherwise only
+// the one visit that is in the range is counted
+const RANGE_VISITS: &str = "
+    WITH bounds AS (
+        SELECT julianday(:since / 1000.0, 'unixepoch') AS range_start,
+            julianday(:until / 1000.0, 'unixepoch') AS range_end
+    ),
+    entries AS (
+        SELECT history_entries.*,
+            (range_start IS NULL OR julianday(created_at) >= range_start)
+            AND (range_end IS NULL OR julianday(created_at) < range_end) AS first_in_range,
+            (range_start IS NULL OR julianday(COALESCE(last_visited_at, created_at)) >= range_start)
+            AND (range_end IS NULL OR julianday(COALESCE(last_visited_at, created_at)) < range_end) AS last_in_range
+        FROM history_entries, bounds
+    ),
+    visits AS (
+        SELECT entry_type, url, title, search_query, created_at,
+            CASE WHEN last_in_range THEN COALESCE(last_visited_at, created_at) ELSE created_at END AS visited_at,
+            CASE WHEN first_in_range AND last_in_range THEN MAX(visit_count, 1) ELSE 1 END AS visits
+        FROM entries
+        WHERE first_in_range OR last_in_range
+    )";
+
+// the result pages of searches are in the history as navigations too
+const PAGE_VISITS: &str = ",
+    page_visits AS (
+        SELECT visits.*, url_domain(url) AS domain
+        FROM visits
+        WHERE lower(entry_type) != 'search'
+    ),
+    domain_visits AS (
+        SELECT * FROM page_visits
+        WHERE domain IS NOT NULL
+        AND (:domain_prefix IS NULL OR substr(domain, 1, length(:domain_prefix)) = :domain_prefix)
+    )";
+
+// the host of a web page without `www.`
+pub fn url_domain(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+    let host = url.host_str()?.to_lowercase();
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+// searches that only differ in case and spacing are the same search
+fn search_query_key(query: &str) -> Option<String> {
+    let key = query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    Some(key).filter(|key| !key.is_empty())
+}
+
+// lets the history queries group visits by domain and searches by query
+pub fn register_history_functions(conn: &Connection) -> BackendResult<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("url_domain", 1, flags, |ctx| {
+        Ok(ctx
+            .get::<Option<String>>(0)?
+            .as_deref()
+            .and_then(url_domain))
+    })?;
+    conn.create_scalar_function("search_query_key", 1, flags, |ctx| {
+        Ok(ctx
+            .get::<Option<String>>(0)?
+            .as_deref()
+            .and_then(search_query_key))
+    })?;
+    Ok(())
+}
+
+impl Database {
+    pub fn get_history_stats(&self, query: &HistoryStatsQuery) -> BackendResult<HistoryStats> {
+        let domain_prefix = query
+            .domain_prefix
+            .as_deref()
+            .map(|prefix| prefix.trim().to_lowercase());
+        let limit = query.limit as i64;
+        // e.g. `+60 minutes`, the days and hours are in local time
+        let utc_offset = format!("{:+} minutes", query.utc_offset_minutes);
+
+        let total_visits: i64 = self.read_only_conn.query_row(
+            &format!(
+                "{RANGE_VISITS}{PAGE_VISITS} SELECT COALESCE(SUM(visits), 0) FROM domain_visits"
+            ),
+            rusqlite::named_params! {
+                ":since": query.since,
+                ":until": query.until,
+                ":domain_prefix": domain_prefix,
+            },
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.read_only_conn.prepare(&format!(
+            "{RANGE_VISITS}{PAGE_VISITS}
+            SELECT domain, SUM(visits) AS visit_count, MIN(created_at), MAX(visited_at)
+            FROM domain_visits
+            GROUP BY domain
+            ORDER BY visit_count DESC, domain
+            LIMIT :limit"
+        ))?;
+        let top_domains = stmt
+            .query_map(
+                rusqlite::named_params! {
+                    ":since": query.since,
+                    ":until": query.until,
+                    ":domain_prefix": domain_prefix,
+                    ":limit": limit,
+                },
+                |row| {
+                    Ok(DomainStats {
+                        domain: row.get(0)?,
+                        visit_count: row.get::<_, i64>(1)? as u64,
+                        first_seen: row.get(2)?,
+                        last_seen: row.get(3)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the title is the one of the latest visit
+        let mut stmt = self.read_only_conn.prepare(&format!(
+            "{RANGE_VISITS}{PAGE_VISITS}
+            SELECT url, title, SUM(visits) AS visit_count, MAX(visited_at)
+            FROM domain_visits
+            GROUP BY url
+            ORDER BY visit_count DESC, url
+            LIMIT :limit"
+        ))?;
+        let top_urls = stmt
+            .query_map(
+                rusqlite::named_params! {
+                    ":since": query.since,
+                    ":until": query.until,
+                    ":domain_prefix": domain_prefix,
+                    ":limit": limit,
+                },
+                |row| {
+                    Ok(UrlStats {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        visit_count: row.get::<_, i64>(2)? as u64,
+                        last_visited_at: row.get(3)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = self.read_only_conn.prepare(&format!(
+            "{RANGE_VISITS}{PAGE_VISITS}
+            SELECT date(visited_at, :utc_offset) AS day, SUM(visits)
+            FROM domain_visits
+            GROUP BY day
+            ORDER BY day"
+        ))?;
+        let days = stmt
+            .query_map(
+                rusqlite::named_params! {
+                    ":since": query.since,
+                    ":until": query.until,
+                    ":domain_prefix": domain_prefix,
+                    ":utc_offset": utc_offset,
+                },
+                |row| {
+                    Ok((
+                        row.get::<_, chrono::NaiveDate>(0)?,
+                        row.get::<_, i64>(1)? as u64,
+                    ))
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut daily_activity = Vec::new();
+        if let (Some((first, _)), Some((last, _))) = (days.first(), days.last()) {
+            let mut days = days.iter().peekable();
+            for date in first.iter_days().take_while(|date| date <= last) {
+                let visit_count = days.next_if(|(day, _)| *day == date).map_or(0, |d| d.1);
+                daily_activity.push(DailyActivity { date, visit_count });
+            }
+        }
+
+        let mut stmt = self.read_only_conn.prepare(&format!(
+            "{RANGE_VISITS}{PAGE_VISITS}
+            SELECT CAST(strftime('%H', visited_at, :utc_offset) AS INTEGER) AS hour, SUM(visits)
+            FROM domain_visits
+            GROUP BY hour"
+        ))?;
+        let mut hourly_activity = vec![0; 24];
+        let hours = stmt.query_map(
+            rusqlite::named_params! {
+                ":since": query.since,
+                ":until": query.until,
+                ":domain_prefix": domain_prefix,
+                ":utc_offset": utc_offset,
+            },
+            |row| Ok((row.get::<_, usize>(0)?, row.get::<_, i64>(1)? as u64)),
+        )?;
+        for hour in hours {
+            let (hour, visit_count) = hour?;
+            hourly_activity[hour % 24] = visit_count;
+        }
+
+        let mut stmt = self.read_only_conn.prepare(&format!(
+            "{RANGE_VISITS}
+            SELECT search_query_key(search_query) AS query, SUM(visits) AS count, MAX(visited_at)
+            FROM visits
+            WHERE lower(entry_type) = 'search' AND query IS NOT NULL
+            GROUP BY query
+            ORDER BY count DESC, query
+            LIMIT :limit"
+        ))?;
+        let top_searches = stmt
+            .query_map(
+                rusqlite::named_params! {
+                    ":since": query.since,
+                    ":until": query.until,
+                    ":limit": limit,
+                },
+                |row| {
+                    Ok(SearchQueryStats {
+                        query: row.get(0)?,
+                        count: row.get::<_, i64>(1)? as u64,
+                        last_searched_at: row.get(2)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(HistoryStats {
+            total_visits: total_visits as u64,
+            top_domains,
+            top_urls,
+            daily_activity,
+            hourly_activity,
+            top_searches,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone, Utc};
+    use tempfile::tempdir;
+
+    fn entry(
+        entry_type: HistoryEntryType,
+        url: &str,
+        search_query: Option<&str>,
+        visit_count: u32,
+        created_at: DateTime<Utc>,
+        last_visited_at: Option<DateTime<Utc>>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            id: random_uuid(),
+            entry_type,
+            url: Some(url.to_string()),
+            title: Some(format!("{} title", url)),
+            search_query: search_query.map(|s| s.to_string()),
+            created_at,
+            updated_at: current_time(),
+            source_profile: None,
+            visit_count,
+            typed_count: 0,
+            last_visited_at,
+            last_transition: None,
+        }
+    }
+
+    fn history_db(entries: &[HistoryEntry]) -> (tempfile::TempDir, Database) {
+        let dir = tempdir().unwrap();
+        let db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        for entry in entries {
+            db.create_history_entry(entry).unwrap();
+        }
+        (dir, db)
+    }
+
+    fn millis(time: DateTime<Utc>) -> Option<f64> {
+        Some(time.timestamp_millis() as f64)
+    }
+
+    #[test]
+    fn test_get_history_stats() {
+        let monday = Utc.with_ymd_and_hms(2024, 5, 6, 23, 30, 0).unwrap();
+        let wednesday = Utc.with_ymd_and_hms(2024, 5, 8, 9, 0, 0).unwrap();
+        let (_dir, db) = history_db(&[
+            entry(
+                HistoryEntryType::Navigation,
+                "https://www.deta.space/docs",
+                None,
+                1,
+                monday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::Navigation,
+                "https://deta.space/",
+                None,
+                1,
+                wednesday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::ImportChrome,
+                "https://example.com/a",
+                None,
+                3,
+                monday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::Navigation,
+                "surf://settings",
+                None,
+                1,
+                monday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::Search,
+                "https://google.com/search?q=rust",
+                Some("Rust  Lifetimes"),
+                1,
+                monday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::Search,
+                "https://google.com/search?q=rust",
+                Some("rust lifetimes"),
+                1,
+                wednesday,
+                None,
+            ),
+        ]);
+
+        let stats = db
+            .get_history_stats(&HistoryStatsQuery {
+                utc_offset_minutes: 60,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(stats.total_visits, 5);
+        assert_eq!(
+            stats.top_domains[0],
+            DomainStats {
+                domain: "example.com".to_string(),
+                visit_count: 3,
+                first_seen: monday,
+                last_seen: monday,
+            }
+        );
+        assert_eq!(stats.top_domains[1].domain, "deta.space");
+        assert_eq!(stats.top_domains[1].first_seen, monday);
+        assert_eq!(stats.top_domains[1].last_seen, wednesday);
+        assert_eq!(stats.top_urls[0].url, "https://example.com/a");
+        assert_eq!(stats.top_urls.len(), 3);
+
+        // monday night is tuesday in UTC+1
+        let daily: Vec<_> = stats
+            .daily_activity
+            .iter()
+            .map(|d| (d.date.to_string(), d.visit_count))
+            .collect();
+        assert_eq!(
+            daily,
+            vec![("2024-05-07".to_string(), 4), ("2024-05-08".to_string(), 1)]
+        );
+        assert_eq!(stats.hourly_activity[0], 4);
+        assert_eq!(stats.hourly_activity[10], 1);
+
+        assert_eq!(stats.top_searches.len(), 1);
+        assert_eq!(stats.top_searches[0].query, "rust lifetimes");
+        assert_eq!(stats.top_searches[0].count, 2);
+        assert_eq!(stats.top_searches[0].last_searched_at, wednesday);
+
+        let stats = db
+            .get_history_stats(&HistoryStatsQuery {
+                domain_prefix: Some(" Det".to_string()),
+                limit: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(stats.total_visits, 2);
+        assert_eq!(stats.top_domains.len(), 1);
+        assert_eq!(stats.top_urls.len(), 1);
+    }
+
+    #[test]
+    fn test_get_history_stats_range() {
+        let monday = Utc.with_ymd_and_hms(2024, 5, 6, 12, 0, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2024, 5, 7, 0, 0, 0).unwrap();
+        let wednesday = Utc.with_ymd_and_hms(2024, 5, 8, 9, 0, 0).unwrap();
+        let thursday = Utc.with_ymd_and_hms(2024, 5, 9, 0, 0, 0).unwrap();
+        let (_dir, db) = history_db(&[
+            entry(
+                HistoryEntryType::ImportChrome,
+                "https://example.com/a",
+                None,
+                5,
+                monday,
+                Some(wednesday),
+            ),
+            entry(
+                HistoryEntryType::ImportChrome,
+                "https://deta.space/",
+                None,
+                3,
+                wednesday,
+                None,
+            ),
+            entry(
+                HistoryEntryType::Navigation,
+                "https://old.com/",
+                None,
+                1,
+                monday,
+                None,
+            ),
+        ]);
+
+        let stats = db.get_history_stats(&HistoryStatsQuery::default()).unwrap();
+        assert_eq!(stats.total_visits, 9);
+        assert_eq!(stats.top_domains[0].domain, "example.com");
+        assert_eq!(stats.top_domains[0].last_seen, wednesday);
+
+        // only the last visit to example.com is known to be after tuesday
+        let stats = db
+            .get_history_stats(&HistoryStatsQuery {
+                since: millis(tuesday),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(stats.total_visits, 4);
+        assert_eq!(stats.top_domains.len(), 2);
+        assert_eq!(stats.top_domains[0].domain, "deta.space");
+        assert_eq!(stats.top_urls[1].visit_count, 1);
+        assert_eq!(stats.daily_activity.len(), 1);
+        assert_eq!(stats.daily_activity[0].visit_count, 4);
+        assert_eq!(stats.hourly_activity[9], 4);
+
+        // and only its first one before tuesday, the entry isn't dropped for its later visit
+        let stats = db
+            .get_history_stats(&HistoryStatsQuery {
+                until: millis(tuesday),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(stats.total_visits, 2);
+        let mut domains: Vec<_> = stats.top_domains.iter().map(|d| &d.domain).collect();
+        domains.sort();
+        assert_eq!(domains, vec!["example.com", "old.com"]);
+        let example = stats
+            .top_domains
+            .iter()
+            .find(|d| d.domain == "example.com")
+            .unwrap();
+        assert_eq!(example.visit_count, 1);
+        assert_eq!(example.last_seen, monday);
+        assert_eq!(stats.hourly_activity[12], 2);
+
+        // all of its visits are counted once both are in the range
+        let stats = db
+            .get_history_stats(&HistoryStatsQuery {
+                since: millis(monday),
+                until: millis(thursday),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(stats.total_visits, 9);
+        assert_eq!(stats.top_urls[0].url, "https://example.com/a");
+        assert_eq!(stats.top_urls[0].visit_count, 5);
+    }
+}
@@ -4,6 +4,7 @@ use strum::Display;
 
 use crate::BackendResult;
 
+use super::backup::{backup_db, restore_db};
 use super::db::setup_connection_settings;
 
 #[derive(thiserror::Error, Debug, Display)]
@@ -32,6 +33,14 @@ impl KeyValueStore {
         Ok(KeyValueStore { conn })
     }
 
+    pub fn backup(&self, backup_db_path: &str) -> BackendResult<()> {
+        backup_db(&self.conn, backup_db_path)
+    }
+
+    pub fn restore(&mut self, backup_db_path: &str) -> BackendResult<()> {
+        restore_db(&mut self.conn, backup_db_path)
+    }
+
     pub fn new_table(&mut self, table: &str) -> BackendResult<()> {
         valid_table_name(table)?;
         self.conn.execute(
@@ -72,6 +81,34 @@ impl KeyValueStore {
         Ok(results)
     }
 
+    pub fn list_tables(&self) -> BackendResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    // the keys with their json data
+    pub fn list_entries(&self, table: &str) -> BackendResult<Vec<(String, String)>> {
+        valid_table_name(table)?;
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT key, data FROM {} ORDER BY key", table))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
     pub fn get(&self, table: &str, key: &str) -> BackendResult<Option<String>> {
         let mut stmt = self
             .conn
@@ -0,0 +1,73 @@
+use super::models::*;
+use crate::{store::db::Database, BackendError, BackendResult};
+
+fn json_column<T: serde::de::DeserializeOwned>(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<T> {
+    let value: String = row.get(idx)?;
+    serde_json::from_str(&value).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+impl Database {
+    // replaces the server with the same id
+    pub fn upsert_mcp_server(&mut self, server: &McpServer) -> BackendResult<()> {
+        validate_custom_name(&server.name).map_err(BackendError::GenericError)?;
+        if server.command.trim().is_empty() {
+            return Err(BackendError::GenericError(
+                "mcp server command must not be empty".to_string(),
+            ));
+        }
+        self.conn.execute(
+            "INSERT INTO mcp_servers (id, name, command, args, env, enabled, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(id) DO UPDATE SET name = ?2, command = ?3, args = ?4, env = ?5,
+                enabled = ?6, updated_at = ?8",
+            rusqlite::params![
+                server.id,
+                server.name,
+                server.command,
+                serde_json::to_string(&server.args)?,
+                serde_json::to_string(&server.env)?,
+                server.enabled,
+                server.created_at,
+                current_time()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_mcp_server(&mut self, id: &str) -> BackendResult<()> {
+        self.conn.execute(
+            "DELETE FROM mcp_servers WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_mcp_servers(&self) -> BackendResult<Vec<McpServer>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, command, args, env, enabled, created_at, updated_at
+            FROM mcp_servers ORDER BY name ASC",
+        )?;
+        let servers = stmt.query_map([], |row| {
+            Ok(McpServer {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                command: row.get(2)?,
+                args: json_column(row, 3)?,
+                env: json_column(row, 4)?,
+                enabled: row.get(5)?,
+                created_at: row.get(6)?,
+                updated_at: row.get(7)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for server in servers {
+            result.push(server?);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,118 @@
+use super::models::*;
+use crate::{store::db::Database, BackendError, BackendResult};
+
+// embeddings are stored as little endian f32s
+fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn embedding_from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+fn memory_from_row(row: &rusqlite::Row) -> rusqlite::Result<Memory> {
+    let embedding: Vec<u8> = row.get(2)?;
+    Ok(Memory {
+        id: row.get(0)?,
+        content: row.get(1)?,
+        embedding: embedding_from_blob(&embedding),
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+impl Database {
+    // replaces the memory with the same id
+    pub fn upsert_memory(&mut self, memory: &Memory) -> BackendResult<()> {
+        if memory.content.trim().is_empty() {
+            return Err(BackendError::GenericError(
+                "memory content must not be empty".to_string(),
+            ));
+        }
+        self.conn.execute(
+            "INSERT INTO memories (id, content, embedding, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET content = ?2, embedding = ?3, updated_at = ?5",
+            rusqlite::params![
+                memory.id,
+                memory.content,
+                embedding_to_blob(&memory.embedding),
+                memory.created_at,
+                current_time()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_memory(&self, id: &str) -> BackendResult<Option<Memory>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, content, embedding, created_at, updated_at FROM memories WHERE id = ?1",
+        )?;
+        let mut memories = stmt.query_map(rusqlite::params![id], memory_from_row)?;
+        Ok(memories.next().transpose()?)
+    }
+
+    pub fn delete_memory(&mut self, id: &str) -> BackendResult<bool> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM memories WHERE id = ?1", rusqlite::params![id])?;
+        Ok(deleted > 0)
+    }
+
+    // newest first
+    pub fn list_memories(&self) -> BackendResult<Vec<Memory>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, content, embedding, created_at, updated_at
+            FROM memories ORDER BY updated_at DESC",
+        )?;
+        let memories = stmt.query_map([], memory_from_row)?;
+        let mut result = Vec::new();
+        for memory in memories {
+            result.push(memory?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_memories() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+
+        let mut memory = Memory {
+            id: random_uuid(),
+            content: "writes in British English".to_string(),
+            embedding: vec![0.5, -1.25, 3.0],
+            created_at: current_time(),
+            updated_at: current_time(),
+        };
+        db.upsert_memory(&memory).unwrap();
+        let stored = db.get_memory(&memory.id).unwrap().unwrap();
+        assert_eq!(stored.content, memory.content);
+        assert_eq!(stored.embedding, memory.embedding);
+
+        memory.content = "writes in American English".to_string();
+        db.upsert_memory(&memory).unwrap();
+        let memories = db.list_memories().unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].content, "writes in American English");
+
+        memory.content = " ".to_string();
+        assert!(db.upsert_memory(&memory).is_err());
+
+        assert!(db.delete_memory(&memory.id).unwrap());
+        assert!(!db.delete_memory(&memory.id).unwrap());
+        assert!(db.get_memory(&memory.id).unwrap().is_none());
+    }
+}
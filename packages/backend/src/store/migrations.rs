@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
+use super::backup::create_pre_migration_snapshot;
 use crate::{BackendError, BackendResult};
 
-use rusqlite::{backup, Connection};
+use rusqlite::Connection;
 use rust_embed::RustEmbed;
 
 #[derive(RustEmbed)]
@@ -45,14 +46,6 @@ fn parse_migration_filenames() -> BackendResult<Vec<String>> {
     Ok(entries.into_iter().map(|(_, v)| v).collect())
 }
 
-#[allow(dead_code)]
-pub fn backup_db(source_conn: &mut Connection, backup_db_path: &str) -> BackendResult<()> {
-    let mut backup_conn = Connection::open(backup_db_path)?;
-    let bk = backup::Backup::new(source_conn, &mut backup_conn)?;
-    bk.step(-1)?;
-    Ok(())
-}
-
 fn execute_ignoring_duplicate_column<T, E>(f: impl FnOnce() -> Result<T, E>) -> Result<Option<T>, E>
 where
     E: std::fmt::Display,
@@ -79,8 +72,8 @@ pub fn run_migration(tx: &mut rusqlite::Transaction, migration_file: &str) -> Ba
     Ok(())
 }
 
-// TODO: run backup
-pub fn migrate(conn: &mut Connection, _backup_db_path: &str) -> BackendResult<()> {
+// the database is snapshotted into the backups directory before it is migrated
+pub fn migrate(conn: &mut Connection, backups_path: &str) -> BackendResult<()> {
     let current_version = get_current_db_version(conn)?;
     let migration_files = parse_migration_filenames()?;
     if migration_files.is_empty() {
@@ -88,7 +81,10 @@ pub fn migrate(conn: &mut Connection, _backup_db_path: &str) -> BackendResult<()
     }
     let latest_version = migration_files.len() as u64;
     if current_version < latest_version {
-        //backup_db(conn, backup_db_path)?;
+        // a new database has nothing to lose
+        if current_version > 0 {
+            create_pre_migration_snapshot(conn, backups_path)?;
+        }
         let mut tx = conn.transaction()?;
         for migration_file in migration_files.iter().skip(current_version as usize) {
             run_migration(&mut tx, migration_file)?;
@@ -101,6 +97,7 @@ pub fn migrate(conn: &mut Connection, _backup_db_path: &str) -> BackendResult<()
 
 #[cfg(test)]
 mod tests {
+    use super::super::backup::backup_db;
     use super::*;
     use rusqlite::Connection;
     use tempfile::tempdir;
@@ -165,7 +162,7 @@ mod tests {
 
     #[test]
     fn test_backup_db() {
-        let (mut conn, temp_dir) = setup_test_db();
+        let (conn, temp_dir) = setup_test_db();
 
         conn.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, name TEXT)", [])
             .unwrap();
@@ -173,7 +170,7 @@ mod tests {
             .unwrap();
 
         let backup_path = temp_dir.path().join("backup.db");
-        backup_db(&mut conn, backup_path.to_str().unwrap()).unwrap();
+        backup_db(&conn, backup_path.to_str().unwrap()).unwrap();
 
         assert!(backup_path.exists());
 
@@ -1,9 +1,16 @@
+pub mod agent_runs;
 pub mod ai_sessions;
 pub mod apps;
+pub mod backup;
+pub mod custom_agents;
 pub mod db;
 pub mod embedding_resources;
 pub mod history_entries;
+pub mod history_imports;
+pub mod history_stats;
 pub mod kv;
+pub mod mcp_servers;
+pub mod memories;
 pub mod models;
 pub mod post_processing_jobs;
 pub mod resource_content_hash;
@@ -13,5 +20,7 @@ pub mod resource_text_content;
 pub mod resources;
 pub mod search;
 pub mod spaces;
+pub mod tool_policies;
+pub mod watch_folders;
 
 mod migrations;
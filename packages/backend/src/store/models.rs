@@ -12,6 +12,10 @@ pub fn default_horizon_tint() -> String {
     "hsl(275, 40%, 80%)".to_owned()
 }
 
+pub fn default_true() -> bool {
+    true
+}
+
 pub fn current_time() -> chrono::DateTime<chrono::Utc> {
     chrono::Utc::now()
 }
@@ -655,6 +659,126 @@ pub struct HistoryEntry {
     pub created_at: chrono::DateTime<chrono::Utc>,
     #[serde(default = "current_time")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    // the browser profile an imported entry comes from
+    #[serde(default)]
+    pub source_profile: Option<String>,
+    // imported entries are one per url with their visits aggregated
+    #[serde(default = "default_visit_count")]
+    pub visit_count: u32,
+    // visits where the url was typed into the address bar
+    #[serde(default)]
+    pub typed_count: u32,
+    #[serde(default)]
+    pub last_visited_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub last_transition: Option<HistoryVisitTransition>,
+}
+
+pub fn default_visit_count() -> u32 {
+    1
+}
+
+// how the user got to a page, from the browser's visit records
+#[derive(Debug, PartialEq, Serialize, Deserialize, EnumString, strum::AsRefStr, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryVisitTransition {
+    Link,
+    Typed,
+    Bookmark,
+    Redirect,
+    Reload,
+    // e.g. a search from the address bar or a form submission
+    Generated,
+    Other,
+}
+
+// how far the history of a browser profile has been imported, in the browser's own units
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryImportMark {
+    pub browser_type: String,
+    pub profile_id: String,
+    pub last_visit_id: i64,
+    pub last_visit_time: i64,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn default_history_stats_limit() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryStatsQuery {
+    // unix timestamps in milliseconds, like the `since` of the history searches
+    #[serde(default)]
+    pub since: Option<f64>,
+    #[serde(default)]
+    pub until: Option<f64>,
+    // minutes east of UTC, the daily and hourly activity is in local time
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    // only visits to domains starting with the prefix, e.g. for suggestions
+    #[serde(default)]
+    pub domain_prefix: Option<String>,
+    // of the top domains, urls and searches
+    #[serde(default = "default_history_stats_limit")]
+    pub limit: usize,
+}
+
+impl Default for HistoryStatsQuery {
+    fn default() -> Self {
+        HistoryStatsQuery {
+            since: None,
+            until: None,
+            utc_offset_minutes: 0,
+            domain_prefix: None,
+            limit: default_history_stats_limit(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DomainStats {
+    pub domain: String,
+    pub visit_count: u64,
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct UrlStats {
+    pub url: String,
+    pub title: Option<String>,
+    pub visit_count: u64,
+    pub last_visited_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DailyActivity {
+    pub date: chrono::NaiveDate,
+    pub visit_count: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SearchQueryStats {
+    pub query: String,
+    pub count: u64,
+    pub last_searched_at: chrono::DateTime<chrono::Utc>,
+}
+
+// imported entries only know how often their url was visited and the first and the last
+// visit, so the daily and hourly activity of imported history is approximate
+#[derive(Debug, Serialize)]
+pub struct HistoryStats {
+    pub total_visits: u64,
+    pub top_domains: Vec<DomainStats>,
+    pub top_urls: Vec<UrlStats>,
+    // every day from the first to the last visit, days without visits included
+    pub daily_activity: Vec<DailyActivity>,
+    // visits per hour of the day, starting at midnight
+    pub hourly_activity: Vec<u64>,
+    pub top_searches: Vec<SearchQueryStats>,
 }
 
 // this is needed because one resource can have multiple embeddings
@@ -875,6 +999,256 @@ pub struct CompositeSpace {
     pub space_entries: Vec<SpaceEntry>,
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize, EnumString, strum::AsRefStr, Clone)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunStatus {
+    // also the status of runs that crashed
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, EnumString, strum::AsRefStr, Clone)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunIterationOutcome {
+    ToolCalls,
+    FinalAnswer,
+    ParseError,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, EnumString, strum::AsRefStr, Clone)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunEventType {
+    // status message shown to the user
+    Status,
+    // change to the context, e.g. a scraped url
+    Context,
+}
+
+macro_rules! impl_sql_for_str_enum {
+    ($($t:ty),*) => {$(
+        impl ToSql for $t {
+            fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+                Ok(rusqlite::types::ToSqlOutput::from(self.as_ref()))
+            }
+        }
+
+        impl FromSql for $t {
+            fn column_result(
+                value: rusqlite::types::ValueRef,
+            ) -> rusqlite::types::FromSqlResult<Self> {
+                let s = String::column_result(value)?;
+                <$t>::from_str(&s).map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+            }
+        }
+    )*};
+}
+
+// what happens when an agent wants to call a tool
+#[derive(Debug, PartialEq, Serialize, Deserialize, EnumString, strum::AsRefStr, Clone)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ToolPolicy {
+    AlwaysAllow,
+    // the user has to approve every call
+    Ask,
+    Deny,
+}
+
+impl_sql_for_str_enum!(
+    AgentRunStatus,
+    AgentRunIterationOutcome,
+    AgentRunEventType,
+    ToolPolicy,
+    HistoryVisitTransition
+);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRun {
+    // the execution id of the run
+    pub id: String,
+    pub note_id: String,
+    // json serialized `Model`
+    pub model: String,
+    pub user_message: String,
+    pub status: AgentRunStatus,
+    pub final_answer: Option<String>,
+    pub error: Option<String>,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRunIteration {
+    pub run_id: String,
+    pub iteration: i64,
+    pub outcome: AgentRunIterationOutcome,
+    // raw model output
+    pub response: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRunToolCall {
+    pub run_id: String,
+    pub iteration: i64,
+    // order of the call within the iteration
+    pub position: i64,
+    pub tool_name: String,
+    pub arguments: String,
+    pub result: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRunEvent {
+    pub run_id: String,
+    pub iteration: i64,
+    pub event_type: AgentRunEventType,
+    // json
+    pub payload: String,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRunTranscript {
+    pub run: AgentRun,
+    pub iterations: Vec<AgentRunIteration>,
+    pub tool_calls: Vec<AgentRunToolCall>,
+    pub events: Vec<AgentRunEvent>,
+}
+
+// an agent defined by the user, exposed to the lead agent as a tool with its name
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomAgent {
+    #[serde(default = "random_uuid")]
+    pub id: String,
+    pub name: String,
+    // tells the lead agent when to use it
+    pub description: String,
+    pub system_prompt: String,
+    // json serialized `Model`, the model of the calling agent if not set
+    pub model: Option<String>,
+    // names of built-in or custom tools the agent may use
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    pub max_iterations: i64,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a tool implemented by a js function registered under the tool's name
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomTool {
+    #[serde(default = "random_uuid")]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // json schema of the arguments the function is called with
+    pub parameters_schema: String,
+    pub execution_message: Option<String>,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a Model Context Protocol server started as a child process and talked to over stdio
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct McpServer {
+    #[serde(default = "random_uuid")]
+    pub id: String,
+    // prefixes the names of the server's tools
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: std::collections::HashMap<String, String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a local directory whose files are added to a space as resources
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchFolder {
+    #[serde(default = "random_uuid")]
+    pub id: String,
+    pub path: String,
+    pub space_id: String,
+    // removing a file also removes the resource that was created for it
+    #[serde(default)]
+    pub propagate_deletions: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a file seen in a watch folder and the resource it was added as
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedFile {
+    pub folder_id: String,
+    // relative to the watch folder, with `/` separators
+    pub path: String,
+    pub resource_id: String,
+    pub size: i64,
+    // unix timestamp in milliseconds
+    pub modified_at: i64,
+    pub content_hash: String,
+    // false if the file matched a resource that was already in the library
+    pub created_resource: bool,
+}
+
+// something the assistant remembers about the user across chats and agent runs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Memory {
+    #[serde(default = "random_uuid")]
+    pub id: String,
+    pub content: String,
+    // of the content, used to pick the memories relevant to a request
+    #[serde(skip)]
+    pub embedding: Vec<f32>,
+    #[serde(default = "current_time")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default = "current_time")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// agent and tool names are used as tool names in prompts
+pub fn validate_custom_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 64 {
+        return Err(format!("name must be 1 to 64 characters long: '{}'", name));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!(
+            "name may only contain lowercase letters, digits and underscores: '{}'",
+            name
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
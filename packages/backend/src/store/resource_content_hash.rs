@@ -23,6 +23,20 @@ impl Database {
         Ok(hash)
     }
 
+    // a resource in the library with the same content, hashes of removed
+    // resources are left behind so the resource has to exist too
+    pub fn get_resource_id_by_hash(&self, hash: &str) -> BackendResult<Option<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT H.resource_id FROM resource_content_hashes H
+            JOIN resources R ON R.id = H.resource_id
+            WHERE H.content_hash = ? AND R.deleted = 0
+            ORDER BY R.created_at ASC
+            LIMIT 1",
+        )?;
+        let resource_id = stmt.query_row([hash], |row| row.get(0)).optional()?;
+        Ok(resource_id)
+    }
+
     pub fn delete_resource_hash_tx(
         tx: &mut rusqlite::Transaction,
         resource_id: &str,
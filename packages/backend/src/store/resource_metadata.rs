@@ -59,6 +59,18 @@ impl Database {
             .map_err(|e| e.into())
     }
 
+    // ids of the resources that are not deleted and were saved from the uri
+    pub fn list_resource_ids_by_source_uri(&self, source_uri: &str) -> BackendResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT M.resource_id FROM resource_metadata M
+            JOIN resources R ON R.id = M.resource_id
+            WHERE M.source_uri = ?1 AND R.deleted = 0",
+        )?;
+        let ids = stmt.query_map(rusqlite::params![source_uri], |row| row.get(0))?;
+        ids.collect::<rusqlite::Result<Vec<String>>>()
+            .map_err(|e| e.into())
+    }
+
     pub fn list_resources_metadata_by_ids(
         &self,
         resource_ids: &[String],
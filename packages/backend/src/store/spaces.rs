@@ -241,6 +241,55 @@ impl Database {
         Ok(())
     }
 
+    pub fn has_space_entry_tx(
+        tx: &mut rusqlite::Transaction,
+        space_id: &str,
+        resource_id: &str,
+    ) -> BackendResult<bool> {
+        let mut stmt = tx.prepare(
+            "SELECT 1 FROM space_entries WHERE space_id = ?1 AND resource_id = ?2 LIMIT 1",
+        )?;
+        Ok(stmt.exists(rusqlite::params![space_id, resource_id])?)
+    }
+
+    pub fn list_all_space_entries(&self) -> BackendResult<Vec<SpaceEntry>> {
+        let mut stmt = self.conn.prepare("SELECT id, space_id, resource_id, created_at, updated_at, manually_added FROM space_entries ORDER BY created_at ASC")?;
+        let space_entries = stmt.query_map([], |row| {
+            Ok(SpaceEntry {
+                id: row.get(0)?,
+                space_id: row.get(1)?,
+                resource_id: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+                manually_added: row.get(5)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for entry in space_entries {
+            result.push(entry?);
+        }
+        Ok(result)
+    }
+
+    pub fn list_all_sub_space_entries(&self) -> BackendResult<Vec<SubSpaceEntry>> {
+        let mut stmt = self.conn.prepare("SELECT id, parent_space_id, child_space_id, created_at, updated_at, manually_added FROM sub_space_entries ORDER BY created_at ASC")?;
+        let sub_space_entries = stmt.query_map([], |row| {
+            Ok(SubSpaceEntry {
+                id: row.get(0)?,
+                parent_space_id: row.get(1)?,
+                child_space_id: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+                manually_added: row.get(5)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for entry in sub_space_entries {
+            result.push(entry?);
+        }
+        Ok(result)
+    }
+
     pub fn update_space_entry(&mut self, space_entry: &SpaceEntry) -> BackendResult<()> {
         self.conn.execute(
             "UPDATE space_entries SET space_id = ?2, resource_id = ?3, created_at = ?4, updated_at = ?5, manually_added = ?6 WHERE id = ?1",
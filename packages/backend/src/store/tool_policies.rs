@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use super::models::*;
+use crate::{store::db::Database, BackendResult};
+
+impl Database {
+    // `None` goes back to the default policy for the tool's risk level
+    pub fn set_tool_policy(
+        &mut self,
+        tool_name: &str,
+        policy: Option<&ToolPolicy>,
+    ) -> BackendResult<()> {
+        match policy {
+            Some(policy) => self.conn.execute(
+                "INSERT INTO tool_policies (tool_name, policy, updated_at) VALUES (?1, ?2, ?3)
+                ON CONFLICT(tool_name) DO UPDATE SET policy = ?2, updated_at = ?3",
+                rusqlite::params![tool_name, policy, current_time()],
+            )?,
+            None => self.conn.execute(
+                "DELETE FROM tool_policies WHERE tool_name = ?1",
+                rusqlite::params![tool_name],
+            )?,
+        };
+        Ok(())
+    }
+
+    pub fn list_tool_policies(&self) -> BackendResult<HashMap<String, ToolPolicy>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tool_name, policy FROM tool_policies")?;
+        let policies = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut result = HashMap::new();
+        for policy in policies {
+            let (tool_name, policy) = policy?;
+            result.insert(tool_name, policy);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::Database;
+    use crate::store::models::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tool_policies() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+
+        db.set_tool_policy("tracker_search_issues", Some(&ToolPolicy::Ask))
+            .unwrap();
+        db.set_tool_policy("tracker_search_issues", Some(&ToolPolicy::Deny))
+            .unwrap();
+        db.set_tool_policy("library_search", Some(&ToolPolicy::AlwaysAllow))
+            .unwrap();
+        let policies = db.list_tool_policies().unwrap();
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies["tracker_search_issues"], ToolPolicy::Deny);
+
+        db.set_tool_policy("library_search", None).unwrap();
+        assert!(!db
+            .list_tool_policies()
+            .unwrap()
+            .contains_key("library_search"));
+    }
+}
@@ -0,0 +1,151 @@
+use super::models::*;
+use crate::{store::db::Database, BackendError, BackendResult};
+use rusqlite::OptionalExtension;
+
+fn watch_folder_from_row(row: &rusqlite::Row) -> rusqlite::Result<WatchFolder> {
+    Ok(WatchFolder {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        space_id: row.get(2)?,
+        propagate_deletions: row.get(3)?,
+        enabled: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+impl Database {
+    // replaces the folder with the same id
+    pub fn upsert_watch_folder(&mut self, folder: &WatchFolder) -> BackendResult<()> {
+        if folder.path.trim().is_empty() {
+            return Err(BackendError::GenericError(
+                "watch folder path must not be empty".to_string(),
+            ));
+        }
+        self.conn.execute(
+            "INSERT INTO watch_folders (id, path, space_id, propagate_deletions, enabled, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(id) DO UPDATE SET path = ?2, space_id = ?3, propagate_deletions = ?4,
+                enabled = ?5, updated_at = ?7",
+            rusqlite::params![
+                folder.id,
+                folder.path,
+                folder.space_id,
+                folder.propagate_deletions,
+                folder.enabled,
+                folder.created_at,
+                current_time()
+            ],
+        )?;
+        Ok(())
+    }
+
+    // the resources that were created for the files are kept
+    pub fn delete_watch_folder(&mut self, id: &str) -> BackendResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM watched_files WHERE folder_id = ?1",
+            rusqlite::params![id],
+        )?;
+        tx.execute(
+            "DELETE FROM watch_folders WHERE id = ?1",
+            rusqlite::params![id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_watch_folder(&self, id: &str) -> BackendResult<Option<WatchFolder>> {
+        self.conn
+            .query_row(
+                "SELECT id, path, space_id, propagate_deletions, enabled, created_at, updated_at
+                FROM watch_folders WHERE id = ?1",
+                rusqlite::params![id],
+                watch_folder_from_row,
+            )
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    pub fn list_watch_folders(&self) -> BackendResult<Vec<WatchFolder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, space_id, propagate_deletions, enabled, created_at, updated_at
+            FROM watch_folders ORDER BY created_at ASC",
+        )?;
+        let folders = stmt.query_map([], watch_folder_from_row)?;
+        let mut result = Vec::new();
+        for folder in folders {
+            result.push(folder?);
+        }
+        Ok(result)
+    }
+
+    pub fn list_watched_files(&self, folder_id: &str) -> BackendResult<Vec<WatchedFile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT folder_id, path, resource_id, size, modified_at, content_hash, created_resource
+            FROM watched_files WHERE folder_id = ?1",
+        )?;
+        let files = stmt.query_map(rusqlite::params![folder_id], |row| {
+            Ok(WatchedFile {
+                folder_id: row.get(0)?,
+                path: row.get(1)?,
+                resource_id: row.get(2)?,
+                size: row.get(3)?,
+                modified_at: row.get(4)?,
+                content_hash: row.get(5)?,
+                created_resource: row.get(6)?,
+            })
+        })?;
+        let mut result = Vec::new();
+        for file in files {
+            result.push(file?);
+        }
+        Ok(result)
+    }
+
+    pub fn upsert_watched_file_tx(
+        tx: &mut rusqlite::Transaction,
+        file: &WatchedFile,
+    ) -> BackendResult<()> {
+        tx.execute(
+            "INSERT OR REPLACE INTO watched_files
+            (folder_id, path, resource_id, size, modified_at, content_hash, created_resource)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                file.folder_id,
+                file.path,
+                file.resource_id,
+                file.size,
+                file.modified_at,
+                file.content_hash,
+                file.created_resource
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_watched_file_tx(
+        tx: &mut rusqlite::Transaction,
+        folder_id: &str,
+        path: &str,
+    ) -> BackendResult<()> {
+        tx.execute(
+            "DELETE FROM watched_files WHERE folder_id = ?1 AND path = ?2",
+            rusqlite::params![folder_id, path],
+        )?;
+        Ok(())
+    }
+
+    // makes another watched file with the resource its owner, false if no other file has it
+    pub fn adopt_watched_resource_tx(
+        tx: &mut rusqlite::Transaction,
+        resource_id: &str,
+    ) -> BackendResult<bool> {
+        let updated = tx.execute(
+            "UPDATE watched_files SET created_resource = 1
+            WHERE rowid = (SELECT rowid FROM watched_files WHERE resource_id = ?1 LIMIT 1)",
+            rusqlite::params![resource_id],
+        )?;
+        Ok(updated > 0)
+    }
+}
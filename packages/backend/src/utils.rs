@@ -22,7 +22,7 @@ pub fn uuid_to_base62(uuid: &str) -> String {
 
 /// Sanitize a filename to be safe for filesystem storage
 pub fn sanitize_filename(name: &str) -> String {
-    let re = Regex::new(r#"[<>:"\\|?*\x00-\x1F]"#).unwrap();
+    let re = Regex::new(r#"[<>:"/\\|?*\x00-\x1F]"#).unwrap();
     let sanitized = re.replace_all(name, "-").into_owned();
     let sanitized = sanitized.replace(|c: char| c.is_whitespace(), " "); // Normalize spaces
 
@@ -107,6 +107,27 @@ pub fn get_resource_file_extension(resource_type: &str) -> String {
         .to_string()
 }
 
+/// Parse the double quoted attributes of an html tag, names are lowercased
+pub fn parse_html_attributes(attributes: &str) -> std::collections::HashMap<String, String> {
+    let re = Regex::new(r#"(?i)([a-z_-]+)\s*=\s*"([^"]*)""#).unwrap();
+    re.captures_iter(attributes)
+        .map(|c| {
+            (
+                c[1].to_lowercase(),
+                html_escape::decode_html_entities(&c[2]).to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Strip the tags from an html snippet and decode its entities
+pub fn html_to_text(html: &str) -> String {
+    let re = Regex::new(r"<[^>]*>").unwrap();
+    html_escape::decode_html_entities(&re.replace_all(html, ""))
+        .trim()
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,6 +161,7 @@ mod tests {
     fn test_sanitize_filename() {
         assert_eq!(sanitize_filename("test.txt"), "test.txt");
         assert_eq!(sanitize_filename("...test"), "test");
+        assert_eq!(sanitize_filename("https://deta.space"), "https---deta.space");
     }
 
     #[test]
@@ -0,0 +1,118 @@
+use crate::{
+    api::message::{AgentRunMessage, TunnelOneshot},
+    store::models::{AgentRun, AgentRunTranscript},
+    worker::{send_worker_response, Worker},
+    BackendError, BackendResult,
+};
+
+#[cfg(feature = "wip")]
+use crate::ai::{
+    brain::{
+        agents::budget::AgentBudget, context::LLMContext, io::NoteIO, js_tools::JSToolRegistry,
+        orchestrator::Orchestrator,
+    },
+    llm::client::{CancellationToken, Model},
+};
+#[cfg(feature = "wip")]
+use neon::prelude::{JsFunction, Root};
+#[cfg(feature = "wip")]
+use std::sync::Arc;
+
+impl Worker {
+    pub fn list_agent_runs(
+        &self,
+        note_id: Option<&str>,
+        limit: Option<i64>,
+    ) -> BackendResult<Vec<AgentRun>> {
+        self.db.list_agent_runs(note_id, limit)
+    }
+
+    pub fn get_agent_run(&self, run_id: &str) -> BackendResult<AgentRunTranscript> {
+        self.db
+            .get_agent_run_transcript(run_id)?
+            .ok_or_else(|| BackendError::GenericError(format!("agent run not found: {}", run_id)))
+    }
+
+    // the run is resumed with the model it was started with, in a fresh context of its note
+    #[cfg(feature = "wip")]
+    pub fn resume_agent_run(
+        &self,
+        run_id: &str,
+        custom_key: Option<String>,
+        stream_callback: Root<JsFunction>,
+        status_callback: Root<JsFunction>,
+        cancellation_token: CancellationToken,
+    ) -> BackendResult<String> {
+        let run = self.get_agent_run(run_id)?.run;
+        let model: Model = serde_json::from_str(&run.model)?;
+
+        // TODO: use the tools the frontend registered once it registers them with the worker
+        let js_tool_registry = Arc::new(JSToolRegistry::new());
+        let orchestrator = Orchestrator::new(
+            String::new(),
+            String::new(),
+            model,
+            js_tool_registry.clone(),
+            &self.db_path,
+            self.ai.clone(),
+        )?;
+        let io = NoteIO::new(
+            &self.resources_path,
+            run.note_id.clone(),
+            stream_callback,
+            status_callback,
+            self.channel.clone(),
+            None,
+        )?;
+        let mut context = LLMContext::new(
+            &self.db_path,
+            js_tool_registry,
+            run.note_id,
+            &[],
+            &[],
+            Some(self.language_setting.clone()),
+        )?;
+        orchestrator.resume_lead_agent(
+            run_id,
+            custom_key,
+            AgentBudget::default(),
+            &io,
+            &mut context,
+            cancellation_token,
+        )
+    }
+}
+
+pub fn handle_agent_run_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: AgentRunMessage,
+) {
+    match message {
+        AgentRunMessage::ListAgentRuns(note_id, limit) => {
+            let result = worker.list_agent_runs(note_id.as_deref(), limit);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        AgentRunMessage::GetAgentRun(run_id) => {
+            let result = worker.get_agent_run(&run_id);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        #[cfg(feature = "wip")]
+        AgentRunMessage::ResumeAgentRun {
+            run_id,
+            custom_key,
+            stream_callback,
+            status_callback,
+            cancellation_token,
+        } => {
+            let result = worker.resume_agent_run(
+                &run_id,
+                custom_key,
+                stream_callback,
+                status_callback,
+                cancellation_token,
+            );
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
@@ -0,0 +1,808 @@
+use chrono::{DateTime, Utc};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, Write};
+use std::path::Path;
+use std::str::FromStr;
+use zip::{result::ZipError, write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    api::message::{ArchiveMessage, TunnelOneshot},
+    store::{
+        db::Database,
+        models::{
+            current_time, random_uuid, AIChatSession, AIChatSessionMessage, App, Resource,
+            ResourceMetadata, ResourceTag, ResourceTextContent, Space, SpaceEntry, SubSpaceEntry,
+        },
+    },
+    worker::{send_worker_response, Worker},
+    BackendError, BackendResult,
+};
+
+pub const ARCHIVE_FORMAT: &str = "surf-library";
+// bump when the manifest changes in a way older versions can't read
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const README_FILE: &str = "README.md";
+const README: &str = "# Surf library archive
+
+This archive holds a full Surf library and can be imported into another
+Surf installation, existing data in the library is kept.
+
+- `manifest.json` describes the library, see below
+- `resources/<resource id>/<file name>` are the files of the resources
+
+The manifest is a JSON object with the fields:
+
+- `format`: always `surf-library`
+- `version`: the version of the format, importers reject newer versions
+- `exported_at`: when the archive was created, RFC 3339
+- `resources`: the resources with their `metadata`, `tags` and extracted
+  `text_content`, `file` is the path of the resource file in the archive or
+  null if the file was missing when the archive was created
+- `spaces`: the spaces, `name` holds the space's settings as JSON
+- `space_entries`: the resources in each space
+- `sub_space_entries`: the spaces nested in other spaces
+- `chats`: the chat sessions with their `messages`
+- `apps`: the generated apps
+- `kv`: the key value store, an object of tables mapping keys to JSON values
+
+Ids are UUIDs. When an id is already taken in the library the archive is
+imported into, the entity either keeps the existing one or is imported under
+a new id, references to it in the archive are rewritten to the new id. In the
+key value store only the values of `id` fields and fields ending in `Id`, `_id`,
+`Ids` or `_ids` are taken as references.
+";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedResource {
+    #[serde(flatten)]
+    pub resource: Resource,
+    // the path of the resource file in the archive
+    pub file: Option<String>,
+    pub metadata: Option<ResourceMetadata>,
+    #[serde(default)]
+    pub tags: Vec<ResourceTag>,
+    #[serde(default)]
+    pub text_content: Vec<ResourceTextContent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedChat {
+    #[serde(flatten)]
+    pub session: AIChatSession,
+    #[serde(default)]
+    pub messages: Vec<AIChatSessionMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryManifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub resources: Vec<ArchivedResource>,
+    #[serde(default)]
+    pub spaces: Vec<Space>,
+    #[serde(default)]
+    pub space_entries: Vec<SpaceEntry>,
+    #[serde(default)]
+    pub sub_space_entries: Vec<SubSpaceEntry>,
+    #[serde(default)]
+    pub chats: Vec<ArchivedChat>,
+    #[serde(default)]
+    pub apps: Vec<App>,
+    #[serde(default)]
+    pub kv: BTreeMap<String, BTreeMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LibraryExportSummary {
+    pub resources: usize,
+    pub spaces: usize,
+    pub chats: usize,
+    pub apps: usize,
+    pub kv_entries: usize,
+    // resources whose file couldn't be found, they are exported without one
+    pub missing_files: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportCounts {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LibraryImportSummary {
+    pub resources: ImportCounts,
+    pub spaces: ImportCounts,
+    pub space_entries: ImportCounts,
+    pub chats: ImportCounts,
+    pub apps: ImportCounts,
+    pub kv_entries: ImportCounts,
+    // resources whose file is not in the archive
+    pub missing_files: Vec<String>,
+}
+
+// what to do with archived entities whose id is already in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ConflictStrategy {
+    // keep the existing entity, references to it point to the existing one
+    Skip,
+    // import the entity under a new id
+    Duplicate,
+}
+
+impl ConflictStrategy {
+    // the id to import an entity under or none if it should be skipped,
+    // new ids are recorded so references can be rewritten
+    fn import_id(
+        self,
+        id: &str,
+        exists: bool,
+        remapped: &mut HashMap<String, String>,
+    ) -> Option<String> {
+        if !exists {
+            return Some(id.to_string());
+        }
+        match self {
+            ConflictStrategy::Skip => None,
+            ConflictStrategy::Duplicate => {
+                let new_id = random_uuid();
+                remapped.insert(id.to_string(), new_id.clone());
+                Some(new_id)
+            }
+        }
+    }
+}
+
+// tags whose value is the id of another entity in the library
+const ID_TAGS: &[&str] = &[
+    "annotates",
+    "horizonId",
+    "linkedChat",
+    "previewImageResource",
+];
+
+fn remap_id(id: &str, remapped: &HashMap<String, String>) -> String {
+    remapped.get(id).cloned().unwrap_or_else(|| id.to_string())
+}
+
+fn remap_tag_value(tag: &ResourceTag, remapped: &HashMap<String, String>) -> String {
+    if ID_TAGS.contains(&tag.tag_name.as_str()) {
+        remap_id(&tag.tag_value, remapped)
+    } else {
+        tag.tag_value.clone()
+    }
+}
+
+// rewrites the ids cited in a chat message, `<citation>id</citation>`
+fn remap_citation_ids(text: &str, remapped: &HashMap<String, String>) -> String {
+    if remapped.is_empty() {
+        return text.to_string();
+    }
+    let re = Regex::new(r"(?s)(<citation\b[^>]*>)([^<]*)(</citation>)").unwrap();
+    re.replace_all(text, |c: &Captures| {
+        format!("{}{}{}", &c[1], remap_id(c[2].trim(), remapped), &c[3])
+    })
+    .to_string()
+}
+
+// keys that hold ids, e.g. `id`, `spaceId`, `resource_id` or `resourceIds`
+fn is_id_key(key: &str) -> bool {
+    ["id", "ids"].contains(&key)
+        || ["Id", "_id", "Ids", "_ids"]
+            .iter()
+            .any(|suffix| key.ends_with(suffix))
+}
+
+// rewrites the remapped ids in the id fields of the json, other strings are kept
+// even if they look like an id
+fn remap_json_ids(value: &mut serde_json::Value, remapped: &HashMap<String, String>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    serde_json::Value::String(id) if is_id_key(key) => {
+                        *id = remap_id(id, remapped);
+                    }
+                    serde_json::Value::Array(ids) if is_id_key(key) => {
+                        for id in ids.iter_mut() {
+                            match id {
+                                serde_json::Value::String(id) => *id = remap_id(id, remapped),
+                                other => remap_json_ids(other, remapped),
+                            }
+                        }
+                    }
+                    other => remap_json_ids(other, remapped),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                remap_json_ids(value, remapped);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn archive_file_name(resource: &Resource) -> Option<String> {
+    Path::new(&resource.resource_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+fn add_archive_file<W: Write + Seek>(
+    writer: &mut ZipWriter<W>,
+    name: &str,
+    data: &[u8],
+) -> BackendResult<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(data.len() as u64 >= u32::MAX as u64);
+    writer.start_file(name, options)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+// the manifest and the archive to read the other files from, one at a time
+fn read_archive<R: Read + Seek>(reader: R) -> BackendResult<(LibraryManifest, ZipArchive<R>)> {
+    let mut archive = ZipArchive::new(reader)?;
+    let manifest: LibraryManifest = match archive.by_name(MANIFEST_FILE) {
+        Ok(file) => serde_json::from_reader(file)?,
+        Err(ZipError::FileNotFound) => {
+            return Err(BackendError::GenericError(
+                "Not a library archive: manifest.json is missing".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(BackendError::GenericError(format!(
+            "Not a library archive: unknown format {}",
+            manifest.format
+        )));
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(BackendError::GenericError(format!(
+            "Library archive version {} is newer than the supported version {}",
+            manifest.version, ARCHIVE_VERSION
+        )));
+    }
+    Ok((manifest, archive))
+}
+
+impl Worker {
+    // writes the whole library into a zip archive at the path, deleted resources are left out
+    pub fn export_library(&mut self, path: &str) -> BackendResult<LibraryExportSummary> {
+        // the archive only replaces an existing file once it's complete
+        let partial_path = format!("{}.partial", path);
+        match self.write_library_archive(&partial_path) {
+            Ok(summary) => {
+                fs::rename(&partial_path, path)?;
+                Ok(summary)
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&partial_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn write_library_archive(&mut self, path: &str) -> BackendResult<LibraryExportSummary> {
+        let mut summary = LibraryExportSummary::default();
+        let mut writer = ZipWriter::new(BufWriter::new(fs::File::create(path)?));
+
+        let mut resources = Vec::new();
+        for resource in self.db.list_all_resources(0)? {
+            let mut file = None;
+            if let Some(name) = archive_file_name(&resource) {
+                match fs::read(&resource.resource_path) {
+                    Ok(data) => {
+                        let archive_path = format!("resources/{}/{}", resource.id, name);
+                        add_archive_file(&mut writer, &archive_path, &data)?;
+                        file = Some(archive_path);
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if file.is_none() {
+                summary.missing_files.push(resource.id.clone());
+            }
+            resources.push(ArchivedResource {
+                file,
+                metadata: self.db.get_resource_metadata_by_resource_id(&resource.id)?,
+                tags: self.db.list_resource_tags(&resource.id)?,
+                text_content: self
+                    .db
+                    .list_resource_text_content_by_resource_id(&resource.id)?,
+                resource,
+            });
+        }
+        let resource_ids: HashSet<&str> = resources
+            .iter()
+            .map(|archived| archived.resource.id.as_str())
+            .collect();
+
+        let spaces: Vec<Space> = self
+            .db
+            .list_spaces()?
+            .into_iter()
+            .map(|space| Space {
+                id: space.id,
+                name: space.name,
+                created_at: space.created_at,
+                updated_at: space.updated_at,
+            })
+            .collect();
+        let space_entries = self
+            .db
+            .list_all_space_entries()?
+            .into_iter()
+            .filter(|entry| resource_ids.contains(entry.resource_id.as_str()))
+            .collect();
+
+        let mut chats = Vec::new();
+        for session in self.db.list_ai_sessions(None)? {
+            chats.push(ArchivedChat {
+                messages: self.db.list_ai_session_messages(&session.id)?,
+                session,
+            });
+        }
+
+        let mut kv = BTreeMap::new();
+        for table in self.kv.list_tables()? {
+            let mut entries = BTreeMap::new();
+            for (key, data) in self.kv.list_entries(&table)? {
+                entries.insert(key, serde_json::from_str(&data)?);
+            }
+            summary.kv_entries += entries.len();
+            kv.insert(table, entries);
+        }
+
+        summary.resources = resources.len();
+        summary.spaces = spaces.len();
+        summary.chats = chats.len();
+        let manifest = LibraryManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            exported_at: current_time(),
+            resources,
+            spaces,
+            space_entries,
+            sub_space_entries: self.db.list_all_sub_space_entries()?,
+            chats,
+            apps: self.db.list_apps()?,
+            kv,
+        };
+        summary.apps = manifest.apps.len();
+
+        add_archive_file(
+            &mut writer,
+            MANIFEST_FILE,
+            &serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        add_archive_file(&mut writer, README_FILE, README.as_bytes())?;
+        writer.finish()?.flush()?;
+        Ok(summary)
+    }
+
+    // merges a library archive into the library, entities that are already in the
+    // library are handled according to the strategy, `skip` by default
+    pub fn import_library(
+        &mut self,
+        path: &str,
+        strategy: Option<&str>,
+    ) -> BackendResult<LibraryImportSummary> {
+        let strategy = match strategy {
+            Some(strategy) => ConflictStrategy::from_str(strategy).map_err(|_| {
+                BackendError::GenericError(format!("Unsupported conflict strategy: {}", strategy))
+            })?,
+            None => ConflictStrategy::Skip,
+        };
+        let (manifest, mut archive) = read_archive(BufReader::new(fs::File::open(path)?))?;
+        let archived_files: HashSet<String> = archive.file_names().map(String::from).collect();
+        let mut summary = LibraryImportSummary::default();
+
+        // all ids are resolved first so references between entities can be rewritten
+        let mut remapped = HashMap::new();
+        let mut resources = Vec::new();
+        let mut imported_resources = HashSet::new();
+        for archived in &manifest.resources {
+            let exists = self.db.get_resource(&archived.resource.id)?.is_some();
+            match strategy.import_id(&archived.resource.id, exists, &mut remapped) {
+                Some(id) => resources.push((id, archived)),
+                None => summary.resources.skipped += 1,
+            }
+            imported_resources.insert(archived.resource.id.as_str());
+        }
+        let mut spaces = Vec::new();
+        let mut imported_spaces = HashSet::new();
+        for space in &manifest.spaces {
+            let exists = self.db.get_space(&space.id)?.is_some();
+            match strategy.import_id(&space.id, exists, &mut remapped) {
+                Some(id) => spaces.push(Space {
+                    id,
+                    ..space.clone()
+                }),
+                None => summary.spaces.skipped += 1,
+            }
+            imported_spaces.insert(space.id.as_str());
+        }
+        let mut chats = Vec::new();
+        for chat in &manifest.chats {
+            let exists = self.db.get_ai_session(&chat.session.id)?.is_some();
+            match strategy.import_id(&chat.session.id, exists, &mut remapped) {
+                Some(id) => chats.push((id, chat)),
+                None => summary.chats.skipped += 1,
+            }
+        }
+        let existing_apps = self.db.list_apps()?;
+        let app_ids: HashSet<String> = existing_apps.iter().map(|app| app.id.clone()).collect();
+        // apps are unique by name and type
+        let mut app_names: HashSet<(String, String)> = existing_apps
+            .into_iter()
+            .filter_map(|app| Some((app.name?, app.app_type)))
+            .collect();
+        let mut apps = Vec::new();
+        for app in &manifest.apps {
+            let name_taken = |names: &HashSet<(String, String)>, name: &Option<String>| {
+                name.as_ref()
+                    .is_some_and(|name| names.contains(&(name.clone(), app.app_type.clone())))
+            };
+            let exists = app_ids.contains(&app.id) || name_taken(&app_names, &app.name);
+            let id = match strategy.import_id(&app.id, exists, &mut remapped) {
+                Some(id) => id,
+                None => {
+                    summary.apps.skipped += 1;
+                    continue;
+                }
+            };
+            let mut name = app.name.clone();
+            let mut copy = 1;
+            while name_taken(&app_names, &name) {
+                name = app.name.as_ref().map(|original| match copy {
+                    1 => format!("{} (imported)", original),
+                    _ => format!("{} (imported {})", original, copy),
+                });
+                copy += 1;
+            }
+            if let Some(name) = &name {
+                app_names.insert((name.clone(), app.app_type.clone()));
+            }
+            apps.push(App {
+                id,
+                app_type: app.app_type.clone(),
+                content: app.content.clone(),
+                created_at: app.created_at,
+                updated_at: app.updated_at,
+                name,
+                icon: app.icon.clone(),
+                meta: app.meta.clone(),
+            });
+        }
+        let remap = |id: &str| remap_id(id, &remapped);
+
+        let resources_path = self.resources_path.clone();
+        let mut files_to_write = Vec::new();
+        let mut claimed_paths = HashSet::new();
+        let mut tx = self.db.begin()?;
+
+        for (id, archived) in resources {
+            let name = archived
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.name.as_str());
+            // resource files keep their name unless it's already taken
+            let mut file_path = archive_file_name(&archived.resource)
+                .map(|file_name| Path::new(&resources_path).join(file_name));
+            if let Some(taken) = file_path
+                .as_ref()
+                .filter(|path| path.exists() || claimed_paths.contains(*path))
+            {
+                let mut file_name = crate::utils::get_resource_filename(&id, name);
+                if let Some(extension) = taken.extension() {
+                    file_name = format!("{}.{}", file_name, extension.to_string_lossy());
+                }
+                file_path = Some(Path::new(&resources_path).join(file_name));
+            }
+            let resource = Resource {
+                id: id.clone(),
+                resource_path: file_path
+                    .as_ref()
+                    .map(|path| path.as_os_str().to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                ..archived.resource.clone()
+            };
+            Database::create_resource_tx(&mut tx, &resource)?;
+
+            if let Some(metadata) = &archived.metadata {
+                Database::create_resource_metadata_tx(
+                    &mut tx,
+                    &ResourceMetadata {
+                        id: random_uuid(),
+                        resource_id: id.clone(),
+                        ..metadata.clone()
+                    },
+                )?;
+            }
+            for tag in &archived.tags {
+                Database::create_resource_tag_tx(
+                    &mut tx,
+                    &ResourceTag {
+                        id: random_uuid(),
+                        resource_id: id.clone(),
+                        tag_name: tag.tag_name.clone(),
+                        tag_value: remap_tag_value(tag, &remapped),
+                    },
+                )?;
+            }
+            for content in &archived.text_content {
+                Database::create_resource_text_content_tx(
+                    &mut tx,
+                    &ResourceTextContent {
+                        id: random_uuid(),
+                        resource_id: id.clone(),
+                        ..content.clone()
+                    },
+                )?;
+            }
+            if !archived.text_content.is_empty() {
+                // the embeddings are not part of the archive
+                Database::create_resource_tag_tx(
+                    &mut tx,
+                    &ResourceTag::new_generate_lazy_embeddings(&id),
+                )?;
+            }
+            // adding tags touches the resource
+            Database::update_resource_tx(&mut tx, &resource)?;
+
+            match (
+                archived
+                    .file
+                    .as_ref()
+                    .filter(|file| archived_files.contains(*file)),
+                file_path,
+            ) {
+                (Some(file), Some(file_path)) => {
+                    claimed_paths.insert(file_path.clone());
+                    files_to_write.push((file_path, file));
+                }
+                _ => summary.missing_files.push(id.clone()),
+            }
+            summary.resources.imported += 1;
+        }
+
+        for space in &spaces {
+            Database::create_space_tx(&mut tx, space)?;
+            summary.spaces.imported += 1;
+        }
+        for entry in &manifest.space_entries {
+            if !imported_spaces.contains(entry.space_id.as_str())
+                || !imported_resources.contains(entry.resource_id.as_str())
+            {
+                continue;
+            }
+            let space_id = remap(&entry.space_id);
+            let resource_id = remap(&entry.resource_id);
+            if Database::has_space_entry_tx(&mut tx, &space_id, &resource_id)? {
+                summary.space_entries.skipped += 1;
+                continue;
+            }
+            Database::create_space_entry_tx(
+                &mut tx,
+                &SpaceEntry {
+                    id: random_uuid(),
+                    space_id,
+                    resource_id,
+                    ..entry.clone()
+                },
+            )?;
+            summary.space_entries.imported += 1;
+        }
+        for entry in &manifest.sub_space_entries {
+            if !imported_spaces.contains(entry.parent_space_id.as_str())
+                || !imported_spaces.contains(entry.child_space_id.as_str())
+            {
+                continue;
+            }
+            Database::create_sub_space_entry_tx(
+                &mut tx,
+                &SubSpaceEntry {
+                    id: random_uuid(),
+                    parent_space_id: remap(&entry.parent_space_id),
+                    child_space_id: remap(&entry.child_space_id),
+                    ..entry.clone()
+                },
+            )?;
+        }
+
+        for (id, chat) in chats {
+            Database::create_ai_session_tx(
+                &mut tx,
+                &AIChatSession {
+                    id: id.clone(),
+                    ..chat.session.clone()
+                },
+            )?;
+            for message in &chat.messages {
+                let mut message = message.clone();
+                message.ai_session_id = id.clone();
+                message.content = remap_citation_ids(&message.content, &remapped);
+                for source in message.sources.iter_mut().flatten() {
+                    source.resource_id = remap(&source.resource_id);
+                }
+                Database::create_ai_session_message_tx(&mut tx, &message)?;
+            }
+            summary.chats.imported += 1;
+        }
+
+        for app in &apps {
+            Database::create_app_tx(&mut tx, app)?;
+            summary.apps.imported += 1;
+        }
+        tx.commit()?;
+
+        fs::create_dir_all(&resources_path)?;
+        for (path, file) in files_to_write {
+            let mut data = archive.by_name(file)?;
+            io::copy(&mut data, &mut fs::File::create(path)?)?;
+        }
+
+        // the key value store lives in its own database, existing keys are kept
+        for (table, entries) in &manifest.kv {
+            self.kv.new_table(table)?;
+            for (key, value) in entries {
+                if self.kv.get(table, key)?.is_some() {
+                    summary.kv_entries.skipped += 1;
+                    continue;
+                }
+                let mut value = value.clone();
+                remap_json_ids(&mut value, &remapped);
+                self.kv.put(table, key, &value.to_string())?;
+                summary.kv_entries.imported += 1;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+pub fn handle_archive_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: ArchiveMessage,
+) {
+    match message {
+        ArchiveMessage::ExportLibrary(path) => {
+            let result = worker.export_library(&path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        ArchiveMessage::ImportLibrary(path, strategy) => {
+            let result = worker.import_library(&path, strategy.as_deref());
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::importers::{write_zip, ZipEntry};
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_remap_ids() {
+        let old_id = random_uuid();
+        let new_id = random_uuid();
+        let kept_id = random_uuid();
+        let remapped = HashMap::from([(old_id.clone(), new_id.clone())]);
+
+        let mut value = serde_json::json!({
+            "spaceId": old_id,
+            "resource_ids": [old_id, kept_id],
+            "tabs": [{ "id": old_id, "title": old_id }],
+            "note": format!("copied from {}", old_id),
+        });
+        remap_json_ids(&mut value, &remapped);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "spaceId": new_id,
+                "resource_ids": [new_id, kept_id],
+                "tabs": [{ "id": new_id, "title": old_id }],
+                "note": format!("copied from {}", old_id),
+            })
+        );
+
+        assert_eq!(
+            remap_citation_ids(
+                &format!("see <citation>{}</citation> and {}", old_id, old_id),
+                &remapped
+            ),
+            format!("see <citation>{}</citation> and {}", new_id, old_id)
+        );
+
+        let tag = |name: &str| ResourceTag {
+            id: random_uuid(),
+            resource_id: random_uuid(),
+            tag_name: name.to_string(),
+            tag_value: old_id.clone(),
+        };
+        assert_eq!(remap_tag_value(&tag("annotates"), &remapped), new_id);
+        assert_eq!(remap_tag_value(&tag("hashtag"), &remapped), old_id);
+
+        let mut remapped = HashMap::new();
+        assert_eq!(
+            ConflictStrategy::Skip.import_id(&old_id, false, &mut remapped),
+            Some(old_id.clone())
+        );
+        assert_eq!(
+            ConflictStrategy::Skip.import_id(&old_id, true, &mut remapped),
+            None
+        );
+        let duplicate = ConflictStrategy::Duplicate.import_id(&old_id, true, &mut remapped);
+        assert_ne!(duplicate, Some(old_id.clone()));
+        assert_eq!(remapped.get(&old_id), duplicate.as_ref());
+    }
+
+    #[test]
+    fn test_read_archive() {
+        let manifest = serde_json::json!({
+            "format": ARCHIVE_FORMAT,
+            "version": ARCHIVE_VERSION,
+            "exported_at": "2024-05-01T10:00:00Z",
+            "resources": [{
+                "id": "5c4a4e4c-9d36-4a57-b1b7-6d9d3d5d6a11",
+                "resource_path": "/library/resources/deta.md",
+                "resource_type": "application/vnd.space.link",
+                "file": "resources/5c4a4e4c-9d36-4a57-b1b7-6d9d3d5d6a11/deta.md",
+                "metadata": null,
+            }],
+            "kv": { "settings": { "theme": { "dark": true } } },
+        });
+        let archive = write_zip(&[
+            ZipEntry {
+                name: MANIFEST_FILE.to_string(),
+                data: manifest.to_string().into_bytes(),
+            },
+            ZipEntry {
+                name: "resources/5c4a4e4c-9d36-4a57-b1b7-6d9d3d5d6a11/deta.md".to_string(),
+                data: b"# Deta".to_vec(),
+            },
+        ])
+        .unwrap();
+        let (manifest, mut archive) = read_archive(Cursor::new(archive)).unwrap();
+        assert_eq!(manifest.resources.len(), 1);
+        assert_eq!(
+            archive_file_name(&manifest.resources[0].resource).as_deref(),
+            Some("deta.md")
+        );
+        let mut data = Vec::new();
+        archive
+            .by_name(manifest.resources[0].file.as_ref().unwrap())
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"# Deta");
+        assert_eq!(manifest.kv["settings"]["theme"]["dark"], true);
+
+        let newer = serde_json::json!({
+            "format": ARCHIVE_FORMAT,
+            "version": ARCHIVE_VERSION + 1,
+            "exported_at": "2024-05-01T10:00:00Z",
+        });
+        let archive = write_zip(&[ZipEntry {
+            name: MANIFEST_FILE.to_string(),
+            data: newer.to_string().into_bytes(),
+        }])
+        .unwrap();
+        assert!(read_archive(Cursor::new(archive)).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    api::message::{BackupMessage, TunnelOneshot},
+    store::{
+        backup::{
+            create_snapshot, is_backup_due, list_snapshots, load_policy, prune_snapshots,
+            restore_snapshot, save_policy, BackupPolicy, Snapshot, SnapshotReason,
+        },
+        models::current_time,
+    },
+    worker::{send_worker_response, Worker},
+    BackendResult,
+};
+
+impl Worker {
+    fn create_backup_with_reason(&mut self, reason: SnapshotReason) -> BackendResult<Snapshot> {
+        let snapshot = create_snapshot(
+            &self.db,
+            &self.kv,
+            &self.resources_path,
+            &self.backups_path,
+            reason,
+        )?;
+        prune_snapshots(&self.backups_path, &load_policy(&self.backups_path))?;
+        Ok(snapshot)
+    }
+
+    pub fn create_backup(&mut self) -> BackendResult<Snapshot> {
+        self.create_backup_with_reason(SnapshotReason::Manual)
+    }
+
+    // takes a snapshot if backups are enabled and the last one is a day old
+    pub fn run_scheduled_backup(&mut self) -> BackendResult<Option<Snapshot>> {
+        let policy = load_policy(&self.backups_path);
+        let snapshots = list_snapshots(&self.backups_path)?;
+        if !policy.enabled || !is_backup_due(&snapshots, current_time()) {
+            return Ok(None);
+        }
+        self.create_backup_with_reason(SnapshotReason::Scheduled)
+            .map(Some)
+    }
+
+    pub fn list_backups(&self) -> BackendResult<Vec<Snapshot>> {
+        list_snapshots(&self.backups_path)
+    }
+
+    pub fn restore_backup(&mut self, id: &str) -> BackendResult<Snapshot> {
+        restore_snapshot(
+            &mut self.db,
+            &mut self.kv,
+            &self.resources_path,
+            &self.backups_path,
+            id,
+        )
+    }
+
+    pub fn set_backup_policy(&mut self, policy: BackupPolicy) -> BackendResult<()> {
+        save_policy(&self.backups_path, &policy)?;
+        prune_snapshots(&self.backups_path, &policy)?;
+        Ok(())
+    }
+}
+
+pub fn handle_backup_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: BackupMessage,
+) {
+    match message {
+        BackupMessage::CreateBackup => {
+            let result = worker.create_backup();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        BackupMessage::RunScheduledBackup => {
+            let result = worker.run_scheduled_backup();
+            // scheduled backups have no one waiting for the result
+            if let Err(e) = &result {
+                tracing::error!("scheduled backup failed: {e}");
+            }
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        BackupMessage::ListBackups => {
+            let result = worker.list_backups();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        BackupMessage::RestoreBackup(id) => {
+            let result = worker.restore_backup(&id);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        BackupMessage::GetBackupPolicy => {
+            let result = Ok(load_policy(&worker.backups_path));
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        BackupMessage::SetBackupPolicy(policy) => {
+            let result = worker.set_backup_policy(policy);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
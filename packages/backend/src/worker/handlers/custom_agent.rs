@@ -0,0 +1,51 @@
+use crate::{
+    api::message::{CustomAgentMessage, TunnelOneshot},
+    store::models::{CustomAgent, CustomTool},
+    worker::{send_worker_response, Worker},
+    BackendResult,
+};
+
+impl Worker {
+    pub fn upsert_custom_agent(&mut self, agent: CustomAgent) -> BackendResult<CustomAgent> {
+        self.db.upsert_custom_agent(&agent)?;
+        Ok(agent)
+    }
+
+    pub fn upsert_custom_tool(&mut self, tool: CustomTool) -> BackendResult<CustomTool> {
+        self.db.upsert_custom_tool(&tool)?;
+        Ok(tool)
+    }
+}
+
+pub fn handle_custom_agent_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: CustomAgentMessage,
+) {
+    match message {
+        CustomAgentMessage::UpsertCustomAgent(agent) => {
+            let result = worker.upsert_custom_agent(agent);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        CustomAgentMessage::DeleteCustomAgent(id) => {
+            let result = worker.db.delete_custom_agent(&id);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        CustomAgentMessage::ListCustomAgents => {
+            let result = worker.db.list_custom_agents();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        CustomAgentMessage::UpsertCustomTool(tool) => {
+            let result = worker.upsert_custom_tool(tool);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        CustomAgentMessage::DeleteCustomTool(id) => {
+            let result = worker.db.delete_custom_tool(&id);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        CustomAgentMessage::ListCustomTools => {
+            let result = worker.db.list_custom_tools();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
@@ -1,22 +1,29 @@
 use rusqlite::{Connection, OpenFlags};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, UNIX_EPOCH};
 
 use crate::{
     api::message::{HistoryMessage, TunnelOneshot},
-    store::models::{HistoryEntry, HistoryEntryType},
+    store::models::{
+        current_time, HistoryEntry, HistoryEntryType, HistoryImportMark, HistoryStats,
+        HistoryStatsQuery, HistoryVisitTransition,
+    },
     worker::{send_worker_response, Worker},
     BackendError, BackendResult,
 };
 
+mod bookmark_spaces;
 mod browser_bookmarks;
 mod browser_config;
-use browser_bookmarks::BookmarkFolder;
+mod netscape_bookmarks;
+use bookmark_spaces::BookmarkSpacesImport;
+use browser_bookmarks::{flatten_folders, BookmarkFolder};
 use browser_config::{
-    get_bookmarks_file_path, get_browser_config, get_history_file_path, BrowserFamily,
+    get_bookmarks_file_path, get_browser_config, get_browser_profile, get_history_file_path,
+    list_browser_profiles, BrowserFamily, BrowserProfile,
 };
 
 impl Worker {
@@ -93,19 +100,31 @@ impl Worker {
         self.db.search_history_by_url_and_title(&prefix, since)
     }
 
+    pub fn get_history_stats(&self, query: HistoryStatsQuery) -> BackendResult<HistoryStats> {
+        self.db.get_history_stats(&query)
+    }
+
+    pub fn list_browser_profiles(
+        &mut self,
+        browser_type: &str,
+    ) -> BackendResult<Vec<BrowserProfile>> {
+        list_browser_profiles(browser_type)
+    }
+
+    // imports from the default profile if there's no profile id
     pub fn import_browser_history(
         &mut self,
         browser_type: &str,
+        profile_id: Option<&str>,
         limit: usize,
     ) -> BackendResult<Vec<HistoryEntry>> {
-        const BATCH_SIZE: usize = 1000;
-
         let browser_config = get_browser_config(browser_type).ok_or_else(|| {
             BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
         })?;
 
-        // Get the history file path for the specified browser
-        let history_path = get_history_file_path(browser_type)?;
+        // Get the history file path for the specified browser profile
+        let profile = get_browser_profile(browser_type, profile_id)?;
+        let history_path = get_history_file_path(&profile)?;
 
         // Check access permissions for Safari before attempting to copy
         if browser_config.family == BrowserFamily::Safari {
@@ -163,25 +182,6 @@ impl Worker {
             )));
         }
 
-        let entries = match browser_config.family {
-            BrowserFamily::Chromium => {
-                self.read_chromium_history_from_file(&temp_history_path, limit)?
-            }
-            BrowserFamily::Firefox => {
-                self.read_firefox_history_from_file(&temp_history_path, limit)?
-            }
-            BrowserFamily::Safari => {
-                self.read_safari_history_from_file(&temp_history_path, limit)?
-            }
-        };
-
-        // Clean up the temporary file
-        if let Err(e) = fs::remove_file(&temp_history_path) {
-            eprintln!("Failed to remove temporary history file: {}", e);
-        }
-
-        let mut history_entries = entries;
-
         // Update the entry type for all entries
         let entry_type = match browser_type {
             "chrome" => HistoryEntryType::ImportChrome,
@@ -204,241 +204,378 @@ impl Worker {
             }
         };
 
-        for entry in &mut history_entries {
-            entry.entry_type = entry_type.clone();
-        }
+        // only visits after the last import of this profile are read, a profile without
+        // a mark may have entries imported before there were marks
+        let mark = self.db.get_history_import_mark(browser_type, &profile.id)?;
+        let since = match &mark {
+            Some(mark) => (mark.last_visit_id, mark.last_visit_time),
+            None => match self.db.get_latest_imported_visit(
+                &entry_type,
+                &profile.id,
+                profile.is_default,
+            )? {
+                Some(visited_at) => (
+                    i64::MAX,
+                    raw_visit_time(&browser_config.family, visited_at.timestamp_millis()),
+                ),
+                None => (0, 0),
+            },
+        };
 
-        let mut successful_entries = Vec::new();
+        let visits = match browser_config.family {
+            BrowserFamily::Chromium => read_chromium_visits(&temp_history_path, since, limit),
+            BrowserFamily::Firefox => read_firefox_visits(&temp_history_path, since, limit),
+            BrowserFamily::Safari => read_safari_visits(&temp_history_path, since, limit),
+        };
 
-        // Process entries in batches
-        for chunk in history_entries.chunks(BATCH_SIZE) {
-            match self.db.create_history_entries_batch(chunk) {
-                Ok(inserted) => successful_entries.extend(inserted),
-                Err(e) => {
-                    eprintln!("Failed to store history batch: {}", e);
-                    continue;
-                }
-            }
+        // Clean up the temporary file
+        if let Err(e) = fs::remove_file(&temp_history_path) {
+            eprintln!("Failed to remove temporary history file: {}", e);
+        }
+        let visits = visits?;
+        if visits.is_empty() {
+            return Ok(vec![]);
         }
 
-        Ok(successful_entries)
+        // the mark never moves back, e.g. if the browser's database was reset
+        let new_mark = HistoryImportMark {
+            browser_type: browser_type.to_string(),
+            profile_id: profile.id.clone(),
+            last_visit_id: visits
+                .iter()
+                .map(|v| v.id)
+                .max()
+                .unwrap_or(0)
+                .max(mark.map(|m| m.last_visit_id).unwrap_or(0)),
+            last_visit_time: visits
+                .iter()
+                .map(|v| v.raw_time)
+                .max()
+                .unwrap_or(0)
+                .max(since.1),
+            updated_at: current_time(),
+        };
+        let entries = aggregate_visits(visits, &entry_type, &profile.id);
+        self.db
+            .import_history_entries(&entries, &new_mark, profile.is_default)
     }
 
-    fn read_safari_history_from_file(
-        &mut self,
-        history_path: &Path,
-        limit: usize,
-    ) -> BackendResult<Vec<HistoryEntry>> {
-        // Try to connect to Safari's History.db file directly
-        let conn = match Connection::open_with_flags(
-            history_path,
-            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
-        ) {
-            Ok(conn) => conn,
-            Err(e) => {
-                let error_msg = e.to_string();
-                if error_msg.contains("unable to open database file") {
-                    return Err(BackendError::GenericError(
-                        "Could not access Safari history database. To fix this:\n\
-                        1. Quit Safari completely (Safari > Quit Safari)\n\
-                        2. Open System Settings\n\
-                        3. Go to Privacy & Security\n\
-                        4. Select Full Disk Access\n\
-                        5. Click the + button\n\
-                        6. Select and add Surf\n\
-                        7. Try importing again\n\
-                        \n\
-                        Note: Safari must be completely closed during the import."
-                            .to_string(),
-                    ));
-                } else {
-                    return Err(BackendError::GenericError(format!(
-                        "Could not access Safari history database: {}",
-                        e
-                    )));
-                }
-            }
-        };
+    // the bookmark folders of the profile as trees, the default profile if there's no profile id
+    fn read_browser_bookmarks(
+        &self,
+        browser_type: &str,
+        profile_id: Option<&str>,
+    ) -> BackendResult<(BrowserProfile, Vec<BookmarkFolder>)> {
+        let browser_config = get_browser_config(browser_type).ok_or_else(|| {
+            BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
+        })?;
 
-        let mut stmt = conn.prepare(
-            "SELECT 
-                history_items.url,
-                history_visits.title,
-                history_items.visit_count,
-                MAX(history_visits.visit_time + 978307200) as last_visit_time
-            FROM history_items 
-            LEFT JOIN history_visits ON history_items.id = history_visits.history_item
-            GROUP BY history_items.id
-            ORDER BY last_visit_time DESC
-            LIMIT ?",
-        )?;
-
-        let mut history_entries = Vec::new();
-        let current_time = SystemTime::now();
-
-        let safari_history = stmt.query_map([limit as i64], |row| {
-            let url: String = row.get(0)?;
-            let title: Option<String> = row.get(1)?;
-            let _visit_count: i32 = row.get(2)?;
-            let visit_time: i64 = row.get(3)?;
+        // Get the bookmarks file path for the specified browser profile
+        let profile = get_browser_profile(browser_type, profile_id)?;
+        let bookmarks_path = get_bookmarks_file_path(&profile)?;
 
-            Ok((url, title, visit_time))
-        })?;
+        let folders = match browser_config.family {
+            BrowserFamily::Chromium => browser_bookmarks::parse_chrome_bookmarks(&bookmarks_path),
+            BrowserFamily::Firefox => {
+                browser_bookmarks::parse_firefox_bookmarks(&bookmarks_path, browser_type)
+            }
+            BrowserFamily::Safari => browser_bookmarks::parse_safari_bookmarks(&bookmarks_path),
+        }?;
+        Ok((profile, folders))
+    }
 
-        for entry_result in safari_history {
-            let (url, title, visit_time) = entry_result?;
-            let created_at = UNIX_EPOCH + Duration::from_secs(visit_time.max(0) as u64);
-
-            let entry = HistoryEntry {
-                id: uuid::Uuid::new_v4().to_string(),
-                entry_type: HistoryEntryType::ImportSafari,
-                url: Some(url),
-                title,
-                search_query: None,
-                created_at: created_at.into(),
-                updated_at: current_time.into(),
-            };
-
-            history_entries.push(entry);
+    // every folder as a flat list, nested folders are listed after their parent
+    pub fn import_browser_bookmarks(
+        &mut self,
+        browser_type: &str,
+        profile_id: Option<&str>,
+        _limit: usize,
+    ) -> BackendResult<Vec<BookmarkFolder>> {
+        let (profile, folders) = self.read_browser_bookmarks(browser_type, profile_id)?;
+        let mut folders = flatten_folders(folders);
+        for folder in &mut folders {
+            folder.source_profile = Some(profile.id.clone());
         }
+        Ok(folders)
+    }
+
+    pub fn import_browser_bookmarks_as_spaces(
+        &mut self,
+        browser_type: &str,
+        profile_id: Option<&str>,
+    ) -> BackendResult<BookmarkSpacesImport> {
+        let (_, folders) = self.read_browser_bookmarks(browser_type, profile_id)?;
+        self.import_bookmarks_as_spaces(&folders)
+    }
 
-        Ok(history_entries)
+    // reads a bookmarks.html export, folders are flattened like for the browser import
+    pub fn import_bookmarks_html(&mut self, path: &str) -> BackendResult<Vec<BookmarkFolder>> {
+        let html = fs::read_to_string(path)?;
+        Ok(flatten_folders(
+            netscape_bookmarks::parse_netscape_bookmarks(&html),
+        ))
     }
 
-    fn read_chromium_history_from_file(
+    pub fn import_bookmarks_html_as_spaces(
         &mut self,
-        history_path: &Path,
-        limit: usize,
-    ) -> BackendResult<Vec<HistoryEntry>> {
-        let conn = Connection::open_with_flags(history_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-
-        let mut stmt = conn.prepare(
-            "SELECT 
-                urls.url, 
-                urls.title, 
-                urls.visit_count, 
-                urls.last_visit_time,
-                COALESCE(MAX(visits.visit_time), urls.last_visit_time) as visit_time
-            FROM urls
-            LEFT JOIN visits ON urls.id = visits.url
-            GROUP BY urls.id
-            ORDER BY visit_time DESC
-            LIMIT ?",
-        )?;
-
-        let mut history_entries = Vec::new();
-        let current_time = SystemTime::now();
-
-        let chrome_history = stmt.query_map([limit as i64], |row| {
-            let url: String = row.get(0)?;
-            let title: String = row.get(1)?;
-            let _visit_count: i32 = row.get(2)?;
-            let visit_time: i64 = row.get(4)?;
+        path: &str,
+    ) -> BackendResult<BookmarkSpacesImport> {
+        let html = fs::read_to_string(path)?;
+        let folders = netscape_bookmarks::parse_netscape_bookmarks(&html);
+        self.import_bookmarks_as_spaces(&folders)
+    }
 
-            // Chrome stores time as microseconds since Jan 1, 1601 UTC
-            // Convert to milliseconds since epoch (Jan 1, 1970)
-            let chrome_epoch = 11644473600000; // Difference in milliseconds between 1601 and 1970
-            let visit_time_ms = (visit_time / 1000) - chrome_epoch;
+    // writes the spaces, all top level spaces if none are given, as a bookmarks.html file
+    pub fn export_bookmarks_html(&mut self, space_ids: &[String], path: &str) -> BackendResult<()> {
+        let folders = self.export_spaces_as_bookmarks(space_ids)?;
+        fs::write(path, netscape_bookmarks::to_netscape_bookmarks(&folders))?;
+        Ok(())
+    }
+}
 
-            Ok((url, title, visit_time_ms))
-        })?;
+// a single visit read from a browser's history database
+struct BrowserVisit {
+    id: i64,
+    url: String,
+    title: Option<String>,
+    // in the browser's own units, for the import mark
+    raw_time: i64,
+    visited_at: chrono::DateTime<chrono::Utc>,
+    // `None` for visits that aren't page loads, e.g. subframes
+    transition: Option<HistoryVisitTransition>,
+}
 
-        for entry_result in chrome_history {
-            let (url, title, visit_time_ms) = entry_result?;
-            let created_at = UNIX_EPOCH + Duration::from_millis(visit_time_ms.max(0) as u64);
-
-            let entry = HistoryEntry {
-                id: uuid::Uuid::new_v4().to_string(),
-                entry_type: HistoryEntryType::ImportChrome, // This will be overwritten in import_browser_history
-                url: Some(url),
-                title: Some(title),
-                search_query: None,
-                created_at: created_at.into(),
-                updated_at: current_time.into(),
-            };
-
-            history_entries.push(entry);
-        }
+fn chromium_transition(transition: i64) -> Option<HistoryVisitTransition> {
+    // CLIENT_REDIRECT | SERVER_REDIRECT qualifiers
+    if transition & 0x3000_0000 != 0 {
+        return Some(HistoryVisitTransition::Redirect);
+    }
+    match transition & 0xFF {
+        0 => Some(HistoryVisitTransition::Link),
+        1 => Some(HistoryVisitTransition::Typed),
+        2 => Some(HistoryVisitTransition::Bookmark),
+        // AUTO_SUBFRAME, MANUAL_SUBFRAME
+        3 | 4 => None,
+        5 | 7 | 9 | 10 => Some(HistoryVisitTransition::Generated),
+        8 => Some(HistoryVisitTransition::Reload),
+        _ => Some(HistoryVisitTransition::Other),
+    }
+}
 
-        Ok(history_entries)
+fn firefox_transition(visit_type: i64) -> Option<HistoryVisitTransition> {
+    match visit_type {
+        1 => Some(HistoryVisitTransition::Link),
+        2 => Some(HistoryVisitTransition::Typed),
+        3 => Some(HistoryVisitTransition::Bookmark),
+        // EMBED, FRAMED_LINK
+        4 | 8 => None,
+        5 | 6 => Some(HistoryVisitTransition::Redirect),
+        9 => Some(HistoryVisitTransition::Reload),
+        _ => Some(HistoryVisitTransition::Other),
     }
+}
 
-    fn read_firefox_history_from_file(
-        &mut self,
-        history_path: &Path,
-        limit: usize,
-    ) -> BackendResult<Vec<HistoryEntry>> {
-        let conn = Connection::open_with_flags(history_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-
-        let mut stmt = conn.prepare(
-            "SELECT 
-                moz_places.url,
-                moz_places.title,
-                moz_places.visit_count,
-                COALESCE(MAX(moz_historyvisits.visit_date), 0) as last_visit_date
-            FROM moz_places
-            LEFT JOIN moz_historyvisits ON moz_places.id = moz_historyvisits.place_id
-            WHERE moz_places.url NOT LIKE 'place:%'
-            GROUP BY moz_places.id
-            ORDER BY last_visit_date DESC
-            LIMIT ?",
-        )?;
-
-        let mut history_entries = Vec::new();
-        let current_time = SystemTime::now();
-
-        let firefox_history = stmt.query_map([limit as i64], |row| {
-            let url: String = row.get(0)?;
-            let title: Option<String> = row.get(1)?;
-            let _visit_count: i32 = row.get(2)?;
+// the raw time of the browser's history for milliseconds since epoch, rounded up to
+// the end of the millisecond so a `>` comparison skips visits within it
+fn raw_visit_time(family: &BrowserFamily, millis: i64) -> i64 {
+    match family {
+        BrowserFamily::Chromium => (millis + 11644473600000 + 1) * 1000 - 1,
+        BrowserFamily::Firefox => (millis + 1) * 1000 - 1,
+        BrowserFamily::Safari => millis,
+    }
+}
+
+fn read_chromium_visits(
+    history_path: &Path,
+    (last_visit_id, last_visit_time): (i64, i64),
+    limit: usize,
+) -> BackendResult<Vec<BrowserVisit>> {
+    let conn = Connection::open_with_flags(history_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT visits.id, urls.url, urls.title, visits.visit_time, visits.transition
+        FROM visits
+        JOIN urls ON urls.id = visits.url
+        WHERE visits.id > ?1 OR visits.visit_time > ?2
+        ORDER BY visits.id
+        LIMIT ?3",
+    )?;
+
+    let visits = stmt.query_map(
+        rusqlite::params![last_visit_id, last_visit_time, limit as i64],
+        |row| {
             let visit_time: i64 = row.get(3)?;
+            // Chrome stores time as microseconds since Jan 1, 1601 UTC
+            // Convert to milliseconds since epoch (Jan 1, 1970)
+            let chrome_epoch = 11644473600000; // Difference in milliseconds between 1601 and 1970
+            let visit_time_ms = (visit_time / 1000) - chrome_epoch;
+            let visited_at = UNIX_EPOCH + Duration::from_millis(visit_time_ms.max(0) as u64);
+
+            Ok(BrowserVisit {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                title: row.get(2)?,
+                raw_time: visit_time,
+                visited_at: visited_at.into(),
+                transition: chromium_transition(row.get(4)?),
+            })
+        },
+    )?;
+    Ok(visits.collect::<Result<_, _>>()?)
+}
 
+fn read_firefox_visits(
+    history_path: &Path,
+    (last_visit_id, last_visit_time): (i64, i64),
+    limit: usize,
+) -> BackendResult<Vec<BrowserVisit>> {
+    let conn = Connection::open_with_flags(history_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT moz_historyvisits.id, moz_places.url, moz_places.title,
+            moz_historyvisits.visit_date, moz_historyvisits.visit_type
+        FROM moz_historyvisits
+        JOIN moz_places ON moz_places.id = moz_historyvisits.place_id
+        WHERE (moz_historyvisits.id > ?1 OR moz_historyvisits.visit_date > ?2)
+            AND moz_places.url NOT LIKE 'place:%'
+        ORDER BY moz_historyvisits.id
+        LIMIT ?3",
+    )?;
+
+    let visits = stmt.query_map(
+        rusqlite::params![last_visit_id, last_visit_time, limit as i64],
+        |row| {
             // Firefox stores time as microseconds since Unix epoch (1970)
-            let visit_time_ms = visit_time / 1000;
-
-            Ok((url, title, visit_time_ms))
-        })?;
+            let visit_time: i64 = row.get(3)?;
+            let visited_at = UNIX_EPOCH + Duration::from_micros(visit_time.max(0) as u64);
+
+            Ok(BrowserVisit {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                title: row.get(2)?,
+                raw_time: visit_time,
+                visited_at: visited_at.into(),
+                transition: firefox_transition(row.get(4)?),
+            })
+        },
+    )?;
+    Ok(visits.collect::<Result<_, _>>()?)
+}
 
-        for entry_result in firefox_history {
-            let (url, title, visit_time_ms) = entry_result?;
-            let created_at = UNIX_EPOCH + Duration::from_millis(visit_time_ms.max(0) as u64);
-
-            let entry = HistoryEntry {
-                id: uuid::Uuid::new_v4().to_string(),
-                entry_type: HistoryEntryType::ImportFirefox, // This will be overwritten in import_browser_history
-                url: Some(url),
-                title,
-                search_query: None,
-                created_at: created_at.into(),
-                updated_at: current_time.into(),
-            };
-
-            history_entries.push(entry);
+fn read_safari_visits(
+    history_path: &Path,
+    (last_visit_id, last_visit_time): (i64, i64),
+    limit: usize,
+) -> BackendResult<Vec<BrowserVisit>> {
+    // Try to connect to Safari's History.db file directly
+    let conn = match Connection::open_with_flags(
+        history_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+    ) {
+        Ok(conn) => conn,
+        Err(e) => {
+            let error_msg = e.to_string();
+            if error_msg.contains("unable to open database file") {
+                return Err(BackendError::GenericError(
+                    "Could not access Safari history database. To fix this:\n\
+                    1. Quit Safari completely (Safari > Quit Safari)\n\
+                    2. Open System Settings\n\
+                    3. Go to Privacy & Security\n\
+                    4. Select Full Disk Access\n\
+                    5. Click the + button\n\
+                    6. Select and add Surf\n\
+                    7. Try importing again\n\
+                    \n\
+                    Note: Safari must be completely closed during the import."
+                        .to_string(),
+                ));
+            } else {
+                return Err(BackendError::GenericError(format!(
+                    "Could not access Safari history database: {}",
+                    e
+                )));
+            }
         }
+    };
+
+    // Safari stores time as seconds since Jan 1, 2001, the mark uses milliseconds since epoch
+    let mut stmt = conn.prepare(
+        "SELECT history_visits.id, history_items.url, history_visits.title,
+            CAST((history_visits.visit_time + 978307200) * 1000 AS INTEGER) AS visit_time_ms
+        FROM history_visits
+        JOIN history_items ON history_items.id = history_visits.history_item
+        WHERE history_visits.id > ?1
+            OR CAST((history_visits.visit_time + 978307200) * 1000 AS INTEGER) > ?2
+        ORDER BY history_visits.id
+        LIMIT ?3",
+    )?;
+
+    let visits = stmt.query_map(
+        rusqlite::params![last_visit_id, last_visit_time, limit as i64],
+        |row| {
+            let visit_time_ms: i64 = row.get(3)?;
+            let visited_at = UNIX_EPOCH + Duration::from_millis(visit_time_ms.max(0) as u64);
+
+            Ok(BrowserVisit {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                title: row.get(2)?,
+                raw_time: visit_time_ms,
+                visited_at: visited_at.into(),
+                // Safari doesn't record how a page was opened
+                transition: Some(HistoryVisitTransition::Other),
+            })
+        },
+    )?;
+    Ok(visits.collect::<Result<_, _>>()?)
+}
 
-        Ok(history_entries)
-    }
-
-    pub fn import_browser_bookmarks(
-        &mut self,
-        browser_type: &str,
-        _limit: usize,
-    ) -> BackendResult<Vec<BookmarkFolder>> {
-        let browser_config = get_browser_config(browser_type).ok_or_else(|| {
-            BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
-        })?;
-
-        // Get the bookmarks file path for the specified browser
-        let bookmarks_path = get_bookmarks_file_path(browser_type)?;
-
-        match browser_config.family {
-            BrowserFamily::Chromium => browser_bookmarks::parse_chrome_bookmarks(&bookmarks_path),
-            BrowserFamily::Firefox => {
-                browser_bookmarks::parse_firefox_bookmarks(&bookmarks_path, browser_type)
+// one entry per url, visits have to be in the order they happened
+fn aggregate_visits(
+    visits: Vec<BrowserVisit>,
+    entry_type: &HistoryEntryType,
+    profile_id: &str,
+) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    let mut by_url: HashMap<String, usize> = HashMap::new();
+    for visit in visits {
+        let transition = match visit.transition {
+            Some(transition) => transition,
+            None => continue,
+        };
+        let title = visit.title.filter(|title| !title.is_empty());
+        let typed = (transition == HistoryVisitTransition::Typed) as u32;
+        match by_url.get(&visit.url) {
+            Some(&i) => {
+                let entry = &mut entries[i];
+                entry.visit_count += 1;
+                entry.typed_count += typed;
+                entry.last_visited_at = Some(visit.visited_at);
+                entry.last_transition = Some(transition);
+                if title.is_some() {
+                    entry.title = title;
+                }
+            }
+            None => {
+                by_url.insert(visit.url.clone(), entries.len());
+                entries.push(HistoryEntry {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    entry_type: entry_type.clone(),
+                    url: Some(visit.url),
+                    title,
+                    search_query: None,
+                    created_at: visit.visited_at,
+                    updated_at: current_time(),
+                    source_profile: Some(profile_id.to_string()),
+                    visit_count: 1,
+                    typed_count: typed,
+                    last_visited_at: Some(visit.visited_at),
+                    last_transition: Some(transition),
+                });
             }
-            BrowserFamily::Safari => browser_bookmarks::parse_safari_bookmarks(&bookmarks_path),
         }
     }
+    entries
 }
 
 #[tracing::instrument(level = "trace", skip(worker, oneshot))]
@@ -480,14 +617,40 @@ pub fn handle_history_message(
             let result = worker.search_history_by_url_and_title(prefix, since);
             send_worker_response(&mut worker.channel, oneshot, result);
         }
-        HistoryMessage::ImportBrowserHistory(browser_type) => {
+        HistoryMessage::GetHistoryStats(query) => {
+            let result = worker.get_history_stats(query);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ListBrowserProfiles(browser_type) => {
+            let result = worker.list_browser_profiles(&browser_type);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ImportBrowserHistory(browser_type, profile_id) => {
             let limit = 1_000_000; // Import up to 1m entries
-            let result = worker.import_browser_history(&browser_type, limit);
+            let result = worker.import_browser_history(&browser_type, profile_id.as_deref(), limit);
             send_worker_response(&mut worker.channel, oneshot, result);
         }
-        HistoryMessage::ImportBrowserBookmarks(browser_type) => {
+        HistoryMessage::ImportBrowserBookmarks(browser_type, profile_id) => {
             let limit = 1_000_000; // Import up to 1m entries
-            let result = worker.import_browser_bookmarks(&browser_type, limit);
+            let result =
+                worker.import_browser_bookmarks(&browser_type, profile_id.as_deref(), limit);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ImportBrowserBookmarksAsSpaces(browser_type, profile_id) => {
+            let result =
+                worker.import_browser_bookmarks_as_spaces(&browser_type, profile_id.as_deref());
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ImportBookmarksHtml(path) => {
+            let result = worker.import_bookmarks_html(&path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ImportBookmarksHtmlAsSpaces(path) => {
+            let result = worker.import_bookmarks_html_as_spaces(&path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        HistoryMessage::ExportBookmarksHtml(space_ids, path) => {
+            let result = worker.export_bookmarks_html(&space_ids, &path);
             send_worker_response(&mut worker.channel, oneshot, result);
         }
         HistoryMessage::RemoveAllHistoryEntries => {
@@ -496,3 +659,54 @@ pub fn handle_history_message(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_read_chromium_visits() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("History");
+        let conn = Connection::open(&path).unwrap();
+        // 13_300_000_000_000_000 is in 2022, in microseconds since 1601
+        conn.execute_batch(
+            "CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT, title TEXT);
+            CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER, transition INTEGER);
+            INSERT INTO urls VALUES (1, 'https://deta.space', 'Deta'), (2, 'https://ads.example', '');
+            INSERT INTO visits VALUES
+                (1, 1, 13300000000000000, 1),
+                (2, 2, 13300000001000000, 3),
+                (3, 1, 13300000002000000, 0),
+                (4, 1, 13300000003000000, 805306368);",
+        )
+        .unwrap();
+
+        let visits = read_chromium_visits(&path, (0, 0), 100).unwrap();
+        assert_eq!(visits.len(), 4);
+        let entries = aggregate_visits(visits, &HistoryEntryType::ImportChrome, "Default");
+        // the subframe visit is skipped
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].visit_count, 3);
+        assert_eq!(entries[0].typed_count, 1);
+        assert_eq!(
+            entries[0].last_transition,
+            Some(HistoryVisitTransition::Redirect)
+        );
+        assert_eq!(entries[0].created_at.timestamp(), 1_655_526_400);
+
+        // only visits after the mark
+        let visits = read_chromium_visits(&path, (2, 13300000001000000), 100).unwrap();
+        assert_eq!(visits.iter().map(|v| v.id).collect::<Vec<_>>(), vec![3, 4]);
+
+        // only visits after the latest one imported without a mark
+        let imported_at = 13300000001000 - 11644473600000;
+        let since = (
+            i64::MAX,
+            raw_visit_time(&BrowserFamily::Chromium, imported_at),
+        );
+        let visits = read_chromium_visits(&path, since, 100).unwrap();
+        assert_eq!(visits.iter().map(|v| v.id).collect::<Vec<_>>(), vec![3, 4]);
+    }
+}
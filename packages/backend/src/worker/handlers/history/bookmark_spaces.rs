@@ -0,0 +1,400 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use super::browser_bookmarks::{BookmarkFolder, BookmarkItem};
+use crate::{
+    store::{
+        db::Database,
+        models::{
+            current_time, random_uuid, SpaceEntry, SpaceEntryType, SpaceExtended, SubSpaceEntry,
+        },
+    },
+    worker::{
+        handlers::importers::{
+            create_imported_resource_tx, create_imported_space_tx, space_title, ImportedResource,
+            LINK_RESOURCE_TYPE,
+        },
+        Worker,
+    },
+    BackendResult,
+};
+
+#[derive(Debug, Serialize)]
+pub struct BookmarkSpacesImport {
+    // spaces created for the top level folders, nested folders are their sub-spaces
+    pub space_ids: Vec<String>,
+    pub spaces_created: usize,
+    pub resources_created: usize,
+}
+
+fn has_bookmarks(folder: &BookmarkFolder) -> bool {
+    !folder.children.is_empty() || folder.folders.iter().any(has_bookmarks)
+}
+
+struct SpacesImporter<'a> {
+    resources_path: &'a str,
+    next_index: usize,
+    result: BookmarkSpacesImport,
+    // resource files are only written once the transaction is committed
+    files: Vec<(String, String)>,
+}
+
+impl SpacesImporter<'_> {
+    fn import_folder(
+        &mut self,
+        tx: &mut rusqlite::Transaction,
+        folder: &BookmarkFolder,
+        parent_space_id: Option<&str>,
+    ) -> BackendResult<()> {
+        if !has_bookmarks(folder) {
+            return Ok(());
+        }
+
+        let ct = current_time();
+        let space = create_imported_space_tx(tx, &folder.title, self.next_index)?;
+        self.next_index += 1;
+        self.result.spaces_created += 1;
+
+        match parent_space_id {
+            Some(parent_space_id) => Database::create_sub_space_entry_tx(
+                tx,
+                &SubSpaceEntry {
+                    id: random_uuid(),
+                    parent_space_id: parent_space_id.to_string(),
+                    child_space_id: space.id.clone(),
+                    created_at: ct,
+                    updated_at: ct,
+                    manually_added: 1,
+                },
+            )?,
+            None => self.result.space_ids.push(space.id.clone()),
+        }
+
+        for item in &folder.children {
+            self.import_bookmark(tx, item, &space.id)?;
+        }
+        for nested in &folder.folders {
+            self.import_folder(tx, nested, Some(&space.id))?;
+        }
+        Ok(())
+    }
+
+    fn import_bookmark(
+        &mut self,
+        tx: &mut rusqlite::Transaction,
+        item: &BookmarkItem,
+        space_id: &str,
+    ) -> BackendResult<()> {
+        let title = if item.title.is_empty() {
+            &item.url
+        } else {
+            &item.title
+        };
+        let (resource, file) = create_imported_resource_tx(
+            tx,
+            self.resources_path,
+            &ImportedResource {
+                resource_type: LINK_RESOURCE_TYPE,
+                title,
+                url: &item.url,
+                user_context: item.description.as_deref().unwrap_or_default(),
+                content: "",
+                tags: item
+                    .tags
+                    .iter()
+                    .map(|tag| ("hashtag", tag.as_str()))
+                    .collect(),
+                created_at: item.created_at,
+                updated_at: item.updated_at,
+            },
+        )?;
+
+        Database::create_space_entry_tx(
+            tx,
+            &SpaceEntry {
+                id: random_uuid(),
+                space_id: space_id.to_string(),
+                resource_id: resource.id.clone(),
+                created_at: resource.created_at,
+                updated_at: resource.updated_at,
+                manually_added: 1,
+            },
+        )?;
+
+        self.files.push((resource.resource_path, file));
+        self.result.resources_created += 1;
+        Ok(())
+    }
+}
+
+// creates a space per bookmark folder with its bookmarks as link resources,
+// nested folders become sub-spaces of their parent folder's space
+fn import_folders_as_spaces(
+    db: &mut Database,
+    resources_path: &str,
+    folders: &[BookmarkFolder],
+) -> BackendResult<BookmarkSpacesImport> {
+    let mut importer = SpacesImporter {
+        resources_path,
+        next_index: db.list_spaces()?.len(),
+        result: BookmarkSpacesImport {
+            space_ids: Vec::new(),
+            spaces_created: 0,
+            resources_created: 0,
+        },
+        files: Vec::new(),
+    };
+
+    let mut tx = db.begin()?;
+    for folder in folders {
+        importer.import_folder(&mut tx, folder, None)?;
+    }
+    tx.commit()?;
+
+    for (path, content) in &importer.files {
+        fs::write(path, content)?;
+    }
+    Ok(importer.result)
+}
+
+impl Worker {
+    pub fn import_bookmarks_as_spaces(
+        &mut self,
+        folders: &[BookmarkFolder],
+    ) -> BackendResult<BookmarkSpacesImport> {
+        import_folders_as_spaces(&mut self.db, &self.resources_path, folders)
+    }
+
+    // the spaces as bookmark folders with their sub-spaces as nested folders,
+    // all top level spaces if no space ids are given
+    pub fn export_spaces_as_bookmarks(
+        &self,
+        space_ids: &[String],
+    ) -> BackendResult<Vec<BookmarkFolder>> {
+        let spaces: HashMap<String, SpaceExtended> = self
+            .db
+            .list_spaces()?
+            .into_iter()
+            .map(|space| (space.id.clone(), space))
+            .collect();
+        let roots: Vec<&SpaceExtended> = if space_ids.is_empty() {
+            let mut roots: Vec<_> = spaces
+                .values()
+                .filter(|space| space.parent_space_ids.is_empty())
+                .collect();
+            roots.sort_by_key(|space| space.created_at);
+            roots
+        } else {
+            space_ids.iter().filter_map(|id| spaces.get(id)).collect()
+        };
+
+        let mut visited = HashSet::new();
+        let mut folders = Vec::new();
+        for space in roots {
+            if let Some(folder) = self.space_as_bookmark_folder(space, &spaces, &mut visited)? {
+                folders.push(folder);
+            }
+        }
+        Ok(folders)
+    }
+
+    fn space_as_bookmark_folder(
+        &self,
+        space: &SpaceExtended,
+        spaces: &HashMap<String, SpaceExtended>,
+        visited: &mut HashSet<String>,
+    ) -> BackendResult<Option<BookmarkFolder>> {
+        // a space can be nested in more than one parent
+        if !visited.insert(space.id.clone()) {
+            return Ok(None);
+        }
+
+        let mut folder = BookmarkFolder {
+            guid: space.id.clone(),
+            title: space_title(&space.name),
+            created_at: space.created_at,
+            updated_at: space.updated_at,
+            last_used_at: space.updated_at,
+            children: Vec::new(),
+            folders: Vec::new(),
+            source_profile: None,
+        };
+        let entries = self.db.list_space_entries(
+            &space.id,
+            Some("resource_added_to_space"),
+            Some("asc"),
+            None,
+        )?;
+        for entry in entries {
+            match entry.entry_type {
+                SpaceEntryType::Resource => {
+                    let resource = match self.db.get_resource(&entry.entry_id)? {
+                        Some(resource) if resource.deleted == 0 => resource,
+                        _ => continue,
+                    };
+                    // only resources that point to a web page can be bookmarks
+                    let metadata =
+                        match self.db.get_resource_metadata_by_resource_id(&resource.id)? {
+                            Some(metadata) if !metadata.source_uri.is_empty() => metadata,
+                            _ => continue,
+                        };
+                    let tags = self
+                        .db
+                        .list_resource_tags(&resource.id)?
+                        .into_iter()
+                        .filter(|tag| tag.tag_name == "hashtag")
+                        .map(|tag| tag.tag_value)
+                        .collect();
+                    folder.children.push(BookmarkItem {
+                        guid: resource.id,
+                        title: metadata.name,
+                        url: metadata.source_uri,
+                        created_at: resource.created_at,
+                        updated_at: resource.updated_at,
+                        last_used_at: resource.updated_at,
+                        description: Some(metadata.user_context).filter(|d| !d.is_empty()),
+                        tags,
+                    });
+                }
+                SpaceEntryType::Space => {
+                    if let Some(child) = spaces.get(&entry.entry_id) {
+                        if let Some(nested) =
+                            self.space_as_bookmark_folder(child, spaces, visited)?
+                        {
+                            folder.folders.push(nested);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Some(folder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::tempdir;
+
+    fn folder(title: &str, urls: &[&str], folders: Vec<BookmarkFolder>) -> BookmarkFolder {
+        let now = Utc::now();
+        BookmarkFolder {
+            guid: title.to_string(),
+            title: title.to_string(),
+            created_at: now,
+            updated_at: now,
+            last_used_at: now,
+            children: urls
+                .iter()
+                .map(|url| BookmarkItem {
+                    guid: url.to_string(),
+                    title: String::new(),
+                    url: url.to_string(),
+                    created_at: now,
+                    updated_at: now,
+                    last_used_at: now,
+                    description: None,
+                    tags: Vec::new(),
+                })
+                .collect(),
+            folders,
+            source_profile: None,
+        }
+    }
+
+    #[test]
+    fn test_has_bookmarks() {
+        let empty = folder("Empty", &[], vec![folder("Nested", &[], vec![])]);
+        assert!(!has_bookmarks(&empty));
+
+        let nested = folder(
+            "Bar",
+            &[],
+            vec![folder("Dev", &["https://deta.space"], vec![])],
+        );
+        assert!(has_bookmarks(&nested));
+    }
+
+    #[test]
+    fn test_import_folders_as_spaces() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        let resources_path = dir.path().to_string_lossy().to_string();
+
+        let mut bar = folder(
+            "Bar",
+            &["https://deta.space"],
+            vec![
+                folder(
+                    "Dev",
+                    &["https://docs.rs", "https://crates.io"],
+                    vec![folder("Rust", &["https://rust-lang.org"], vec![])],
+                ),
+                folder("Empty", &[], vec![]),
+            ],
+        );
+        bar.children[0].description = Some("spaces for everything".to_string());
+        bar.children[0].tags = vec!["work".to_string()];
+        let folders = vec![bar, folder("Other", &[], vec![])];
+        let result = import_folders_as_spaces(&mut db, &resources_path, &folders).unwrap();
+        assert_eq!(result.space_ids.len(), 1);
+        assert_eq!(result.spaces_created, 3);
+        assert_eq!(result.resources_created, 4);
+
+        let spaces: HashMap<String, SpaceExtended> = db
+            .list_spaces()
+            .unwrap()
+            .into_iter()
+            .map(|space| (space_title(&space.name), space))
+            .collect();
+        assert_eq!(spaces.len(), 3);
+        assert_eq!(spaces["Bar"].id, result.space_ids[0]);
+        assert!(spaces["Bar"].parent_space_ids.is_empty());
+        assert_eq!(
+            spaces["Dev"].parent_space_ids,
+            vec![spaces["Bar"].id.clone()]
+        );
+        assert_eq!(
+            spaces["Rust"].parent_space_ids,
+            vec![spaces["Dev"].id.clone()]
+        );
+
+        let urls = |title: &str| {
+            let mut urls = db
+                .list_space_entries(&spaces[title].id, None, None, None)
+                .unwrap()
+                .into_iter()
+                .filter(|entry| matches!(entry.entry_type, SpaceEntryType::Resource))
+                .map(|entry| {
+                    db.get_resource_metadata_by_resource_id(&entry.entry_id)
+                        .unwrap()
+                        .unwrap()
+                        .source_uri
+                })
+                .collect::<Vec<_>>();
+            urls.sort();
+            urls
+        };
+        assert_eq!(urls("Bar"), vec!["https://deta.space"]);
+        assert_eq!(urls("Dev"), vec!["https://crates.io", "https://docs.rs"]);
+        assert_eq!(urls("Rust"), vec!["https://rust-lang.org"]);
+
+        // the description is the user context, the tags are hashtags
+        let deta = db
+            .list_resource_ids_by_source_uri("https://deta.space")
+            .unwrap()
+            .remove(0);
+        let metadata = db
+            .get_resource_metadata_by_resource_id(&deta)
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.user_context, "spaces for everything");
+        assert!(db
+            .list_resource_tags(&deta)
+            .unwrap()
+            .iter()
+            .any(|tag| tag.tag_name == "hashtag" && tag.tag_value == "work"));
+    }
+}
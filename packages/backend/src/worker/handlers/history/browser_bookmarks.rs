@@ -17,6 +17,11 @@ pub struct BookmarkItem {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_used_at: DateTime<Utc>,
+    // bookmark files can have a description and tags, browser bookmarks don't
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,6 +32,12 @@ pub struct BookmarkFolder {
     pub updated_at: DateTime<Utc>,
     pub last_used_at: DateTime<Utc>,
     pub children: Vec<BookmarkItem>,
+    // nested folders in the order the browser shows them
+    #[serde(default)]
+    pub folders: Vec<BookmarkFolder>,
+    // the browser profile the folder was imported from
+    #[serde(default)]
+    pub source_profile: Option<String>,
 }
 
 // Chrome-specific structures for parsing the bookmarks file
@@ -89,10 +100,7 @@ fn chrome_time_to_datetime(chrome_time: &str) -> DateTime<Utc> {
     DateTime::from(time)
 }
 
-fn process_chrome_bookmark(
-    bookmark: &ChromeBookmark,
-    folders: &mut Vec<BookmarkFolder>,
-) -> Option<BookmarkFolder> {
+fn process_chrome_bookmark(bookmark: &ChromeBookmark) -> Option<BookmarkFolder> {
     if bookmark.bookmark_type != "folder" {
         return None;
     }
@@ -110,6 +118,7 @@ fn process_chrome_bookmark(
         .unwrap_or(updated_at);
 
     let mut children = Vec::new();
+    let mut folders = Vec::new();
     for child in &bookmark.children {
         if child.bookmark_type == "url" {
             let child_created_at = chrome_time_to_datetime(&child.date_added);
@@ -131,12 +140,12 @@ fn process_chrome_bookmark(
                 created_at: child_created_at,
                 updated_at: child_updated_at,
                 last_used_at: child_last_used_at,
+                description: None,
+                tags: Vec::new(),
             });
-        } else if child.bookmark_type == "folder" {
+        } else if let Some(nested_folder) = process_chrome_bookmark(child) {
             // Recursively process nested folders
-            if let Some(nested_folder) = process_chrome_bookmark(child, folders) {
-                folders.push(nested_folder);
-            }
+            folders.push(nested_folder);
         }
     }
 
@@ -147,15 +156,92 @@ fn process_chrome_bookmark(
         updated_at,
         last_used_at,
         children,
+        folders,
+        source_profile: None,
     })
 }
 
+// assembles the folders stored with parent ids into trees, `folders` and `items` come with
+// their parent id and in the order they should appear in, folders without a parent folder are roots
+fn build_folder_tree(
+    folders: Vec<(i64, i64, BookmarkFolder)>,
+    items: Vec<(i64, BookmarkItem)>,
+) -> Vec<BookmarkFolder> {
+    fn assemble(
+        id: i64,
+        by_id: &mut HashMap<i64, BookmarkFolder>,
+        subfolders: &HashMap<i64, Vec<i64>>,
+    ) -> Option<BookmarkFolder> {
+        let mut folder = by_id.remove(&id)?;
+        for child_id in subfolders.get(&id).into_iter().flatten() {
+            if let Some(child) = assemble(*child_id, by_id, subfolders) {
+                folder.folders.push(child);
+            }
+        }
+        Some(folder)
+    }
+
+    let mut order = Vec::new();
+    let mut parents = HashMap::new();
+    let mut by_id = HashMap::new();
+    for (id, parent, folder) in folders {
+        order.push(id);
+        parents.insert(id, parent);
+        by_id.insert(id, folder);
+    }
+    for (parent, item) in items {
+        if let Some(folder) = by_id.get_mut(&parent) {
+            folder.children.push(item);
+        }
+    }
+
+    let mut subfolders: HashMap<i64, Vec<i64>> = HashMap::new();
+    let mut roots = Vec::new();
+    for id in &order {
+        match parents.get(id) {
+            Some(parent) if by_id.contains_key(parent) => {
+                subfolders.entry(*parent).or_default().push(*id)
+            }
+            _ => roots.push(*id),
+        }
+    }
+    roots
+        .into_iter()
+        .filter_map(|id| assemble(id, &mut by_id, &subfolders))
+        .filter_map(prune_empty_folders)
+        .collect()
+}
+
+// drops the folders that hold no bookmarks, directly or in a nested folder
+fn prune_empty_folders(mut folder: BookmarkFolder) -> Option<BookmarkFolder> {
+    folder.folders = std::mem::take(&mut folder.folders)
+        .into_iter()
+        .filter_map(prune_empty_folders)
+        .collect();
+    if folder.children.is_empty() && folder.folders.is_empty() {
+        return None;
+    }
+    Some(folder)
+}
+
+// every folder of the trees as a flat list without nested folders
+pub fn flatten_folders(folders: Vec<BookmarkFolder>) -> Vec<BookmarkFolder> {
+    let mut flat = Vec::new();
+    for mut folder in folders {
+        let nested = std::mem::take(&mut folder.folders);
+        flat.push(folder);
+        flat.extend(flatten_folders(nested));
+    }
+    flat
+}
+
 fn process_firefox_bookmarks(conn: &Connection) -> BackendResult<Vec<BookmarkFolder>> {
     let mut stmt = conn.prepare(
         "SELECT b.id, b.title, p.url, b.guid, b.parent, b.dateAdded, b.lastModified
          FROM moz_bookmarks b
          LEFT JOIN moz_places p ON b.fk = p.id
-         WHERE b.type IN (1,2)",
+         WHERE b.type IN (1,2)
+         ORDER BY b.parent, b.position",
     )?;
 
     let bookmark_iter = stmt.query_map([], |row| {
@@ -170,49 +256,51 @@ fn process_firefox_bookmarks(conn: &Connection) -> BackendResult<Vec<BookmarkFol
         })
     })?;
 
-    let mut bookmarks = Vec::new();
-    let mut folders_map: HashMap<i64, Vec<BookmarkItem>> = HashMap::new();
-    let mut folder_info: HashMap<i64, (String, String, i64, i64)> = HashMap::new(); // (title, guid, dateAdded, lastModified)
+    let mut folders = Vec::new();
+    let mut items = Vec::new();
+    let mut tags_folder = None;
 
     for b in bookmark_iter.flatten() {
-        if let Some(url) = b.url {
-            let created_at = UNIX_EPOCH + Duration::from_micros(b.date_added.max(0) as u64);
-            let updated_at = UNIX_EPOCH + Duration::from_micros(b.last_modified.max(0) as u64);
-
-            let item = BookmarkItem {
-                guid: b.guid,
-                title: b.title,
-                url,
-                created_at: DateTime::from(created_at),
-                updated_at: DateTime::from(updated_at),
-                last_used_at: DateTime::from(updated_at),
-            };
-
-            folders_map.entry(b.parent).or_default().push(item);
-        } else {
-            // This is a folder
-            folder_info.insert(b.id, (b.title, b.guid, b.date_added, b.last_modified));
-        }
-    }
+        let created_at = UNIX_EPOCH + Duration::from_micros(b.date_added.max(0) as u64);
+        let updated_at = UNIX_EPOCH + Duration::from_micros(b.last_modified.max(0) as u64);
 
-    // Convert the folders map into BookmarkFolder structs
-    for (folder_id, (title, guid, date_added, last_modified)) in folder_info {
-        if let Some(children) = folders_map.remove(&folder_id) {
-            let created_at = UNIX_EPOCH + Duration::from_micros(date_added.max(0) as u64);
-            let updated_at = UNIX_EPOCH + Duration::from_micros(last_modified.max(0) as u64);
-
-            bookmarks.push(BookmarkFolder {
-                guid,
-                title,
-                created_at: DateTime::from(created_at),
-                updated_at: DateTime::from(updated_at),
-                last_used_at: DateTime::from(updated_at),
-                children,
-            });
+        if let Some(url) = b.url {
+            items.push((
+                b.parent,
+                BookmarkItem {
+                    guid: b.guid,
+                    title: b.title,
+                    url,
+                    created_at: DateTime::from(created_at),
+                    updated_at: DateTime::from(updated_at),
+                    last_used_at: DateTime::from(updated_at),
+                    description: None,
+                    tags: Vec::new(),
+                },
+            ));
+        } else if b.guid == "tags________" {
+            // tags are stored as folders holding copies of the tagged bookmarks
+            tags_folder = Some(b.id);
+        } else if b.parent != 0 && Some(b.parent) != tags_folder {
+            // the places root (parent 0) only holds the menu, toolbar and other folders
+            folders.push((
+                b.id,
+                b.parent,
+                BookmarkFolder {
+                    guid: b.guid,
+                    title: b.title,
+                    created_at: DateTime::from(created_at),
+                    updated_at: DateTime::from(updated_at),
+                    last_used_at: DateTime::from(updated_at),
+                    children: Vec::new(),
+                    folders: Vec::new(),
+                    source_profile: None,
+                },
+            ));
         }
     }
 
-    Ok(bookmarks)
+    Ok(build_folder_tree(folders, items))
 }
 
 fn process_safari_bookmarks(conn: &Connection) -> BackendResult<Vec<BookmarkFolder>> {
@@ -233,49 +321,46 @@ fn process_safari_bookmarks(conn: &Connection) -> BackendResult<Vec<BookmarkFold
         })
     })?;
 
-    let mut bookmarks = Vec::new();
-    let mut folders_map: HashMap<i64, Vec<BookmarkItem>> = HashMap::new();
-    let mut folder_info: HashMap<i64, (String, i64, i64)> = HashMap::new(); // (title, created_at, updated_at)
+    let mut folders = Vec::new();
+    let mut items = Vec::new();
 
     for b in bookmark_iter.flatten() {
+        let created_at = UNIX_EPOCH + Duration::from_secs(b.created_at.max(0) as u64);
+        let updated_at = UNIX_EPOCH + Duration::from_secs(b.updated_at.max(0) as u64);
+
         if let Some(url) = b.url {
-            let created_at = UNIX_EPOCH + Duration::from_secs(b.created_at.max(0) as u64);
-            let updated_at = UNIX_EPOCH + Duration::from_secs(b.updated_at.max(0) as u64);
-
-            let item = BookmarkItem {
-                guid: b.id.to_string(),
-                title: b.title,
-                url,
-                created_at: DateTime::from(created_at),
-                updated_at: DateTime::from(updated_at),
-                last_used_at: DateTime::from(updated_at),
-            };
-
-            folders_map.entry(b.parent_id).or_default().push(item);
+            items.push((
+                b.parent_id,
+                BookmarkItem {
+                    guid: b.id.to_string(),
+                    title: b.title,
+                    url,
+                    created_at: DateTime::from(created_at),
+                    updated_at: DateTime::from(updated_at),
+                    last_used_at: DateTime::from(updated_at),
+                    description: None,
+                    tags: Vec::new(),
+                },
+            ));
         } else {
-            // This is a folder
-            folder_info.insert(b.id, (b.title, b.created_at, b.updated_at));
-        }
-    }
-
-    // Convert the folders map into BookmarkFolder structs
-    for (folder_id, (title, created_at_secs, updated_at_secs)) in folder_info {
-        if let Some(children) = folders_map.remove(&folder_id) {
-            let created_at = UNIX_EPOCH + Duration::from_secs(created_at_secs.max(0) as u64);
-            let updated_at = UNIX_EPOCH + Duration::from_secs(updated_at_secs.max(0) as u64);
-
-            bookmarks.push(BookmarkFolder {
-                guid: folder_id.to_string(),
-                title,
-                created_at: DateTime::from(created_at),
-                updated_at: DateTime::from(updated_at),
-                last_used_at: DateTime::from(updated_at),
-                children,
-            });
+            folders.push((
+                b.id,
+                b.parent_id,
+                BookmarkFolder {
+                    guid: b.id.to_string(),
+                    title: b.title,
+                    created_at: DateTime::from(created_at),
+                    updated_at: DateTime::from(updated_at),
+                    last_used_at: DateTime::from(updated_at),
+                    children: Vec::new(),
+                    folders: Vec::new(),
+                    source_profile: None,
+                },
+            ));
         }
     }
 
-    Ok(bookmarks)
+    Ok(build_folder_tree(folders, items))
 }
 
 pub fn parse_chrome_bookmarks(
@@ -284,22 +369,13 @@ pub fn parse_chrome_bookmarks(
     let content = fs::read_to_string(bookmarks_path)?;
     let chrome_bookmarks: ChromeBookmarks = serde_json::from_str(&content)?;
 
-    let mut folders = Vec::new();
-
-    // Process main bookmark folders
-    if let Some(folder) =
-        process_chrome_bookmark(&chrome_bookmarks.roots.bookmark_bar, &mut folders)
-    {
-        folders.push(folder);
-    }
-    if let Some(folder) = process_chrome_bookmark(&chrome_bookmarks.roots.other, &mut folders) {
-        folders.push(folder);
-    }
-    if let Some(folder) = process_chrome_bookmark(&chrome_bookmarks.roots.synced, &mut folders) {
-        folders.push(folder);
-    }
-
-    Ok(folders)
+    // the main bookmark folders are the roots of the tree
+    let roots = &chrome_bookmarks.roots;
+    Ok([&roots.bookmark_bar, &roots.other, &roots.synced]
+        .iter()
+        .copied()
+        .filter_map(process_chrome_bookmark)
+        .collect())
 }
 
 pub fn parse_firefox_bookmarks(
@@ -338,3 +414,57 @@ pub fn parse_safari_bookmarks(
 
     process_safari_bookmarks(&conn)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_firefox_bookmarks_tree() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT);
+            CREATE TABLE moz_bookmarks (id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER, parent INTEGER,
+                position INTEGER, title TEXT, guid TEXT, dateAdded INTEGER, lastModified INTEGER);
+            INSERT INTO moz_places VALUES (1, 'https://deta.space'), (2, 'https://example.com'), (3, 'https://rust-lang.org');
+            INSERT INTO moz_bookmarks VALUES
+                (1, 2, NULL, 0, 0, '', 'root________', 0, 0),
+                (2, 2, NULL, 1, 0, 'toolbar', 'toolbar_____', 0, 0),
+                (3, 2, NULL, 1, 1, 'tags', 'tags________', 0, 0),
+                (4, 2, NULL, 3, 0, 'work', 'tagfolder001', 0, 0),
+                (5, 2, NULL, 2, 1, 'Dev', 'devfolder001', 0, 0),
+                (6, 2, NULL, 5, 0, 'Rust', 'rustfolder01', 0, 0),
+                (7, 2, NULL, 2, 2, 'Empty', 'emptyfolder1', 0, 0),
+                (8, 1, 1, 2, 0, 'Deta', 'bookmark0001', 0, 0),
+                (9, 1, 3, 6, 0, 'Rust', 'bookmark0002', 0, 0),
+                (10, 1, 2, 6, 1, 'Example', 'bookmark0003', 0, 0),
+                (11, 1, 1, 4, 0, 'Deta', 'bookmark0004', 0, 0);",
+        )
+        .unwrap();
+
+        let folders = process_firefox_bookmarks(&conn).unwrap();
+        assert_eq!(folders.len(), 1);
+        let toolbar = &folders[0];
+        assert_eq!(toolbar.title, "toolbar");
+        assert_eq!(toolbar.children.len(), 1);
+        assert_eq!(toolbar.folders.len(), 1);
+        let dev = &toolbar.folders[0];
+        assert_eq!(dev.title, "Dev");
+        assert!(dev.children.is_empty());
+        let rust = &dev.folders[0];
+        assert_eq!(
+            rust.children
+                .iter()
+                .map(|c| c.url.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://rust-lang.org", "https://example.com"]
+        );
+
+        let flat = flatten_folders(folders);
+        assert_eq!(
+            flat.iter().map(|f| f.title.as_str()).collect::<Vec<_>>(),
+            vec!["toolbar", "Dev", "Rust"]
+        );
+        assert!(flat.iter().all(|f| f.folders.is_empty()));
+    }
+}
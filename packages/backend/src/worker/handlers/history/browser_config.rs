@@ -1,6 +1,9 @@
-use crate::BackendResult;
+use crate::{BackendError, BackendResult};
 use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -14,266 +17,144 @@ pub enum BrowserFamily {
 pub struct BrowserConfig {
     pub name: &'static str,
     pub family: BrowserFamily,
-    /// Function that returns the path to the browser's history database
-    pub get_history_path: fn(&str) -> PathBuf,
-    /// Function that returns the path to the browser's bookmarks database
-    pub get_bookmarks_path: fn(&str) -> PathBuf,
+    /// Function that returns the directory holding the browser's profiles,
+    /// the user data directory for Chromium and the one with `profiles.ini` for Firefox
+    pub get_profiles_dir: fn(&str) -> PathBuf,
 }
 
-fn get_home_dir() -> Option<String> {
-    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
-}
-
-// Chromium-based browser paths
-fn chrome_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Google/Chrome/Default/History")
-        }
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Google/Chrome/User Data/Default/History")
-        }
-        "linux" => Path::new(home_dir).join(".config/google-chrome/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn chrome_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Google/Chrome/Default/Bookmarks")
-        }
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Google/Chrome/User Data/Default/Bookmarks")
-        }
-        "linux" => Path::new(home_dir).join(".config/google-chrome/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn brave_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir)
-            .join("Library/Application Support/BraveSoftware/Brave-Browser/Default/History"),
-        "windows" => Path::new(home_dir)
-            .join("AppData/Local/BraveSoftware/Brave-Browser/User Data/Default/History"),
-        "linux" => Path::new(home_dir).join(".config/BraveSoftware/Brave-Browser/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn edge_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Microsoft Edge/Default/History")
-        }
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Microsoft/Edge/User Data/Default/History")
-        }
-        "linux" => Path::new(home_dir).join(".config/microsoft-edge/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-fn opera_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir)
-            .join("Library/Application Support/com.operasoftware.Opera/Default/History"),
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Opera Software/Opera Stable/Default/History")
-        }
-        "linux" => Path::new(home_dir).join(".config/opera/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn vivaldi_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/Vivaldi/Default/History"),
-        "windows" => Path::new(home_dir).join("AppData/Local/Vivaldi/User Data/Default/History"),
-        "linux" => Path::new(home_dir).join(".config/vivaldi/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserProfile {
+    /// Directory name of the profile, e.g. `Profile 1` or `abcd1234.default-release`
+    pub id: String,
+    /// Name shown in the browser's profile picker
+    pub name: String,
+    pub is_default: bool,
+    #[serde(skip)]
+    pub history_path: PathBuf,
+    #[serde(skip)]
+    pub bookmarks_path: PathBuf,
 }
 
-fn arc_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Arc/User Data/Default/History")
-        }
-        "windows" => Path::new(home_dir).join("AppData/Local/Arc/User Data/Default/History"),
-        "linux" => Path::new(home_dir).join(".config/arc/Default/History"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn get_home_dir() -> Option<String> {
+    env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()
 }
 
-fn dia_history_path(home_dir: &str) -> PathBuf {
+fn os_path(home_dir: &str, macos: &str, windows: &str, linux: &str) -> PathBuf {
     match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Dia/User Data/Default/History")
-        }
-        "windows" => Path::new(home_dir).join("AppData/Local/Dia/User Data/Default/History"),
-        "linux" => Path::new(home_dir).join(".config/dia/Default/History"),
+        "macos" => Path::new(home_dir).join(macos),
+        "windows" => Path::new(home_dir).join(windows),
+        "linux" => Path::new(home_dir).join(linux),
         _ => Path::new(home_dir).join(""),
     }
 }
 
-fn safari_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Safari/History.db"),
-        _ => Path::new(home_dir).join(""), // Safari is only available on macOS
-    }
+// Chromium-based browser user data directories
+fn chrome_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Google/Chrome",
+        "AppData/Local/Google/Chrome/User Data",
+        ".config/google-chrome",
+    )
 }
 
-// Firefox-based browser paths
-fn firefox_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/Firefox/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/Mozilla/Firefox/Profiles"),
-        "linux" => Path::new(home_dir).join(".mozilla/firefox"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn brave_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/BraveSoftware/Brave-Browser",
+        "AppData/Local/BraveSoftware/Brave-Browser/User Data",
+        ".config/BraveSoftware/Brave-Browser",
+    )
 }
 
-fn tor_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/TorBrowser-Data/Browser/profile.default"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/TorBrowser/Browser/TorBrowser/Data/Browser/profile.default"),
-        "linux" => Path::new(home_dir).join(".local/share/torbrowser/tbb/x86_64/tor-browser/Browser/TorBrowser/Data/Browser/profile.default"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn edge_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Microsoft Edge",
+        "AppData/Local/Microsoft/Edge/User Data",
+        ".config/microsoft-edge",
+    )
 }
 
-fn waterfox_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/Waterfox/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/Waterfox/Profiles"),
-        "linux" => Path::new(home_dir).join(".waterfox"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn opera_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/com.operasoftware.Opera",
+        "AppData/Local/Opera Software/Opera Stable",
+        ".config/opera",
+    )
 }
 
-fn zen_history_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/zen/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/zen/Profiles"),
-        "linux" => Path::new(home_dir).join(".zen"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn vivaldi_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Vivaldi",
+        "AppData/Local/Vivaldi/User Data",
+        ".config/vivaldi",
+    )
 }
 
-fn firefox_bookmarks_path(home_dir: &str) -> PathBuf {
-    // For Firefox, the bookmarks are in the same places.sqlite file as history
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/Firefox/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/Mozilla/Firefox/Profiles"),
-        "linux" => Path::new(home_dir).join(".mozilla/firefox"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn tor_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/TorBrowser-Data/Browser/profile.default/places.sqlite"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/TorBrowser/Browser/TorBrowser/Data/Browser/profile.default/places.sqlite"),
-        "linux" => Path::new(home_dir).join(".local/share/torbrowser/tbb/x86_64/tor-browser/Browser/TorBrowser/Data/Browser/profile.default/places.sqlite"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn arc_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Arc/User Data",
+        "AppData/Local/Arc/User Data",
+        ".config/arc",
+    )
 }
 
-fn waterfox_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/Waterfox/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/Waterfox/Profiles"),
-        "linux" => Path::new(home_dir).join(".waterfox"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn dia_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Dia/User Data",
+        "AppData/Local/Dia/User Data",
+        ".config/dia",
+    )
 }
 
-fn zen_bookmarks_path(home_dir: &str) -> PathBuf {
+fn safari_profiles_dir(home_dir: &str) -> PathBuf {
     match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Application Support/zen/Profiles"),
-        "windows" => Path::new(home_dir).join("AppData/Roaming/zen/Profiles"),
-        "linux" => Path::new(home_dir).join(".zen"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn safari_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir).join("Library/Safari/Bookmarks.db"),
+        "macos" => Path::new(home_dir).join("Library/Safari"),
         _ => Path::new(home_dir).join(""), // Safari is only available on macOS
     }
 }
 
-fn brave_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir)
-            .join("Library/Application Support/BraveSoftware/Brave-Browser/Default/Bookmarks"),
-        "windows" => Path::new(home_dir)
-            .join("AppData/Local/BraveSoftware/Brave-Browser/User Data/Default/Bookmarks"),
-        "linux" => {
-            Path::new(home_dir).join(".config/BraveSoftware/Brave-Browser/Default/Bookmarks")
-        }
-        _ => Path::new(home_dir).join(""),
-    }
+// Firefox-based browser directories
+fn firefox_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Firefox",
+        "AppData/Roaming/Mozilla/Firefox",
+        ".mozilla/firefox",
+    )
 }
 
-fn edge_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Microsoft Edge/Default/Bookmarks")
-        }
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Microsoft/Edge/User Data/Default/Bookmarks")
-        }
-        "linux" => Path::new(home_dir).join(".config/microsoft-edge/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
+// Tor Browser has no profiles.ini, only a single `profile.default`
+fn tor_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/TorBrowser-Data/Browser",
+        "AppData/Roaming/TorBrowser/Browser/TorBrowser/Data/Browser",
+        ".local/share/torbrowser/tbb/x86_64/tor-browser/Browser/TorBrowser/Data/Browser",
+    )
 }
 
-fn opera_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => Path::new(home_dir)
-            .join("Library/Application Support/com.operasoftware.Opera/Default/Bookmarks"),
-        "windows" => {
-            Path::new(home_dir).join("AppData/Local/Opera Software/Opera Stable/Default/Bookmarks")
-        }
-        "linux" => Path::new(home_dir).join(".config/opera/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn waterfox_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/Waterfox",
+        "AppData/Roaming/Waterfox",
+        ".waterfox",
+    )
 }
 
-fn vivaldi_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Vivaldi/Default/Bookmarks")
-        }
-        "windows" => Path::new(home_dir).join("AppData/Local/Vivaldi/User Data/Default/Bookmarks"),
-        "linux" => Path::new(home_dir).join(".config/vivaldi/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn arc_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Arc/User Data/Default/Bookmarks")
-        }
-        "windows" => Path::new(home_dir).join("AppData/Local/Arc/User Data/Default/Bookmarks"),
-        "linux" => Path::new(home_dir).join(".config/arc/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
-}
-
-fn dia_bookmarks_path(home_dir: &str) -> PathBuf {
-    match env::consts::OS {
-        "macos" => {
-            Path::new(home_dir).join("Library/Application Support/Dia/User Data/Default/Bookmarks")
-        }
-        "windows" => Path::new(home_dir).join("AppData/Local/Dia/User Data/Default/Bookmarks"),
-        "linux" => Path::new(home_dir).join(".config/dia/Default/Bookmarks"),
-        _ => Path::new(home_dir).join(""),
-    }
+fn zen_profiles_dir(home_dir: &str) -> PathBuf {
+    os_path(
+        home_dir,
+        "Library/Application Support/zen",
+        "AppData/Roaming/zen",
+        ".zen",
+    )
 }
 
 static SUPPORTED_BROWSERS: Lazy<Vec<BrowserConfig>> = Lazy::new(|| {
@@ -282,77 +163,65 @@ static SUPPORTED_BROWSERS: Lazy<Vec<BrowserConfig>> = Lazy::new(|| {
         BrowserConfig {
             name: "safari",
             family: BrowserFamily::Safari,
-            get_history_path: safari_history_path,
-            get_bookmarks_path: safari_bookmarks_path,
+            get_profiles_dir: safari_profiles_dir,
         },
         // Chromium-based browsers
         BrowserConfig {
             name: "chrome",
             family: BrowserFamily::Chromium,
-            get_history_path: chrome_history_path,
-            get_bookmarks_path: chrome_bookmarks_path,
+            get_profiles_dir: chrome_profiles_dir,
         },
         BrowserConfig {
             name: "brave",
             family: BrowserFamily::Chromium,
-            get_history_path: brave_history_path,
-            get_bookmarks_path: brave_bookmarks_path,
+            get_profiles_dir: brave_profiles_dir,
         },
         BrowserConfig {
             name: "edge",
             family: BrowserFamily::Chromium,
-            get_history_path: edge_history_path,
-            get_bookmarks_path: edge_bookmarks_path,
+            get_profiles_dir: edge_profiles_dir,
         },
         BrowserConfig {
             name: "opera",
             family: BrowserFamily::Chromium,
-            get_history_path: opera_history_path,
-            get_bookmarks_path: opera_bookmarks_path,
+            get_profiles_dir: opera_profiles_dir,
         },
         BrowserConfig {
             name: "vivaldi",
             family: BrowserFamily::Chromium,
-            get_history_path: vivaldi_history_path,
-            get_bookmarks_path: vivaldi_bookmarks_path,
+            get_profiles_dir: vivaldi_profiles_dir,
         },
         BrowserConfig {
             name: "arc",
             family: BrowserFamily::Chromium,
-            get_history_path: arc_history_path,
-            get_bookmarks_path: arc_bookmarks_path,
+            get_profiles_dir: arc_profiles_dir,
         },
         BrowserConfig {
             name: "dia",
             family: BrowserFamily::Chromium,
-            get_history_path: dia_history_path,
-            get_bookmarks_path: dia_bookmarks_path,
+            get_profiles_dir: dia_profiles_dir,
         },
         // Firefox-based browsers
         BrowserConfig {
             name: "firefox",
             family: BrowserFamily::Firefox,
-            get_history_path: firefox_history_path,
-            get_bookmarks_path: firefox_bookmarks_path,
+            get_profiles_dir: firefox_profiles_dir,
         },
         BrowserConfig {
             name: "tor",
             family: BrowserFamily::Firefox,
-            get_history_path: tor_history_path,
-            get_bookmarks_path: tor_bookmarks_path,
+            get_profiles_dir: tor_profiles_dir,
         },
         BrowserConfig {
             name: "waterfox",
             family: BrowserFamily::Firefox,
-            get_history_path: waterfox_history_path,
-            get_bookmarks_path: waterfox_bookmarks_path,
+            get_profiles_dir: waterfox_profiles_dir,
         },
         // Add Zen browser
         BrowserConfig {
             name: "zen",
             family: BrowserFamily::Firefox,
-            get_history_path: zen_history_path,
-            get_bookmarks_path: zen_bookmarks_path,
+            get_profiles_dir: zen_profiles_dir,
         },
     ]
 });
@@ -361,126 +230,296 @@ pub fn get_browser_config(browser_type: &str) -> Option<&'static BrowserConfig>
     SUPPORTED_BROWSERS.iter().find(|b| b.name == browser_type)
 }
 
-pub fn get_history_file_path(browser_type: &str) -> BackendResult<PathBuf> {
-    let home_dir = get_home_dir().ok_or_else(|| {
-        crate::BackendError::GenericError("Could not determine home directory".to_string())
-    })?;
+fn chromium_profile(dir: &Path, id: &str, name: &str) -> BrowserProfile {
+    BrowserProfile {
+        id: id.to_string(),
+        name: name.to_string(),
+        is_default: id == "Default",
+        history_path: dir.join(id).join("History"),
+        bookmarks_path: dir.join(id).join("Bookmarks"),
+    }
+}
 
-    let browser_config = get_browser_config(browser_type).ok_or_else(|| {
-        crate::BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
-    })?;
+/// Reads the profiles from the `Local State` file in the user data directory,
+/// the `Default` profile stays the default as it was the only one we imported before
+fn list_chromium_profiles(dir: &Path) -> Vec<BrowserProfile> {
+    let local_state = fs::read_to_string(dir.join("Local State"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok());
+
+    let mut profiles: Vec<BrowserProfile> = local_state
+        .as_ref()
+        .and_then(|state| state["profile"]["info_cache"].as_object())
+        .map(|info_cache| {
+            info_cache
+                .iter()
+                .map(|(id, info)| chromium_profile(dir, id, info["name"].as_str().unwrap_or(id)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Local State is missing or unreadable, fall back to the default profile
+    if profiles.is_empty() {
+        profiles.push(chromium_profile(dir, "Default", "Default"));
+    }
 
-    let base_path = (browser_config.get_history_path)(&home_dir);
+    profiles.retain(|profile| dir.join(&profile.id).is_dir());
+    profiles.sort_by(|a, b| b.is_default.cmp(&a.is_default).then(a.id.cmp(&b.id)));
+    if !profiles.iter().any(|p| p.is_default) {
+        if let Some(first) = profiles.first_mut() {
+            first.is_default = true;
+        }
+    }
+    profiles
+}
 
-    match browser_config.family {
-        BrowserFamily::Chromium => {
-            if !base_path.exists() {
-                return Err(crate::BackendError::GenericError(format!(
-                    "Browser history file not found at: {:?}",
-                    base_path
-                )));
-            }
-            Ok(base_path)
+/// Minimal ini parser, returns the sections in file order
+fn parse_ini(content: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut sections: Vec<(String, HashMap<String, String>)> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), HashMap::new()));
+        } else if let (Some((key, value)), Some((_, section))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            section.insert(key.trim().to_string(), value.trim().to_string());
         }
-        BrowserFamily::Firefox => {
-            if base_path.exists() {
-                // For Firefox-based browsers, we need to find the default profile
-                if let Ok(entries) = std::fs::read_dir(&base_path) {
-                    for entry in entries.filter_map(Result::ok) {
-                        let path = entry.path();
-                        if path.is_dir() {
-                            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                                // Handle various profile naming patterns
-                                if name.ends_with(".default-release")
-                                    || name.ends_with(".default")
-                                    || name.contains("Default")
-                                    || (name.contains('.') && name.contains("release"))
-                                {
-                                    let history_file = path.join("places.sqlite");
-                                    if history_file.exists() {
-                                        return Ok(history_file);
-                                    }
-                                }
-                            }
-                        }
+    }
+    sections
+}
+
+fn firefox_profile(path: PathBuf, name: Option<&str>, is_default: bool) -> Option<BrowserProfile> {
+    let id = path.file_name()?.to_str()?.to_string();
+    Some(BrowserProfile {
+        name: name.unwrap_or(&id).to_string(),
+        id,
+        is_default,
+        history_path: path.join("places.sqlite"),
+        // For Firefox, the bookmarks are in the same places.sqlite file as history
+        bookmarks_path: path.join("places.sqlite"),
+    })
+}
+
+/// Reads the profiles from `profiles.ini`, browsers without one get their
+/// profile directories scanned for a `places.sqlite`
+fn list_firefox_profiles(dir: &Path) -> Vec<BrowserProfile> {
+    let sections = fs::read_to_string(dir.join("profiles.ini"))
+        .map(|content| parse_ini(&content))
+        .unwrap_or_default();
+
+    // the default of the latest install takes precedence over the `Default=1` flag
+    let install_default = sections
+        .iter()
+        .find(|(name, _)| name.starts_with("Install"))
+        .and_then(|(_, section)| section.get("Default"));
+
+    let mut profiles: Vec<BrowserProfile> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Profile"))
+        .filter_map(|(_, section)| {
+            let path = section.get("Path")?;
+            let full_path = if section.get("IsRelative").map(String::as_str) == Some("0") {
+                PathBuf::from(path)
+            } else {
+                dir.join(path)
+            };
+            let is_default = match install_default {
+                Some(default) => default == path,
+                None => section.get("Default").map(String::as_str) == Some("1"),
+            };
+            firefox_profile(
+                full_path,
+                section.get("Name").map(String::as_str),
+                is_default,
+            )
+        })
+        .filter(|profile| profile.history_path.parent().is_some_and(Path::is_dir))
+        .collect();
+
+    if profiles.is_empty() {
+        for search_dir in [dir.to_path_buf(), dir.join("Profiles")] {
+            let entries = match fs::read_dir(&search_dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                if !path.join("places.sqlite").exists() {
+                    continue;
+                }
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    // Handle various profile naming patterns
+                    let is_default = name.ends_with(".default-release")
+                        || name.ends_with(".default")
+                        || name.contains("Default")
+                        || (name.contains('.') && name.contains("release"));
+                    if let Some(profile) = firefox_profile(path.clone(), None, is_default) {
+                        profiles.push(profile);
                     }
                 }
             }
-            Err(crate::BackendError::GenericError(format!(
-                "Could not find history file in profiles directory: {:?}",
-                base_path
-            )))
         }
-        BrowserFamily::Safari => {
-            if !base_path.exists() {
-                return Err(crate::BackendError::GenericError(format!(
-                    "Browser history file not found at: {:?}",
-                    base_path
-                )));
-            }
-            Ok(base_path)
+        profiles.sort_by(|a, b| b.is_default.cmp(&a.is_default).then(a.id.cmp(&b.id)));
+        // only one of the profiles matching the naming patterns can be the default
+        for profile in profiles.iter_mut().skip(1) {
+            profile.is_default = false;
+        }
+    }
+
+    if !profiles.iter().any(|p| p.is_default) {
+        if let Some(first) = profiles.first_mut() {
+            first.is_default = true;
         }
     }
+    profiles
 }
 
-pub fn get_bookmarks_file_path(browser_type: &str) -> BackendResult<PathBuf> {
+fn list_safari_profiles(dir: &Path) -> Vec<BrowserProfile> {
+    if !dir.is_dir() {
+        return vec![];
+    }
+    vec![BrowserProfile {
+        id: "default".to_string(),
+        name: "Safari".to_string(),
+        is_default: true,
+        history_path: dir.join("History.db"),
+        bookmarks_path: dir.join("Bookmarks.db"),
+    }]
+}
+
+pub fn list_browser_profiles(browser_type: &str) -> BackendResult<Vec<BrowserProfile>> {
     let home_dir = get_home_dir().ok_or_else(|| {
-        crate::BackendError::GenericError("Could not determine home directory".to_string())
+        BackendError::GenericError("Could not determine home directory".to_string())
     })?;
 
     let browser_config = get_browser_config(browser_type).ok_or_else(|| {
-        crate::BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
+        BackendError::GenericError(format!("Unsupported browser type: {}", browser_type))
     })?;
 
-    let base_path = (browser_config.get_bookmarks_path)(&home_dir);
+    let dir = (browser_config.get_profiles_dir)(&home_dir);
+    Ok(match browser_config.family {
+        BrowserFamily::Chromium => list_chromium_profiles(&dir),
+        BrowserFamily::Firefox => list_firefox_profiles(&dir),
+        BrowserFamily::Safari => list_safari_profiles(&dir),
+    })
+}
 
-    match browser_config.family {
-        BrowserFamily::Chromium => {
-            if !base_path.exists() {
-                return Err(crate::BackendError::GenericError(format!(
-                    "Browser history file not found at: {:?}",
-                    base_path
-                )));
-            }
-            Ok(base_path)
-        }
-        BrowserFamily::Firefox => {
-            if base_path.exists() {
-                // For Firefox-based browsers, we need to find the default profile
-                if let Ok(entries) = std::fs::read_dir(&base_path) {
-                    for entry in entries.filter_map(Result::ok) {
-                        let path = entry.path();
-                        if path.is_dir() {
-                            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                                // Handle various profile naming patterns
-                                if name.ends_with(".default-release")
-                                    || name.ends_with(".default")
-                                    || name.contains("Default")
-                                    || (name.contains('.') && name.contains("release"))
-                                {
-                                    let history_file = path.join("places.sqlite");
-                                    if history_file.exists() {
-                                        return Ok(history_file);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Err(crate::BackendError::GenericError(format!(
-                "Could not find history file in profiles directory: {:?}",
-                base_path
-            )))
-        }
-        BrowserFamily::Safari => {
-            if !base_path.exists() {
-                return Err(crate::BackendError::GenericError(format!(
-                    "Browser history file not found at: {:?}",
-                    base_path
-                )));
-            }
-            Ok(base_path)
-        }
+/// The profile with the given id, or the default one if there's no id
+pub fn get_browser_profile(
+    browser_type: &str,
+    profile_id: Option<&str>,
+) -> BackendResult<BrowserProfile> {
+    let profiles = list_browser_profiles(browser_type)?;
+    let profile = match profile_id {
+        Some(id) => profiles.into_iter().find(|p| p.id == id),
+        None => profiles.into_iter().find(|p| p.is_default),
+    };
+    profile.ok_or_else(|| {
+        BackendError::GenericError(format!(
+            "No {} profile found for {}",
+            profile_id.unwrap_or("default"),
+            browser_type
+        ))
+    })
+}
+
+pub fn get_history_file_path(profile: &BrowserProfile) -> BackendResult<PathBuf> {
+    if !profile.history_path.exists() {
+        return Err(BackendError::GenericError(format!(
+            "Browser history file not found at: {:?}",
+            profile.history_path
+        )));
+    }
+    Ok(profile.history_path.clone())
+}
+
+pub fn get_bookmarks_file_path(profile: &BrowserProfile) -> BackendResult<PathBuf> {
+    if !profile.bookmarks_path.exists() {
+        return Err(BackendError::GenericError(format!(
+            "Browser bookmarks file not found at: {:?}",
+            profile.bookmarks_path
+        )));
+    }
+    Ok(profile.bookmarks_path.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_chromium_profiles() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("Default")).unwrap();
+        fs::create_dir(dir.path().join("Profile 1")).unwrap();
+        fs::write(
+            dir.path().join("Local State"),
+            r#"{"profile": {"info_cache": {
+                "Profile 1": {"name": "Work"},
+                "Default": {"name": "Personal"},
+                "Profile 2": {"name": "Deleted"}
+            }}}"#,
+        )
+        .unwrap();
+
+        let profiles = list_chromium_profiles(dir.path());
+        let names: Vec<(&str, &str, bool)> = profiles
+            .iter()
+            .map(|p| (p.id.as_str(), p.name.as_str(), p.is_default))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("Default", "Personal", true), ("Profile 1", "Work", false)]
+        );
+        assert_eq!(
+            profiles[1].history_path,
+            dir.path().join("Profile 1").join("History")
+        );
+    }
+
+    #[test]
+    fn test_list_firefox_profiles() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Profiles/abc.default")).unwrap();
+        fs::create_dir_all(dir.path().join("Profiles/xyz.work")).unwrap();
+        fs::write(
+            dir.path().join("profiles.ini"),
+            "[Install4F96D1932A9F858E]\nDefault=Profiles/xyz.work\nLocked=1\n\n\
+             [Profile1]\nName=default\nIsRelative=1\nPath=Profiles/abc.default\nDefault=1\n\n\
+             [Profile0]\nName=work\nIsRelative=1\nPath=Profiles/xyz.work\n\n\
+             [General]\nStartWithLastProfile=1\nVersion=2\n",
+        )
+        .unwrap();
+
+        let profiles = list_firefox_profiles(dir.path());
+        let names: Vec<(&str, &str, bool)> = profiles
+            .iter()
+            .map(|p| (p.id.as_str(), p.name.as_str(), p.is_default))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("abc.default", "default", false),
+                ("xyz.work", "work", true)
+            ]
+        );
+        assert_eq!(
+            profiles[1].bookmarks_path,
+            dir.path().join("Profiles/xyz.work/places.sqlite")
+        );
+
+        // without profiles.ini, e.g. Tor Browser
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("profile.default")).unwrap();
+        fs::write(dir.path().join("profile.default/places.sqlite"), "").unwrap();
+        let profiles = list_firefox_profiles(dir.path());
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].id, "profile.default");
+        assert!(profiles[0].is_default);
     }
 }
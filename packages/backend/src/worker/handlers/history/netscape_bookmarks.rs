@@ -0,0 +1,255 @@
+use chrono::{DateTime, TimeZone, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::browser_bookmarks::{BookmarkFolder, BookmarkItem};
+use crate::store::models::{current_time, random_uuid};
+use crate::utils::{html_to_text, parse_html_attributes};
+use crate::worker::handlers::importers::split_tags;
+
+// the dates are unix timestamps in seconds
+fn parse_timestamp(attributes: &HashMap<String, String>, name: &str) -> Option<DateTime<Utc>> {
+    let seconds = attributes.get(name)?.trim().parse::<i64>().ok()?;
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+fn new_folder(title: String, attributes: &HashMap<String, String>) -> BookmarkFolder {
+    let created_at = parse_timestamp(attributes, "add_date").unwrap_or_else(current_time);
+    let updated_at = parse_timestamp(attributes, "last_modified").unwrap_or(created_at);
+    BookmarkFolder {
+        guid: random_uuid(),
+        title,
+        created_at,
+        updated_at,
+        last_used_at: updated_at,
+        children: Vec::new(),
+        folders: Vec::new(),
+        source_profile: None,
+    }
+}
+
+// parses the bookmark file format every browser and bookmark service exports,
+// bookmarks outside of any folder end up in a folder named after the file's heading,
+// the `<DD>` after a bookmark is its description
+pub fn parse_netscape_bookmarks(html: &str) -> Vec<BookmarkFolder> {
+    let re = Regex::new(
+        r"(?is)<h1\b[^>]*>(.*?)</h1>|<h3\b([^>]*)>(.*?)</h3>|<a\b([^>]*)>(.*?)</a>|<dd\b[^>]*>([^<]*)|<(/?)dl\b[^>]*>",
+    )
+    .unwrap();
+
+    let mut root = new_folder("Bookmarks".to_string(), &HashMap::new());
+    // folders whose list is open, the root is below them
+    let mut stack: Vec<BookmarkFolder> = Vec::new();
+    // a folder heading waiting for its list
+    let mut pending: Option<BookmarkFolder> = None;
+    // for every open list whether it belongs to a folder on the stack
+    let mut lists: Vec<bool> = Vec::new();
+    // whether the last match was a bookmark a description can belong to
+    let mut after_bookmark = false;
+
+    for c in re.captures_iter(html) {
+        let follows_bookmark = std::mem::replace(&mut after_bookmark, false);
+        if let Some(title) = c.get(1) {
+            let title = html_to_text(title.as_str());
+            if !title.is_empty() {
+                root.title = title;
+            }
+        } else if let Some(attributes) = c.get(2) {
+            let attributes = parse_html_attributes(attributes.as_str());
+            pending = Some(new_folder(html_to_text(&c[3]), &attributes));
+        } else if let Some(attributes) = c.get(4) {
+            let attributes = parse_html_attributes(attributes.as_str());
+            let url = match attributes.get("href") {
+                Some(url) if !url.trim().is_empty() => url.trim().to_string(),
+                _ => continue,
+            };
+            let created_at = parse_timestamp(&attributes, "add_date").unwrap_or_else(current_time);
+            let updated_at = parse_timestamp(&attributes, "last_modified").unwrap_or(created_at);
+            let item = BookmarkItem {
+                guid: random_uuid(),
+                title: html_to_text(&c[5]),
+                url,
+                created_at,
+                updated_at,
+                last_used_at: parse_timestamp(&attributes, "last_visit").unwrap_or(updated_at),
+                description: None,
+                tags: attributes
+                    .get("tags")
+                    .map(|tags| split_tags(tags, ','))
+                    .unwrap_or_default(),
+            };
+            stack.last_mut().unwrap_or(&mut root).children.push(item);
+            after_bookmark = true;
+        } else if let Some(description) = c.get(6) {
+            let description = html_to_text(description.as_str());
+            let item = stack.last_mut().unwrap_or(&mut root).children.last_mut();
+            if let Some(item) = item.filter(|_| follows_bookmark && !description.is_empty()) {
+                item.description = Some(description);
+            }
+        } else if &c[7] == "/" {
+            if lists.pop() == Some(true) {
+                if let Some(folder) = stack.pop() {
+                    stack.last_mut().unwrap_or(&mut root).folders.push(folder);
+                }
+            }
+        } else if let Some(folder) = pending.take() {
+            stack.push(folder);
+            lists.push(true);
+        } else {
+            // the outermost list belongs to the file itself, nested lists without a heading
+            // are flattened into their parent
+            lists.push(false);
+        }
+    }
+    // lists that were never closed
+    while let Some(folder) = stack.pop() {
+        stack.last_mut().unwrap_or(&mut root).folders.push(folder);
+    }
+
+    let mut folders = std::mem::take(&mut root.folders);
+    if !root.children.is_empty() {
+        folders.insert(0, root);
+    }
+    folders
+}
+
+fn write_folder(out: &mut String, folder: &BookmarkFolder, indent: usize) {
+    let pad = "    ".repeat(indent);
+    let _ = writeln!(
+        out,
+        "{}<DT><H3 ADD_DATE=\"{}\" LAST_MODIFIED=\"{}\">{}</H3>",
+        pad,
+        folder.created_at.timestamp(),
+        folder.updated_at.timestamp(),
+        html_escape::encode_text(&folder.title)
+    );
+    let _ = writeln!(out, "{}<DL><p>", pad);
+    write_entries(out, folder, indent + 1);
+    let _ = writeln!(out, "{}</DL><p>", pad);
+}
+
+fn write_entries(out: &mut String, folder: &BookmarkFolder, indent: usize) {
+    let pad = "    ".repeat(indent);
+    for item in &folder.children {
+        let tags = if item.tags.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " TAGS=\"{}\"",
+                html_escape::encode_double_quoted_attribute(&item.tags.join(","))
+            )
+        };
+        let _ = writeln!(
+            out,
+            "{}<DT><A HREF=\"{}\" ADD_DATE=\"{}\" LAST_MODIFIED=\"{}\"{}>{}</A>",
+            pad,
+            html_escape::encode_double_quoted_attribute(&item.url),
+            item.created_at.timestamp(),
+            item.updated_at.timestamp(),
+            tags,
+            html_escape::encode_text(&item.title)
+        );
+        if let Some(description) = &item.description {
+            let _ = writeln!(out, "{}<DD>{}", pad, html_escape::encode_text(description));
+        }
+    }
+    for nested in &folder.folders {
+        write_folder(out, nested, indent);
+    }
+}
+
+pub fn to_netscape_bookmarks(folders: &[BookmarkFolder]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+",
+    );
+    for folder in folders {
+        write_folder(&mut out, folder, 1);
+    }
+    out.push_str("</DL><p>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::browser_bookmarks::flatten_folders;
+    use super::*;
+
+    const EXPORT: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Pinboard</H1>
+<DL><p>
+    <DT><A HREF="https://deta.space" ADD_DATE="1700000000">Deta &amp; Space</A>
+    <DT><H3 ADD_DATE="1690000000">Reading</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/a?b=1&amp;c=2" ADD_DATE="1700000100" TAGS="web, examples,">Example</A>
+        <DD>a page with &lt;examples&gt;
+        <DT><H3>Rust</H3>
+        <DL><p>
+            <DT><A HREF="https://rust-lang.org">Rust</A>
+        </DL><p>
+    </DL><p>
+    <DT><A>no link</A>
+    <DD>the description of a bookmark without a link
+</DL><p>
+"#;
+
+    #[test]
+    fn test_parse_netscape_bookmarks() {
+        let folders = parse_netscape_bookmarks(EXPORT);
+        assert_eq!(folders.len(), 2);
+
+        let loose = &folders[0];
+        assert_eq!(loose.title, "Pinboard");
+        assert_eq!(loose.children.len(), 1);
+        assert_eq!(loose.children[0].title, "Deta & Space");
+        assert_eq!(loose.children[0].created_at.timestamp(), 1700000000);
+
+        let reading = &folders[1];
+        assert_eq!(reading.title, "Reading");
+        assert_eq!(reading.created_at.timestamp(), 1690000000);
+        assert_eq!(reading.children[0].url, "https://example.com/a?b=1&c=2");
+        assert_eq!(
+            reading.children[0].description.as_deref(),
+            Some("a page with <examples>")
+        );
+        assert_eq!(reading.children[0].tags, vec!["web", "examples"]);
+        assert_eq!(loose.children[0].description, None);
+        assert!(loose.children[0].tags.is_empty());
+        assert_eq!(reading.folders.len(), 1);
+        assert_eq!(reading.folders[0].children[0].url, "https://rust-lang.org");
+    }
+
+    #[test]
+    fn test_netscape_bookmarks_round_trip() {
+        let summary = |folders: Vec<BookmarkFolder>| {
+            flatten_folders(folders)
+                .into_iter()
+                .map(|f| {
+                    let items = f
+                        .children
+                        .into_iter()
+                        .map(|c| (c.url, c.description, c.tags))
+                        .collect::<Vec<_>>();
+                    (f.title, items)
+                })
+                .collect::<Vec<_>>()
+        };
+        let folders = parse_netscape_bookmarks(EXPORT);
+        let html = to_netscape_bookmarks(&folders);
+        assert!(html.contains("HREF=\"https://example.com/a?b=1&amp;c=2\""));
+        assert_eq!(
+            summary(parse_netscape_bookmarks(&html)),
+            summary(parse_netscape_bookmarks(EXPORT))
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+use zip::ZipArchive;
+
+use crate::{
+    api::message::{ImportMessage, TunnelOneshot},
+    store::{
+        db::Database,
+        models::{
+            current_time, random_uuid, Resource, ResourceMetadata, ResourceTag, Space, SpaceEntry,
+        },
+    },
+    worker::{send_worker_response, Worker},
+    BackendError, BackendResult,
+};
+
+mod csv;
+mod instapaper;
+mod notion;
+mod omnivore;
+mod pocket;
+mod raindrop;
+
+pub const LINK_RESOURCE_TYPE: &str = "application/vnd.space.link";
+pub const ARTICLE_RESOURCE_TYPE: &str = "application/vnd.space.article";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ImportService {
+    Pocket,
+    Raindrop,
+    Instapaper,
+    Omnivore,
+    Notion,
+}
+
+// an item read from a service's export file
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportItem {
+    pub title: String,
+    pub url: Option<String>,
+    // notes and highlights, stored as the resource's user context
+    pub note: Option<String>,
+    // the saved page or note itself, items with content become articles
+    pub content: Option<String>,
+    pub tags: Vec<String>,
+    // the folder, collection or database the item was in
+    pub collection: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedImportItem {
+    pub title: String,
+    pub reason: String,
+}
+
+impl SkippedImportItem {
+    pub fn new(title: &str, reason: &str) -> Self {
+        SkippedImportItem {
+            title: title.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ServiceExport {
+    pub items: Vec<ImportItem>,
+    pub skipped: Vec<SkippedImportItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceImportSummary {
+    pub resources_created: usize,
+    pub spaces_created: usize,
+    // the spaces the collections were imported into, including existing ones
+    pub space_ids: Vec<String>,
+    pub skipped: Vec<SkippedImportItem>,
+}
+
+fn parse_service_export(service: ImportService, data: &[u8]) -> BackendResult<ServiceExport> {
+    match service {
+        ImportService::Pocket => pocket::parse(data),
+        ImportService::Raindrop => raindrop::parse(data),
+        ImportService::Instapaper => instapaper::parse(data),
+        ImportService::Omnivore => omnivore::parse(data),
+        ImportService::Notion => notion::parse(data),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+// reads the files of a zip export, directories are left out
+pub fn read_zip_entries(data: &[u8]) -> BackendResult<Vec<ZipEntry>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().replace('\\', "/");
+        if name.ends_with('/') {
+            continue;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        entries.push(ZipEntry { name, data });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+pub fn write_zip(entries: &[ZipEntry]) -> BackendResult<Vec<u8>> {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for entry in entries {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file(entry.name.as_str(), options)?;
+        writer.write_all(&entry.data)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+// splits a tag list like "a, b" or "a|b" and drops empty tags
+pub fn split_tags(tags: &str, separator: char) -> Vec<String> {
+    tags.split(separator)
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+pub fn parse_unix_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(value.trim().parse::<i64>().ok()?, 0)
+}
+
+pub fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// the space name holds the notebook data the frontend reads
+pub fn notebook_data(title: &str, index: usize) -> String {
+    serde_json::json!({
+        "name": title,
+        "description": "",
+        "index": index,
+        "pinned": false,
+        "onboarding": false,
+        "imported": true,
+        "customization": {},
+        "icon": { "type": "icon", "data": "file-text-ai" },
+    })
+    .to_string()
+}
+
+// the title from the notebook data, older spaces use `folderName`
+pub fn space_title(name: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(name)
+        .ok()
+        .and_then(|data| {
+            data.get("name")
+                .or_else(|| data.get("folderName"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+pub fn create_imported_space_tx(
+    tx: &mut rusqlite::Transaction,
+    title: &str,
+    index: usize,
+) -> BackendResult<Space> {
+    let ct = current_time();
+    let space = Space {
+        id: random_uuid(),
+        name: notebook_data(title, index),
+        created_at: ct,
+        updated_at: ct,
+    };
+    Database::create_space_tx(tx, &space)?;
+    Ok(space)
+}
+
+pub struct ImportedResource<'a> {
+    pub resource_type: &'a str,
+    pub title: &'a str,
+    // empty if the resource doesn't point to a web page
+    pub url: &'a str,
+    pub user_context: &'a str,
+    // markdown or html body of the resource file
+    pub content: &'a str,
+    // tags on top of the ones every imported resource gets
+    pub tags: Vec<(&'a str, &'a str)>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ResourceFrontmatter<'a> {
+    title: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    url: &'a str,
+}
+
+// creates the resource with its metadata and tags, returns the resource and the
+// contents of its file, the file should only be written once the transaction is committed
+pub fn create_imported_resource_tx(
+    tx: &mut rusqlite::Transaction,
+    resources_path: &str,
+    imported: &ImportedResource,
+) -> BackendResult<(Resource, String)> {
+    let resource_id = random_uuid();
+    let resource_name = crate::utils::get_resource_filename(&resource_id, Some(imported.title));
+    let extension = crate::utils::get_resource_file_extension(imported.resource_type);
+    let resource = Resource {
+        id: resource_id.clone(),
+        resource_path: Path::new(resources_path)
+            .join(format!("{}.{}", resource_name, extension))
+            .as_os_str()
+            .to_string_lossy()
+            .to_string(),
+        resource_type: imported.resource_type.to_string(),
+        created_at: imported.created_at,
+        updated_at: imported.updated_at,
+        deleted: 0,
+    };
+    Database::create_resource_tx(tx, &resource)?;
+
+    let metadata = ResourceMetadata {
+        id: random_uuid(),
+        resource_id: resource_id.clone(),
+        name: imported.title.to_string(),
+        source_uri: imported.url.to_string(),
+        alt: String::new(),
+        user_context: imported.user_context.to_string(),
+    };
+    Database::create_resource_metadata_tx(tx, &metadata)?;
+
+    let mut tags = metadata.get_tags();
+    tags.push(ResourceTag::new_deleted(&resource_id, false));
+    tags.push(ResourceTag::new_type(&resource_id, imported.resource_type));
+    let mut extra_tags = vec![
+        ("savedWithAction", "import"),
+        // without content the page still has to be fetched
+        (
+            "dataState",
+            if imported.content.is_empty() {
+                "partial"
+            } else {
+                "complete"
+            },
+        ),
+    ];
+    if !imported.url.is_empty() {
+        extra_tags.push(("canonicalUrl", imported.url));
+    }
+    extra_tags.extend(imported.tags.iter().copied());
+    for (tag_name, tag_value) in extra_tags {
+        tags.push(ResourceTag {
+            id: random_uuid(),
+            resource_id: resource_id.clone(),
+            tag_name: tag_name.to_string(),
+            tag_value: tag_value.to_string(),
+        });
+    }
+    for tag in &tags {
+        Database::create_resource_tag_tx(tx, tag)?;
+    }
+
+    let frontmatter = serde_yaml::to_string(&ResourceFrontmatter {
+        title: imported.title,
+        url: imported.url,
+    })
+    .map_err(|e| BackendError::GenericError(e.to_string()))?;
+    let file = format!("---\n{}---\n\n{}", frontmatter, imported.content);
+    Ok((resource, file))
+}
+
+impl Worker {
+    pub fn import_service_export(
+        &mut self,
+        service: &str,
+        path: &str,
+    ) -> BackendResult<ServiceImportSummary> {
+        let service = ImportService::from_str(service).map_err(|_| {
+            BackendError::GenericError(format!("Unsupported import service: {}", service))
+        })?;
+        let data = fs::read(path)?;
+        let export = parse_service_export(service, &data)?;
+        self.import_items(export)
+    }
+
+    // creates a resource per item, collections are mapped to spaces with the same
+    // title, urls that are already in the library are skipped
+    fn import_items(&mut self, export: ServiceExport) -> BackendResult<ServiceImportSummary> {
+        let mut summary = ServiceImportSummary {
+            resources_created: 0,
+            spaces_created: 0,
+            space_ids: Vec::new(),
+            skipped: export.skipped,
+        };
+
+        let existing_spaces = self.db.list_spaces()?;
+        let mut next_index = existing_spaces.len();
+        let mut spaces: HashMap<String, String> = existing_spaces
+            .into_iter()
+            .map(|space| (space_title(&space.name), space.id))
+            .collect();
+
+        let mut items = Vec::new();
+        let mut seen_urls = HashSet::new();
+        for item in export.items {
+            if let Some(url) = &item.url {
+                if !seen_urls.insert(url.clone()) {
+                    summary
+                        .skipped
+                        .push(SkippedImportItem::new(&item.title, "duplicate in export"));
+                    continue;
+                }
+                if !self.db.list_resource_ids_by_source_uri(url)?.is_empty() {
+                    summary
+                        .skipped
+                        .push(SkippedImportItem::new(&item.title, "already saved"));
+                    continue;
+                }
+            }
+            items.push(item);
+        }
+
+        let resources_path = self.resources_path.clone();
+        let mut files = Vec::new();
+        let mut tx = self.db.begin()?;
+        for item in &items {
+            let tags = item
+                .tags
+                .iter()
+                .map(|tag| ("hashtag", tag.as_str()))
+                .collect();
+            let created_at = item.created_at.unwrap_or_else(current_time);
+            let content = item.content.as_deref().unwrap_or_default();
+            let (resource, file) = create_imported_resource_tx(
+                &mut tx,
+                &resources_path,
+                &ImportedResource {
+                    resource_type: if content.is_empty() {
+                        LINK_RESOURCE_TYPE
+                    } else {
+                        ARTICLE_RESOURCE_TYPE
+                    },
+                    title: &item.title,
+                    url: item.url.as_deref().unwrap_or_default(),
+                    user_context: item.note.as_deref().unwrap_or_default(),
+                    content,
+                    tags,
+                    created_at,
+                    updated_at: created_at,
+                },
+            )?;
+            files.push((resource.resource_path.clone(), file));
+            summary.resources_created += 1;
+
+            let collection = match &item.collection {
+                Some(collection) => collection,
+                None => continue,
+            };
+            let space_id = match spaces.get(collection) {
+                Some(space_id) => space_id.clone(),
+                None => {
+                    let space = create_imported_space_tx(&mut tx, collection, next_index)?;
+                    next_index += 1;
+                    summary.spaces_created += 1;
+                    spaces.insert(collection.clone(), space.id.clone());
+                    space.id
+                }
+            };
+            if !summary.space_ids.contains(&space_id) {
+                summary.space_ids.push(space_id.clone());
+            }
+            Database::create_space_entry_tx(
+                &mut tx,
+                &SpaceEntry {
+                    id: random_uuid(),
+                    space_id,
+                    resource_id: resource.id,
+                    created_at: current_time(),
+                    updated_at: current_time(),
+                    manually_added: 1,
+                },
+            )?;
+        }
+        tx.commit()?;
+
+        for (path, content) in &files {
+            fs::write(path, content)?;
+        }
+        Ok(summary)
+    }
+}
+
+pub fn handle_import_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: ImportMessage,
+) {
+    match message {
+        ImportMessage::ImportServiceExport(service, path) => {
+            let result = worker.import_service_export(&service, &path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_title() {
+        assert_eq!(space_title(&notebook_data("Dev", 1)), "Dev");
+        assert_eq!(space_title(r#"{"folderName":"Old"}"#), "Old");
+        assert_eq!(space_title("Plain"), "Plain");
+    }
+}
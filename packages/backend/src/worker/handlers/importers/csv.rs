@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+// splits csv text into rows of fields, quoted fields can hold separators,
+// escaped quotes and line breaks
+fn parse_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.is_empty()));
+    rows
+}
+
+// the records keyed by their lowercased header
+pub fn parse_records(text: &str) -> Vec<HashMap<String, String>> {
+    let mut rows = parse_rows(text).into_iter();
+    let header = match rows.next() {
+        Some(header) => header
+            .into_iter()
+            .map(|name| name.trim().to_lowercase())
+            .collect::<Vec<_>>(),
+        None => return Vec::new(),
+    };
+    rows.map(|row| header.iter().cloned().zip(row).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(
+            "\u{feff}Title,URL,Note\r\n\"Deta, Space\",https://deta.space,\"said \"\"hi\"\"\nthen left\"\r\nEmpty,,\r\n\r\n",
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["title"], "Deta, Space");
+        assert_eq!(records[0]["url"], "https://deta.space");
+        assert_eq!(records[0]["note"], "said \"hi\"\nthen left");
+        assert_eq!(records[1]["url"], "");
+    }
+}
@@ -0,0 +1,69 @@
+use super::{csv, parse_unix_timestamp, split_tags, ImportItem, ServiceExport, SkippedImportItem};
+use crate::BackendResult;
+
+// the built-in folders, every other folder was created by the user
+const BUILTIN_FOLDERS: [&str; 3] = ["Unread", "Archive", "Starred"];
+
+pub fn parse(data: &[u8]) -> BackendResult<ServiceExport> {
+    let text = String::from_utf8_lossy(data);
+    let mut export = ServiceExport::default();
+    for record in csv::parse_records(&text) {
+        let field = |name: &str| record.get(name).map(|v| v.trim()).unwrap_or_default();
+        let url = field("url");
+        if url.is_empty() {
+            export
+                .skipped
+                .push(SkippedImportItem::new(field("title"), "missing url"));
+            continue;
+        }
+
+        // tags are a json list in newer exports
+        let tags = field("tags");
+        let tags =
+            serde_json::from_str::<Vec<String>>(tags).unwrap_or_else(|_| split_tags(tags, ','));
+        let collection = match field("folder") {
+            folder if folder.is_empty() || BUILTIN_FOLDERS.contains(&folder) => None,
+            folder => Some(folder.to_string()),
+        };
+        export.items.push(ImportItem {
+            title: if field("title").is_empty() {
+                url.to_string()
+            } else {
+                field("title").to_string()
+            },
+            url: Some(url.to_string()),
+            note: Some(field("selection"))
+                .filter(|selection| !selection.is_empty())
+                .map(|selection| selection.to_string()),
+            tags,
+            collection,
+            created_at: parse_unix_timestamp(field("timestamp")),
+            ..Default::default()
+        });
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instapaper_export() {
+        let csv = r#"URL,Title,Selection,Folder,Timestamp,Tags
+https://deta.space,Deta Space,the best part,Reading,1700000000,"[""cloud"",""tools""]"
+https://example.com,,,Archive,1700000100,
+,Missing,,Unread,1700000200,
+"#;
+        let export = parse(csv.as_bytes()).unwrap();
+        assert_eq!(export.items.len(), 2);
+        let deta = &export.items[0];
+        assert_eq!(deta.note.as_deref(), Some("the best part"));
+        assert_eq!(deta.tags, vec!["cloud", "tools"]);
+        assert_eq!(deta.collection.as_deref(), Some("Reading"));
+        let example = &export.items[1];
+        assert_eq!(example.title, "https://example.com");
+        assert_eq!(example.collection, None);
+        assert_eq!(export.skipped[0].title, "Missing");
+    }
+}
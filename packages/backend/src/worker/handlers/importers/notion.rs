@@ -0,0 +1,158 @@
+use regex::Regex;
+
+use super::{read_zip_entries, ImportItem, ServiceExport, SkippedImportItem, ZipEntry};
+use crate::BackendResult;
+
+// notion appends the page id to every exported file and folder name
+fn strip_page_id(name: &str) -> String {
+    let re = Regex::new(r"\s+[0-9a-f]{32}$").unwrap();
+    re.replace(name.trim(), "").to_string()
+}
+
+// large workspaces are exported as a zip of zipped parts
+fn collect_entries(data: &[u8], entries: &mut Vec<ZipEntry>) -> BackendResult<()> {
+    for entry in read_zip_entries(data)? {
+        if entry.name.ends_with(".zip") {
+            collect_entries(&entry.data, entries)?;
+        } else {
+            entries.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn parse_page(path: &str, markdown: &str) -> ImportItem {
+    let mut segments = path.split('/').collect::<Vec<_>>();
+    let file_name = segments.pop().unwrap_or_default();
+    let mut title = strip_page_id(file_name.trim_end_matches(".md"));
+    // pages are exported into the folder of their parent page
+    let collection = segments.last().map(|folder| strip_page_id(folder));
+
+    let mut lines = markdown.lines().peekable();
+    while lines.peek().map(|line| line.trim().is_empty()) == Some(true) {
+        lines.next();
+    }
+    if let Some(heading) = lines.peek().and_then(|line| line.strip_prefix("# ")) {
+        title = heading.trim().to_string();
+        lines.next();
+    }
+
+    // database pages start with their properties, e.g. `URL: https://..`
+    let mut url = None;
+    let mut tags = Vec::new();
+    let mut body = Vec::new();
+    let mut in_properties = true;
+    for line in lines {
+        if in_properties {
+            if line.trim().is_empty() {
+                if !body.is_empty() || url.is_some() || !tags.is_empty() {
+                    in_properties = false;
+                }
+                continue;
+            }
+            if let Some((name, value)) = line.split_once(": ") {
+                let value = value.trim();
+                match name.trim() {
+                    "URL" | "Url" | "Link" if value.starts_with("http") => {
+                        url = Some(value.to_string());
+                        continue;
+                    }
+                    "Tags" => {
+                        tags = super::split_tags(value, ',');
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            in_properties = false;
+        }
+        body.push(line);
+    }
+
+    ImportItem {
+        title,
+        url,
+        content: Some(body.join("\n").trim().to_string()).filter(|body| !body.is_empty()),
+        tags,
+        collection,
+        ..Default::default()
+    }
+}
+
+// every markdown page becomes an item, databases are imported through their pages
+pub fn parse(data: &[u8]) -> BackendResult<ServiceExport> {
+    let mut entries = Vec::new();
+    collect_entries(data, &mut entries)?;
+
+    let mut export = ServiceExport::default();
+    for entry in entries {
+        if entry.name.ends_with(".md") {
+            let item = parse_page(&entry.name, &String::from_utf8_lossy(&entry.data));
+            if item.url.is_none() && item.content.is_none() {
+                export
+                    .skipped
+                    .push(SkippedImportItem::new(&item.title, "empty page"));
+            } else {
+                export.items.push(item);
+            }
+        } else if !entry.name.ends_with(".csv") {
+            export
+                .skipped
+                .push(SkippedImportItem::new(&entry.name, "unsupported file"));
+        }
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::write_zip;
+    use super::*;
+
+    #[test]
+    fn test_parse_notion_export() {
+        let entry = |name: &str, data: &str| ZipEntry {
+            name: name.to_string(),
+            data: data.as_bytes().to_vec(),
+        };
+        let part = write_zip(&[
+            entry(
+                "Reading List 0123456789abcdef0123456789abcdef/Deta 11111111111111111111111111111111.md",
+                "# Deta Space\n\nURL: https://deta.space\nTags: cloud, tools\n\nA personal cloud.\n",
+            ),
+            entry(
+                "Reading List 0123456789abcdef0123456789abcdef.csv",
+                "Name,URL\nDeta Space,https://deta.space\n",
+            ),
+            entry("Notes 22222222222222222222222222222222.md", "# Notes\n\nSome *thoughts*.\n"),
+            entry("Empty 33333333333333333333333333333333.md", "# Empty\n"),
+            entry("Notes/image.png", "png"),
+        ])
+        .unwrap();
+        let archive = write_zip(&[ZipEntry {
+            name: "Export-Part-1.zip".to_string(),
+            data: part,
+        }])
+        .unwrap();
+
+        let export = parse(&archive).unwrap();
+        assert_eq!(export.items.len(), 2);
+        let deta = &export.items[0];
+        assert_eq!(deta.title, "Deta Space");
+        assert_eq!(deta.url.as_deref(), Some("https://deta.space"));
+        assert_eq!(deta.tags, vec!["cloud", "tools"]);
+        assert_eq!(deta.collection.as_deref(), Some("Reading List"));
+        assert_eq!(deta.content.as_deref(), Some("A personal cloud."));
+        let notes = &export.items[1];
+        assert_eq!(notes.collection, None);
+        assert_eq!(notes.content.as_deref(), Some("Some *thoughts*."));
+        assert_eq!(
+            export
+                .skipped
+                .iter()
+                .map(|s| s.reason.as_str())
+                .collect::<Vec<_>>(),
+            vec!["empty page", "unsupported file"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::{parse_rfc3339, read_zip_entries, ImportItem, ServiceExport, SkippedImportItem};
+use crate::BackendResult;
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OmnivoreLabel {
+    Name(String),
+    Label { name: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OmnivoreItem {
+    #[serde(default)]
+    slug: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    labels: Vec<OmnivoreLabel>,
+    #[serde(default)]
+    saved_at: Option<String>,
+}
+
+// the export is a zip of `metadata_*.json` lists with the saved pages in `content/`
+// and the highlights in `highlights/`, both named after the item's slug
+pub fn parse(data: &[u8]) -> BackendResult<ServiceExport> {
+    let mut metadata = Vec::new();
+    let mut files = HashMap::new();
+    for entry in read_zip_entries(data)? {
+        let file_name = entry.name.rsplit('/').next().unwrap_or_default();
+        if file_name.starts_with("metadata_") && file_name.ends_with(".json") {
+            metadata.extend(serde_json::from_slice::<Vec<OmnivoreItem>>(&entry.data)?);
+        } else {
+            files.insert(entry.name.clone(), entry.data);
+        }
+    }
+    let text_file = |dir: &str, slug: &str, extensions: &[&str]| {
+        extensions.iter().find_map(|extension| {
+            files
+                .iter()
+                .find(|(name, _)| name.ends_with(&format!("{}/{}.{}", dir, slug, extension)))
+                .map(|(_, data)| String::from_utf8_lossy(data).trim().to_string())
+                .filter(|text| !text.is_empty())
+        })
+    };
+
+    let mut export = ServiceExport::default();
+    for item in metadata {
+        if item.state.as_deref() == Some("DELETED") {
+            export
+                .skipped
+                .push(SkippedImportItem::new(&item.title, "deleted in Omnivore"));
+            continue;
+        }
+        let url = match item.url.filter(|url| !url.trim().is_empty()) {
+            Some(url) => url.trim().to_string(),
+            None => {
+                export
+                    .skipped
+                    .push(SkippedImportItem::new(&item.title, "missing url"));
+                continue;
+            }
+        };
+
+        let note = [
+            item.description.unwrap_or_default(),
+            text_file("highlights", &item.slug, &["md"]).unwrap_or_default(),
+        ]
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+        export.items.push(ImportItem {
+            title: if item.title.is_empty() {
+                url.clone()
+            } else {
+                item.title
+            },
+            url: Some(url),
+            note: if note.is_empty() { None } else { Some(note) },
+            content: text_file("content", &item.slug, &["md", "html"]),
+            tags: item
+                .labels
+                .into_iter()
+                .map(|label| match label {
+                    OmnivoreLabel::Name(name) | OmnivoreLabel::Label { name } => name,
+                })
+                .collect(),
+            collection: None,
+            created_at: item.saved_at.as_deref().and_then(parse_rfc3339),
+        });
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{write_zip, ZipEntry};
+    use super::*;
+
+    #[test]
+    fn test_parse_omnivore_export() {
+        let metadata = r#"[
+            {"id": "1", "slug": "deta-space", "title": "Deta Space", "description": "A personal cloud",
+             "url": "https://deta.space", "state": "SUCCEEDED", "labels": ["cloud", {"name": "tools"}],
+             "savedAt": "2023-11-14T22:13:20.000Z"},
+            {"id": "2", "slug": "gone", "title": "Gone", "url": "https://example.com", "state": "DELETED"}
+        ]"#;
+        let archive = write_zip(&[
+            ZipEntry {
+                name: "metadata_0_to_2.json".to_string(),
+                data: metadata.as_bytes().to_vec(),
+            },
+            ZipEntry {
+                name: "content/deta-space.html".to_string(),
+                data: b"<p>Your personal cloud computer</p>".to_vec(),
+            },
+            ZipEntry {
+                name: "highlights/deta-space.md".to_string(),
+                data: b"> personal cloud".to_vec(),
+            },
+        ])
+        .unwrap();
+
+        let export = parse(&archive).unwrap();
+        assert_eq!(export.items.len(), 1);
+        let deta = &export.items[0];
+        assert_eq!(deta.tags, vec!["cloud", "tools"]);
+        assert_eq!(
+            deta.note.as_deref(),
+            Some("A personal cloud\n\n> personal cloud")
+        );
+        assert_eq!(
+            deta.content.as_deref(),
+            Some("<p>Your personal cloud computer</p>")
+        );
+        assert_eq!(deta.created_at.map(|t| t.timestamp()), Some(1700000000));
+        assert_eq!(export.skipped[0].title, "Gone");
+    }
+}
@@ -0,0 +1,104 @@
+use regex::Regex;
+
+use super::{csv, parse_unix_timestamp, split_tags, ImportItem, ServiceExport, SkippedImportItem};
+use crate::utils::{html_to_text, parse_html_attributes};
+use crate::BackendResult;
+
+// older exports are a html list of links, newer ones a csv file
+pub fn parse(data: &[u8]) -> BackendResult<ServiceExport> {
+    let text = String::from_utf8_lossy(data);
+    if text.trim_start().starts_with('<') {
+        Ok(parse_html(&text))
+    } else {
+        Ok(parse_csv(&text))
+    }
+}
+
+fn parse_html(text: &str) -> ServiceExport {
+    let re = Regex::new(r"(?is)<a\b([^>]*)>(.*?)</a>").unwrap();
+    let mut export = ServiceExport::default();
+    for c in re.captures_iter(text) {
+        let attributes = parse_html_attributes(&c[1]);
+        let title = html_to_text(&c[2]);
+        match attributes.get("href").filter(|url| !url.trim().is_empty()) {
+            Some(url) => export.items.push(ImportItem {
+                title: if title.is_empty() { url.clone() } else { title },
+                url: Some(url.trim().to_string()),
+                tags: split_tags(
+                    attributes
+                        .get("tags")
+                        .map(|t| t.as_str())
+                        .unwrap_or_default(),
+                    ',',
+                ),
+                created_at: attributes
+                    .get("time_added")
+                    .and_then(|t| parse_unix_timestamp(t)),
+                ..Default::default()
+            }),
+            None => export
+                .skipped
+                .push(SkippedImportItem::new(&title, "missing url")),
+        }
+    }
+    export
+}
+
+fn parse_csv(text: &str) -> ServiceExport {
+    let mut export = ServiceExport::default();
+    for record in csv::parse_records(text) {
+        let field = |name: &str| record.get(name).map(|v| v.trim()).unwrap_or_default();
+        let url = field("url");
+        if url.is_empty() {
+            export
+                .skipped
+                .push(SkippedImportItem::new(field("title"), "missing url"));
+            continue;
+        }
+        export.items.push(ImportItem {
+            title: if field("title").is_empty() {
+                url.to_string()
+            } else {
+                field("title").to_string()
+            },
+            url: Some(url.to_string()),
+            tags: split_tags(field("tags"), '|'),
+            created_at: parse_unix_timestamp(field("time_added")),
+            ..Default::default()
+        });
+    }
+    export
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pocket_exports() {
+        let html = r#"<!DOCTYPE html><html><body>
+<h1>Unread</h1>
+<ul>
+<li><a href="https://deta.space" time_added="1700000000" tags="tools,cloud">Deta Space</a></li>
+<li><a href="https://example.com" time_added="1700000100" tags="">https://example.com</a></li>
+</ul>
+<h1>Read Archive</h1>
+<ul><li><a>broken</a></li></ul>
+</body></html>"#;
+        let export = parse(html.as_bytes()).unwrap();
+        assert_eq!(export.items.len(), 2);
+        assert_eq!(export.items[0].title, "Deta Space");
+        assert_eq!(export.items[0].tags, vec!["tools", "cloud"]);
+        assert_eq!(
+            export.items[0].created_at.map(|t| t.timestamp()),
+            Some(1700000000)
+        );
+        assert_eq!(export.skipped.len(), 1);
+
+        let csv = "title,url,time_added,tags,status\nDeta Space,https://deta.space,1700000000,tools|cloud,unread\nNo link,,1700000000,,archive\n";
+        let export = parse(csv.as_bytes()).unwrap();
+        assert_eq!(export.items.len(), 1);
+        assert_eq!(export.items[0].tags, vec!["tools", "cloud"]);
+        assert_eq!(export.skipped[0].title, "No link");
+    }
+}
@@ -0,0 +1,76 @@
+use super::{csv, parse_rfc3339, split_tags, ImportItem, ServiceExport, SkippedImportItem};
+use crate::{BackendError, BackendResult};
+
+// raindrops that aren't in a collection end up in "Unsorted"
+const UNSORTED_COLLECTION: &str = "Unsorted";
+
+// the csv export, the html export is a bookmark file and goes through the bookmark import
+pub fn parse(data: &[u8]) -> BackendResult<ServiceExport> {
+    let text = String::from_utf8_lossy(data);
+    if text.trim_start().starts_with('<') {
+        return Err(BackendError::GenericError(
+            "Raindrop.io html exports have to be imported as a bookmarks file".to_string(),
+        ));
+    }
+
+    let mut export = ServiceExport::default();
+    for record in csv::parse_records(&text) {
+        let field = |name: &str| record.get(name).map(|v| v.trim()).unwrap_or_default();
+        let url = field("url");
+        if url.is_empty() {
+            export
+                .skipped
+                .push(SkippedImportItem::new(field("title"), "missing url"));
+            continue;
+        }
+
+        let note = [field("note"), field("excerpt"), field("highlights")]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let collection = match field("folder") {
+            "" | UNSORTED_COLLECTION => None,
+            folder => Some(folder.to_string()),
+        };
+        export.items.push(ImportItem {
+            title: if field("title").is_empty() {
+                url.to_string()
+            } else {
+                field("title").to_string()
+            },
+            url: Some(url.to_string()),
+            note: if note.is_empty() { None } else { Some(note) },
+            tags: split_tags(field("tags"), ','),
+            collection,
+            created_at: parse_rfc3339(field("created")),
+            ..Default::default()
+        });
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_raindrop_export() {
+        let csv = r#"id,title,note,excerpt,url,folder,tags,created,cover,highlights,favorite
+1,Deta Space,my note,,https://deta.space,Tools,"cloud, personal",2023-11-14T22:13:20.000Z,,,false
+2,Example,,An example,https://example.com,Unsorted,,2023-11-14T22:13:20.000Z,,,false
+3,Nothing,,,,Tools,,,,,false
+"#;
+        let export = parse(csv.as_bytes()).unwrap();
+        assert_eq!(export.items.len(), 2);
+        let deta = &export.items[0];
+        assert_eq!(deta.note.as_deref(), Some("my note"));
+        assert_eq!(deta.tags, vec!["cloud", "personal"]);
+        assert_eq!(deta.collection.as_deref(), Some("Tools"));
+        assert_eq!(deta.created_at.map(|t| t.timestamp()), Some(1700000000));
+        assert_eq!(export.items[1].collection, None);
+        assert_eq!(export.items[1].note.as_deref(), Some("An example"));
+        assert_eq!(export.skipped.len(), 1);
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{
     ai::{
         llm::{
-            client::Model,
+            client::{CancellationToken, CompletionUsage, Model},
             models::{Message, MessageContent},
         },
         youtube::YoutubeTranscript,
-        {ChatInput, ChatResult, DocsSimilarity},
+        {chat_summary_message, ChatInput, ChatResult, DocsSimilarity},
     },
     api::message::{MiscMessage, TunnelOneshot},
     store::{
@@ -13,7 +13,7 @@ use crate::{
         models::{
             random_uuid, AIChatSession, AIChatSessionHistory, AIChatSessionMessage,
             AIChatSessionMessageSource, CompositeResource, EmbeddingType, InternalResourceTagNames,
-            ResourceTextContent,
+            Memory, ResourceTextContent,
         },
     },
     worker::{send_worker_response, Worker},
@@ -88,6 +88,20 @@ impl Worker {
         Ok(())
     }
 
+    pub fn list_memories(&mut self) -> BackendResult<Vec<Memory>> {
+        self.db.list_memories()
+    }
+
+    pub fn delete_memory(&mut self, id: String) -> BackendResult<()> {
+        if !self.db.delete_memory(&id)? {
+            return Err(BackendError::GenericError(format!(
+                "memory {} not found",
+                id
+            )));
+        }
+        Ok(())
+    }
+
     pub fn get_ai_docs_similarity(
         &mut self,
         query: String,
@@ -97,6 +111,7 @@ impl Worker {
         self.ai.get_docs_similarity(query, docs, threshold)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_app_query(
         &mut self,
         mut chunk_callback: Root<JsFunction>,
@@ -105,6 +120,7 @@ impl Worker {
         model: &Model,
         custom_key: Option<String>,
         inline_images: Option<Vec<String>>,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<()> {
         // frontend sends a query with a trailing <p></p> for some reason
         let query = match query.strip_suffix("<p></p>") {
@@ -112,9 +128,9 @@ impl Worker {
             None => query,
         };
 
-        let mut stream = self
-            .ai
-            .create_app(query, model, custom_key, inline_images)?;
+        let mut stream =
+            self.ai
+                .create_app(query, model, custom_key, inline_images, cancellation_token)?;
 
         for chunk in stream.by_ref() {
             match chunk {
@@ -134,10 +150,15 @@ impl Worker {
         model: Model,
         custom_key: Option<String>,
         _response_format: Option<&str>,
+        cancellation_token: CancellationToken,
     ) -> BackendResult<String> {
-        self.ai
-            .client
-            .create_chat_completion(messages, &model, custom_key, None)
+        self.ai.client.create_chat_completion(
+            messages,
+            &model,
+            custom_key,
+            None,
+            cancellation_token,
+        )
     }
 
     pub fn send_chat_query(
@@ -146,7 +167,7 @@ impl Worker {
         callback: Root<JsFunction>,
         search_only: bool,
         chat_input: ChatInput,
-    ) -> BackendResult<()> {
+    ) -> BackendResult<Option<CompletionUsage>> {
         // frontend sends a query with a trailing <p></p> sometimes for some reason
         let query = match chat_input.query.strip_suffix("<p></p>") {
             Some(q) => q.to_string(),
@@ -154,12 +175,13 @@ impl Worker {
         };
 
         if search_only {
-            return self.handle_search_only_query(
+            self.handle_search_only_query(
                 query,
                 chat_input.number_documents,
                 Some(chat_input.resource_ids),
                 callback,
-            );
+            )?;
+            return Ok(None);
         }
         self.handle_full_chat_query(session_id, callback, chat_input)
     }
@@ -243,13 +265,23 @@ impl Worker {
         session_id: Option<String>,
         callback: Root<JsFunction>,
         mut chat_input: ChatInput,
-    ) -> BackendResult<()> {
+    ) -> BackendResult<Option<CompletionUsage>> {
         let mut history: Vec<Message> = vec![];
+        let mut history_created_at = vec![];
 
         if let Some(ref session_id) = session_id {
-            history = self
-                .ai
-                .parse_chat_history(self.db.list_ai_session_messages_skip_sources(session_id)?)?;
+            let history_entries = self.db.list_ai_session_messages_skip_sources(session_id)?;
+            let history_entries = match chat_input.compact_history {
+                true => self.ai.apply_chat_summary(history_entries),
+                // cached summaries are ignored when compaction is turned off
+                false => history_entries
+                    .into_iter()
+                    .filter(|msg| msg.msg_type != "summary")
+                    .collect(),
+            };
+            let parsed = self.ai.parse_chat_history(history_entries)?;
+            history = parsed.messages;
+            history_created_at = parsed.created_at;
         }
 
         let mut should_cluster = false;
@@ -266,6 +298,7 @@ impl Worker {
                 chat_input.custom_key.clone(),
                 self.ai
                     .llm_metadata_messages_from_sources(&composite_resources),
+                &chat_input.cancellation_token,
             )?;
             should_cluster = should_cluster_result.embeddings_search_needed;
             // we are already narrowing down the search space
@@ -293,11 +326,18 @@ impl Worker {
 
         let (assistant_message, chat_result) =
             self.process_chat_stream(callback, chat_input, history, should_cluster)?;
+        // the frontend shows how much of the prompt was served from the cache
+        let usage = chat_result.stream.usage().cloned();
 
         if let Some(session_id) = session_id {
-            self.save_messages(session_id, assistant_message, chat_result)?;
+            self.save_messages(
+                session_id,
+                assistant_message,
+                chat_result,
+                &history_created_at,
+            )?;
         }
-        Ok(())
+        Ok(usage)
     }
 
     fn upsert_lazy_embedding(&mut self, resource_id: &str) -> BackendResult<()> {
@@ -381,6 +421,15 @@ impl Worker {
                 Err(err) => return Err(err),
             }
         }
+        if let Some(usage) = chat_result.stream.usage() {
+            tracing::debug!(
+                input_tokens = usage.input_tokens,
+                output_tokens = usage.output_tokens,
+                cache_read_tokens = usage.cache_read_tokens,
+                cache_write_tokens = usage.cache_write_tokens,
+                "chat completion usage"
+            );
+        }
 
         Ok((assistant_message, chat_result))
     }
@@ -422,8 +471,14 @@ impl Worker {
         session_id: String,
         assistant_message: String,
         chat_result: ChatResult,
+        history_created_at: &[chrono::DateTime<chrono::Utc>],
     ) -> BackendResult<()> {
         let mut tx = self.db.begin()?;
+        if let Some(summary) = chat_result.summary {
+            if let Some(message) = chat_summary_message(&session_id, summary, history_created_at) {
+                Database::create_ai_session_message_tx(&mut tx, &message)?;
+            }
+        }
         for msg in chat_result.messages.iter() {
             if msg.content.len() != 1 {
                 continue;
@@ -431,6 +486,11 @@ impl Worker {
             let (msg_type, content) = match msg.content[0] {
                 MessageContent::Text(ref t) => ("text".to_owned(), t.text.clone()),
                 MessageContent::Image(ref i) => ("image".to_owned(), i.image_url.url.clone()),
+                MessageContent::Document(ref d) => (
+                    "document".to_owned(),
+                    serde_json::to_string(&d.without_data())?,
+                ),
+                MessageContent::Audio(ref a) => ("audio".to_owned(), serde_json::to_string(a)?),
             };
 
             let message = AIChatSessionMessage {
@@ -469,6 +529,7 @@ impl Worker {
         crate::ai::youtube::fetch_transcript(&video_url, lang)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn query_sffs_resources(
         &self,
         prompt: String,
@@ -477,6 +538,7 @@ impl Worker {
         sql_query: Option<String>,
         embedding_query: Option<String>,
         embedding_distance_threshold: Option<f32>,
+        cancellation_token: &CancellationToken,
     ) -> BackendResult<String> {
         #[derive(serde::Deserialize, Debug)]
         struct JsonResult {
@@ -498,7 +560,7 @@ impl Worker {
             },
             None => serde_json::from_str::<JsonResult>(
                 self.ai
-                    .get_sql_query(prompt, model, custom_key)?
+                    .get_sql_query(prompt, model, custom_key, cancellation_token)?
                     .replace("```json", "")
                     .replace("```", "")
                     .as_str(),
@@ -577,6 +639,7 @@ impl Worker {
         custom_key: Option<String>,
         number_documents: i32,
         resource_ids: Option<Vec<String>>,
+        cancellation_token: &CancellationToken,
     ) -> BackendResult<Vec<CompositeResource>> {
         let query = match query.strip_suffix("<p></p>") {
             Some(q) => q.to_string(),
@@ -599,6 +662,7 @@ impl Worker {
                     custom_key,
                     self.ai
                         .llm_metadata_messages_from_sources(&composite_resources),
+                    cancellation_token,
                 )?;
                 should_cluster = should_cluster_result.embeddings_search_needed;
 
@@ -721,12 +785,14 @@ pub fn handle_misc_message(
             model,
             custom_key,
             response_format,
+            cancellation_token,
         } => {
             let result = worker.create_chat_completion(
                 messages,
                 model,
                 custom_key,
                 response_format.as_deref(),
+                cancellation_token,
             );
             send_worker_response(&mut worker.channel, oneshot, result)
         }
@@ -741,8 +807,12 @@ pub fn handle_misc_message(
             callback,
             resource_ids,
             inline_images,
+            inline_documents,
+            inline_audio,
             general,
             app_creation,
+            compact_history,
+            cancellation_token,
         } => {
             let input = ChatInput {
                 query,
@@ -751,10 +821,14 @@ pub fn handle_misc_message(
                 number_documents,
                 resource_ids,
                 inline_images,
+                inline_documents,
+                inline_audio,
                 general: general || app_creation, // general is true for app creation
                 note_resource_id: None,
                 websearch: false,
                 surflet: false,
+                compact_history,
+                cancellation_token,
             };
             let result = worker.send_chat_query(Some(session_id), callback, search_only, input);
 
@@ -769,9 +843,12 @@ pub fn handle_misc_message(
             callback,
             resource_ids,
             inline_images,
+            inline_documents,
+            inline_audio,
             general,
             surflet,
             websearch,
+            cancellation_token,
         } => {
             let input = ChatInput {
                 query,
@@ -780,10 +857,14 @@ pub fn handle_misc_message(
                 number_documents,
                 resource_ids,
                 inline_images,
+                inline_documents,
+                inline_audio,
                 general,
                 note_resource_id: Some(note_resource_id),
                 websearch,
                 surflet,
+                compact_history: false,
+                cancellation_token,
             };
 
             let result = worker.send_chat_query(None, callback, false, input);
@@ -796,6 +877,7 @@ pub fn handle_misc_message(
             chunk_callback,
             done_callback,
             inline_images,
+            cancellation_token,
         } => {
             let result = worker.create_app_query(
                 chunk_callback,
@@ -804,6 +886,7 @@ pub fn handle_misc_message(
                 &model,
                 custom_key,
                 inline_images,
+                cancellation_token,
             );
             send_worker_response(&mut worker.channel, oneshot, result)
         }
@@ -814,6 +897,7 @@ pub fn handle_misc_message(
             sql_query,
             embedding_query,
             embedding_distance_threshold,
+            cancellation_token,
         ) => {
             let result = worker.query_sffs_resources(
                 prompt,
@@ -822,6 +906,7 @@ pub fn handle_misc_message(
                 sql_query,
                 embedding_query,
                 embedding_distance_threshold,
+                &cancellation_token,
             );
             send_worker_response(&mut worker.channel, oneshot, result)
         }
@@ -833,6 +918,14 @@ pub fn handle_misc_message(
             let result = worker.delete_ai_chat_message(session_id);
             send_worker_response(&mut worker.channel, oneshot, result)
         }
+        MiscMessage::ListMemories => {
+            let result = worker.list_memories();
+            send_worker_response(&mut worker.channel, oneshot, result)
+        }
+        MiscMessage::DeleteMemory(id) => {
+            let result = worker.delete_memory(id);
+            send_worker_response(&mut worker.channel, oneshot, result)
+        }
         MiscMessage::GetAIDocsSimilarity {
             query,
             docs,
@@ -860,6 +953,7 @@ pub fn handle_misc_message(
             custom_key,
             number_documents,
             resource_ids,
+            cancellation_token,
         } => {
             let result = worker.search_chat_resources(
                 query,
@@ -867,6 +961,7 @@ pub fn handle_misc_message(
                 custom_key,
                 number_documents,
                 resource_ids,
+                &cancellation_token,
             );
             send_worker_response(&mut worker.channel, oneshot, result)
         }
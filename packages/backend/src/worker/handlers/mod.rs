@@ -1,13 +1,27 @@
+pub mod agent_run;
 pub mod app;
+pub mod archive;
+pub mod backup;
+pub mod custom_agent;
 pub mod history;
+pub mod importers;
 pub mod kv;
 pub mod misc;
 pub mod resource;
 pub mod space;
+pub mod vault;
+pub mod watch_folder;
 
+pub use agent_run::handle_agent_run_message;
 pub use app::handle_app_message;
+pub use archive::handle_archive_message;
+pub use backup::handle_backup_message;
+pub use custom_agent::handle_custom_agent_message;
 pub use history::handle_history_message;
+pub use importers::handle_import_message;
 pub use kv::handle_kv_store_message;
 pub use misc::handle_misc_message;
 pub use resource::{handle_resource_message, handle_resource_tag_message};
 pub use space::handle_space_message;
+pub use vault::handle_vault_message;
+pub use watch_folder::handle_watch_folder_message;
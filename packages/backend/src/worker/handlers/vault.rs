@@ -0,0 +1,724 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::importers::{
+    create_imported_resource_tx, create_imported_space_tx, parse_rfc3339, space_title,
+    ImportedResource, SkippedImportItem, ARTICLE_RESOURCE_TYPE, LINK_RESOURCE_TYPE,
+};
+use crate::{
+    api::message::{TunnelOneshot, VaultMessage},
+    store::{
+        db::Database,
+        models::{
+            current_time, random_uuid, ResourceTag, ResourceTextContent,
+            ResourceTextContentMetadata, ResourceTextContentType, SpaceEntry, SpaceEntryType,
+            SpaceExtended, SubSpaceEntry,
+        },
+    },
+    worker::{processor::parse_markdown_with_frontmatter, send_worker_response, Worker},
+    BackendError, BackendResult,
+};
+
+const NOTE_RESOURCE_TYPE: &str = "application/vnd.space.document.space-note";
+// attachments are collected in one folder at the root of the vault
+const ATTACHMENTS_DIR: &str = "attachments";
+
+// the frontmatter of a note, notes from other tools only have some of the fields
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct NoteFrontmatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub surf_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_uri: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // path of the resource file relative to the vault root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl NoteFrontmatter {
+    // reads the known fields and ignores everything else, `url` and `source`
+    // are what web clippers use for the page a note was taken from
+    fn from_yaml(value: &serde_yaml::Value) -> Self {
+        let get = |key: &str| {
+            value
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let tags = match value.get("tags") {
+            Some(serde_yaml::Value::Sequence(tags)) => tags
+                .iter()
+                .filter_map(|tag| tag.as_str())
+                .map(|tag| tag.trim().trim_start_matches('#').to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            Some(serde_yaml::Value::String(tags)) => tags
+                .split([',', ' '])
+                .map(|tag| tag.trim().trim_start_matches('#').to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        };
+        NoteFrontmatter {
+            surf_id: get("surf_id"),
+            title: get("title"),
+            resource_type: get("type"),
+            source_uri: get("source_uri")
+                .or_else(|| get("url"))
+                .or_else(|| get("source")),
+            tags,
+            note: get("note"),
+            attachment: get("attachment"),
+            created_at: get("created_at").and_then(|s| parse_rfc3339(&s)),
+            updated_at: get("updated_at").and_then(|s| parse_rfc3339(&s)),
+        }
+    }
+}
+
+pub fn note_markdown(frontmatter: &NoteFrontmatter, body: &str) -> BackendResult<String> {
+    let yaml = serde_yaml::to_string(frontmatter)
+        .map_err(|e| BackendError::GenericError(e.to_string()))?;
+    Ok(format!("---\n{}---\n\n{}\n", yaml, body.trim()))
+}
+
+pub fn parse_note(markdown: &str) -> (NoteFrontmatter, String) {
+    match parse_markdown_with_frontmatter(markdown) {
+        Ok((body, frontmatter)) => (NoteFrontmatter::from_yaml(&frontmatter), body),
+        // a note whose frontmatter isn't valid yaml is kept as it is
+        Err(_) => (NoteFrontmatter::default(), markdown.to_string()),
+    }
+}
+
+// makes the title usable as a file or folder name on every platform
+pub fn sanitize_file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(100)
+        .collect();
+    let name = name.trim().trim_matches('.').trim();
+    if name.is_empty() {
+        "Untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// the name with a number appended if it's already taken, names are compared
+// case insensitively as most file systems do
+fn unique_name(taken: &mut HashSet<String>, name: &str, extension: &str) -> String {
+    let mut candidate = format!("{}{}", name, extension);
+    let mut copy = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{} {}{}", name, copy, extension);
+        copy += 1;
+    }
+    candidate
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VaultExport {
+    // the folder of the exported space
+    pub path: String,
+    pub folders: usize,
+    pub notes: usize,
+    pub attachments: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultImport {
+    pub space_id: String,
+    pub spaces_created: usize,
+    pub resources_created: usize,
+    // notes exported from this library that were added back to a space
+    pub resources_linked: usize,
+    pub skipped: Vec<SkippedImportItem>,
+}
+
+#[derive(Debug)]
+struct VaultNote {
+    title: String,
+    frontmatter: NoteFrontmatter,
+    body: String,
+    attachment: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct VaultFolder {
+    title: String,
+    notes: Vec<VaultNote>,
+    folders: Vec<VaultFolder>,
+}
+
+struct VaultExporter<'a> {
+    worker: &'a Worker,
+    root: PathBuf,
+    attachment_names: HashSet<String>,
+    visited: HashSet<String>,
+    result: VaultExport,
+}
+
+impl VaultExporter<'_> {
+    fn export_space(&mut self, space: &SpaceExtended, dir: &Path) -> BackendResult<()> {
+        fs::create_dir_all(dir)?;
+        self.result.folders += 1;
+        let depth = dir
+            .strip_prefix(&self.root)
+            .map_or(0, |p| p.components().count());
+        let mut names = HashSet::new();
+
+        let entries = self.worker.db.list_space_entries(
+            &space.id,
+            Some("resource_added_to_space"),
+            Some("asc"),
+            None,
+        )?;
+        for entry in entries {
+            match entry.entry_type {
+                SpaceEntryType::Resource => {
+                    self.export_resource(&entry.entry_id, dir, depth, &mut names)?
+                }
+                SpaceEntryType::Space => {
+                    // a space can be nested in more than one parent
+                    if !self.visited.insert(entry.entry_id.clone()) {
+                        continue;
+                    }
+                    if let Some(child) = self.worker.db.get_space(&entry.entry_id)? {
+                        let name = unique_name(
+                            &mut names,
+                            &sanitize_file_name(&space_title(&child.name)),
+                            "",
+                        );
+                        self.export_space(&child, &dir.join(name))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn export_resource(
+        &mut self,
+        resource_id: &str,
+        dir: &Path,
+        depth: usize,
+        names: &mut HashSet<String>,
+    ) -> BackendResult<()> {
+        let resource = match self.worker.db.get_resource(resource_id)? {
+            Some(resource) if resource.deleted == 0 => resource,
+            _ => return Ok(()),
+        };
+        let metadata = self
+            .worker
+            .db
+            .get_resource_metadata_by_resource_id(&resource.id)?;
+        let title = metadata
+            .as_ref()
+            .map(|metadata| metadata.name.trim())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&resource.id)
+            .to_string();
+        let non_empty = |value: Option<&str>| {
+            value
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| value.to_string())
+        };
+
+        let mut body = String::new();
+        let mut attachment = None;
+        // the files of space types hold data the text content is extracted from,
+        // files like pdfs and images are copied next to the notes
+        let source = Path::new(&resource.resource_path);
+        if !resource.resource_type.starts_with("application/vnd.space.") && source.is_file() {
+            let extension = source
+                .extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_default();
+            let name = unique_name(
+                &mut self.attachment_names,
+                &sanitize_file_name(&title),
+                &extension,
+            );
+            fs::create_dir_all(self.root.join(ATTACHMENTS_DIR))?;
+            fs::copy(source, self.root.join(ATTACHMENTS_DIR).join(&name))?;
+            let link = format!("{}/{}", ATTACHMENTS_DIR, name);
+            // links are relative to the note so they work outside of obsidian too
+            let prefix = "../".repeat(depth);
+            let embed = if resource.resource_type.starts_with("image/") {
+                "!"
+            } else {
+                ""
+            };
+            body.push_str(&format!(
+                "{}[{}](<{}{}>)\n\n",
+                embed,
+                title.replace(['[', ']'], ""),
+                prefix,
+                link
+            ));
+            attachment = Some(link);
+            self.result.attachments += 1;
+        }
+        let text_content = self
+            .worker
+            .db
+            .list_resource_text_content_by_resource_id(&resource.id)?;
+        for content in text_content {
+            body.push_str(content.content.trim());
+            body.push_str("\n\n");
+        }
+
+        let frontmatter = NoteFrontmatter {
+            surf_id: Some(resource.id.clone()),
+            title: Some(title.clone()),
+            resource_type: Some(resource.resource_type.clone()),
+            source_uri: non_empty(metadata.as_ref().map(|m| m.source_uri.as_str())),
+            tags: self
+                .worker
+                .db
+                .list_resource_tags(&resource.id)?
+                .into_iter()
+                .filter(|tag| tag.tag_name == "hashtag")
+                .map(|tag| tag.tag_value)
+                .collect(),
+            note: non_empty(metadata.as_ref().map(|m| m.user_context.as_str())),
+            attachment,
+            created_at: Some(resource.created_at),
+            updated_at: Some(resource.updated_at),
+        };
+        let name = unique_name(names, &sanitize_file_name(&title), ".md");
+        fs::write(dir.join(name), note_markdown(&frontmatter, &body)?)?;
+        self.result.notes += 1;
+        Ok(())
+    }
+}
+
+// the attachment's file if it is inside the vault, paths can't leave it through
+// `..` or by being absolute
+fn attachment_path(root: &Path, attachment: &str) -> Option<PathBuf> {
+    let relative = Path::new(attachment);
+    let inside = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    Some(root.join(relative)).filter(|path| inside && path.is_file())
+}
+
+// reads the notes of the folder and its sub-folders, the attachments folder at the
+// root and hidden folders like `.obsidian` are left out
+fn read_vault_folder(
+    root: &Path,
+    dir: &Path,
+    skipped: &mut Vec<SkippedImportItem>,
+) -> BackendResult<VaultFolder> {
+    let mut folder = VaultFolder {
+        title: dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Vault".to_string()),
+        notes: Vec::new(),
+        folders: Vec::new(),
+    };
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if dir == root && name == ATTACHMENTS_DIR {
+                continue;
+            }
+            folder
+                .folders
+                .push(read_vault_folder(root, &path, skipped)?);
+            continue;
+        }
+        let title = match name.strip_suffix(".md") {
+            Some(title) => title.to_string(),
+            None => continue,
+        };
+        let markdown = match fs::read_to_string(&path) {
+            Ok(markdown) => markdown,
+            Err(_) => {
+                skipped.push(SkippedImportItem::new(&title, "not a text file"));
+                continue;
+            }
+        };
+        let (frontmatter, body) = parse_note(&markdown);
+        let attachment = frontmatter
+            .attachment
+            .as_deref()
+            .and_then(|attachment| attachment_path(root, attachment));
+        folder.notes.push(VaultNote {
+            title: frontmatter.title.clone().unwrap_or(title),
+            frontmatter,
+            body,
+            attachment,
+        });
+    }
+    Ok(folder)
+}
+
+fn collect_surf_ids<'a>(folder: &'a VaultFolder, ids: &mut Vec<&'a str>) {
+    for note in &folder.notes {
+        if let Some(id) = &note.frontmatter.surf_id {
+            ids.push(id);
+        }
+    }
+    for nested in &folder.folders {
+        collect_surf_ids(nested, ids);
+    }
+}
+
+enum ResourceFile {
+    Text(String),
+    Copy(PathBuf),
+}
+
+struct VaultImporter<'a> {
+    resources_path: &'a str,
+    // resources of the library the notes were exported from
+    existing: HashSet<String>,
+    next_index: usize,
+    result: VaultImport,
+    // resource files are only written once the transaction is committed
+    files: Vec<(String, ResourceFile)>,
+}
+
+impl VaultImporter<'_> {
+    fn import_folder(
+        &mut self,
+        tx: &mut rusqlite::Transaction,
+        folder: &VaultFolder,
+        parent_space_id: Option<&str>,
+    ) -> BackendResult<String> {
+        let space = create_imported_space_tx(tx, &folder.title, self.next_index)?;
+        self.next_index += 1;
+        self.result.spaces_created += 1;
+        if let Some(parent_space_id) = parent_space_id {
+            Database::create_sub_space_entry_tx(
+                tx,
+                &SubSpaceEntry {
+                    id: random_uuid(),
+                    parent_space_id: parent_space_id.to_string(),
+                    child_space_id: space.id.clone(),
+                    created_at: space.created_at,
+                    updated_at: space.updated_at,
+                    manually_added: 1,
+                },
+            )?;
+        }
+
+        for note in &folder.notes {
+            let resource_id = match &note.frontmatter.surf_id {
+                Some(id) if self.existing.contains(id) => {
+                    self.result.resources_linked += 1;
+                    id.clone()
+                }
+                _ => self.import_note(tx, note)?,
+            };
+            Database::create_space_entry_tx(
+                tx,
+                &SpaceEntry {
+                    id: random_uuid(),
+                    space_id: space.id.clone(),
+                    resource_id,
+                    created_at: current_time(),
+                    updated_at: current_time(),
+                    manually_added: 1,
+                },
+            )?;
+        }
+        for nested in &folder.folders {
+            self.import_folder(tx, nested, Some(&space.id))?;
+        }
+        Ok(space.id)
+    }
+
+    fn import_note(
+        &mut self,
+        tx: &mut rusqlite::Transaction,
+        note: &VaultNote,
+    ) -> BackendResult<String> {
+        let frontmatter = &note.frontmatter;
+        let url = frontmatter.source_uri.as_deref().unwrap_or_default();
+        let body = note.body.trim();
+        let resource_type = match (&note.attachment, &frontmatter.resource_type) {
+            (Some(_), Some(resource_type)) => resource_type.as_str(),
+            (None, Some(resource_type))
+                if crate::utils::get_resource_file_extension(resource_type) == "md" =>
+            {
+                resource_type.as_str()
+            }
+            _ if url.is_empty() => NOTE_RESOURCE_TYPE,
+            _ if body.is_empty() => LINK_RESOURCE_TYPE,
+            _ => ARTICLE_RESOURCE_TYPE,
+        };
+        let created_at = frontmatter.created_at.unwrap_or_else(current_time);
+        let (resource, file) = create_imported_resource_tx(
+            tx,
+            self.resources_path,
+            &ImportedResource {
+                resource_type,
+                title: &note.title,
+                url,
+                user_context: frontmatter.note.as_deref().unwrap_or_default(),
+                content: body,
+                tags: frontmatter
+                    .tags
+                    .iter()
+                    .map(|tag| ("hashtag", tag.as_str()))
+                    .collect(),
+                created_at,
+                updated_at: frontmatter.updated_at.unwrap_or(created_at),
+            },
+        )?;
+
+        // the text is searchable right away, embeddings are generated later
+        if !body.is_empty() {
+            Database::create_resource_text_content_tx(
+                tx,
+                &ResourceTextContent {
+                    id: random_uuid(),
+                    resource_id: resource.id.clone(),
+                    content: body.to_string(),
+                    content_type: ResourceTextContentType::from_resource_type(resource_type)
+                        .unwrap_or(ResourceTextContentType::Note),
+                    metadata: ResourceTextContentMetadata {
+                        url: Some(url.to_string()).filter(|url| !url.is_empty()),
+                        ..Default::default()
+                    },
+                },
+            )?;
+            Database::create_resource_tag_tx(
+                tx,
+                &ResourceTag::new_generate_lazy_embeddings(&resource.id),
+            )?;
+        }
+
+        let file = match &note.attachment {
+            Some(attachment) => ResourceFile::Copy(attachment.clone()),
+            None => ResourceFile::Text(file),
+        };
+        self.files.push((resource.resource_path, file));
+        self.result.resources_created += 1;
+        Ok(resource.id)
+    }
+}
+
+impl Worker {
+    // writes the space as a folder of markdown notes with its sub-spaces as sub-folders
+    pub fn export_space_as_vault(&self, space_id: &str, path: &str) -> BackendResult<VaultExport> {
+        let space = self
+            .db
+            .get_space(space_id)?
+            .ok_or_else(|| BackendError::GenericError(format!("Space not found: {}", space_id)))?;
+        let root = Path::new(path).join(sanitize_file_name(&space_title(&space.name)));
+        let mut exporter = VaultExporter {
+            worker: self,
+            root: root.clone(),
+            attachment_names: HashSet::new(),
+            visited: HashSet::from([space.id.clone()]),
+            result: VaultExport {
+                path: root.as_os_str().to_string_lossy().to_string(),
+                ..Default::default()
+            },
+        };
+        exporter.export_space(&space, &root)?;
+        Ok(exporter.result)
+    }
+
+    // imports the folder as a space with a resource per note, notes that were
+    // exported from this library are added back instead of being duplicated
+    pub fn import_vault(&mut self, path: &str) -> BackendResult<VaultImport> {
+        let root = Path::new(path);
+        let mut skipped = Vec::new();
+        let folder = read_vault_folder(root, root, &mut skipped)?;
+
+        let mut surf_ids = Vec::new();
+        collect_surf_ids(&folder, &mut surf_ids);
+        let mut existing = HashSet::new();
+        for id in surf_ids {
+            if let Some(resource) = self.db.get_resource(id)? {
+                if resource.deleted == 0 {
+                    existing.insert(resource.id);
+                }
+            }
+        }
+
+        let resources_path = self.resources_path.clone();
+        let mut importer = VaultImporter {
+            resources_path: &resources_path,
+            existing,
+            next_index: self.db.list_spaces()?.len(),
+            result: VaultImport {
+                space_id: String::new(),
+                spaces_created: 0,
+                resources_created: 0,
+                resources_linked: 0,
+                skipped,
+            },
+            files: Vec::new(),
+        };
+        let mut tx = self.db.begin()?;
+        importer.result.space_id = importer.import_folder(&mut tx, &folder, None)?;
+        tx.commit()?;
+
+        for (path, file) in &importer.files {
+            match file {
+                ResourceFile::Text(content) => fs::write(path, content)?,
+                ResourceFile::Copy(source) => fs::copy(source, path).map(|_| ())?,
+            }
+        }
+        Ok(importer.result)
+    }
+}
+
+pub fn handle_vault_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: VaultMessage,
+) {
+    match message {
+        VaultMessage::ExportSpaceAsVault(space_id, path) => {
+            let result = worker.export_space_as_vault(&space_id, &path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        VaultMessage::ImportVault(path) => {
+            let result = worker.import_vault(&path);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_note_round_trip() {
+        let frontmatter = NoteFrontmatter {
+            surf_id: Some(random_uuid()),
+            title: Some("Deta: Space".to_string()),
+            resource_type: Some(ARTICLE_RESOURCE_TYPE.to_string()),
+            source_uri: Some("https://deta.space".to_string()),
+            tags: vec!["rust".to_string(), "notes".to_string()],
+            note: Some("first line\nsecond line".to_string()),
+            attachment: None,
+            created_at: Some(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap()),
+            updated_at: None,
+        };
+        let markdown = note_markdown(&frontmatter, "Some text\n\n---\n\nmore text").unwrap();
+        let (parsed, body) = parse_note(&markdown);
+        assert_eq!(parsed, frontmatter);
+        assert_eq!(body, "Some text\n\n---\n\nmore text");
+
+        // notes from other tools
+        let (parsed, body) = parse_note(
+            "---\nurl: https://example.com\ntags: \"a, #b\"\ncreated: 2024-01-01\n---\nHello",
+        );
+        assert_eq!(parsed.source_uri.as_deref(), Some("https://example.com"));
+        assert_eq!(parsed.tags, vec!["a", "b"]);
+        assert_eq!(body, "Hello");
+        let (parsed, body) = parse_note("No frontmatter\n\n---\n\nat all");
+        assert_eq!(parsed, NoteFrontmatter::default());
+        assert_eq!(body, "No frontmatter\n\n---\n\nat all");
+
+        // --- only closes the frontmatter on a line of its own
+        let frontmatter = NoteFrontmatter {
+            title: Some("Before --- after".to_string()),
+            note: Some("first part\n---\nsecond part".to_string()),
+            ..Default::default()
+        };
+        let markdown = note_markdown(&frontmatter, "body --- text\n\n---\n\nend").unwrap();
+        let (parsed, body) = parse_note(&markdown);
+        assert_eq!(parsed, frontmatter);
+        assert_eq!(body, "body --- text\n\n---\n\nend");
+        let (parsed, body) = parse_note("---\ntitle: a\nno closing line");
+        assert_eq!(parsed, NoteFrontmatter::default());
+        assert_eq!(body, "---\ntitle: a\nno closing line");
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("a/b: c?"), "a-b- c-");
+        assert_eq!(sanitize_file_name("  ..  "), "Untitled");
+        let mut taken = HashSet::new();
+        assert_eq!(unique_name(&mut taken, "Note", ".md"), "Note.md");
+        assert_eq!(unique_name(&mut taken, "note", ".md"), "note 2.md");
+    }
+
+    #[test]
+    fn test_read_vault_folder() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("Reading");
+        fs::create_dir_all(root.join("Rust")).unwrap();
+        fs::create_dir_all(root.join(ATTACHMENTS_DIR)).unwrap();
+        fs::create_dir_all(root.join(".obsidian")).unwrap();
+        fs::write(root.join(ATTACHMENTS_DIR).join("paper.pdf"), b"%PDF").unwrap();
+        fs::write(
+            root.join("Paper.md"),
+            "---\ntype: application/pdf\nattachment: attachments/paper.pdf\n---\ntext",
+        )
+        .unwrap();
+        fs::write(root.join("Rust").join("Book.md"), "The book").unwrap();
+        fs::write(root.join("image.png"), b"png").unwrap();
+
+        let mut skipped = Vec::new();
+        let folder = read_vault_folder(&root, &root, &mut skipped).unwrap();
+        assert_eq!(folder.title, "Reading");
+        assert_eq!(folder.notes.len(), 1);
+        assert_eq!(
+            folder.notes[0].attachment.as_deref(),
+            Some(root.join("attachments/paper.pdf").as_path())
+        );
+        assert_eq!(folder.folders.len(), 1);
+        assert_eq!(folder.folders[0].notes[0].title, "Book");
+        assert_eq!(folder.folders[0].notes[0].body, "The book");
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn test_attachment_path() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("Reading");
+        fs::create_dir_all(root.join(ATTACHMENTS_DIR)).unwrap();
+        fs::write(root.join(ATTACHMENTS_DIR).join("paper.pdf"), b"%PDF").unwrap();
+        fs::write(dir.path().join("secret.pdf"), b"%PDF").unwrap();
+
+        assert_eq!(
+            attachment_path(&root, "./attachments/paper.pdf"),
+            Some(root.join("./attachments/paper.pdf"))
+        );
+        assert_eq!(attachment_path(&root, "attachments/missing.pdf"), None);
+        assert_eq!(attachment_path(&root, "../secret.pdf"), None);
+        assert_eq!(attachment_path(&root, "attachments/../../secret.pdf"), None);
+        let absolute = dir.path().join("secret.pdf");
+        assert_eq!(attachment_path(&root, &absolute.to_string_lossy()), None);
+    }
+}
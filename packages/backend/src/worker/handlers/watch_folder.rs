@@ -0,0 +1,611 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    api::message::{TunnelOneshot, WatchFolderMessage},
+    store::{
+        db::Database,
+        models::{
+            current_time, random_uuid, Resource, ResourceMetadata, ResourceTag, SpaceEntry,
+            WatchFolder, WatchedFile,
+        },
+    },
+    worker::{send_worker_response, Worker},
+    BackendError, BackendResult,
+};
+
+// files modified more recently are probably still being written or downloaded
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+// the resource type a file is added as, other files are ignored
+pub fn watched_file_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let resource_type = match extension.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "md" | "markdown" => "text/markdown",
+        "txt" => "text/plain",
+        _ => return None,
+    };
+    Some(resource_type)
+}
+
+// same hash the app computes when it writes a resource file
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug)]
+struct FolderFile {
+    // relative to the watch folder, with `/` separators
+    relative_path: String,
+    path: PathBuf,
+    size: i64,
+    modified: SystemTime,
+}
+
+impl FolderFile {
+    fn modified_at(&self) -> i64 {
+        self.modified
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64)
+    }
+}
+
+// the supported files in the folder and its sub-folders, hidden files and
+// folders are left out
+fn list_folder_files(root: &Path, dir: &Path, files: &mut Vec<FolderFile>) -> BackendResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.is_dir() {
+            list_folder_files(root, &path, files)?;
+            continue;
+        }
+        if !metadata.is_file() || watched_file_type(&path).is_none() {
+            continue;
+        }
+        let relative_path = match path.strip_prefix(root) {
+            Ok(relative) => relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => continue,
+        };
+        files.push(FolderFile {
+            relative_path,
+            path,
+            size: metadata.len() as i64,
+            modified: metadata.modified()?,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct WatchFolderScan {
+    pub folder_id: String,
+    pub created: usize,
+    pub updated: usize,
+    // files that matched a resource already in the library
+    pub linked: usize,
+    pub removed: usize,
+    // files that couldn't be read, they are retried on the next scan
+    pub failed: usize,
+}
+
+struct FolderScanner<'a> {
+    db: &'a mut Database,
+    resources_path: &'a str,
+    folder: &'a WatchFolder,
+    scan: WatchFolderScan,
+    // created and changed resources, they are post processed after the scan
+    processed: Vec<String>,
+    // resources of deleted files, they are removed by the worker with their embeddings
+    removed: Vec<String>,
+}
+
+impl FolderScanner<'_> {
+    fn scan(&mut self, now: SystemTime) -> BackendResult<()> {
+        let root = Path::new(&self.folder.path);
+        // an unmounted drive shouldn't look like every file was deleted
+        if !root.is_dir() {
+            return Err(BackendError::GenericError(format!(
+                "watch folder is not a directory: {}",
+                self.folder.path
+            )));
+        }
+        let mut files = Vec::new();
+        list_folder_files(root, root, &mut files)?;
+        let known: HashMap<String, WatchedFile> = self
+            .db
+            .list_watched_files(&self.folder.id)?
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect();
+
+        let mut seen = HashSet::new();
+        for file in files {
+            seen.insert(file.relative_path.clone());
+            let record = known.get(&file.relative_path);
+            if record.is_some_and(|r| r.size == file.size && r.modified_at == file.modified_at()) {
+                continue;
+            }
+            if now.duration_since(file.modified).unwrap_or_default() < SETTLE_TIME {
+                continue;
+            }
+            if let Err(e) = self.ingest_file(&file, record) {
+                tracing::error!("failed to add watched file {}: {e}", file.path.display());
+                self.scan.failed += 1;
+            }
+        }
+
+        // the records of deleted files go first so that their resources are only
+        // handed to files that are still there
+        let mut tx = self.db.begin()?;
+        let mut orphaned = Vec::new();
+        for (path, record) in known {
+            if seen.contains(&path) {
+                continue;
+            }
+            Database::delete_watched_file_tx(&mut tx, &self.folder.id, &path)?;
+            // resources that were in the library before the file are kept
+            if record.created_resource {
+                orphaned.push(record.resource_id);
+            }
+        }
+        for resource_id in orphaned {
+            // a copy of the file is still watched, it keeps the resource
+            if Database::adopt_watched_resource_tx(&mut tx, &resource_id)? {
+                continue;
+            }
+            if self.folder.propagate_deletions {
+                self.removed.push(resource_id);
+                self.scan.removed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // the resource and the file's record are saved together, a file that fails is
+    // retried on the next scan
+    fn ingest_file(
+        &mut self,
+        file: &FolderFile,
+        record: Option<&WatchedFile>,
+    ) -> BackendResult<()> {
+        let data = fs::read(&file.path)?;
+        let hash = content_hash(&data);
+        let mut watched = WatchedFile {
+            folder_id: self.folder.id.clone(),
+            path: file.relative_path.clone(),
+            resource_id: String::new(),
+            size: file.size,
+            modified_at: file.modified_at(),
+            content_hash: hash.clone(),
+            created_resource: false,
+        };
+
+        // looked up before the transaction borrows the database
+        let changed_resource = match record {
+            Some(record) if record.created_resource && record.content_hash != hash => {
+                self.db.get_resource(&record.resource_id)?
+            }
+            _ => None,
+        };
+        let duplicate = self.db.get_resource_id_by_hash(&hash)?;
+
+        let mut tx = self.db.begin()?;
+        match record {
+            // only touched, the content is the same
+            Some(record) if record.content_hash == hash => {
+                watched.resource_id = record.resource_id.clone();
+                watched.created_resource = record.created_resource;
+            }
+            Some(record) if record.created_resource => {
+                watched.resource_id = record.resource_id.clone();
+                watched.created_resource = true;
+                // a resource removed in the app isn't brought back
+                if let Some(mut resource) = changed_resource.filter(|r| r.deleted == 0) {
+                    fs::write(&resource.resource_path, &data)?;
+                    resource.updated_at = current_time();
+                    Database::update_resource_tx(&mut tx, &resource)?;
+                    Database::upsert_resource_hash_tx(&mut tx, &resource.id, &hash)?;
+                    self.processed.push(resource.id);
+                    self.scan.updated += 1;
+                }
+            }
+            _ => match duplicate {
+                Some(resource_id) => {
+                    add_to_space_tx(&mut tx, &self.folder.space_id, &resource_id)?;
+                    watched.resource_id = resource_id;
+                    self.scan.linked += 1;
+                }
+                None => {
+                    let resource = create_file_resource_tx(&mut tx, self.resources_path, file)?;
+                    Database::upsert_resource_hash_tx(&mut tx, &resource.id, &hash)?;
+                    add_to_space_tx(&mut tx, &self.folder.space_id, &resource.id)?;
+                    // the resource isn't saved if its file can't be written
+                    fs::write(&resource.resource_path, &data)?;
+                    watched.resource_id = resource.id.clone();
+                    watched.created_resource = true;
+                    self.processed.push(resource.id);
+                    self.scan.created += 1;
+                }
+            },
+        }
+        Database::upsert_watched_file_tx(&mut tx, &watched)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn create_file_resource_tx(
+    tx: &mut rusqlite::Transaction,
+    resources_path: &str,
+    file: &FolderFile,
+) -> BackendResult<Resource> {
+    let resource_type = watched_file_type(&file.path).unwrap_or("application/octet-stream");
+    let name = file
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let resource_id = random_uuid();
+    let ct = current_time();
+    let resource = Resource {
+        id: resource_id.clone(),
+        resource_path: Path::new(resources_path)
+            .join(format!(
+                "{}.{}",
+                crate::utils::get_resource_filename(&resource_id, Some(&name)),
+                crate::utils::get_resource_file_extension(resource_type)
+            ))
+            .as_os_str()
+            .to_string_lossy()
+            .to_string(),
+        resource_type: resource_type.to_string(),
+        created_at: ct,
+        updated_at: ct,
+        deleted: 0,
+    };
+    Database::create_resource_tx(tx, &resource)?;
+
+    let metadata = ResourceMetadata {
+        id: random_uuid(),
+        resource_id: resource_id.clone(),
+        name,
+        source_uri: url::Url::from_file_path(&file.path)
+            .map(|url| url.to_string())
+            .unwrap_or_default(),
+        alt: String::new(),
+        user_context: String::new(),
+    };
+    Database::create_resource_metadata_tx(tx, &metadata)?;
+
+    let mut tags = metadata.get_tags();
+    tags.push(ResourceTag {
+        id: random_uuid(),
+        resource_id: resource_id.clone(),
+        tag_name: "savedWithAction".to_string(),
+        tag_value: "import".to_string(),
+    });
+    tags.push(ResourceTag::new_deleted(&resource_id, false));
+    tags.push(ResourceTag::new_type(&resource_id, resource_type));
+    for tag in &tags {
+        Database::create_resource_tag_tx(tx, tag)?;
+    }
+    Ok(resource)
+}
+
+fn add_to_space_tx(
+    tx: &mut rusqlite::Transaction,
+    space_id: &str,
+    resource_id: &str,
+) -> BackendResult<()> {
+    let ct = current_time();
+    // like an entry added by the user, it moves to the top of the space
+    Database::delete_space_entry_by_resource_id_tx(tx, space_id, resource_id)?;
+    Database::create_space_entry_tx(
+        tx,
+        &SpaceEntry {
+            id: random_uuid(),
+            space_id: space_id.to_string(),
+            resource_id: resource_id.to_string(),
+            created_at: ct,
+            updated_at: ct,
+            manually_added: 1,
+        },
+    )
+}
+
+impl Worker {
+    pub fn upsert_watch_folder(&mut self, folder: WatchFolder) -> BackendResult<WatchFolder> {
+        if !Path::new(&folder.path).is_dir() {
+            return Err(BackendError::GenericError(format!(
+                "watch folder is not a directory: {}",
+                folder.path
+            )));
+        }
+        if self.db.get_space(&folder.space_id)?.is_none() {
+            return Err(BackendError::GenericError(format!(
+                "Space not found: {}",
+                folder.space_id
+            )));
+        }
+        self.db.upsert_watch_folder(&folder)?;
+        Ok(folder)
+    }
+
+    pub fn remove_watch_folder(&mut self, id: &str) -> BackendResult<()> {
+        self.db.delete_watch_folder(id)
+    }
+
+    pub fn list_watch_folders(&self) -> BackendResult<Vec<WatchFolder>> {
+        self.db.list_watch_folders()
+    }
+
+    // scans every enabled folder, a folder that fails doesn't stop the others
+    pub fn scan_watch_folders(&mut self) -> BackendResult<Vec<WatchFolderScan>> {
+        let mut scans = Vec::new();
+        for folder in self.db.list_watch_folders()? {
+            if !folder.enabled {
+                continue;
+            }
+            // another worker thread is still scanning the folder, e.g. a scan started
+            // from the app while the scheduled one runs
+            let _scanning = match self.watch_folder_scans.start(&folder.id) {
+                Some(scanning) => scanning,
+                None => continue,
+            };
+            match self.scan_watch_folder(&folder) {
+                Ok(scan) => scans.push(scan),
+                Err(e) => tracing::error!("failed to scan watch folder {}: {e}", folder.path),
+            }
+        }
+        Ok(scans)
+    }
+
+    fn scan_watch_folder(&mut self, folder: &WatchFolder) -> BackendResult<WatchFolderScan> {
+        let mut scanner = FolderScanner {
+            db: &mut self.db,
+            resources_path: &self.resources_path,
+            folder,
+            scan: WatchFolderScan {
+                folder_id: folder.id.clone(),
+                ..Default::default()
+            },
+            processed: Vec::new(),
+            removed: Vec::new(),
+        };
+        scanner.scan(SystemTime::now())?;
+        let FolderScanner {
+            scan,
+            processed,
+            removed,
+            ..
+        } = scanner;
+
+        self.remove_resources(removed)?;
+        for resource_id in processed {
+            if let Err(e) = self.post_processing_job(resource_id.clone()) {
+                tracing::error!("failed to process watched resource {resource_id}: {e}");
+            }
+        }
+        Ok(scan)
+    }
+}
+
+pub fn handle_watch_folder_message(
+    worker: &mut Worker,
+    oneshot: Option<TunnelOneshot>,
+    message: WatchFolderMessage,
+) {
+    match message {
+        WatchFolderMessage::UpsertWatchFolder(folder) => {
+            let result = worker.upsert_watch_folder(folder);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        WatchFolderMessage::RemoveWatchFolder(id) => {
+            let result = worker.remove_watch_folder(&id);
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        WatchFolderMessage::ListWatchFolders => {
+            let result = worker.list_watch_folders();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+        WatchFolderMessage::ScanWatchFolders => {
+            let result = worker.scan_watch_folders();
+            send_worker_response(&mut worker.channel, oneshot, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{store::models::Space, worker::tunnel::WatchFolderScans};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_folder_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("papers")).unwrap();
+        fs::create_dir_all(root.join(".cache")).unwrap();
+        fs::write(root.join("papers").join("attention.PDF"), b"%PDF").unwrap();
+        fs::write(root.join("notes.md"), b"# notes").unwrap();
+        fs::write(root.join("paper.pdf.crdownload"), b"%PD").unwrap();
+        fs::write(root.join(".hidden.txt"), b"hidden").unwrap();
+        fs::write(root.join(".cache").join("page.txt"), b"cached").unwrap();
+
+        let mut files = Vec::new();
+        list_folder_files(root, root, &mut files).unwrap();
+        let mut paths: Vec<_> = files.iter().map(|f| f.relative_path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["notes.md", "papers/attention.PDF"]);
+        assert_eq!(
+            watched_file_type(Path::new("papers/attention.PDF")),
+            Some("application/pdf")
+        );
+    }
+
+    // written a while ago so the scan doesn't wait for the file to settle
+    fn write_settled(path: &Path, data: &[u8], age_secs: u64) {
+        fs::write(path, data).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+    }
+
+    fn scan_folder(
+        db: &mut Database,
+        resources_path: &str,
+        folder: &WatchFolder,
+    ) -> (WatchFolderScan, Vec<String>, Vec<String>) {
+        let mut scanner = FolderScanner {
+            db,
+            resources_path,
+            folder,
+            scan: WatchFolderScan::default(),
+            processed: Vec::new(),
+            removed: Vec::new(),
+        };
+        scanner.scan(SystemTime::now()).unwrap();
+        (scanner.scan, scanner.processed, scanner.removed)
+    }
+
+    #[test]
+    fn test_scan_watch_folder() {
+        let dir = tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db").to_string_lossy(), true).unwrap();
+        let resources_path = dir.path().join("resources");
+        fs::create_dir_all(&resources_path).unwrap();
+        let resources_path = resources_path.to_string_lossy().to_string();
+        let root = dir.path().join("watched");
+        fs::create_dir_all(&root).unwrap();
+
+        let space = Space {
+            id: random_uuid(),
+            name: "Watched".to_string(),
+            created_at: current_time(),
+            updated_at: current_time(),
+        };
+        db.create_space(&space).unwrap();
+        let folder = WatchFolder {
+            id: random_uuid(),
+            path: root.to_string_lossy().to_string(),
+            space_id: space.id.clone(),
+            propagate_deletions: true,
+            enabled: true,
+            created_at: current_time(),
+            updated_at: current_time(),
+        };
+        db.upsert_watch_folder(&folder).unwrap();
+
+        // new files are added, a copy of one is linked to the same resource
+        write_settled(&root.join("paper.pdf"), b"%PDF-1", 60);
+        write_settled(&root.join("copy.pdf"), b"%PDF-1", 60);
+        write_settled(&root.join("notes.md"), b"# notes", 60);
+        // still being written
+        write_settled(&root.join("download.pdf"), b"%PD", 0);
+        let (scan, processed, removed) = scan_folder(&mut db, &resources_path, &folder);
+        assert_eq!((scan.created, scan.linked, scan.failed), (2, 1, 0));
+        assert_eq!(processed.len(), 2);
+        assert!(removed.is_empty());
+
+        let files: HashMap<String, WatchedFile> = db
+            .list_watched_files(&folder.id)
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect();
+        assert_eq!(files.len(), 3);
+        // which of the two is added first depends on the listing order
+        let (created, linked) = match files["paper.pdf"].created_resource {
+            true => (&files["paper.pdf"], &files["copy.pdf"]),
+            false => (&files["copy.pdf"], &files["paper.pdf"]),
+        };
+        assert!(!linked.created_resource);
+        assert_eq!(created.resource_id, linked.resource_id);
+        let resource_id = created.resource_id.clone();
+        let entries = db.list_space_entries(&space.id, None, None, None).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // nothing changed
+        let (scan, processed, _) = scan_folder(&mut db, &resources_path, &folder);
+        assert_eq!((scan.created, scan.updated, scan.linked), (0, 0, 0));
+        assert!(processed.is_empty());
+
+        // the resource of a changed file is updated in place
+        write_settled(&root.join(&created.path), b"%PDF-2", 30);
+        let (scan, processed, _) = scan_folder(&mut db, &resources_path, &folder);
+        assert_eq!((scan.created, scan.updated), (0, 1));
+        assert_eq!(processed, vec![resource_id.clone()]);
+        let resource = db.get_resource(&resource_id).unwrap().unwrap();
+        assert_eq!(fs::read(&resource.resource_path).unwrap(), b"%PDF-2");
+        assert_eq!(
+            db.get_resource_hash(&resource_id).unwrap(),
+            Some(content_hash(b"%PDF-2"))
+        );
+
+        // the copy keeps the resource when the file it was created for is deleted
+        fs::remove_file(root.join(&created.path)).unwrap();
+        let (scan, _, removed) = scan_folder(&mut db, &resources_path, &folder);
+        assert_eq!(scan.removed, 0);
+        assert!(removed.is_empty());
+        let remaining = db.list_watched_files(&folder.id).unwrap();
+        let copy = remaining.iter().find(|f| f.path == linked.path).unwrap();
+        assert_eq!(copy.resource_id, resource_id);
+        assert!(copy.created_resource);
+
+        // and the resource is removed with the last file
+        fs::remove_file(root.join(&linked.path)).unwrap();
+        let (scan, _, removed) = scan_folder(&mut db, &resources_path, &folder);
+        assert_eq!(scan.removed, 1);
+        assert_eq!(removed, vec![resource_id]);
+        let remaining = db.list_watched_files(&folder.id).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].path, "notes.md");
+    }
+
+    #[test]
+    fn test_watch_folder_scans() {
+        let scans = WatchFolderScans::default();
+        let scanning = scans.start("folder").unwrap();
+        assert!(scans.start("folder").is_none());
+        assert!(scans.start("other").is_some());
+        drop(scanning);
+        assert!(scans.start("folder").is_some());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
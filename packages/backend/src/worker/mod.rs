@@ -7,13 +7,14 @@ const _MODULE_PREFIX: &str = "backend";
 use crate::{
     ai::AI,
     api::message::{
-        AIMessage, EventBusMessage, ProcessorMessage, TunnelMessage, TunnelOneshot, WorkerMessage,
+        AIMessage, BackupMessage, EventBusMessage, ProcessorMessage, TunnelMessage, TunnelOneshot,
+        WorkerMessage,
     },
     store::{db::Database, kv::KeyValueStore, models::current_time},
     BackendError, BackendResult,
 };
 use handlers::*;
-use tunnel::SurfBackendHealth;
+use tunnel::{LibraryLock, SurfBackendHealth, WatchFolderScans};
 
 use chrono::{DateTime, Utc};
 use crossbeam_channel as crossbeam;
@@ -58,6 +59,11 @@ impl PathConfig {
             .to_string()
     }
 
+    // where the database's migrations put their backups too
+    pub fn backups_path(&self) -> String {
+        crate::store::backup::backups_path(&self.db_path())
+    }
+
     pub fn local_ai_socket_path(&self) -> String {
         Path::new(&self.backend_root_path)
             .join("sffs-ai.sock")
@@ -91,12 +97,15 @@ pub struct WorkerConfig {
     pub language_setting: String,
     pub run_migrations: bool,
     pub surf_backend_health: SurfBackendHealth,
+    pub watch_folder_scans: WatchFolderScans,
+    pub library_lock: LibraryLock,
 }
 
 pub struct Worker {
     pub db: Database,
+    pub db_path: String,
     pub kv: KeyValueStore,
-    pub ai: AI,
+    pub ai: Arc<AI>,
     pub channel: Channel,
     pub event_bus_rx: Arc<Root<JsFunction>>,
     pub tqueue_tx: crossbeam::Sender<ProcessorMessage>,
@@ -104,9 +113,12 @@ pub struct Worker {
     pub app_path: String,
     pub backend_root_path: String,
     pub resources_path: String,
+    pub backups_path: String,
     pub language_setting: String,
     pub async_runtime: tokio::runtime::Runtime,
     pub surf_backend_health: SurfBackendHealth,
+    pub watch_folder_scans: WatchFolderScans,
+    pub library_lock: LibraryLock,
     pub created_at: DateTime<Utc>,
 }
 
@@ -119,8 +131,9 @@ impl Worker {
 
         Ok(Self {
             db: Database::new(&db_path, config.run_migrations)?,
+            db_path,
             kv: KeyValueStore::new(&kv_db_path)?,
-            ai: AI::new(local_ai_socket_path)?,
+            ai: Arc::new(AI::new(local_ai_socket_path)?),
             channel: config.channel_config.channel,
             event_bus_rx: config.channel_config.event_bus_rx,
             tqueue_tx: config.channel_config.tqueue_tx,
@@ -128,9 +141,12 @@ impl Worker {
             app_path: config.path_config.app_path.clone(),
             backend_root_path: config.path_config.backend_root_path.clone(),
             resources_path,
+            backups_path: config.path_config.backups_path(),
             language_setting: config.language_setting,
             async_runtime: tokio::runtime::Runtime::new()?,
             surf_backend_health: config.surf_backend_health,
+            watch_folder_scans: config.watch_folder_scans,
+            library_lock: config.library_lock,
             created_at: current_time(),
         })
     }
@@ -163,8 +179,17 @@ pub fn worker_thread_entry_point(
     config: WorkerConfig,
 ) {
     let mut worker = Worker::new(config).expect("Failed to initialize worker");
+    let library_lock = worker.library_lock.clone();
 
     while let Ok(TunnelMessage(message, oneshot)) = worker_rx.recv() {
+        // a restore waits for the messages other worker threads are handling,
+        // e.g. chat responses that are still streaming, and new messages wait for it
+        let (_shared, _exclusive) = match message {
+            WorkerMessage::BackupMessage(BackupMessage::RestoreBackup(_)) => {
+                (None, Some(library_lock.exclusive()))
+            }
+            _ => (Some(library_lock.shared()), None),
+        };
         match message {
             WorkerMessage::MiscMessage(message) => {
                 handle_misc_message(&mut worker, oneshot, message)
@@ -185,6 +210,27 @@ pub fn worker_thread_entry_point(
                 handle_kv_store_message(&mut worker, oneshot, message)
             }
             WorkerMessage::AppMessage(message) => handle_app_message(&mut worker, oneshot, message),
+            WorkerMessage::ImportMessage(message) => {
+                handle_import_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::ArchiveMessage(message) => {
+                handle_archive_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::BackupMessage(message) => {
+                handle_backup_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::VaultMessage(message) => {
+                handle_vault_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::WatchFolderMessage(message) => {
+                handle_watch_folder_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::AgentRunMessage(message) => {
+                handle_agent_run_message(&mut worker, oneshot, message)
+            }
+            WorkerMessage::CustomAgentMessage(message) => {
+                handle_custom_agent_message(&mut worker, oneshot, message)
+            }
         }
     }
 }
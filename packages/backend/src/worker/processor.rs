@@ -265,27 +265,33 @@ fn is_markdown_file(file_name: &str) -> bool {
     file_name.ends_with(".md")
 }
 
-fn parse_markdown_with_frontmatter(content: &str) -> BackendResult<(String, serde_yaml::Value)> {
-    // Simple frontmatter parser - finds content between --- markers
-    let parts: Vec<&str> = content.split("---").collect();
-    
-    match parts.len() {
-        // No frontmatter or invalid format
-        0 | 1 => Ok((content.to_string(), serde_yaml::Value::Null)),
-        
-        // Has frontmatter
-        _ => {
-            // Parse the YAML frontmatter (second part, index 1)
-            let frontmatter_yaml = parts[1].trim();
+pub fn parse_markdown_with_frontmatter(content: &str) -> BackendResult<(String, serde_yaml::Value)> {
+    // Frontmatter has to open the file with a --- line and ends at the next line that is
+    // exactly ---, any other --- is content, e.g. a horizontal rule or part of a title
+    let text = content.trim_start_matches('\u{feff}').trim_start();
+    let mut lines = text.split_inclusive('\n');
+    let start = match lines.next() {
+        Some(line) if line.trim_end() == "---" => line.len(),
+        _ => return Ok((content.to_string(), serde_yaml::Value::Null)),
+    };
+
+    let mut end = start;
+    for line in lines {
+        if line.trim_end_matches(&['\r', '\n'][..]) == "---" {
+            let frontmatter_yaml = text[start..end].trim();
             let frontmatter = serde_yaml::from_str(frontmatter_yaml)
                 .map_err(|e| BackendError::GenericError(format!("Failed to parse frontmatter: {}", e)))?;
-            
-            // Get the content (everything after second ---)
-            let content = parts[2..].join("---").trim().to_string();
-            
-            Ok((content, frontmatter))
+
+            // Get the content (everything after the closing ---)
+            let content = text[end + line.len()..].trim().to_string();
+
+            return Ok((content, frontmatter));
         }
+        end += line.len();
     }
+
+    // No closing ---, the file has no frontmatter
+    Ok((content.to_string(), serde_yaml::Value::Null))
 }
 
 pub fn get_youtube_contents_metadatas(
@@ -3,8 +3,10 @@ use super::{
     PathConfig, WorkerConfig,
 };
 use crate::{
+    ai::llm::client::CancellationToken,
     api::message::{
-        AIMessage, ProcessorMessage, ResourceMessage, TunnelMessage, TunnelOneshot, WorkerMessage,
+        AIMessage, BackupMessage, ProcessorMessage, ResourceMessage, TunnelMessage, TunnelOneshot,
+        WatchFolderMessage, WorkerMessage,
     },
     BackendResult,
 };
@@ -18,7 +20,12 @@ use std::panic;
 
 const NUM_WORKER_THREADS: usize = 12;
 const NUM_PROCESSOR_THREADS: usize = 12;
-use std::sync::{Arc, Condvar, Mutex};
+// how often the scheduler checks whether a backup is due
+const BACKUP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+// how often watch folders are scanned for new and changed files
+const WATCH_FOLDER_SCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Clone)]
 pub struct WorkerTunnel {
@@ -27,6 +34,9 @@ pub struct WorkerTunnel {
     pub aiqueue_rx: crossbeam::Receiver<AIMessage>,
     pub event_bus_rx_callback: Arc<Root<JsFunction>>,
     pub surf_backend_health: SurfBackendHealth,
+    pub cancellation_registry: CancellationRegistry,
+    pub watch_folder_scans: WatchFolderScans,
+    pub library_lock: LibraryLock,
 }
 
 pub struct SurfBackendHealth(Arc<(Mutex<bool>, Condvar)>);
@@ -68,6 +78,83 @@ impl Clone for SurfBackendHealth {
     }
 }
 
+// cancellation tokens of in-flight llm requests by their frontend request id
+//
+// requests are cancelled from the js thread directly as all worker threads could be busy
+#[derive(Clone, Default)]
+pub struct CancellationRegistry(Arc<Mutex<HashMap<String, CancellationToken>>>);
+
+impl CancellationRegistry {
+    pub fn register(&self, request_id: Option<String>) -> CancellationToken {
+        let token = CancellationToken::new();
+        let mut tokens = self.0.lock().unwrap();
+        // requests which already finished have dropped their clone of the token
+        tokens.retain(|_, token| !token.is_orphaned());
+        if let Some(request_id) = request_id {
+            tokens.insert(request_id, token.clone());
+        }
+        token
+    }
+
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.0.lock().unwrap().remove(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// ids of the watch folders a worker thread is scanning
+//
+// scans are started by the scheduler and from the app, a folder that is already being
+// scanned is skipped so that its new files aren't added twice
+#[derive(Clone, Default)]
+pub struct WatchFolderScans(Arc<Mutex<HashSet<String>>>);
+
+impl WatchFolderScans {
+    // `None` if the folder is already being scanned, it's released when the guard is dropped
+    pub fn start(&self, folder_id: &str) -> Option<WatchFolderScanGuard> {
+        if !self.0.lock().unwrap().insert(folder_id.to_string()) {
+            return None;
+        }
+        Some(WatchFolderScanGuard {
+            scans: self.clone(),
+            folder_id: folder_id.to_string(),
+        })
+    }
+}
+
+pub struct WatchFolderScanGuard {
+    scans: WatchFolderScans,
+    folder_id: String,
+}
+
+impl Drop for WatchFolderScanGuard {
+    fn drop(&mut self) {
+        self.scans.0.lock().unwrap().remove(&self.folder_id);
+    }
+}
+
+// held by every worker thread while it handles a message, and exclusively while a backup
+// is restored so that no message changes the database, the kv store or the resource
+// files halfway through a restore
+#[derive(Clone, Default)]
+pub struct LibraryLock(Arc<RwLock<()>>);
+
+impl LibraryLock {
+    // a worker thread that panicked while holding the lock is restarted, the lock stays usable
+    pub fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TunnelConfig {
     pub backend_root_path: String,
@@ -100,11 +187,16 @@ impl WorkerTunnel {
             aiqueue_rx,
             event_bus_rx_callback: event_bus_rx_callback.clone(),
             surf_backend_health: surf_backend_health.clone(),
+            cancellation_registry: CancellationRegistry::default(),
+            watch_folder_scans: WatchFolderScans::default(),
+            library_lock: LibraryLock::default(),
         };
 
         Self::spawn_threads(cx, config, worker_rx, tqueue_tx, aiqueue_tx, &tunnel);
 
         tunnel.initiate_worker_startup_jobs();
+        tunnel.spawn_backup_scheduler();
+        tunnel.spawn_watch_folder_scheduler();
         tunnel
     }
 
@@ -126,10 +218,13 @@ impl WorkerTunnel {
             aiqueue_tx,
             Arc::clone(&tunnel.event_bus_rx_callback),
             tunnel.surf_backend_health.clone(),
+            tunnel.watch_folder_scans.clone(),
+            tunnel.library_lock.clone(),
         );
         Self::spawn_processor_threads(tunnel, &config);
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn spawn_worker_threads<'a, C>(
         cx: &mut C,
         config: &TunnelConfig,
@@ -138,6 +233,8 @@ impl WorkerTunnel {
         aiqueue_tx: crossbeam::Sender<AIMessage>,
         event_bus_rx_callback: Arc<Root<JsFunction>>,
         surf_backend_health: SurfBackendHealth,
+        watch_folder_scans: WatchFolderScans,
+        library_lock: LibraryLock,
     ) where
         C: Context<'a>,
     {
@@ -151,6 +248,8 @@ impl WorkerTunnel {
             let aiqueue_tx = aiqueue_tx.clone();
             let callback = Arc::clone(&event_bus_rx_callback);
             let surf_backend_health = surf_backend_health.clone();
+            let watch_folder_scans = watch_folder_scans.clone();
+            let library_lock = library_lock.clone();
             let libuv_ch = libuv_ch.clone();
             let thread_name = format!("W{n}");
 
@@ -189,6 +288,8 @@ impl WorkerTunnel {
                         language_setting: language_setting.clone(),
                         run_migrations: _run_migrations,
                         surf_backend_health: surf_backend_health.clone(),
+                        watch_folder_scans: watch_folder_scans.clone(),
+                        library_lock: library_lock.clone(),
                     };
 
                     worker_thread_entry_point(worker_rx.clone(), worker_config)
@@ -246,6 +347,34 @@ impl WorkerTunnel {
             .ok();
     }
 
+    fn spawn_backup_scheduler(&self) {
+        let tunnel = self.clone();
+        std::thread::Builder::new()
+            .name("backup-scheduler".to_string())
+            .spawn(move || loop {
+                tunnel.worker_send_rust(
+                    WorkerMessage::BackupMessage(BackupMessage::RunScheduledBackup),
+                    None,
+                );
+                std::thread::sleep(BACKUP_CHECK_INTERVAL);
+            })
+            .expect("failed to spawn backup scheduler thread");
+    }
+
+    fn spawn_watch_folder_scheduler(&self) {
+        let tunnel = self.clone();
+        std::thread::Builder::new()
+            .name("watch-folder-scheduler".to_string())
+            .spawn(move || loop {
+                tunnel.worker_send_rust(
+                    WorkerMessage::WatchFolderMessage(WatchFolderMessage::ScanWatchFolders),
+                    None,
+                );
+                std::thread::sleep(WATCH_FOLDER_SCAN_INTERVAL);
+            })
+            .expect("failed to spawn watch folder scheduler thread");
+    }
+
     pub fn worker_send_js(&self, message: WorkerMessage, deferred: Deferred) {
         self.worker_tx
             .send(TunnelMessage(
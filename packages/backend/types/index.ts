@@ -33,8 +33,13 @@ export interface CreateChatCompletionOptions {
   model: Model
   custom_key?: string
   response_format?: string
+  // id to cancel the request with `js__ai_cancel_request`
+  request_id?: string
 }
 
+// a base64 data url, or one with the file's name and the transcript of an audio file
+export type InlineFile = string | { data_url: string; filename?: string; transcript?: string }
+
 export interface ChatMessageOptions {
   query: string
   chat_id: string
@@ -44,8 +49,14 @@ export interface ChatMessageOptions {
   rag_only?: boolean
   resource_ids?: string[]
   inline_images?: string[]
+  // pdfs
+  inline_documents?: InlineFile[]
+  // audio files
+  inline_audio?: InlineFile[]
   general?: boolean
   app_creation?: boolean
+  compact_history?: boolean
+  request_id?: string
 }
 
 export interface NoteMessageOptions {
@@ -56,9 +67,14 @@ export interface NoteMessageOptions {
   limit?: number
   resource_ids?: string[]
   inline_images?: string[]
+  // pdfs
+  inline_documents?: InlineFile[]
+  // audio files
+  inline_audio?: InlineFile[]
   general?: boolean
   websearch?: boolean
   surflet?: boolean
+  request_id?: string
 }
 
 export interface QueryResourcesOptions {
@@ -75,6 +91,7 @@ export interface CreateAppOptions {
   model: Model
   custom_key?: string
   inline_images?: string[]
+  request_id?: string
 }
 
 export class TooManyRequestsError extends Error {